@@ -0,0 +1,276 @@
+-- Upgrade pg_walrus from 0.1.0 to 0.2.0
+--
+-- Applied by: ALTER EXTENSION pg_walrus UPDATE TO '0.2.0';
+
+\echo Use "ALTER EXTENSION pg_walrus UPDATE TO '0.2.0'" to load this file. \quit
+
+-- =========================================================================
+-- History table
+-- =========================================================================
+
+-- New actions: 'note' (informational rows), 'external_change' (max_wal_size
+-- changed outside pg_walrus), and 'manual' (walrus.set_size())
+ALTER TABLE walrus.history
+    DROP CONSTRAINT history_action_check,
+    ADD CONSTRAINT history_action_check
+        CHECK (action IN ('increase', 'decrease', 'capped', 'dry_run', 'skipped', 'note', 'external_change', 'manual'));
+
+-- Existing rows were recorded from the database the table lives in
+ALTER TABLE walrus.history
+    ADD COLUMN database_name TEXT NOT NULL DEFAULT current_database();
+
+-- Deduplicates retried inserts of the same applied decision
+CREATE UNIQUE INDEX walrus_history_idempotency_key_idx
+    ON walrus.history ((metadata->>'idempotency_key'))
+    WHERE metadata ? 'idempotency_key';
+
+COMMENT ON COLUMN walrus.history.action IS 'Decision type: increase, decrease, capped, dry_run, skipped, note, external_change, or manual';
+COMMENT ON COLUMN walrus.history.database_name IS 'Database whose connection recorded the row';
+
+-- Daily rollup of deleted history rows (walrus.archive_before_delete)
+CREATE TABLE walrus.history_archive (
+    day DATE PRIMARY KEY,
+    increases INTEGER NOT NULL DEFAULT 0,
+    decreases INTEGER NOT NULL DEFAULT 0,
+    capped INTEGER NOT NULL DEFAULT 0,
+    peak_size_mb INTEGER,
+    min_size_mb INTEGER
+);
+
+COMMENT ON TABLE walrus.history_archive IS 'Per-day summary of history rows removed by cleanup; never cleaned up';
+COMMENT ON COLUMN walrus.history_archive.day IS 'UTC day of the archived history rows';
+COMMENT ON COLUMN walrus.history_archive.increases IS 'Number of increase rows';
+COMMENT ON COLUMN walrus.history_archive.decreases IS 'Number of decrease rows';
+COMMENT ON COLUMN walrus.history_archive.capped IS 'Number of capped rows';
+COMMENT ON COLUMN walrus.history_archive.peak_size_mb IS 'Largest max_wal_size seen that day (in MB)';
+COMMENT ON COLUMN walrus.history_archive.min_size_mb IS 'Smallest max_wal_size seen that day (in MB)';
+
+-- =========================================================================
+-- Functions whose signature changed
+-- =========================================================================
+
+DROP FUNCTION walrus."history"();
+CREATE FUNCTION walrus."history"(
+    "database_name" TEXT DEFAULT NULL
+) RETURNS TABLE (
+    "timestamp" timestamp with time zone,
+    "action" TEXT,
+    "old_size_mb" INT,
+    "new_size_mb" INT,
+    "forced_checkpoints" bigint,
+    "reason" TEXT,
+    "database_name" TEXT
+)
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'history_wrapper';
+
+DROP FUNCTION walrus."recommendation"();
+CREATE FUNCTION walrus."recommendation"(
+    "hypothetical_delta" bigint DEFAULT NULL,
+    "hypothetical_quiet_intervals" INT DEFAULT NULL
+) RETURNS jsonb
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'recommendation_wrapper';
+
+DROP FUNCTION walrus."analyze"(bool);
+CREATE FUNCTION walrus."analyze"(
+    "apply" bool DEFAULT false,
+    "force" bool DEFAULT false
+) RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'analyze_wrapper';
+
+-- =========================================================================
+-- New functions
+-- =========================================================================
+
+CREATE FUNCTION walrus."history_after"(
+    "after_id" bigint,
+    "limit_rows" INT DEFAULT 100
+) RETURNS TABLE (
+    "id" bigint,
+    "timestamp" timestamp with time zone,
+    "action" TEXT,
+    "old_size_mb" INT,
+    "new_size_mb" INT,
+    "forced_checkpoints" bigint,
+    "reason" TEXT
+)
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'history_after_wrapper';
+
+CREATE FUNCTION walrus."history_ndjson"(
+    "since" timestamp with time zone DEFAULT NULL
+) RETURNS SETOF TEXT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'history_ndjson_wrapper';
+
+CREATE FUNCTION walrus."metadata_keys"() RETURNS TABLE (
+    "action" TEXT,
+    "key" TEXT,
+    "sample_value" TEXT
+)
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'metadata_keys_wrapper';
+
+CREATE FUNCTION walrus."guc_sources"() RETURNS TABLE (
+    "name" TEXT,
+    "setting" TEXT,
+    "source" TEXT,
+    "pending_restart" bool
+)
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'guc_sources_wrapper';
+
+CREATE FUNCTION walrus."clear_suppress"() RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'clear_suppress_wrapper';
+
+CREATE FUNCTION walrus."export_history"() RETURNS TEXT
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'export_history_wrapper';
+
+CREATE FUNCTION walrus."import_history"(
+    "csv" TEXT
+) RETURNS bigint
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'import_history_wrapper';
+
+CREATE FUNCTION walrus."version"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'version_wrapper';
+
+CREATE FUNCTION walrus."diagnostics"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'diagnostics_wrapper';
+
+CREATE FUNCTION walrus."state"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'state_wrapper';
+
+CREATE FUNCTION walrus."decision_stats"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'decision_stats_wrapper';
+
+CREATE FUNCTION walrus."validate_config"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'validate_config_wrapper';
+
+CREATE FUNCTION walrus."checkpoint_stats"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'checkpoint_stats_wrapper';
+
+CREATE FUNCTION walrus."preview_shrink_path"(
+    "factor" double precision,
+    "steps" INT DEFAULT 10
+) RETURNS TABLE (
+    "step" INT,
+    "size_mb" INT
+)
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'preview_shrink_path_wrapper';
+
+CREATE FUNCTION walrus."recommend_min"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'recommend_min_wrapper';
+
+CREATE FUNCTION walrus."worker_running"() RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'worker_running_wrapper';
+
+CREATE FUNCTION walrus."heartbeat"() RETURNS timestamp with time zone
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'heartbeat_wrapper';
+
+CREATE FUNCTION walrus."ignore_next_checkpoints"(
+    "n" INT
+) RETURNS bigint
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'ignore_next_checkpoints_wrapper';
+
+CREATE FUNCTION walrus."kick"() RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'kick_wrapper';
+
+CREATE FUNCTION walrus."tick"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'tick_wrapper';
+
+CREATE FUNCTION walrus."next_allowed_at"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'next_allowed_at_wrapper';
+
+CREATE FUNCTION walrus."begin_bulk_load"(
+    "minutes" INT
+) RETURNS timestamp with time zone
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'begin_bulk_load_wrapper';
+
+CREATE FUNCTION walrus."end_bulk_load"() RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'end_bulk_load_wrapper';
+
+CREATE FUNCTION walrus."pin"(
+    "minutes" INT
+) RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'pin_wrapper';
+
+CREATE FUNCTION walrus."temporary_max"(
+    "mb" INT,
+    "minutes" INT
+) RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'temporary_max_wrapper';
+
+CREATE FUNCTION walrus."set"(
+    "param" TEXT,
+    "value" TEXT
+) RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'set_wrapper';
+
+CREATE FUNCTION walrus."set_size"(
+    "mb" INT
+) RETURNS bool
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'set_size_wrapper';
+
+CREATE FUNCTION walrus."explain"() RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'explain_wrapper';
+
+CREATE FUNCTION walrus."reproduce"(
+    "history_id" bigint
+) RETURNS jsonb
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'reproduce_wrapper';
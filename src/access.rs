@@ -0,0 +1,106 @@
+//! Database allowlist for pg_walrus apply functions.
+//!
+//! In shared clusters the extension may be installed in many databases, but
+//! only the metadata database should change max_wal_size or clear worker
+//! state. `walrus.apply_allowed_databases` lists the databases where
+//! `walrus.analyze(apply := true)`, `walrus.set_size()`, `walrus.tick()`, and
+//! `walrus.reset()` may run; empty allows every database. The check is in addition to the superuser check.
+//!
+//! `walrus.read_only` turns an install observe-only (e.g. on a read replica):
+//! the apply functions raise an error, the worker logs its decisions instead
+//! of applying them, and `history::insert_history_record()` writes nothing.
+
+use crate::guc::{WALRUS_APPLY_ALLOWED_DATABASES, WALRUS_READ_ONLY};
+
+use pgrx::prelude::*;
+
+/// Whether `database` may run apply functions under a comma-separated allowlist.
+///
+/// Names are trimmed and compared exactly (database names are case-sensitive).
+/// An allowlist with no names allows every database.
+pub(crate) fn database_allowed(allowlist: &str, database: &str) -> bool {
+    let mut names = allowlist
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .peekable();
+    names.peek().is_none() || names.any(|name| name == database)
+}
+
+/// Raise a permission error unless the current database is allowed by
+/// walrus.apply_allowed_databases.
+///
+/// `function` names the caller in the error, e.g. "walrus.reset()".
+pub(crate) fn require_apply_allowed(function: &str) {
+    let allowlist = WALRUS_APPLY_ALLOWED_DATABASES
+        .get()
+        .and_then(|s| s.to_str().ok().map(|s| s.to_owned()))
+        .unwrap_or_default();
+    if database_allowed(&allowlist, "") {
+        return;
+    }
+
+    let database = Spi::get_one::<String>("SELECT current_database()::text")
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    if !database_allowed(&allowlist, &database) {
+        pgrx::error!(
+            "permission denied: {} is not allowed in database \"{}\" (see walrus.apply_allowed_databases)",
+            function,
+            database
+        );
+    }
+}
+
+/// Raise an error if walrus.read_only is on.
+///
+/// `function` names the caller in the error, e.g. "walrus.set_size()".
+pub(crate) fn require_writable(function: &str) {
+    if WALRUS_READ_ONLY.get() {
+        pgrx::error!("{} is not allowed while walrus.read_only is on", function);
+    }
+}
+
+/// Log a worker decision at DEBUG1 instead of applying it if walrus.read_only is on.
+///
+/// Returns true when the caller must stop without running ALTER SYSTEM.
+pub(crate) fn skip_read_only(current_size: i32, new_size: i32, reason: &str) -> bool {
+    if !WALRUS_READ_ONLY.get() {
+        return false;
+    }
+    pgrx::debug1!(
+        "pg_walrus [read-only]: would change max_wal_size from {} MB to {} MB ({})",
+        current_size,
+        new_size,
+        reason
+    );
+    true
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test an empty allowlist allows every database
+    #[test]
+    fn test_empty_allowlist_allows_all() {
+        assert!(database_allowed("", "postgres"));
+        assert!(database_allowed(" , ", "app"));
+    }
+
+    /// Test listed databases are allowed and others blocked
+    #[test]
+    fn test_allowlist_blocks_unlisted_database() {
+        let allowlist = "postgres, walrus_meta";
+        assert!(database_allowed(allowlist, "postgres"));
+        assert!(database_allowed(allowlist, "walrus_meta"));
+        assert!(!database_allowed(allowlist, "app"));
+        assert!(
+            !database_allowed(allowlist, "Postgres"),
+            "names are case-sensitive"
+        );
+        assert!(!database_allowed(allowlist, ""));
+    }
+}
@@ -0,0 +1,31 @@
+// Integration tests for where pg_walrus may change max_wal_size.
+//
+// walrus.apply_allowed_databases is postmaster-context, so each test overrides
+// it for this backend only; the override is rolled back with the transaction
+// the expected error aborts.
+
+/// Override walrus.apply_allowed_databases for this backend.
+fn set_apply_allowed_databases(value: &std::ffi::CStr) {
+    unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.apply_allowed_databases".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_POSTMASTER,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    }
+}
+
+/// Test walrus.set_size() is refused in a database outside the allowlist
+#[pg_test(error = "permission denied: walrus.set_size() is not allowed in database \"pgrx_tests\" (see walrus.apply_allowed_databases)")]
+fn test_set_size_outside_allowed_databases() {
+    set_apply_allowed_databases(c"walrus_metadata");
+    Spi::run("SELECT walrus.set_size(2048)").expect("query failed");
+}
+
+/// Test walrus.tick() is refused in a database outside the allowlist
+#[pg_test(error = "permission denied: walrus.tick() is not allowed in database \"pgrx_tests\" (see walrus.apply_allowed_databases)")]
+fn test_tick_outside_allowed_databases() {
+    set_apply_allowed_databases(c"walrus_metadata");
+    Spi::run("SELECT walrus.tick()").expect("query failed");
+}
@@ -5,17 +5,44 @@
 //! enables consistent behavior and comprehensive testing.
 //!
 //! Key functions:
-//! - `calculate_new_size()`: Compute grow target based on checkpoint delta
-//! - `calculate_shrink_size()`: Compute shrink target with floor clamping
+//! - `min_grow_delta()`: Smallest delta that grows under walrus.threshold_inclusive
+//! - `lacks_real_baseline()`: Detect a delta that is the counter's lifetime total
+//! - `calculate_new_size()`: Compute grow target based on checkpoint delta and walrus.grow_exponent
+//! - `grow_multiplier()`: Grow multiplier for a delta and exponent
+//! - `calculate_shrink_size()`: Compute shrink target with walrus.shrink_rounding and floor clamping
+//! - `peak_ratio_shrink_size()`: Shrink target for walrus.shrink_target = 'peak_ratio'
+//! - `shrink_floor()`: Effective shrink floor from walrus.min_size and min_wal_size
+//! - `shrink_is_stuck()` / `min_step_shrink_size()`: Shrinks that rounding keeps at the current size
+//! - `is_usable_current_size()`: Guard against a zero or negative max_wal_size reading
+//! - `estimated_intervals_to_ceiling()`: Heuristic forecast of grows until walrus.max
+//! - `ceiling_excess_target()`: Cap-down target when walrus.max is lowered below current
+//! - `samples_per_interval()`: Sub-samples per checkpoint_timeout interval
+//! - `accumulate_sample()`: Accumulate a sub-sample delta toward a decision
+//! - `apply_ignored_checkpoints()`: Exclude operator-requested checkpoints from delta
+//! - `is_wal_quiet()` / `next_quiet_intervals()`: WAL volume check for quiet intervals
 //! - `compute_recommendation()`: Full recommendation with action and confidence
+//! - `compute_recommendation_for_delta()`: Recommendation for a hypothetical delta
 //! - `compute_confidence()`: Data quality confidence score
-
+//! - `action_confidence()`: Confidence adjusted for the prospective action
+//! - `forced_checkpoints_per_minute()`: Delta normalized by checkpoint_timeout
+//! - `meets_confidence_threshold()`: Confidence gate for applying changes
+//! - `mode_gate()` / `records_only()`: walrus.mode and walrus.dry_run handling
+//! - `required_shrink_intervals()` / `backed_off_shrink_intervals()`: walrus.shrink_backoff
+//! - `shrink_path()`: Successive shrink targets for walrus.preview_shrink_path()
+
+use crate::bulk_load::effective_threshold;
 use crate::guc::{
-    WALRUS_ENABLE, WALRUS_MAX, WALRUS_MIN_SIZE, WALRUS_SHRINK_ENABLE, WALRUS_SHRINK_FACTOR,
-    WALRUS_SHRINK_INTERVALS, WALRUS_THRESHOLD,
+    ShrinkRounding, ShrinkTarget, WALRUS_ENABLE, WALRUS_GROW_EXPONENT, WALRUS_MIN_SIZE,
+    WALRUS_PEAK_RATIO, WALRUS_SHRINK_BACKOFF, WALRUS_SHRINK_CONFIDENCE_PENALTY,
+    WALRUS_SHRINK_ENABLE, WALRUS_SHRINK_FACTOR, WALRUS_SHRINK_INTERVALS, WALRUS_SHRINK_ROUNDING,
+    WALRUS_SHRINK_TARGET, WALRUS_THRESHOLD_INCLUSIVE, WalrusMode,
 };
 use crate::shmem::WalrusState;
-use crate::stats::{get_current_max_wal_size, get_requested_checkpoints};
+use crate::stats::{
+    checkpoint_timeout, get_current_max_wal_size, get_current_min_wal_size,
+    get_requested_checkpoints,
+};
+use crate::temporary_max::{ceiling_max, effective_max};
 use serde::{Deserialize, Serialize};
 
 /// Recommendation result from sizing analysis.
@@ -38,33 +65,461 @@ pub struct Recommendation {
 
     /// Confidence level 0-100 based on data quality
     pub confidence: i32,
+
+    /// checkpoint_timeout in seconds at recommendation time (the delta window)
+    pub checkpoint_timeout_sec: i32,
+
+    /// Forced checkpoint delta normalized to a per-minute rate
+    /// (None if no delta was available, e.g. awaiting baseline)
+    pub forced_checkpoints_per_minute: Option<f64>,
+}
+
+/// Grow multiplier for a forced checkpoint delta.
+///
+/// Formula: 1 + delta^grow_exponent (negative deltas are treated as 0)
+#[inline]
+pub fn grow_multiplier(delta: i64, grow_exponent: f64) -> f64 {
+    1.0 + (delta.max(0) as f64).powf(grow_exponent)
 }
 
 /// Calculate the new max_wal_size based on forced checkpoint count.
 ///
-/// Formula: current_size * (delta + 1)
+/// Formula: current_size * (1 + delta^grow_exponent)
+///
+/// With grow_exponent = 1.0 (the default) this is exactly current_size * (delta + 1),
+/// computed in i64 and saturated to i32::MAX, so neither a large size nor a
+/// delta beyond i32::MAX (a huge counter against a zero baseline) can wrap. Other
+/// exponents dampen (< 1.0) or amplify (> 1.0) large bursts; the result is
+/// rounded up and saturates at i32::MAX. Returns the calculated value before
+/// capping at walrus.max (capping is done by the caller).
+#[inline]
+pub fn calculate_new_size(current_size: i32, delta: i64, grow_exponent: f64) -> i32 {
+    if grow_exponent == 1.0 {
+        let multiplier = delta.max(0).saturating_add(1);
+        let size = (current_size as i64).saturating_mul(multiplier);
+        return size.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+    }
+    let raw = (current_size as f64 * grow_multiplier(delta, grow_exponent)).ceil();
+    if raw >= i32::MAX as f64 {
+        i32::MAX
+    } else {
+        raw as i32
+    }
+}
+
+/// Whether a forced checkpoint delta shows there was no real baseline.
+///
+/// With a baseline of 0 (walrus.reset()) or a tiny one next to a huge
+/// counter, `current - prev_requested` is the counter's lifetime total, not
+/// one interval's activity. Such a delta is more than one forced checkpoint
+/// per second of `timeout_secs` and larger than the baseline itself (the
+/// counter more than doubled in one interval). A real storm on a fresh
+/// cluster fails the second test from its second interval on, so at most one
+/// interval of it is ignored.
+#[inline]
+pub fn lacks_real_baseline(prev_requested: i64, delta: i64, timeout_secs: i64) -> bool {
+    delta > timeout_secs.max(1) && delta > prev_requested
+}
+
+/// Smallest forced checkpoint delta that triggers a grow.
 ///
-/// Uses saturating_mul to prevent i32 overflow. Returns the calculated value
-/// before capping at walrus.max (capping is done by the caller).
+/// With walrus.threshold_inclusive (the default) a delta equal to `threshold`
+/// grows (`delta >= threshold`); without it only a larger delta does
+/// (`delta > threshold`, i.e. from `threshold + 1`).
 #[inline]
-pub fn calculate_new_size(current_size: i32, delta: i64) -> i32 {
-    let multiplier = (delta + 1) as i32;
-    current_size.saturating_mul(multiplier)
+pub fn min_grow_delta(threshold: i64, inclusive: bool) -> i64 {
+    if inclusive {
+        threshold
+    } else {
+        threshold.saturating_add(1)
+    }
 }
 
 /// Calculate the shrink target size for max_wal_size.
 ///
-/// Formula: ceil(current_size * shrink_factor), clamped to min_size
+/// Formula: round(current_size * shrink_factor), clamped to min_size
 ///
-/// Uses f64 multiplication then rounds up via ceiling to ensure we don't
-/// under-size. The result is clamped to min_size as a floor.
+/// Uses f64 multiplication, then rounds per walrus.shrink_rounding: up
+/// (ceiling, the default, so we don't under-size), down (floor, reclaims more
+/// space), or to the nearest MB. The result is clamped to min_size as a floor.
+/// With a shrink_factor below 1.0, rounding down always strictly reduces.
 #[inline]
-pub fn calculate_shrink_size(current_size: i32, shrink_factor: f64, min_size: i32) -> i32 {
+pub fn calculate_shrink_size(
+    current_size: i32,
+    shrink_factor: f64,
+    min_size: i32,
+    rounding: ShrinkRounding,
+) -> i32 {
     let raw = (current_size as f64) * shrink_factor;
-    let rounded = raw.ceil() as i32;
+    let rounded = match rounding {
+        ShrinkRounding::Up => raw.ceil(),
+        ShrinkRounding::Down => raw.floor(),
+        ShrinkRounding::Nearest => raw.round(),
+    } as i32;
     rounded.max(min_size)
 }
 
+/// Calculate the walrus.shrink_target = 'peak_ratio' shrink target.
+///
+/// Formula: max(min_size, ceil(peak_ratio * peak_mb)), never above
+/// current_size, so a peak close to the current size leaves it unchanged.
+#[inline]
+pub fn peak_ratio_shrink_size(
+    current_size: i32,
+    peak_mb: i32,
+    peak_ratio: f64,
+    min_size: i32,
+) -> i32 {
+    let raw = (peak_mb as f64 * peak_ratio).ceil();
+    let target = if raw >= i32::MAX as f64 {
+        i32::MAX
+    } else {
+        raw as i32
+    };
+    target.max(min_size).min(current_size)
+}
+
+/// Successive shrink targets starting from `current_size`.
+///
+/// Element 0 is `current_size`; each following element applies
+/// `calculate_shrink_size()` to the previous one, for `steps` shrinks. Once a
+/// size is at or below `min_size` the worker no longer shrinks, so the path
+/// stays flat from there.
+pub fn shrink_path(
+    current_size: i32,
+    shrink_factor: f64,
+    min_size: i32,
+    rounding: ShrinkRounding,
+    steps: i32,
+) -> Vec<i32> {
+    let mut path = vec![current_size];
+    let mut size = current_size;
+    for _ in 0..steps.max(0) {
+        if size > min_size {
+            size = calculate_shrink_size(size, shrink_factor, min_size, rounding);
+        }
+        path.push(size);
+    }
+    path
+}
+
+/// Whether a shrink cannot make progress given the factor and size.
+///
+/// Rounding up (or to the nearest MB) just above the floor with a factor near
+/// 0.99 can leave the target at the current size, and every shrink attempt is
+/// a no-op.
+#[inline]
+pub fn shrink_is_stuck(
+    current_size: i32,
+    shrink_factor: f64,
+    min_size: i32,
+    rounding: ShrinkRounding,
+) -> bool {
+    current_size > min_size
+        && calculate_shrink_size(current_size, shrink_factor, min_size, rounding) >= current_size
+}
+
+/// Shrink target one step (e.g. one WAL segment) below the current size.
+///
+/// Used by walrus.force_min_shrink when `shrink_is_stuck()`. Never below `min_size`.
+#[inline]
+pub fn min_step_shrink_size(current_size: i32, step_mb: i32, min_size: i32) -> i32 {
+    current_size.saturating_sub(step_mb.max(1)).max(min_size)
+}
+
+/// True when a resize would leave max_wal_size unchanged.
+///
+/// Checked after every clamp and rounding step, so an adjustment that
+/// collapses to zero skips ALTER SYSTEM, the history record, and the reload.
+#[inline]
+pub fn is_noop_resize(current_size: i32, new_size: i32) -> bool {
+    new_size == current_size
+}
+
+/// True when a change computed from `planned_from` is stale.
+///
+/// `intended_size` is the max_wal_size last written by ALTER SYSTEM and not
+/// yet confirmed by a reload (shared memory `intended_size_mb`, 0 = none).
+/// A pending write of any other size means another session changed
+/// max_wal_size after the change was computed, so `apply_max_wal_size()`
+/// aborts it.
+#[inline]
+pub fn is_stale_change(planned_from: i32, intended_size: i32) -> bool {
+    intended_size != 0 && intended_size != planned_from
+}
+
+/// Whether walrus.grow_protect_intervals still forbids a shrink in `interval`.
+///
+/// Shrinks are blocked for the `protect_intervals` decision intervals after the
+/// grow applied in `last_grow_interval` (0 = no grow since start or reset).
+#[inline]
+pub fn grow_protected(interval: i64, last_grow_interval: i64, protect_intervals: i32) -> bool {
+    protect_intervals > 0
+        && last_grow_interval > 0
+        && interval - last_grow_interval <= protect_intervals as i64
+}
+
+/// Factor applied to the quiet interval requirement after each shrink (walrus.shrink_backoff).
+pub const SHRINK_BACKOFF_FACTOR: i32 = 2;
+
+/// Quiet intervals required before the next shrink.
+///
+/// `effective` is the backed-off requirement tracked in shared memory (0 when
+/// none). It only applies while walrus.shrink_backoff is on, and never lowers
+/// the requirement below walrus.shrink_intervals (`base`).
+#[inline]
+pub fn required_shrink_intervals(base: i32, effective: i32, backoff: bool) -> i32 {
+    if backoff { base.max(effective) } else { base }
+}
+
+/// Requirement for the shrink after one that needed `required` intervals (saturating).
+#[inline]
+pub fn backed_off_shrink_intervals(required: i32) -> i32 {
+    required.saturating_mul(SHRINK_BACKOFF_FACTOR)
+}
+
+/// Quiet intervals required before the next shrink under the current settings.
+pub fn current_shrink_intervals(state: &WalrusState) -> i32 {
+    required_shrink_intervals(
+        WALRUS_SHRINK_INTERVALS.get(),
+        state.effective_shrink_intervals,
+        WALRUS_SHRINK_BACKOFF.get(),
+    )
+}
+
+/// Whether a max_wal_size reading can be used for sizing math.
+///
+/// max_wal_size_mb should always be positive, but a 0 or negative reading
+/// (e.g. garbage during early startup) would make `calculate_new_size()`
+/// return 0 and `calculate_shrink_size()` return the floor, so callers treat
+/// it as a no-op instead.
+#[inline]
+pub fn is_usable_current_size(current_size: i32) -> bool {
+    current_size > 0
+}
+
+/// Heuristic forecast of grow intervals until max_wal_size reaches walrus.max.
+///
+/// Assumes every upcoming interval sees `avg_delta` forced checkpoints (the
+/// rolling average) and grows by `calculate_new_size()`. This is a trend
+/// projection, not a prediction of actual grows (rate limits, settling, and
+/// confidence gates are ignored). Returns None when that rate would not grow:
+/// `avg_delta` below `threshold`, zero, or an unusable current size. Returns
+/// Some(0) at or above the ceiling.
+pub fn estimated_intervals_to_ceiling(
+    current_size: i32,
+    avg_delta: i64,
+    threshold: i64,
+    max_size: i32,
+    grow_exponent: f64,
+) -> Option<i64> {
+    if !is_usable_current_size(current_size) {
+        return None;
+    }
+    if current_size >= max_size {
+        return Some(0);
+    }
+    if avg_delta <= 0 || avg_delta < threshold {
+        return None;
+    }
+
+    // Every step at least doubles the size, so this ends within ~31 steps
+    let mut size = current_size;
+    let mut intervals = 0;
+    while size < max_size {
+        let next = calculate_new_size(size, avg_delta, grow_exponent);
+        if next <= size {
+            return None;
+        }
+        size = next;
+        intervals += 1;
+    }
+    Some(intervals)
+}
+
+/// Compute the effective floor for shrink operations.
+///
+/// pg_walrus never shrinks max_wal_size below PostgreSQL's live min_wal_size,
+/// even if walrus.min_size is configured lower.
+///
+/// # Returns
+///
+/// `max(walrus_min_size, min_wal_size)`
+#[inline]
+pub fn shrink_floor(walrus_min_size: i32, min_wal_size: i32) -> i32 {
+    walrus_min_size.max(min_wal_size)
+}
+
+/// Target for bringing max_wal_size back under a lowered walrus.max.
+///
+/// Returns `Some(target)` when `current_size` exceeds `walrus_max`. The target
+/// is walrus.max, but never below `floor` (see `shrink_floor()`). Returns None
+/// if current is within the ceiling or no down-adjustment is possible.
+#[inline]
+pub fn ceiling_excess_target(current_size: i32, walrus_max: i32, floor: i32) -> Option<i32> {
+    if current_size <= walrus_max {
+        return None;
+    }
+    let target = walrus_max.max(floor);
+    (target < current_size).then_some(target)
+}
+
+/// Number of checkpoint count samples per checkpoint_timeout interval.
+///
+/// Returns 1 (one sample per interval) when `sample_interval_secs` is 0 or
+/// not smaller than `timeout_secs`; otherwise the number of sub-samples needed
+/// to cover the interval, rounded up.
+#[inline]
+pub fn samples_per_interval(timeout_secs: i64, sample_interval_secs: i32) -> i32 {
+    if sample_interval_secs <= 0 || sample_interval_secs as i64 >= timeout_secs {
+        return 1;
+    }
+    let samples = (timeout_secs + sample_interval_secs as i64 - 1) / sample_interval_secs as i64;
+    samples.min(i32::MAX as i64) as i32
+}
+
+/// State after accumulating one checkpoint count sub-sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleStep {
+    /// Delta carried into the next sub-sample (0 once a decision is made)
+    pub pending_delta: i64,
+    /// Sub-samples taken so far in the interval (0 once a decision is made)
+    pub sample_count: i32,
+    /// Accumulated delta to decide on, or None to keep sampling
+    pub decision_delta: Option<i64>,
+}
+
+/// Accumulate a sub-sample delta toward a grow/shrink decision.
+///
+/// A decision is made once a full interval of samples has been taken, or
+/// early as soon as the accumulated delta reaches `threshold`, so a burst is
+/// acted on without waiting for the rest of the interval. Shrink decisions
+/// therefore always cover a full interval.
+#[inline]
+pub fn accumulate_sample(
+    pending_delta: i64,
+    sample_count: i32,
+    sub_delta: i64,
+    samples_per_interval: i32,
+    threshold: i64,
+) -> SampleStep {
+    let total = pending_delta.saturating_add(sub_delta);
+    let count = sample_count.saturating_add(1);
+    if count >= samples_per_interval || total >= threshold {
+        SampleStep {
+            pending_delta: 0,
+            sample_count: 0,
+            decision_delta: Some(total),
+        }
+    } else {
+        SampleStep {
+            pending_delta: total,
+            sample_count: count,
+            decision_delta: None,
+        }
+    }
+}
+
+/// Number of recent interval deltas kept for walrus.use_rolling_delta.
+pub const DELTA_RING_SIZE: usize = 8;
+
+/// Record an interval delta in the ring of recent deltas.
+///
+/// `pos` is the next slot to write and `len` the number of filled slots.
+/// Returns the updated `(pos, len)`. Deltas are clamped to the i32 range
+/// (negative deltas count as 0).
+#[inline]
+pub fn push_delta(ring: &mut [i32; DELTA_RING_SIZE], pos: i32, len: i32, delta: i64) -> (i32, i32) {
+    let slot = pos.max(0) as usize % DELTA_RING_SIZE;
+    ring[slot] = delta.clamp(0, i32::MAX as i64) as i32;
+    let next = ((slot + 1) % DELTA_RING_SIZE) as i32;
+    (next, len.saturating_add(1).clamp(1, DELTA_RING_SIZE as i32))
+}
+
+/// Average of the filled slots of the ring of recent deltas, rounded down.
+///
+/// Until the ring has wrapped, the filled slots are the first `len`. Returns 0
+/// for an empty ring.
+#[inline]
+pub fn rolling_average(ring: &[i32; DELTA_RING_SIZE], len: i32) -> i64 {
+    let len = len.clamp(0, DELTA_RING_SIZE as i32) as usize;
+    if len == 0 {
+        return 0;
+    }
+    ring[..len].iter().map(|&d| d as i64).sum::<i64>() / len as i64
+}
+
+/// How an adjustment is handled under walrus.mode and walrus.dry_run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeGate {
+    /// Execute ALTER SYSTEM
+    Apply,
+    /// Record the decision as 'dry_run' without changing configuration
+    Record,
+    /// Direction disabled by walrus.mode; nothing is recorded
+    Skip,
+}
+
+/// Whether decisions are only recorded, never applied (dry-run or observe mode).
+#[inline]
+pub fn records_only(mode: WalrusMode, dry_run: bool) -> bool {
+    dry_run || mode == WalrusMode::Observe
+}
+
+/// Decide how a grow (`grow = true`) or shrink is handled.
+///
+/// grow_only skips shrinks and shrink_only skips grows. observe, like
+/// dry-run, records allowed decisions without applying them.
+#[inline]
+pub fn mode_gate(mode: WalrusMode, grow: bool, dry_run: bool) -> ModeGate {
+    let allowed = match mode {
+        WalrusMode::Both | WalrusMode::Observe => true,
+        WalrusMode::GrowOnly => grow,
+        WalrusMode::ShrinkOnly => !grow,
+    };
+    if !allowed {
+        ModeGate::Skip
+    } else if records_only(mode, dry_run) {
+        ModeGate::Record
+    } else {
+        ModeGate::Apply
+    }
+}
+
+/// Exclude ignored checkpoints from a forced checkpoint delta.
+///
+/// Subtracts up to `ignored` checkpoints from `delta`, never going below zero.
+/// Used to keep manual CHECKPOINT commands (registered via
+/// `walrus.ignore_next_checkpoints()`) from triggering growth.
+///
+/// # Returns
+///
+/// `(effective_delta, consumed)` where `consumed` is the number of ignored
+/// checkpoints that were subtracted and should be removed from the pending count.
+#[inline]
+pub fn apply_ignored_checkpoints(delta: i64, ignored: i64) -> (i64, i64) {
+    let consumed = ignored.clamp(0, delta.max(0));
+    (delta - consumed, consumed)
+}
+
+/// Check whether the WAL generated in an interval is low enough to be quiet.
+///
+/// Forced checkpoint counts alone can miss heavy WAL generation that stays
+/// just under max_wal_size. Returns true when the limit is disabled
+/// (`max_mb_per_interval` = 0) or `wal_bytes` is below the limit.
+#[inline]
+pub fn is_wal_quiet(wal_bytes: i64, max_mb_per_interval: i32) -> bool {
+    max_mb_per_interval == 0 || wal_bytes < max_mb_per_interval as i64 * 1024 * 1024
+}
+
+/// Quiet interval count after an interval with forced checkpoints below threshold.
+///
+/// Increments the count if WAL volume was also quiet, otherwise resets it to 0.
+#[inline]
+pub fn next_quiet_intervals(quiet_intervals: i32, wal_quiet: bool) -> i32 {
+    if wal_quiet { quiet_intervals + 1 } else { 0 }
+}
+
 /// Compute confidence score for a recommendation.
 ///
 /// Confidence calculation:
@@ -108,6 +563,39 @@ pub fn compute_confidence(state: &WalrusState, checkpoint_count: i64) -> i32 {
     confidence
 }
 
+/// Confidence for a prospective `action`: a 'decrease' loses `shrink_penalty`
+/// points (never below 0), grows and other actions keep `confidence`.
+#[inline]
+pub fn action_confidence(confidence: i32, action: &str, shrink_penalty: i32) -> i32 {
+    if action == "decrease" {
+        (confidence - shrink_penalty).max(0)
+    } else {
+        confidence
+    }
+}
+
+/// Normalize a forced checkpoint delta to a per-minute rate.
+///
+/// The delta accrues over one checkpoint_timeout window, so comparing raw
+/// deltas across different checkpoint_timeout values is misleading.
+///
+/// Formula: delta / (timeout_secs / 60)
+#[inline]
+pub fn forced_checkpoints_per_minute(delta: i64, timeout_secs: i32) -> f64 {
+    if timeout_secs <= 0 {
+        return 0.0;
+    }
+    delta as f64 / (timeout_secs as f64 / 60.0)
+}
+
+/// Check whether a confidence score passes a minimum confidence gate.
+///
+/// A `min_confidence` of 0 disables the gate (every score passes).
+#[inline]
+pub fn meets_confidence_threshold(confidence: i32, min_confidence: i32) -> bool {
+    confidence >= min_confidence
+}
+
 /// Compute a sizing recommendation based on current state and statistics.
 ///
 /// This function performs the same analysis as the background worker but
@@ -127,11 +615,31 @@ pub fn compute_confidence(state: &WalrusState, checkpoint_count: i64) -> i32 {
 /// - `"increase"`: Checkpoint activity warrants size increase
 /// - `"decrease"`: Sustained low activity warrants shrink
 /// - `"none"`: Current size is optimal
-/// - `"error"`: Cannot compute (stats unavailable or extension disabled)
+/// - `"error"`: Cannot compute (stats unavailable, extension disabled, or
+///   max_wal_size reads as zero or negative)
 pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
+    compute_recommendation_for_delta(state, None)
+}
+
+/// Compute a sizing recommendation, optionally for a hypothetical delta.
+///
+/// When `delta_override` is `Some`, the live checkpoint delta is bypassed and
+/// the given value is run through the same grow/shrink logic. This allows
+/// previewing decisions without waiting for real checkpoint activity.
+/// When `None`, behaves exactly like `compute_recommendation()`.
+///
+/// # Arguments
+///
+/// * `state` - Worker state (may be synthetic)
+/// * `delta_override` - Forced checkpoint delta to use instead of the live value
+pub fn compute_recommendation_for_delta(
+    state: &WalrusState,
+    delta_override: Option<i64>,
+) -> Recommendation {
     let current_size = get_current_max_wal_size();
-    let max_allowed = WALRUS_MAX.get();
-    let threshold = WALRUS_THRESHOLD.get() as i64;
+    let timeout_secs = checkpoint_timeout().as_secs() as i32;
+    let max_allowed = effective_max();
+    let threshold = effective_threshold();
 
     // Check if extension is enabled
     if !WALRUS_ENABLE.get() {
@@ -141,45 +649,96 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
             action: "error".to_string(),
             reason: "extension is disabled".to_string(),
             confidence: 0,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: None,
         };
     }
 
-    // Fetch current checkpoint count
-    let current_requested = get_requested_checkpoints();
-
-    // Handle stats unavailable
-    if current_requested < 0 {
+    // A nonsensical max_wal_size would produce a garbage target
+    if !is_usable_current_size(current_size) {
         return Recommendation {
             current_size_mb: current_size,
             recommended_size_mb: current_size,
             action: "error".to_string(),
-            reason: "checkpoint statistics unavailable".to_string(),
+            reason: format!("max_wal_size unavailable (reads as {} MB)", current_size),
             confidence: 0,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: None,
         };
     }
 
-    // Calculate confidence
+    // Fetch current checkpoint count
+    let current_requested = get_requested_checkpoints();
+
+    // Calculate confidence (0 if stats unavailable)
     let confidence = compute_confidence(state, current_requested);
 
-    // Calculate delta from previous count
-    // On first run (prev_requested = 0), delta will be the full current count
-    // which may be large; we handle this gracefully
-    let delta = if state.prev_requested > 0 {
-        current_requested - state.prev_requested
-    } else {
-        // First run: no baseline yet, cannot recommend grow/shrink
+    // walrus.max lowered below the current size: cap down without waiting for quiet intervals
+    let floor = shrink_floor(WALRUS_MIN_SIZE.get(), get_current_min_wal_size());
+    let ceiling = ceiling_max();
+    if let Some(target) = ceiling_excess_target(current_size, ceiling, floor) {
         return Recommendation {
             current_size_mb: current_size,
-            recommended_size_mb: current_size,
-            action: "none".to_string(),
-            reason: "awaiting baseline checkpoint count".to_string(),
-            confidence: confidence.min(50), // Cap confidence without baseline
+            recommended_size_mb: target,
+            action: "decrease".to_string(),
+            reason: format!(
+                "current ({} MB) exceeds lowered walrus.max ({} MB), recommend decrease to {} MB",
+                current_size, ceiling, target
+            ),
+            confidence,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: None,
         };
+    }
+
+    let delta = match delta_override {
+        // Hypothetical delta: skip live stats and baseline checks
+        Some(delta) => delta,
+        None => {
+            // Handle stats unavailable
+            if current_requested < 0 {
+                return Recommendation {
+                    current_size_mb: current_size,
+                    recommended_size_mb: current_size,
+                    action: "error".to_string(),
+                    reason: "checkpoint statistics unavailable".to_string(),
+                    confidence: 0,
+                    checkpoint_timeout_sec: timeout_secs,
+                    forced_checkpoints_per_minute: None,
+                };
+            }
+
+            // Calculate delta from previous count, plus any sub-samples accumulated
+            // so far this interval (walrus.sample_interval_sec)
+            // On first run (prev_requested = 0), delta will be the full current count
+            // which may be large; we handle this gracefully
+            let raw_delta = current_requested - state.prev_requested + state.pending_delta;
+            if state.prev_requested > 0
+                && !lacks_real_baseline(state.prev_requested, raw_delta, timeout_secs as i64)
+            {
+                apply_ignored_checkpoints(raw_delta, state.ignore_checkpoints).0
+            } else {
+                // First run, or a delta that is the counter's lifetime total:
+                // no baseline yet, cannot recommend grow/shrink
+                return Recommendation {
+                    current_size_mb: current_size,
+                    recommended_size_mb: current_size,
+                    action: "none".to_string(),
+                    reason: "awaiting baseline checkpoint count".to_string(),
+                    confidence: confidence.min(50), // Cap confidence without baseline
+                    checkpoint_timeout_sec: timeout_secs,
+                    forced_checkpoints_per_minute: None,
+                };
+            }
+        }
     };
 
-    // Check if delta exceeds threshold (grow path)
-    if delta >= threshold {
-        let calculated_size = calculate_new_size(current_size, delta);
+    // Normalize delta to a rate so it can be judged if checkpoint_timeout changed
+    let rate_per_minute = Some(forced_checkpoints_per_minute(delta, timeout_secs));
+
+    // Check if delta reaches threshold (grow path), per walrus.threshold_inclusive
+    if delta >= min_grow_delta(threshold, WALRUS_THRESHOLD_INCLUSIVE.get()) {
+        let calculated_size = calculate_new_size(current_size, delta, WALRUS_GROW_EXPONENT.get());
         let mut new_size = calculated_size;
         let is_capped = new_size > max_allowed;
 
@@ -198,6 +757,8 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
                     current_size, delta
                 ),
                 confidence,
+                checkpoint_timeout_sec: timeout_secs,
+                forced_checkpoints_per_minute: rate_per_minute,
             };
         }
 
@@ -219,13 +780,15 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
             action: "increase".to_string(),
             reason,
             confidence,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: rate_per_minute,
         };
     }
 
     // Check shrink conditions
     let shrink_enable = WALRUS_SHRINK_ENABLE.get();
-    let shrink_intervals = WALRUS_SHRINK_INTERVALS.get();
-    let min_size = WALRUS_MIN_SIZE.get();
+    let shrink_intervals = current_shrink_intervals(state);
+    let min_size = shrink_floor(WALRUS_MIN_SIZE.get(), get_current_min_wal_size());
 
     if !shrink_enable {
         return Recommendation {
@@ -237,6 +800,8 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
                 delta
             ),
             confidence,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: rate_per_minute,
         };
     }
 
@@ -253,6 +818,8 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
                 state.quiet_intervals, shrink_intervals
             ),
             confidence,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: rate_per_minute,
         };
     }
 
@@ -267,12 +834,25 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
                 current_size, state.quiet_intervals
             ),
             confidence,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: rate_per_minute,
         };
     }
 
-    // Calculate shrink target
-    let shrink_factor = WALRUS_SHRINK_FACTOR.get();
-    let new_size = calculate_shrink_size(current_size, shrink_factor, min_size);
+    // Calculate shrink target; 'peak_ratio' falls back to the factor without a peak
+    let peak_mb = match WALRUS_SHRINK_TARGET.get() {
+        ShrinkTarget::Factor => None,
+        ShrinkTarget::PeakRatio => crate::history::recent_peak_size_mb().unwrap_or(None),
+    };
+    let new_size = match peak_mb {
+        Some(peak) => peak_ratio_shrink_size(current_size, peak, WALRUS_PEAK_RATIO.get(), min_size),
+        None => calculate_shrink_size(
+            current_size,
+            WALRUS_SHRINK_FACTOR.get(),
+            min_size,
+            WALRUS_SHRINK_ROUNDING.get(),
+        ),
+    };
 
     // Check if shrink would actually reduce size
     if new_size >= current_size {
@@ -285,6 +865,8 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
                 new_size, current_size
             ),
             confidence,
+            checkpoint_timeout_sec: timeout_secs,
+            forced_checkpoints_per_minute: rate_per_minute,
         };
     }
 
@@ -296,231 +878,19 @@ pub fn compute_recommendation(state: &WalrusState) -> Recommendation {
             "{} quiet intervals, recommend decrease to {} MB",
             state.quiet_intervals, new_size
         ),
-        confidence,
+        confidence: action_confidence(
+            confidence,
+            "decrease",
+            WALRUS_SHRINK_CONFIDENCE_PENALTY.get(),
+        ),
+        checkpoint_timeout_sec: timeout_secs,
+        forced_checkpoints_per_minute: rate_per_minute,
     }
 }
 
 // Pure Rust unit tests (do not require PostgreSQL)
+// Tests are in a separate file to keep algorithm.rs under 900 LOC
 #[cfg(test)]
 mod tests {
-    use super::*;
-
-    // =========================================================================
-    // Tests for calculate_new_size (grow) - T012
-    // =========================================================================
-
-    /// Test that calculate_new_size follows the formula: current_size * (delta + 1)
-    #[test]
-    fn test_new_size_calculation() {
-        // 1024 MB with 3 forced checkpoints: 1024 * 4 = 4096
-        assert_eq!(calculate_new_size(1024, 3), 4096);
-
-        // 2048 MB with 1 forced checkpoint: 2048 * 2 = 4096
-        assert_eq!(calculate_new_size(2048, 1), 4096);
-
-        // 512 MB with 2 forced checkpoints: 512 * 3 = 1536
-        assert_eq!(calculate_new_size(512, 2), 1536);
-
-        // Minimum case: 1 MB with 0 delta (should not happen, but test anyway)
-        assert_eq!(calculate_new_size(1, 0), 1);
-    }
-
-    /// Test that calculate_new_size handles i32 overflow with saturating_mul
-    #[test]
-    fn test_overflow_protection() {
-        // Large base * large multiplier should saturate to i32::MAX
-        // i32::MAX / 2 = 1073741823, * 3 = 3221225469 which overflows
-        let result = calculate_new_size(i32::MAX / 2, 2);
-        assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
-
-        // i32::MAX * 2 overflows
-        let result = calculate_new_size(i32::MAX, 1);
-        assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
-
-        // 1_000_000_000 * 3 = 3_000_000_000 which overflows i32
-        let result = calculate_new_size(1_000_000_000, 2);
-        assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
-    }
-
-    // =========================================================================
-    // Tests for calculate_shrink_size (shrink) - T013
-    // =========================================================================
-
-    /// Test that calculate_shrink_size follows the formula: ceil(current_size * shrink_factor)
-    #[test]
-    fn test_shrink_size_normal() {
-        // 4096 MB * 0.75 = 3072.0 -> ceil = 3072
-        assert_eq!(calculate_shrink_size(4096, 0.75, 1024), 3072);
-
-        // 2048 MB * 0.75 = 1536.0 -> ceil = 1536
-        assert_eq!(calculate_shrink_size(2048, 0.75, 1024), 1536);
-
-        // 1536 MB * 0.75 = 1152.0 -> ceil = 1152
-        assert_eq!(calculate_shrink_size(1536, 0.75, 1024), 1152);
-    }
-
-    /// Test that calculate_shrink_size rounds up via f64::ceil()
-    #[test]
-    fn test_shrink_size_rounding_up() {
-        // 1001 MB * 0.75 = 750.75 -> ceil = 751
-        assert_eq!(calculate_shrink_size(1001, 0.75, 100), 751);
-
-        // 1000 MB * 0.75 = 750.0 -> ceil = 750
-        assert_eq!(calculate_shrink_size(1000, 0.75, 100), 750);
-
-        // 1003 MB * 0.75 = 752.25 -> ceil = 753
-        assert_eq!(calculate_shrink_size(1003, 0.75, 100), 753);
-
-        // Test with very small fraction
-        // 101 MB * 0.01 = 1.01 -> ceil = 2
-        assert_eq!(calculate_shrink_size(101, 0.01, 1), 2);
-    }
-
-    /// Test that calculate_shrink_size clamps to min_size
-    #[test]
-    fn test_shrink_size_clamped_to_min() {
-        // 2560 MB * 0.75 = 1920.0, but min_size is 2048 -> returns 2048
-        assert_eq!(calculate_shrink_size(2560, 0.75, 2048), 2048);
-
-        // 1024 MB * 0.75 = 768.0, but min_size is 1024 -> returns 1024
-        assert_eq!(calculate_shrink_size(1024, 0.75, 1024), 1024);
-
-        // 900 MB * 0.75 = 675.0, but min_size is 1024 -> returns 1024 (below floor)
-        assert_eq!(calculate_shrink_size(900, 0.75, 1024), 1024);
-    }
-
-    /// Test calculate_shrink_size with different shrink factors
-    #[test]
-    fn test_shrink_size_different_factors() {
-        // 4096 MB * 0.5 = 2048.0 (50% reduction)
-        assert_eq!(calculate_shrink_size(4096, 0.5, 1024), 2048);
-
-        // 4096 MB * 0.9 = 3686.4 -> ceil = 3687 (10% reduction)
-        assert_eq!(calculate_shrink_size(4096, 0.9, 1024), 3687);
-
-        // 4096 MB * 0.1 = 409.6 -> ceil = 410, but min_size 1024 -> 1024
-        assert_eq!(calculate_shrink_size(4096, 0.1, 1024), 1024);
-    }
-
-    /// Test large value edge case
-    #[test]
-    fn test_shrink_size_large_value() {
-        // i32::MAX * 0.99 should not overflow (shrink always produces smaller values)
-        let result = calculate_shrink_size(i32::MAX, 0.99, 1024);
-        // i32::MAX = 2147483647, * 0.99 = 2126008810.53 -> ceil = 2126008811
-        assert_eq!(result, 2126008811);
-        assert!(result < i32::MAX);
-    }
-
-    // =========================================================================
-    // Tests for compute_confidence - T012
-    // =========================================================================
-
-    /// Test confidence calculation with various state combinations
-    #[test]
-    fn test_compute_confidence_base() {
-        let state = WalrusState {
-            quiet_intervals: 0,
-            total_adjustments: 0,
-            prev_requested: 0,
-            last_check_time: 0,
-            last_adjustment_time: 0,
-            changes_this_hour: 0,
-            hour_window_start: 0,
-        };
-
-        // Base case: valid stats but no history
-        let confidence = compute_confidence(&state, 5);
-        assert_eq!(confidence, 50, "Base confidence should be 50");
-    }
-
-    /// Test confidence with sufficient checkpoint samples
-    #[test]
-    fn test_compute_confidence_with_samples() {
-        let state = WalrusState {
-            quiet_intervals: 0,
-            total_adjustments: 0,
-            prev_requested: 0,
-            last_check_time: 0,
-            last_adjustment_time: 0,
-            changes_this_hour: 0,
-            hour_window_start: 0,
-        };
-
-        // Checkpoint count > 10 adds 20
-        let confidence = compute_confidence(&state, 15);
-        assert_eq!(confidence, 70, "Should add 20 for checkpoint count > 10");
-    }
-
-    /// Test confidence with quiet intervals
-    #[test]
-    fn test_compute_confidence_with_quiet_intervals() {
-        let state = WalrusState {
-            quiet_intervals: 3,
-            total_adjustments: 0,
-            prev_requested: 0,
-            last_check_time: 0,
-            last_adjustment_time: 0,
-            changes_this_hour: 0,
-            hour_window_start: 0,
-        };
-
-        // quiet_intervals > 0 adds 15
-        let confidence = compute_confidence(&state, 5);
-        assert_eq!(confidence, 65, "Should add 15 for quiet_intervals > 0");
-    }
-
-    /// Test confidence with established baseline
-    #[test]
-    fn test_compute_confidence_with_baseline() {
-        let state = WalrusState {
-            quiet_intervals: 0,
-            total_adjustments: 0,
-            prev_requested: 100,
-            last_check_time: 0,
-            last_adjustment_time: 0,
-            changes_this_hour: 0,
-            hour_window_start: 0,
-        };
-
-        // prev_requested > 0 adds 15
-        let confidence = compute_confidence(&state, 5);
-        assert_eq!(confidence, 65, "Should add 15 for prev_requested > 0");
-    }
-
-    /// Test maximum confidence
-    #[test]
-    fn test_compute_confidence_maximum() {
-        let state = WalrusState {
-            quiet_intervals: 5,
-            total_adjustments: 10,
-            prev_requested: 100,
-            last_check_time: 1000,
-            last_adjustment_time: 900,
-            changes_this_hour: 0,
-            hour_window_start: 0,
-        };
-
-        // All conditions: 50 + 20 + 15 + 15 = 100
-        let confidence = compute_confidence(&state, 50);
-        assert_eq!(confidence, 100, "Maximum confidence should be 100");
-    }
-
-    /// Test confidence when stats unavailable
-    #[test]
-    fn test_compute_confidence_stats_unavailable() {
-        let state = WalrusState {
-            quiet_intervals: 5,
-            total_adjustments: 10,
-            prev_requested: 100,
-            last_check_time: 1000,
-            last_adjustment_time: 900,
-            changes_this_hour: 0,
-            hour_window_start: 0,
-        };
-
-        // Stats unavailable (-1) returns 0
-        let confidence = compute_confidence(&state, -1);
-        assert_eq!(confidence, 0, "Should return 0 when stats unavailable");
-    }
+    include!("algorithm_tests.rs");
 }
@@ -0,0 +1,867 @@
+use super::*;
+
+// =========================================================================
+// Tests for calculate_new_size (grow) - T012
+// =========================================================================
+
+/// Test that calculate_new_size follows the formula: current_size * (delta + 1)
+#[test]
+fn test_new_size_calculation() {
+    // 1024 MB with 3 forced checkpoints: 1024 * 4 = 4096
+    assert_eq!(calculate_new_size(1024, 3, 1.0), 4096);
+
+    // 2048 MB with 1 forced checkpoint: 2048 * 2 = 4096
+    assert_eq!(calculate_new_size(2048, 1, 1.0), 4096);
+
+    // 512 MB with 2 forced checkpoints: 512 * 3 = 1536
+    assert_eq!(calculate_new_size(512, 2, 1.0), 1536);
+
+    // Minimum case: 1 MB with 0 delta (should not happen, but test anyway)
+    assert_eq!(calculate_new_size(1, 0, 1.0), 1);
+}
+
+/// Test that calculate_new_size handles i32 overflow with saturating_mul
+#[test]
+fn test_overflow_protection() {
+    // Large base * large multiplier should saturate to i32::MAX
+    // i32::MAX / 2 = 1073741823, * 3 = 3221225469 which overflows
+    let result = calculate_new_size(i32::MAX / 2, 2, 1.0);
+    assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
+
+    // i32::MAX * 2 overflows
+    let result = calculate_new_size(i32::MAX, 1, 1.0);
+    assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
+
+    // 1_000_000_000 * 3 = 3_000_000_000 which overflows i32
+    let result = calculate_new_size(1_000_000_000, 2, 1.0);
+    assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
+}
+
+/// Test deltas beyond i32::MAX saturate instead of wrapping the multiplier
+#[test]
+fn test_huge_delta_saturates() {
+    // (2^32 + 1) as i32 would wrap to 1, and (i32::MAX + 1) as i32 to i32::MIN
+    assert_eq!(calculate_new_size(1024, 1 << 32, 1.0), i32::MAX);
+    assert_eq!(calculate_new_size(1024, i32::MAX as i64, 1.0), i32::MAX);
+    assert_eq!(calculate_new_size(1, i32::MAX as i64 + 5, 1.0), i32::MAX);
+    assert_eq!(calculate_new_size(1024, i64::MAX, 1.0), i32::MAX);
+}
+
+// =========================================================================
+// Tests for calculate_shrink_size (shrink) - T013
+// =========================================================================
+
+/// Test that calculate_shrink_size follows the formula: ceil(current_size * shrink_factor)
+#[test]
+fn test_shrink_size_normal() {
+    // 4096 MB * 0.75 = 3072.0 -> ceil = 3072
+    assert_eq!(calculate_shrink_size(4096, 0.75, 1024, ShrinkRounding::Up), 3072);
+
+    // 2048 MB * 0.75 = 1536.0 -> ceil = 1536
+    assert_eq!(calculate_shrink_size(2048, 0.75, 1024, ShrinkRounding::Up), 1536);
+
+    // 1536 MB * 0.75 = 1152.0 -> ceil = 1152
+    assert_eq!(calculate_shrink_size(1536, 0.75, 1024, ShrinkRounding::Up), 1152);
+}
+
+/// Test that calculate_shrink_size rounds up via f64::ceil()
+#[test]
+fn test_shrink_size_rounding_up() {
+    // 1001 MB * 0.75 = 750.75 -> ceil = 751
+    assert_eq!(calculate_shrink_size(1001, 0.75, 100, ShrinkRounding::Up), 751);
+
+    // 1000 MB * 0.75 = 750.0 -> ceil = 750
+    assert_eq!(calculate_shrink_size(1000, 0.75, 100, ShrinkRounding::Up), 750);
+
+    // 1003 MB * 0.75 = 752.25 -> ceil = 753
+    assert_eq!(calculate_shrink_size(1003, 0.75, 100, ShrinkRounding::Up), 753);
+
+    // Test with very small fraction
+    // 101 MB * 0.01 = 1.01 -> ceil = 2
+    assert_eq!(calculate_shrink_size(101, 0.01, 1, ShrinkRounding::Up), 2);
+}
+
+/// Test that calculate_shrink_size clamps to min_size
+#[test]
+fn test_shrink_size_clamped_to_min() {
+    // 2560 MB * 0.75 = 1920.0, but min_size is 2048 -> returns 2048
+    assert_eq!(calculate_shrink_size(2560, 0.75, 2048, ShrinkRounding::Up), 2048);
+
+    // 1024 MB * 0.75 = 768.0, but min_size is 1024 -> returns 1024
+    assert_eq!(calculate_shrink_size(1024, 0.75, 1024, ShrinkRounding::Up), 1024);
+
+    // 900 MB * 0.75 = 675.0, but min_size is 1024 -> returns 1024 (below floor)
+    assert_eq!(calculate_shrink_size(900, 0.75, 1024, ShrinkRounding::Up), 1024);
+}
+
+/// Test calculate_shrink_size with different shrink factors
+#[test]
+fn test_shrink_size_different_factors() {
+    // 4096 MB * 0.5 = 2048.0 (50% reduction)
+    assert_eq!(calculate_shrink_size(4096, 0.5, 1024, ShrinkRounding::Up), 2048);
+
+    // 4096 MB * 0.9 = 3686.4 -> ceil = 3687 (10% reduction)
+    assert_eq!(calculate_shrink_size(4096, 0.9, 1024, ShrinkRounding::Up), 3687);
+
+    // 4096 MB * 0.1 = 409.6 -> ceil = 410, but min_size 1024 -> 1024
+    assert_eq!(calculate_shrink_size(4096, 0.1, 1024, ShrinkRounding::Up), 1024);
+}
+
+/// Test large value edge case
+#[test]
+fn test_shrink_size_large_value() {
+    // i32::MAX * 0.99 should not overflow (shrink always produces smaller values)
+    let result = calculate_shrink_size(i32::MAX, 0.99, 1024, ShrinkRounding::Up);
+    // i32::MAX = 2147483647, * 0.99 = 2126008810.53 -> ceil = 2126008811
+    assert_eq!(result, 2126008811);
+    assert!(result < i32::MAX);
+}
+
+// =========================================================================
+// Tests for shrink_floor
+// =========================================================================
+
+/// Test that min_wal_size is the binding floor when above walrus.min_size
+#[test]
+fn test_shrink_floor_min_wal_size_binding() {
+    // walrus.min_size 256 MB, min_wal_size 512 MB: floor is 512
+    assert_eq!(shrink_floor(256, 512), 512);
+
+    // Shrink target is clamped to the min_wal_size floor
+    let floor = shrink_floor(256, 512);
+    assert_eq!(calculate_shrink_size(600, 0.5, floor, ShrinkRounding::Up), 512);
+}
+
+/// Test that walrus.min_size is the binding floor when above min_wal_size
+#[test]
+fn test_shrink_floor_walrus_min_size_binding() {
+    assert_eq!(shrink_floor(1024, 80), 1024);
+    assert_eq!(shrink_floor(1024, 1024), 1024);
+}
+
+// =========================================================================
+// Tests for shrink_is_stuck and min_step_shrink_size
+// =========================================================================
+
+/// Test a factor that rounds back up to the current size is detected as stuck
+#[test]
+fn test_shrink_stuck_at_factor() {
+    // ceil(50 * 0.99) = ceil(49.5) = 50: the shrink can never make progress
+    assert_eq!(calculate_shrink_size(50, 0.99, 32, ShrinkRounding::Up), 50);
+    assert!(shrink_is_stuck(50, 0.99, 32, ShrinkRounding::Up));
+
+    // A larger size or smaller factor does shrink
+    assert!(!shrink_is_stuck(1024, 0.99, 32, ShrinkRounding::Up));
+    assert!(!shrink_is_stuck(50, 0.75, 32, ShrinkRounding::Up));
+
+    // At the floor there is nothing to shrink, so it is not "stuck"
+    assert!(!shrink_is_stuck(32, 0.99, 32, ShrinkRounding::Up));
+
+    // Rounding down always makes progress; nearest can stall like up
+    assert_eq!(calculate_shrink_size(50, 0.99, 32, ShrinkRounding::Down), 49);
+    assert!(!shrink_is_stuck(50, 0.99, 32, ShrinkRounding::Down));
+    assert!(shrink_is_stuck(50, 0.995, 32, ShrinkRounding::Nearest));
+}
+
+// =========================================================================
+// Tests for estimated_intervals_to_ceiling
+// =========================================================================
+
+/// Test a steady positive delta gives a finite estimate that falls as size nears the ceiling
+#[test]
+fn test_estimated_intervals_to_ceiling_decreases() {
+    // delta 1 doubles the size each interval: 1024 -> 2048 -> 4096 -> 8192 -> 16384
+    let estimates: Vec<Option<i64>> = [1024, 2048, 4096, 8192]
+        .iter()
+        .map(|&size| estimated_intervals_to_ceiling(size, 1, 1, 16384, 1.0))
+        .collect();
+    assert_eq!(estimates, vec![Some(4), Some(3), Some(2), Some(1)]);
+
+    // A steeper trend reaches the ceiling sooner; fractional exponents still finish
+    assert_eq!(estimated_intervals_to_ceiling(1024, 3, 1, 16384, 1.0), Some(2));
+    assert!(estimated_intervals_to_ceiling(1024, 3, 1, 16384, 0.5).is_some());
+
+    // At or above the ceiling
+    assert_eq!(estimated_intervals_to_ceiling(16384, 1, 1, 16384, 1.0), Some(0));
+}
+
+/// Test no estimate when the recent rate would not grow
+#[test]
+fn test_estimated_intervals_to_ceiling_flat() {
+    assert_eq!(estimated_intervals_to_ceiling(1024, 0, 2, 16384, 1.0), None);
+    assert_eq!(estimated_intervals_to_ceiling(1024, 1, 2, 16384, 1.0), None);
+    assert_eq!(estimated_intervals_to_ceiling(1024, 0, 0, 16384, 1.0), None);
+    assert_eq!(estimated_intervals_to_ceiling(0, 5, 2, 16384, 1.0), None);
+}
+
+// =========================================================================
+// Tests for walrus.shrink_rounding
+// =========================================================================
+
+/// Test each rounding mode against the same fractional target
+#[test]
+fn test_shrink_rounding_modes() {
+    // 1001 * 0.75 = 750.75
+    assert_eq!(calculate_shrink_size(1001, 0.75, 100, ShrinkRounding::Up), 751);
+    assert_eq!(calculate_shrink_size(1001, 0.75, 100, ShrinkRounding::Down), 750);
+    assert_eq!(calculate_shrink_size(1001, 0.75, 100, ShrinkRounding::Nearest), 751);
+
+    // 1003 * 0.75 = 752.25
+    assert_eq!(calculate_shrink_size(1003, 0.75, 100, ShrinkRounding::Up), 753);
+    assert_eq!(calculate_shrink_size(1003, 0.75, 100, ShrinkRounding::Down), 752);
+    assert_eq!(calculate_shrink_size(1003, 0.75, 100, ShrinkRounding::Nearest), 752);
+
+    // Exact results are unaffected by the mode
+    for rounding in [ShrinkRounding::Up, ShrinkRounding::Down, ShrinkRounding::Nearest] {
+        assert_eq!(calculate_shrink_size(1000, 0.75, 100, rounding), 750);
+    }
+}
+
+/// Test every rounding mode still clamps to the floor and strictly reduces above it
+#[test]
+fn test_shrink_rounding_clamps_and_reduces() {
+    for rounding in [ShrinkRounding::Up, ShrinkRounding::Down, ShrinkRounding::Nearest] {
+        assert_eq!(calculate_shrink_size(1001, 0.75, 800, rounding), 800);
+        assert_eq!(calculate_shrink_size(900, 0.75, 1024, rounding), 1024);
+        assert!(calculate_shrink_size(4096, 0.9, 1024, rounding) < 4096);
+    }
+}
+
+/// Test the peak_ratio target is clamped between the floor and the current size
+#[test]
+fn test_peak_ratio_shrink_size() {
+    assert_eq!(peak_ratio_shrink_size(4096, 1200, 1.5, 1024), 1800);
+    assert_eq!(peak_ratio_shrink_size(4096, 1001, 1.5, 1024), 1502, "rounded up");
+    assert_eq!(peak_ratio_shrink_size(4096, 500, 1.5, 1024), 1024, "never below the floor");
+    assert_eq!(peak_ratio_shrink_size(1500, 1200, 1.5, 1024), 1500, "never above current");
+    assert_eq!(peak_ratio_shrink_size(4096, i32::MAX, 10.0, 1024), 4096);
+}
+
+/// Test a lifetime-sized delta is recognized as having no real baseline
+#[test]
+fn test_lacks_real_baseline() {
+    assert!(lacks_real_baseline(1, 1_000_000, 300), "tiny baseline, huge counter");
+    assert!(lacks_real_baseline(0, 5_000, 300), "baseline cleared by walrus.reset()");
+    assert!(!lacks_real_baseline(0, 10, 300), "a fresh cluster's first burst is real");
+    assert!(!lacks_real_baseline(1_000_000, 5_000, 300), "a storm on a busy server is real");
+    assert!(!lacks_real_baseline(1, 300, 300), "at most one per second is plausible");
+}
+
+/// Test walrus.threshold_inclusive moves the grow edge by one checkpoint
+#[test]
+fn test_min_grow_delta() {
+    assert_eq!(min_grow_delta(2, true), 2, "inclusive: delta == threshold grows");
+    assert_eq!(min_grow_delta(2, false), 3, "exclusive: delta must exceed threshold");
+    assert_eq!(min_grow_delta(i64::MAX, false), i64::MAX);
+}
+
+/// Test the forced one-segment shrink target respects the floor
+#[test]
+fn test_min_step_shrink_size() {
+    assert_eq!(min_step_shrink_size(50, 16, 32), 34);
+    assert_eq!(min_step_shrink_size(40, 16, 32), 32, "clamped to the floor");
+    assert_eq!(min_step_shrink_size(50, 0, 32), 49, "step of at least 1 MB");
+    assert!(min_step_shrink_size(50, 16, 32) < 50, "always makes progress above the floor");
+}
+
+/// Test a resize that rounding collapses back to the current size is a no-op
+#[test]
+fn test_is_noop_resize() {
+    let target = calculate_shrink_size(50, 0.99, 32, ShrinkRounding::Up);
+    assert!(is_noop_resize(50, target), "ceil(49.5) leaves the size at 50 MB");
+    assert!(!is_noop_resize(50, calculate_shrink_size(50, 0.99, 32, ShrinkRounding::Down)));
+    assert!(!is_noop_resize(1024, 2048));
+    assert!(is_noop_resize(4096, 4096));
+}
+
+/// Test shrinks are blocked for exactly walrus.grow_protect_intervals intervals after a grow
+#[test]
+fn test_grow_protected() {
+    // Grow applied in interval 10, protection for 3 intervals
+    assert!(grow_protected(11, 10, 3));
+    assert!(grow_protected(12, 10, 3));
+    assert!(grow_protected(13, 10, 3));
+    assert!(!grow_protected(14, 10, 3), "the fourth interval after the grow may shrink");
+    assert!(!grow_protected(11, 10, 0), "0 disables the protection");
+    assert!(!grow_protected(2, 0, 3), "no grow since start or reset");
+}
+
+/// Test a change aborts when another max_wal_size was written after it was computed
+#[test]
+fn test_is_stale_change() {
+    assert!(!is_stale_change(4096, 0), "no pending write: apply");
+    assert!(!is_stale_change(4096, 4096), "pending write of the planned size");
+    // An intervening grow, or a manual size at or below the target
+    assert!(is_stale_change(4096, 8192));
+    assert!(is_stale_change(4096, 2048));
+}
+
+// =========================================================================
+// Tests for is_usable_current_size
+// =========================================================================
+
+/// Test a zero or negative max_wal_size is rejected instead of producing a garbage target
+#[test]
+fn test_zero_current_size_is_not_usable() {
+    // Without the guard, a zero reading yields a 0 MB grow target
+    assert_eq!(calculate_new_size(0, 3, 1.0), 0);
+
+    assert!(!is_usable_current_size(0));
+    assert!(!is_usable_current_size(-1));
+    assert!(!is_usable_current_size(i32::MIN));
+    assert!(is_usable_current_size(1));
+    assert!(is_usable_current_size(1024));
+}
+
+// =========================================================================
+// Tests for apply_ignored_checkpoints
+// =========================================================================
+
+/// Test that ignored checkpoints reduce the effective delta
+#[test]
+fn test_apply_ignored_checkpoints_reduces_delta() {
+    // 3 forced checkpoints, 2 of them manual: effective delta 1
+    assert_eq!(apply_ignored_checkpoints(3, 2), (1, 2));
+
+    // Exactly matching counts cancel out
+    assert_eq!(apply_ignored_checkpoints(4, 4), (0, 4));
+
+    // No pending ignores leaves delta unchanged
+    assert_eq!(apply_ignored_checkpoints(5, 0), (5, 0));
+}
+
+/// Test that ignored checkpoints never drive delta negative
+#[test]
+fn test_apply_ignored_checkpoints_clamps_to_zero() {
+    // More ignores pending than checkpoints observed: consume only what was seen
+    assert_eq!(apply_ignored_checkpoints(2, 5), (0, 2));
+
+    // Zero delta consumes nothing, so the pending count carries over
+    assert_eq!(apply_ignored_checkpoints(0, 3), (0, 0));
+}
+
+// =========================================================================
+// Tests for compute_confidence - T012
+// =========================================================================
+
+/// Test confidence calculation with various state combinations
+#[test]
+fn test_compute_confidence_base() {
+    let state = WalrusState {
+        quiet_intervals: 0,
+        total_adjustments: 0,
+        prev_requested: 0,
+        last_check_time: 0,
+        last_adjustment_time: 0,
+        changes_this_hour: 0,
+        hour_window_start: 0,
+        ..Default::default()
+    };
+
+    // Base case: valid stats but no history
+    let confidence = compute_confidence(&state, 5);
+    assert_eq!(confidence, 50, "Base confidence should be 50");
+}
+
+/// Test confidence with sufficient checkpoint samples
+#[test]
+fn test_compute_confidence_with_samples() {
+    let state = WalrusState {
+        quiet_intervals: 0,
+        total_adjustments: 0,
+        prev_requested: 0,
+        last_check_time: 0,
+        last_adjustment_time: 0,
+        changes_this_hour: 0,
+        hour_window_start: 0,
+        ..Default::default()
+    };
+
+    // Checkpoint count > 10 adds 20
+    let confidence = compute_confidence(&state, 15);
+    assert_eq!(confidence, 70, "Should add 20 for checkpoint count > 10");
+}
+
+/// Test confidence with quiet intervals
+#[test]
+fn test_compute_confidence_with_quiet_intervals() {
+    let state = WalrusState {
+        quiet_intervals: 3,
+        total_adjustments: 0,
+        prev_requested: 0,
+        last_check_time: 0,
+        last_adjustment_time: 0,
+        changes_this_hour: 0,
+        hour_window_start: 0,
+        ..Default::default()
+    };
+
+    // quiet_intervals > 0 adds 15
+    let confidence = compute_confidence(&state, 5);
+    assert_eq!(confidence, 65, "Should add 15 for quiet_intervals > 0");
+}
+
+/// Test confidence with established baseline
+#[test]
+fn test_compute_confidence_with_baseline() {
+    let state = WalrusState {
+        quiet_intervals: 0,
+        total_adjustments: 0,
+        prev_requested: 100,
+        last_check_time: 0,
+        last_adjustment_time: 0,
+        changes_this_hour: 0,
+        hour_window_start: 0,
+        ..Default::default()
+    };
+
+    // prev_requested > 0 adds 15
+    let confidence = compute_confidence(&state, 5);
+    assert_eq!(confidence, 65, "Should add 15 for prev_requested > 0");
+}
+
+/// Test maximum confidence
+#[test]
+fn test_compute_confidence_maximum() {
+    let state = WalrusState {
+        quiet_intervals: 5,
+        total_adjustments: 10,
+        prev_requested: 100,
+        last_check_time: 1000,
+        last_adjustment_time: 900,
+        changes_this_hour: 0,
+        hour_window_start: 0,
+        ..Default::default()
+    };
+
+    // All conditions: 50 + 20 + 15 + 15 = 100
+    let confidence = compute_confidence(&state, 50);
+    assert_eq!(confidence, 100, "Maximum confidence should be 100");
+}
+
+/// Test confidence when stats unavailable
+#[test]
+fn test_compute_confidence_stats_unavailable() {
+    let state = WalrusState {
+        quiet_intervals: 5,
+        total_adjustments: 10,
+        prev_requested: 100,
+        last_check_time: 1000,
+        last_adjustment_time: 900,
+        changes_this_hour: 0,
+        hour_window_start: 0,
+        ..Default::default()
+    };
+
+    // Stats unavailable (-1) returns 0
+    let confidence = compute_confidence(&state, -1);
+    assert_eq!(confidence, 0, "Should return 0 when stats unavailable");
+}
+
+/// Test the same state scores a shrink lower than a grow once a penalty is set
+#[test]
+fn test_action_confidence_shrink_penalty() {
+    let state = WalrusState {
+        quiet_intervals: 5,
+        prev_requested: 100,
+        ..Default::default()
+    };
+    let confidence = compute_confidence(&state, 50);
+
+    let grow = action_confidence(confidence, "increase", 20);
+    let shrink = action_confidence(confidence, "decrease", 20);
+    assert_eq!(grow, 100);
+    assert_eq!(shrink, 80);
+    assert!(shrink < grow);
+
+    assert_eq!(action_confidence(confidence, "decrease", 0), grow, "no penalty by default");
+    assert_eq!(action_confidence(10, "decrease", 20), 0, "never below 0");
+}
+
+// =========================================================================
+// Tests for meets_confidence_threshold
+// =========================================================================
+
+/// Test that a low-confidence state is blocked by the gate
+#[test]
+fn test_confidence_gate_blocks_low_confidence() {
+    // No baseline, no quiet intervals, few samples: base confidence 50
+    let state = WalrusState::default();
+    let confidence = compute_confidence(&state, 5);
+    assert_eq!(confidence, 50);
+    assert!(!meets_confidence_threshold(confidence, 80));
+}
+
+/// Test that a high-confidence state passes the gate
+#[test]
+fn test_confidence_gate_allows_high_confidence() {
+    let state = WalrusState {
+        quiet_intervals: 3,
+        prev_requested: 100,
+        ..Default::default()
+    };
+    let confidence = compute_confidence(&state, 50);
+    assert_eq!(confidence, 100);
+    assert!(meets_confidence_threshold(confidence, 80));
+
+    // Boundary: equal to the threshold passes
+    assert!(meets_confidence_threshold(80, 80));
+}
+
+/// Test that min_confidence = 0 disables the gate
+#[test]
+fn test_confidence_gate_disabled_at_zero() {
+    assert!(meets_confidence_threshold(0, 0));
+    assert!(meets_confidence_threshold(50, 0));
+}
+
+// =========================================================================
+// Tests for forced_checkpoints_per_minute
+// =========================================================================
+
+/// Test that the normalized rate equals delta / (timeout / 60)
+#[test]
+fn test_forced_checkpoints_per_minute() {
+    // 10 forced checkpoints over the default 5 minute timeout: 2 per minute
+    assert_eq!(forced_checkpoints_per_minute(10, 300), 2.0);
+
+    // Same delta over a 10 minute timeout: 1 per minute
+    assert_eq!(forced_checkpoints_per_minute(10, 600), 1.0);
+
+    // 3 forced checkpoints over 30 seconds: 6 per minute
+    assert_eq!(forced_checkpoints_per_minute(3, 30), 6.0);
+
+    // No activity
+    assert_eq!(forced_checkpoints_per_minute(0, 300), 0.0);
+}
+
+/// Test that a non-positive timeout does not divide by zero
+#[test]
+fn test_forced_checkpoints_per_minute_zero_timeout() {
+    assert_eq!(forced_checkpoints_per_minute(5, 0), 0.0);
+}
+
+// =========================================================================
+// Tests for WAL volume quiet detection
+// =========================================================================
+
+/// Test that a zero limit disables the WAL volume check
+#[test]
+fn test_wal_quiet_disabled() {
+    assert!(is_wal_quiet(0, 0));
+    assert!(is_wal_quiet(i64::MAX, 0));
+}
+
+/// Test WAL volume below and at the limit
+#[test]
+fn test_wal_quiet_limit() {
+    let limit_mb = 64;
+    let limit_bytes = 64 * 1024 * 1024;
+    assert!(is_wal_quiet(0, limit_mb));
+    assert!(is_wal_quiet(limit_bytes - 1, limit_mb));
+    assert!(!is_wal_quiet(limit_bytes, limit_mb));
+}
+
+/// Test that quiet intervals accumulate only when WAL volume is quiet
+#[test]
+fn test_quiet_intervals_with_wal_volume() {
+    let limit_mb = 64;
+
+    // Low WAL volume: quiet intervals accumulate
+    let quiet = next_quiet_intervals(3, is_wal_quiet(1024 * 1024, limit_mb));
+    assert_eq!(quiet, 4);
+
+    // High WAL byte delta: quiet intervals reset instead of accumulating
+    let high_wal_bytes = 10 * 1024 * 1024 * 1024_i64;
+    let quiet = next_quiet_intervals(3, is_wal_quiet(high_wal_bytes, limit_mb));
+    assert_eq!(quiet, 0);
+
+    // Repeated high-churn intervals never accumulate
+    let mut quiet = 0;
+    for _ in 0..10 {
+        quiet = next_quiet_intervals(quiet, is_wal_quiet(high_wal_bytes, limit_mb));
+    }
+    assert_eq!(quiet, 0);
+}
+
+// =========================================================================
+// Tests for ceiling_excess_target
+// =========================================================================
+
+/// Test that lowering walrus.max below current produces a cap-down to walrus.max
+#[test]
+fn test_ceiling_excess_caps_down() {
+    // current=8192, walrus.max=4096: down-adjust to 4096
+    assert_eq!(ceiling_excess_target(8192, 4096, 1024), Some(4096));
+}
+
+/// Test that no adjustment happens when current is within walrus.max
+#[test]
+fn test_ceiling_excess_within_max() {
+    assert_eq!(ceiling_excess_target(4096, 4096, 1024), None);
+    assert_eq!(ceiling_excess_target(2048, 4096, 1024), None);
+}
+
+/// Test that the cap-down target respects the shrink floor
+#[test]
+fn test_ceiling_excess_respects_floor() {
+    // walrus.max below the floor: target is the floor
+    assert_eq!(ceiling_excess_target(8192, 512, 1024), Some(1024));
+    // Floor at or above current: no down-adjustment possible
+    assert_eq!(ceiling_excess_target(1024, 512, 1024), None);
+}
+
+// =========================================================================
+// Tests for samples_per_interval and accumulate_sample
+// =========================================================================
+
+/// Test sample counts per checkpoint_timeout interval
+#[test]
+fn test_samples_per_interval() {
+    // Disabled or not smaller than checkpoint_timeout: one sample per interval
+    assert_eq!(samples_per_interval(300, 0), 1);
+    assert_eq!(samples_per_interval(300, 300), 1);
+    assert_eq!(samples_per_interval(300, 600), 1);
+    // Evenly divides
+    assert_eq!(samples_per_interval(300, 60), 5);
+    // Rounds up to cover the whole interval
+    assert_eq!(samples_per_interval(300, 70), 5);
+    assert_eq!(samples_per_interval(300, 1), 300);
+}
+
+/// Test deltas accumulate across sub-samples until the interval completes
+#[test]
+fn test_accumulate_sample_across_subsamples() {
+    // checkpoint_timeout=300, sample_interval_sec=60: 5 samples, threshold=10
+    let spi = samples_per_interval(300, 60);
+    let threshold = 10;
+
+    let step = accumulate_sample(0, 0, 1, spi, threshold);
+    assert_eq!(step.pending_delta, 1);
+    assert_eq!(step.sample_count, 1);
+    assert_eq!(step.decision_delta, None);
+
+    let step = accumulate_sample(step.pending_delta, step.sample_count, 2, spi, threshold);
+    assert_eq!(step.pending_delta, 3);
+    assert_eq!(step.sample_count, 2);
+    assert_eq!(step.decision_delta, None);
+
+    let step = accumulate_sample(step.pending_delta, step.sample_count, 0, spi, threshold);
+    let step = accumulate_sample(step.pending_delta, step.sample_count, 4, spi, threshold);
+    assert_eq!(step.pending_delta, 7);
+    assert_eq!(step.sample_count, 4);
+    assert_eq!(step.decision_delta, None);
+
+    // Fifth sample completes the interval: decide on the accumulated delta and reset
+    let step = accumulate_sample(step.pending_delta, step.sample_count, 1, spi, threshold);
+    assert_eq!(step.decision_delta, Some(8));
+    assert_eq!(step.pending_delta, 0);
+    assert_eq!(step.sample_count, 0);
+}
+
+/// Test a mid-interval burst reaching the threshold decides early
+#[test]
+fn test_accumulate_sample_early_decision_on_burst() {
+    let spi = samples_per_interval(300, 60);
+    let step = accumulate_sample(0, 0, 1, spi, 3);
+    assert_eq!(step.decision_delta, None);
+    let step = accumulate_sample(step.pending_delta, step.sample_count, 2, spi, 3);
+    assert_eq!(step.decision_delta, Some(3));
+    assert_eq!(step.pending_delta, 0);
+    assert_eq!(step.sample_count, 0);
+}
+
+/// Test that without sub-sampling every sample is a decision
+#[test]
+fn test_accumulate_sample_single_sample_interval() {
+    let step = accumulate_sample(0, 0, 0, 1, 2);
+    assert_eq!(step.decision_delta, Some(0));
+    let step = accumulate_sample(0, 0, 5, 1, 2);
+    assert_eq!(step.decision_delta, Some(5));
+}
+
+/// Test accumulation saturates instead of overflowing
+#[test]
+fn test_accumulate_sample_saturates() {
+    let step = accumulate_sample(i64::MAX, 1, 5, 5, i64::MAX);
+    assert_eq!(step.decision_delta, Some(i64::MAX));
+}
+
+// =========================================================================
+// Tests for mode_gate and records_only
+// =========================================================================
+
+/// Test 'both' applies grows and shrinks
+#[test]
+fn test_mode_gate_both() {
+    assert_eq!(mode_gate(WalrusMode::Both, true, false), ModeGate::Apply);
+    assert_eq!(mode_gate(WalrusMode::Both, false, false), ModeGate::Apply);
+}
+
+/// Test 'grow_only' applies grows and skips shrinks
+#[test]
+fn test_mode_gate_grow_only() {
+    assert_eq!(mode_gate(WalrusMode::GrowOnly, true, false), ModeGate::Apply);
+    assert_eq!(mode_gate(WalrusMode::GrowOnly, false, false), ModeGate::Skip);
+}
+
+/// Test 'shrink_only' applies shrinks and skips grows
+#[test]
+fn test_mode_gate_shrink_only() {
+    assert_eq!(mode_gate(WalrusMode::ShrinkOnly, true, false), ModeGate::Skip);
+    assert_eq!(mode_gate(WalrusMode::ShrinkOnly, false, false), ModeGate::Apply);
+}
+
+/// Test 'observe' only records, in both directions
+#[test]
+fn test_mode_gate_observe() {
+    assert_eq!(mode_gate(WalrusMode::Observe, true, false), ModeGate::Record);
+    assert_eq!(mode_gate(WalrusMode::Observe, false, false), ModeGate::Record);
+    assert!(records_only(WalrusMode::Observe, false));
+}
+
+/// Test walrus.dry_run records allowed decisions and does not re-enable skipped ones
+#[test]
+fn test_mode_gate_with_dry_run() {
+    assert_eq!(mode_gate(WalrusMode::Both, true, true), ModeGate::Record);
+    assert_eq!(mode_gate(WalrusMode::GrowOnly, true, true), ModeGate::Record);
+    assert_eq!(mode_gate(WalrusMode::GrowOnly, false, true), ModeGate::Skip);
+    assert!(records_only(WalrusMode::Both, true));
+    assert!(!records_only(WalrusMode::Both, false));
+}
+
+// =========================================================================
+// Tests for walrus.grow_exponent
+// =========================================================================
+
+/// Test exponent 1.0 reproduces current_size * (delta + 1) exactly
+#[test]
+fn test_grow_exponent_one_matches_linear_formula() {
+    let cases = [
+        (1024, 1, 2048),
+        (1024, 2, 3072),
+        (1024, 3, 4096),
+        (2048, 5, 12288),
+        (512, 10, 5632),
+        (100, 0, 100),
+    ];
+    for (current, delta, expected) in cases {
+        assert_eq!(calculate_new_size(current, delta, 1.0), expected);
+        assert_eq!(
+            calculate_new_size(current, delta, 1.0),
+            current * (delta as i32 + 1)
+        );
+    }
+}
+
+/// Test exponent 0.5 dampens a large burst
+#[test]
+fn test_grow_exponent_half_dampens_large_delta() {
+    // delta=16: linear multiplier 17, dampened multiplier 1 + sqrt(16) = 5
+    assert_eq!(calculate_new_size(1024, 16, 1.0), 17408);
+    assert_eq!(calculate_new_size(1024, 16, 0.5), 5120);
+    assert!(calculate_new_size(1024, 100, 0.5) < calculate_new_size(1024, 100, 1.0));
+}
+
+/// Test exponent above 1.0 amplifies growth
+#[test]
+fn test_grow_exponent_amplifies() {
+    // delta=3: 1 + 3^2 = 10
+    assert_eq!(calculate_new_size(1024, 3, 2.0), 10240);
+}
+
+/// Test non-default exponents still saturate instead of overflowing
+#[test]
+fn test_grow_exponent_saturates() {
+    assert_eq!(calculate_new_size(i32::MAX, 5, 0.5), i32::MAX);
+    assert_eq!(calculate_new_size(1024, i64::MAX, 2.0), i32::MAX);
+}
+
+/// Test the grow multiplier formula
+#[test]
+fn test_grow_multiplier() {
+    assert_eq!(grow_multiplier(3, 1.0), 4.0);
+    assert_eq!(grow_multiplier(16, 0.5), 5.0);
+    assert_eq!(grow_multiplier(-5, 0.5), 1.0);
+}
+
+// =========================================================================
+// Tests for the rolling delta ring (walrus.use_rolling_delta)
+// =========================================================================
+
+/// Test the average covers only the filled slots while partially filled
+#[test]
+fn test_rolling_average_partial_fill() {
+    let mut ring = [0; DELTA_RING_SIZE];
+    assert_eq!(rolling_average(&ring, 0), 0);
+
+    let (pos, len) = push_delta(&mut ring, 0, 0, 4);
+    assert_eq!((pos, len), (1, 1));
+    assert_eq!(rolling_average(&ring, len), 4);
+
+    let (pos, len) = push_delta(&mut ring, pos, len, 0);
+    let (_, len) = push_delta(&mut ring, pos, len, 1);
+    assert_eq!(len, 3);
+    // (4 + 0 + 1) / 3 rounds down to 1
+    assert_eq!(rolling_average(&ring, len), 1);
+}
+
+/// Test the ring wraps and the average covers the most recent deltas only
+#[test]
+fn test_rolling_average_wraps() {
+    let mut ring = [0; DELTA_RING_SIZE];
+    let (mut pos, mut len) = (0, 0);
+    for _ in 0..DELTA_RING_SIZE {
+        (pos, len) = push_delta(&mut ring, pos, len, 8);
+    }
+    assert_eq!((pos, len), (0, DELTA_RING_SIZE as i32));
+    assert_eq!(rolling_average(&ring, len), 8);
+
+    // A single dip barely moves the average: (7 * 8 + 0) / 8 = 7
+    (pos, len) = push_delta(&mut ring, pos, len, 0);
+    assert_eq!((pos, len), (1, DELTA_RING_SIZE as i32));
+    assert_eq!(rolling_average(&ring, len), 7);
+}
+
+/// Test out-of-range deltas are clamped when stored
+#[test]
+fn test_push_delta_clamps() {
+    let mut ring = [0; DELTA_RING_SIZE];
+    let (pos, len) = push_delta(&mut ring, 0, 0, i64::MAX);
+    let (_, len) = push_delta(&mut ring, pos, len, -5);
+    assert_eq!(ring[0], i32::MAX);
+    assert_eq!(ring[1], 0);
+    assert_eq!(rolling_average(&ring, len), i32::MAX as i64 / 2);
+}
+
+// =========================================================================
+// Tests for the shrink backoff (walrus.shrink_backoff)
+// =========================================================================
+
+/// Test the requirement doubles after successive shrinks and resets on a grow
+#[test]
+fn test_shrink_backoff_grows_and_resets() {
+    let base = 5;
+    // effective_shrink_intervals starts at 0 (no backoff)
+    let mut effective = 0;
+    assert_eq!(required_shrink_intervals(base, effective, true), 5);
+
+    // Each shrink backs off from the requirement it needed
+    effective = backed_off_shrink_intervals(required_shrink_intervals(base, effective, true));
+    assert_eq!(required_shrink_intervals(base, effective, true), 10);
+    effective = backed_off_shrink_intervals(required_shrink_intervals(base, effective, true));
+    assert_eq!(required_shrink_intervals(base, effective, true), 20);
+
+    // A grow resets effective_shrink_intervals to 0
+    effective = 0;
+    assert_eq!(required_shrink_intervals(base, effective, true), base);
+}
+
+/// Test the backoff is ignored when disabled and never lowers the base
+#[test]
+fn test_shrink_backoff_bounds() {
+    assert_eq!(required_shrink_intervals(5, 40, false), 5);
+    // walrus.shrink_intervals raised above the backed-off value
+    assert_eq!(required_shrink_intervals(50, 40, true), 50);
+    assert_eq!(backed_off_shrink_intervals(i32::MAX), i32::MAX);
+}
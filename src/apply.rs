@@ -0,0 +1,210 @@
+//! Serialized max_wal_size changes for pg_walrus.
+//!
+//! The worker, `walrus.analyze(apply := true)`, and `walrus.set_size()` all
+//! change max_wal_size through `apply_max_wal_size()`. PostgreSQL's own lock
+//! keeps postgresql.auto.conf consistent, but not our shared-memory counters
+//! or reloads: two overlapping applies could each count an adjustment and
+//! stack reloads for what ends up as one value. The helper takes the
+//! transaction-scoped adjustment lock (`ADJUSTMENT_LOCK_KEY`) and, while
+//! holding it, runs ALTER SYSTEM, updates shared memory, and records the
+//! history row, then requests the reload. From the worker that transaction is
+//! its own and has committed by then. SQL callers (`walrus.analyze()`,
+//! `walrus.set_size()`, `walrus.tick()`) run in their caller's transaction, so
+//! the reload is requested before it commits and the lock is held until it
+//! does. An apply that finds the lock held changes nothing and returns
+//! `ApplyError::Busy`.
+//!
+//! A worker shrink is computed from max_wal_size at the start of the cycle,
+//! but another session can change it before the shrink is applied. Once the
+//! lock is held, a change marked `abort_if_stale` compares its `old_size_mb`
+//! with the size last written by ALTER SYSTEM and not yet confirmed by a
+//! reload (shared memory `intended_size_mb`, set by every apply). If another
+//! size is pending, e.g. from `walrus.set_size()` since the cycle started,
+//! nothing changes and it returns `ApplyError::Stale`.
+
+use crate::algorithm::is_stale_change;
+use crate::config::{ConfigError, execute_alter_system_timed, signal_postmaster_reload};
+use crate::history;
+use crate::shmem::{self, WalrusState};
+use crate::worker::{in_transaction, suppress_next_sighup};
+
+use pgrx::datum::DatumWithOid;
+use pgrx::prelude::*;
+use serde_json::Value as JsonValue;
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+
+/// Advisory lock key serializing max_wal_size changes ("walrus" in ASCII).
+///
+/// Taken with pg_try_advisory_xact_lock() so it is released at transaction end.
+pub(crate) const ADJUSTMENT_LOCK_KEY: i64 = 0x7761_6C72_7573;
+
+/// Who is applying a max_wal_size change.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ApplySource {
+    /// The background worker (or walrus.tick())
+    Worker,
+    /// walrus.analyze(apply := true)
+    Analyze,
+    /// walrus.set_size()
+    SetSize,
+}
+
+impl ApplySource {
+    /// Name used in log messages and `source` metadata.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ApplySource::Worker => "worker",
+            ApplySource::Analyze => "walrus.analyze",
+            ApplySource::SetSize => "walrus.set_size",
+        }
+    }
+}
+
+/// A max_wal_size change and the history row that records it.
+pub(crate) struct SizeChange<'a> {
+    pub action: &'a str,
+    pub old_size_mb: i32,
+    pub new_size_mb: i32,
+    pub forced_checkpoints: i64,
+    pub checkpoint_timeout_sec: i32,
+    pub reason: &'a str,
+    /// Abort with `ApplyError::Stale` if another size was written since
+    /// `old_size_mb` was read (worker shrinks)
+    pub abort_if_stale: bool,
+}
+
+/// Why a max_wal_size change was not applied.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum ApplyError {
+    /// Another session holds the adjustment lock
+    Busy,
+    /// ALTER SYSTEM failed
+    Config(ConfigError),
+    /// The adjustment lock could not be queried
+    Spi(String),
+    /// max_wal_size was set to this size (MB) after the change was computed
+    Stale(i32),
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::Busy => f.write_str("another adjustment in progress"),
+            ApplyError::Config(e) => write!(f, "{}", e),
+            ApplyError::Spi(msg) => f.write_str(msg),
+            ApplyError::Stale(size) => write!(
+                f,
+                "max_wal_size was set to {} MB after the change was computed",
+                size
+            ),
+        }
+    }
+}
+
+/// Try to take the transaction-scoped adjustment lock.
+///
+/// Returns false if another session holds it. A session that already holds it
+/// takes it again.
+pub(crate) fn try_adjustment_lock() -> Result<bool, spi::Error> {
+    let args: &[DatumWithOid<'_>] = &[ADJUSTMENT_LOCK_KEY.into()];
+    let acquired = Spi::get_one_with_args::<bool>("SELECT pg_try_advisory_xact_lock($1)", args)?;
+    Ok(acquired.unwrap_or(false))
+}
+
+/// Apply a max_wal_size change while holding the adjustment lock.
+///
+/// In one transaction (the caller's, from SQL functions): takes the lock, runs
+/// ALTER SYSTEM, counts the adjustment in shared memory together with the
+/// caller's own `update`, and records the history row with the metadata built
+/// by `metadata` from the apply duration. Then requests the reload, which the
+/// worker does not process as a change of its own (`suppress_next_sighup()`):
+/// after its transaction commits for the worker, before the caller's commits
+/// for SQL functions. ALTER SYSTEM writes postgresql.auto.conf immediately, so
+/// the reload sees the new value either way.
+///
+/// With `abort_if_stale`, once the lock is held it first checks for a size
+/// written by another apply since the change was computed (`is_stale_change()`).
+///
+/// Returns the ALTER SYSTEM duration in milliseconds. On an error nothing has
+/// changed; rate limiting stays with the caller.
+pub(crate) fn apply_max_wal_size(
+    change: &SizeChange<'_>,
+    source: ApplySource,
+    update: impl FnOnce(&mut WalrusState),
+    metadata: impl FnOnce(i64) -> JsonValue,
+) -> Result<i64, ApplyError> {
+    let applied = in_transaction(AssertUnwindSafe(|| {
+        match try_adjustment_lock() {
+            Ok(true) => {}
+            Ok(false) => return Err(ApplyError::Busy),
+            Err(e) => return Err(ApplyError::Spi(e.to_string())),
+        }
+        if change.abort_if_stale {
+            let intended = shmem::read_state().intended_size_mb;
+            if is_stale_change(change.old_size_mb, intended) {
+                return Err(ApplyError::Stale(intended));
+            }
+        }
+        let apply_ms =
+            execute_alter_system_timed(change.new_size_mb).map_err(ApplyError::Config)?;
+
+        shmem::update_state(|state| {
+            state.total_adjustments += 1;
+            update(state);
+        });
+
+        if let Err(e) = history::insert_history_record(
+            change.action,
+            change.old_size_mb,
+            change.new_size_mb,
+            change.forced_checkpoints,
+            change.checkpoint_timeout_sec,
+            Some(change.reason),
+            Some(history::versioned_metadata(metadata(apply_ms))),
+        ) {
+            pgrx::warning!(
+                "pg_walrus: failed to log {} history from {}: {}",
+                change.action,
+                source.name(),
+                e
+            );
+        }
+        Ok(apply_ms)
+    }))?;
+
+    if source == ApplySource::Worker {
+        suppress_next_sighup();
+    }
+    signal_postmaster_reload();
+    Ok(applied)
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test errors read as the reason callers report
+    #[test]
+    fn test_apply_error_display() {
+        assert_eq!(
+            ApplyError::Busy.to_string(),
+            "another adjustment in progress"
+        );
+        let denied = ApplyError::Config(ConfigError::PermissionDenied("no".to_string()));
+        assert_eq!(denied.to_string(), "permission denied: no");
+        assert_eq!(
+            ApplyError::Stale(8192).to_string(),
+            "max_wal_size was set to 8192 MB after the change was computed"
+        );
+    }
+
+    /// Test each source has a distinct name
+    #[test]
+    fn test_apply_source_name() {
+        assert_eq!(ApplySource::Worker.name(), "worker");
+        assert_eq!(ApplySource::Analyze.name(), "walrus.analyze");
+        assert_eq!(ApplySource::SetSize.name(), "walrus.set_size");
+    }
+}
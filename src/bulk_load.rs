@@ -0,0 +1,130 @@
+//! Bulk-load mode for pg_walrus.
+//!
+//! Known bulk loads (COPY, pg_restore) spike forced checkpoints legitimately,
+//! and chasing them leaves max_wal_size permanently grown for a transient
+//! event. `walrus.begin_bulk_load(minutes)` sets `bulk_load_until` in shared
+//! memory; until it expires (or `walrus.end_bulk_load()` clears it), the
+//! effective grow threshold is walrus.threshold multiplied by
+//! `walrus.bulk_load_threshold_multiplier`, so only truly sustained pressure
+//! grows the size. Shrinking is unaffected.
+
+use crate::guc::{WALRUS_BULK_LOAD_THRESHOLD_MULTIPLIER, WALRUS_THRESHOLD};
+use crate::shmem::{self, now_unix};
+
+use pgrx::datum::TimestampWithTimeZone;
+use pgrx::pg_sys;
+
+/// Whether bulk-load mode is in effect at `now`.
+///
+/// A `bulk_load_until` of 0 (never set, ended, or cleared by walrus.reset()) is not active.
+#[inline]
+pub(crate) fn is_bulk_load_active(now: i64, bulk_load_until: i64) -> bool {
+    now < bulk_load_until
+}
+
+/// Scale the grow threshold while bulk-load mode is active (saturating).
+#[inline]
+pub(crate) fn scaled_threshold(threshold: i64, multiplier: i32, active: bool) -> i64 {
+    if active {
+        threshold.saturating_mul(multiplier.max(1) as i64)
+    } else {
+        threshold
+    }
+}
+
+/// Effective forced checkpoint threshold for the grow decision.
+///
+/// walrus.threshold, multiplied by walrus.bulk_load_threshold_multiplier
+/// while bulk-load mode is active.
+pub(crate) fn effective_threshold() -> i64 {
+    scaled_threshold(
+        WALRUS_THRESHOLD.get() as i64,
+        WALRUS_BULK_LOAD_THRESHOLD_MULTIPLIER.get(),
+        is_bulk_load_active(now_unix(), shmem::read_state().bulk_load_until),
+    )
+}
+
+/// Implementation for starting bulk-load mode.
+///
+/// Sets `bulk_load_until` to `minutes` from now, replacing any earlier end time.
+///
+/// # Returns
+///
+/// The time at which bulk-load mode ends.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn begin_bulk_load(minutes: i32) -> TimestampWithTimeZone {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.begin_bulk_load() requires superuser");
+    }
+    if minutes <= 0 {
+        pgrx::error!("minutes must be > 0");
+    }
+
+    let until = now_unix().saturating_add(minutes as i64 * 60);
+    shmem::update_state(|state| {
+        state.bulk_load_until = until;
+    });
+    pgrx::log!(
+        "pg_walrus: bulk-load mode for {} minutes, threshold multiplied by {}",
+        minutes,
+        WALRUS_BULK_LOAD_THRESHOLD_MULTIPLIER.get()
+    );
+    pgrx::datum::to_timestamp(until as f64)
+}
+
+/// Implementation for ending bulk-load mode early.
+///
+/// # Returns
+///
+/// true if bulk-load mode was active.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn end_bulk_load() -> bool {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.end_bulk_load() requires superuser");
+    }
+
+    let mut was_active = false;
+    shmem::update_state(|state| {
+        was_active = is_bulk_load_active(now_unix(), state.bulk_load_until);
+        state.bulk_load_until = 0;
+    });
+    if was_active {
+        pgrx::log!("pg_walrus: bulk-load mode ended");
+    }
+    was_active
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test bulk-load mode is active only before its end time
+    #[test]
+    fn test_is_bulk_load_active() {
+        assert!(is_bulk_load_active(1000, 1600));
+        assert!(is_bulk_load_active(1599, 1600));
+        assert!(!is_bulk_load_active(1600, 1600));
+        assert!(!is_bulk_load_active(1000, 0));
+    }
+
+    /// Test the threshold is scaled while active and unchanged otherwise
+    #[test]
+    fn test_scaled_threshold() {
+        assert_eq!(scaled_threshold(2, 4, true), 8);
+        assert_eq!(scaled_threshold(2, 4, false), 2);
+        assert_eq!(scaled_threshold(2, 1, true), 2);
+        assert_eq!(scaled_threshold(2, 0, true), 2);
+        assert_eq!(scaled_threshold(i64::MAX, 4, true), i64::MAX);
+    }
+}
@@ -0,0 +1,146 @@
+//! Ceiling enforcement for pg_walrus when walrus.max is lowered.
+//!
+//! The grow path only clamps new sizes to walrus.max, and the shrink path
+//! waits for sustained quiet intervals. If an operator lowers walrus.max below
+//! the current max_wal_size, the worker instead caps max_wal_size down to the
+//! new ceiling on the next cycle (never below the min_size floor). Unlike an
+//! emergency shrink, this adjustment is subject to the normal cooldown and
+//! hourly limits. A walrus.temporary_max() override raises the ceiling while it
+//! is recorded.
+
+use crate::access::skip_read_only;
+use crate::algorithm::{ceiling_excess_target, records_only, shrink_floor};
+use crate::apply::{ApplySource, SizeChange, apply_max_wal_size};
+use crate::emergency::OverrideOutcome;
+use crate::guc::{WALRUS_MIN_SIZE, WALRUS_MODE};
+use crate::history;
+use crate::logging;
+use crate::pin::effective_dry_run;
+use crate::rate_limit::{check_rate_limit, update_rate_limit_state_after_adjustment};
+use crate::shmem::{self, now_unix};
+use crate::stats::{checkpoint_timeout, get_current_max_wal_size, get_current_min_wal_size};
+use crate::temporary_max::ceiling_max;
+use crate::worker::in_transaction;
+
+use serde_json::json;
+
+/// History reason for a cap-down to a lowered walrus.max.
+const CEILING_REASON: &str = "current exceeds lowered walrus.max";
+
+/// Cap max_wal_size down if it exceeds walrus.max.
+///
+/// Called each worker cycle before the grow/shrink decision. Returns None when
+/// max_wal_size is within walrus.max (or already at the floor), so normal
+/// processing continues. Otherwise the cycle is consumed by the cap-down:
+/// rate-limited attempts are recorded as 'skipped' and retried next cycle,
+/// dry-run (or observe) mode records 'dry_run', and applied changes record
+/// 'capped'. walrus.mode = grow_only does not prevent the cap-down.
+pub(crate) fn check_ceiling_excess(current_requested: i64) -> Option<OverrideOutcome> {
+    let current_size = get_current_max_wal_size();
+    let max_allowed = ceiling_max();
+    let floor = shrink_floor(WALRUS_MIN_SIZE.get(), get_current_min_wal_size());
+    let new_size = ceiling_excess_target(current_size, max_allowed, floor)?;
+    if skip_read_only(current_size, new_size, CEILING_REASON) {
+        return Some(OverrideOutcome::Handled);
+    }
+
+    let timeout_secs = checkpoint_timeout().as_secs() as i32;
+    let metadata = history::with_effective_ceiling(
+        json!({
+            "calculated_size_mb": new_size,
+            "walrus_max_mb": max_allowed
+        }),
+        max_allowed,
+    );
+
+    // RATE LIMIT CHECK: Cap-downs honor cooldown and hourly limit like other adjustments
+    let rate_limit_result = check_rate_limit();
+    if rate_limit_result.is_blocked() {
+        let reason = rate_limit_result
+            .reason
+            .as_deref()
+            .unwrap_or("rate limit blocked");
+        logging::info(format_args!(
+            "pg_walrus: cap-down to walrus.max ({} MB) blocked - {}",
+            max_allowed, reason
+        ));
+
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "skipped",
+                current_size,
+                new_size,
+                current_requested,
+                timeout_secs,
+                Some(reason),
+                rate_limit_result
+                    .metadata
+                    .clone()
+                    .map(history::versioned_metadata),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log skipped history: {}", e);
+        }
+        return Some(OverrideOutcome::Handled);
+    }
+
+    if records_only(WALRUS_MODE.get(), effective_dry_run()) {
+        logging::info(format_args!(
+            "pg_walrus [DRY-RUN]: would change max_wal_size from {} MB to {} MB ({})",
+            current_size, new_size, CEILING_REASON
+        ));
+        // Dry-run counts against the rate limit as well (FR-014)
+        update_rate_limit_state_after_adjustment();
+
+        let mut metadata = metadata;
+        metadata["dry_run"] = json!(true);
+        metadata["would_apply"] = json!("capped");
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "dry_run",
+                current_size,
+                new_size,
+                current_requested,
+                timeout_secs,
+                Some(CEILING_REASON),
+                Some(history::versioned_metadata(metadata.clone())),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log history: {}", e);
+        }
+        return Some(OverrideOutcome::Handled);
+    }
+
+    logging::applied(format_args!(
+        "pg_walrus: max_wal_size ({} MB) exceeds walrus.max ({} MB), resizing to {} MB",
+        current_size, max_allowed, new_size
+    ));
+
+    let change = SizeChange {
+        action: "capped",
+        old_size_mb: current_size,
+        new_size_mb: new_size,
+        forced_checkpoints: current_requested,
+        checkpoint_timeout_sec: timeout_secs,
+        reason: CEILING_REASON,
+        abort_if_stale: false,
+    };
+    let update = |state: &mut shmem::WalrusState| {
+        state.last_adjustment_time = now_unix();
+        state.quiet_intervals = 0;
+    };
+    let metadata = |_: i64| {
+        let cycle_time = shmem::read_state().last_check_time;
+        let key = history::decision_key(cycle_time, "capped", current_requested, new_size);
+        history::with_idempotency_key(metadata, &key)
+    };
+    if let Err(e) = apply_max_wal_size(&change, ApplySource::Worker, update, metadata) {
+        pgrx::warning!(
+            "pg_walrus: failed to execute ALTER SYSTEM, will retry next cycle: {}",
+            e
+        );
+        return Some(OverrideOutcome::Handled);
+    }
+    update_rate_limit_state_after_adjustment();
+    Some(OverrideOutcome::Applied)
+}
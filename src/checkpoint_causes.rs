@@ -0,0 +1,145 @@
+//! Forced checkpoint causes for pg_walrus.
+//!
+//! A requested checkpoint is forced either by WAL volume reaching the
+//! checkpoint distance derived from max_wal_size (what pg_walrus sizes for) or
+//! by something else: an explicit CHECKPOINT, a base backup, CREATE DATABASE.
+//! No PostgreSQL version's cumulative statistics record the cause (PG 17's
+//! pg_stat_checkpointer only splits off restartpoints), so the split is
+//! estimated on every version from the WAL generated in the interval: WAL
+//! volume can account for at most one checkpoint per checkpoint distance, and
+//! the remaining requested checkpoints had another cause.
+//!
+//! With `walrus.wal_pressure_only` on, only the WAL pressure share counts
+//! toward walrus.threshold. Without a WAL position baseline (the first interval
+//! after walrus.reset()) the split is unknown and every checkpoint counts.
+
+use crate::guc::WALRUS_WAL_PRESSURE_ONLY;
+
+use serde_json::{Value as JsonValue, json};
+
+/// Bytes in one MB of max_wal_size.
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+/// Estimated split of an interval's requested checkpoints.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct CheckpointCauses {
+    /// Checkpoints WAL volume accounts for
+    pub wal_pressure: i64,
+    /// Remaining requested checkpoints (CHECKPOINT, base backups, ...)
+    pub other: i64,
+}
+
+/// WAL generated between two checkpoints forced by WAL volume, in bytes.
+///
+/// PostgreSQL requests a checkpoint once max_wal_size / (1 +
+/// checkpoint_completion_target) of WAL has been written since the last one.
+#[inline]
+pub(crate) fn checkpoint_distance_bytes(max_wal_size_mb: i32, completion_target: f64) -> i64 {
+    let distance =
+        max_wal_size_mb.max(1) as f64 * BYTES_PER_MB / (1.0 + completion_target.max(0.0));
+    (distance as i64).max(1)
+}
+
+/// Split `requested` checkpoints by cause, given the interval's WAL volume.
+///
+/// None when `wal_bytes` is unknown (no WAL position baseline).
+#[inline]
+pub(crate) fn split_requested(
+    requested: i64,
+    wal_bytes: Option<i64>,
+    distance_bytes: i64,
+) -> Option<CheckpointCauses> {
+    let requested = requested.max(0);
+    let wal_pressure = (wal_bytes?.max(0) / distance_bytes.max(1)).min(requested);
+    Some(CheckpointCauses {
+        wal_pressure,
+        other: requested - wal_pressure,
+    })
+}
+
+/// Delta compared against walrus.threshold.
+///
+/// The WAL pressure share with `wal_pressure_only` on and a known split,
+/// otherwise every requested checkpoint.
+#[inline]
+pub(crate) fn counted_delta(
+    requested: i64,
+    causes: Option<CheckpointCauses>,
+    wal_pressure_only: bool,
+) -> i64 {
+    match causes {
+        Some(causes) if wal_pressure_only => causes.wal_pressure,
+        _ => requested,
+    }
+}
+
+/// Delta for this interval under the live walrus.wal_pressure_only setting.
+pub(crate) fn pressure_delta(requested: i64, causes: Option<CheckpointCauses>) -> i64 {
+    counted_delta(requested, causes, WALRUS_WAL_PRESSURE_ONLY.get())
+}
+
+/// `walrus.status()` `estimated_checkpoint_causes` view of the last interval's
+/// estimated split (null when unknown).
+pub(crate) fn causes_json(known: bool, wal_pressure: i64, other: i64) -> JsonValue {
+    if known {
+        json!({ "wal_pressure": wal_pressure, "other": other })
+    } else {
+        JsonValue::Null
+    }
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the distance matches PostgreSQL's checkpoint spacing
+    #[test]
+    fn test_checkpoint_distance_bytes() {
+        let mb = BYTES_PER_MB as i64;
+        assert_eq!(checkpoint_distance_bytes(1024, 0.0), 1024 * mb);
+        assert_eq!(
+            checkpoint_distance_bytes(1024, 0.9),
+            (1024.0 * BYTES_PER_MB / 1.9) as i64
+        );
+        assert!(
+            checkpoint_distance_bytes(0, 0.9) > 0,
+            "never divides by zero"
+        );
+    }
+
+    /// Test WAL volume accounts for at most the requested checkpoints
+    #[test]
+    fn test_split_requested() {
+        let distance = 100;
+        assert_eq!(
+            split_requested(3, Some(250), distance),
+            Some(CheckpointCauses {
+                wal_pressure: 2,
+                other: 1
+            }),
+            "two distances of WAL, one manual CHECKPOINT"
+        );
+        assert_eq!(
+            split_requested(1, Some(1000), distance),
+            Some(CheckpointCauses {
+                wal_pressure: 1,
+                other: 0
+            })
+        );
+        assert_eq!(split_requested(2, None, distance), None, "no WAL baseline");
+    }
+
+    /// Test the filtered count is used only when enabled and known
+    #[test]
+    fn test_counted_delta() {
+        let causes = split_requested(3, Some(150), 100);
+        assert_eq!(counted_delta(3, causes, true), 1);
+        assert_eq!(counted_delta(3, causes, false), 3);
+        assert_eq!(
+            counted_delta(3, None, true),
+            3,
+            "falls back to every checkpoint"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Raw checkpoint counters for pg_walrus.
+//!
+//! `walrus.checkpoint_stats()` returns the counters the worker reads each
+//! cycle, alongside its `prev_requested` baseline, so a mismatch between
+//! pg_stat_checkpointer (or pg_stat_bgwriter) and a sizing decision can be
+//! traced to the numbers the worker actually saw.
+
+use crate::shmem::read_state;
+use crate::stats::{
+    checkpoint_timeout, get_current_max_wal_size, get_requested_checkpoints, get_timed_checkpoints,
+};
+
+use pgrx::JsonB;
+use serde_json::json;
+
+/// Delta the worker would compute from `requested` and its baseline.
+///
+/// None when checkpoint statistics are unavailable (`requested` < 0).
+#[inline]
+pub(crate) fn counter_delta(requested: i64, prev_requested: i64) -> Option<i64> {
+    (requested >= 0).then(|| requested - prev_requested)
+}
+
+/// Implementation for the raw checkpoint counters.
+///
+/// Returns a JSONB object with:
+/// - `requested`: Forced checkpoints since startup (`num_requested` on PG 17+,
+///   `requested_checkpoints` before); null if statistics are unavailable
+/// - `timed`: Timed checkpoints since startup (`num_timed`/`timed_checkpoints`)
+/// - `prev_requested`: The worker's baseline from its last sample
+/// - `delta`: `requested - prev_requested`, before walrus.ignore_next_checkpoints()
+///   exclusions (`ignore_checkpoints` is the number still pending)
+/// - `max_wal_size_mb`, `checkpoint_timeout_sec`: Live settings
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn checkpoint_stats() -> JsonB {
+    let state = read_state();
+    let requested = get_requested_checkpoints();
+    let timed = get_timed_checkpoints();
+
+    JsonB(json!({
+        "requested": (requested >= 0).then_some(requested),
+        "timed": (timed >= 0).then_some(timed),
+        "prev_requested": state.prev_requested,
+        "delta": counter_delta(requested, state.prev_requested),
+        "ignore_checkpoints": state.ignore_checkpoints,
+        "max_wal_size_mb": get_current_max_wal_size(),
+        "checkpoint_timeout_sec": checkpoint_timeout().as_secs(),
+    }))
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the delta is current minus baseline, and absent without statistics
+    #[test]
+    fn test_counter_delta() {
+        assert_eq!(counter_delta(12, 10), Some(2));
+        assert_eq!(counter_delta(0, 0), Some(0));
+        assert_eq!(counter_delta(-1, 10), None);
+    }
+}
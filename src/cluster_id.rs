@@ -0,0 +1,176 @@
+//! Cluster identification for pg_walrus.
+//!
+//! On hosts running several PostgreSQL clusters, the worker's log lines are
+//! otherwise identical from one cluster to the next. At startup the worker
+//! derives a `cluster_id` from the `port` and `data_directory` settings
+//! ("5432:/var/lib/postgresql/data", shortened from the left to fit), stores
+//! it in shared memory as a fixed-size buffer for `walrus.status()`, and
+//! installs an `emit_log_hook` that tags its own "pg_walrus" messages with it
+//! ("pg_walrus[5432:/var/lib/postgresql/data]: ...").
+
+use crate::shmem;
+
+use pgrx::pg_guard;
+use pgrx::pg_sys;
+use std::ffi::{CStr, CString};
+use std::sync::OnceLock;
+
+/// Size of the shared-memory buffer holding the cluster id, in bytes.
+pub const CLUSTER_ID_LEN: usize = 32;
+
+/// Message prefix tagged by the worker's log hook.
+const LOG_PREFIX: &str = "pg_walrus";
+
+/// Tagged prefix for this worker's messages, set once at startup.
+static TAGGED_PREFIX: OnceLock<String> = OnceLock::new();
+
+/// emit_log_hook installed before ours, called after tagging.
+static mut PREV_EMIT_LOG_HOOK: pg_sys::emit_log_hook_type = None;
+
+/// Cluster id for `port` and `data_directory`, at most `CLUSTER_ID_LEN` bytes.
+///
+/// A data directory too long to fit keeps its tail, the most distinctive part,
+/// after "...".
+pub(crate) fn format_cluster_id(port: &str, data_directory: &str) -> String {
+    let full = format!("{}:{}", port, data_directory);
+    if full.len() <= CLUSTER_ID_LEN {
+        return full;
+    }
+    let head = format!("{}:...", port);
+    let budget = CLUSTER_ID_LEN.saturating_sub(head.len());
+    let mut start = data_directory.len().saturating_sub(budget);
+    while !data_directory.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("{}{}", head, &data_directory[start..])
+}
+
+/// Store `id` in a zero-padded buffer (truncated to `CLUSTER_ID_LEN` bytes).
+pub(crate) fn encode_cluster_id(id: &str) -> [u8; CLUSTER_ID_LEN] {
+    let mut buf = [0; CLUSTER_ID_LEN];
+    let len = id.len().min(CLUSTER_ID_LEN);
+    buf[..len].copy_from_slice(&id.as_bytes()[..len]);
+    buf
+}
+
+/// Read a cluster id back from its buffer (empty when never set).
+pub(crate) fn decode_cluster_id(buf: &[u8; CLUSTER_ID_LEN]) -> String {
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(CLUSTER_ID_LEN);
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// `message` tagged with `tagged_prefix` if it is one of ours.
+pub(crate) fn tag_message(message: &str, tagged_prefix: &str) -> Option<String> {
+    message
+        .strip_prefix(LOG_PREFIX)
+        .map(|rest| format!("{}{}", tagged_prefix, rest))
+}
+
+/// Current value of a configuration setting, as `SHOW` displays it.
+fn setting(name: &CStr) -> Option<String> {
+    // SAFETY: the result is palloc'd in the current context; missing_ok avoids an ERROR.
+    unsafe {
+        let value = pg_sys::GetConfigOptionByName(name.as_ptr(), std::ptr::null_mut(), true);
+        (!value.is_null()).then(|| CStr::from_ptr(value).to_string_lossy().into_owned())
+    }
+}
+
+/// Cluster id of the PostgreSQL instance this process belongs to.
+pub(crate) fn local_cluster_id() -> String {
+    format_cluster_id(
+        &setting(c"port").unwrap_or_default(),
+        &setting(c"data_directory").unwrap_or_default(),
+    )
+}
+
+/// Cluster id published by the worker, or derived here if it has not started.
+pub(crate) fn cluster_id() -> String {
+    let published = decode_cluster_id(&shmem::read_state().cluster_id);
+    if published.is_empty() {
+        local_cluster_id()
+    } else {
+        published
+    }
+}
+
+/// Tagged text for the message in `edata`, or None to leave it alone.
+unsafe fn tagged_message(edata: *mut pg_sys::ErrorData) -> Option<CString> {
+    let prefix = TAGGED_PREFIX.get()?;
+    if edata.is_null() || unsafe { (*edata).message.is_null() } {
+        return None;
+    }
+    let message = unsafe { CStr::from_ptr((*edata).message) }.to_string_lossy();
+    CString::new(tag_message(&message, prefix)?).ok()
+}
+
+/// Tag the worker's own messages with the cluster id.
+#[pg_guard]
+unsafe extern "C-unwind" fn tag_log_message(edata: *mut pg_sys::ErrorData) {
+    unsafe {
+        if let Some(tagged) = tagged_message(edata) {
+            // The message lives in ErrorContext, which is reset after the report
+            (*edata).message = pg_sys::MemoryContextStrdup(pg_sys::ErrorContext, tagged.as_ptr());
+        }
+        let prev = PREV_EMIT_LOG_HOOK;
+        if let Some(prev) = prev {
+            prev(edata);
+        }
+    }
+}
+
+/// Publish the cluster id and tag this process's log messages with it.
+///
+/// Called once at worker startup.
+pub(crate) fn publish_cluster_id() {
+    let id = local_cluster_id();
+    shmem::update_state(|state| state.cluster_id = encode_cluster_id(&id));
+    if TAGGED_PREFIX.set(format!("{}[{}]", LOG_PREFIX, id)).is_ok() {
+        // SAFETY: hooks are per-process; the worker installs this before logging anything else.
+        unsafe {
+            PREV_EMIT_LOG_HOOK = pg_sys::emit_log_hook;
+            pg_sys::emit_log_hook = Some(tag_log_message);
+        }
+    }
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test a short data directory is kept whole and a long one keeps its tail
+    #[test]
+    fn test_format_cluster_id() {
+        assert_eq!(
+            format_cluster_id("5432", "/srv/pg/main"),
+            "5432:/srv/pg/main"
+        );
+        let long = format_cluster_id("5433", "/var/lib/postgresql/16/main-replica");
+        assert_eq!(long.len(), CLUSTER_ID_LEN);
+        assert!(long.starts_with("5433:..."));
+        assert!(long.ends_with("16/main-replica"));
+    }
+
+    /// Test the buffer round-trips and is empty when never set
+    #[test]
+    fn test_cluster_id_buffer() {
+        let id = format_cluster_id("5432", "/data");
+        assert_eq!(decode_cluster_id(&encode_cluster_id(&id)), id);
+        assert_eq!(decode_cluster_id(&[0; CLUSTER_ID_LEN]), "");
+    }
+
+    /// Test only our own messages are tagged
+    #[test]
+    fn test_tag_message() {
+        let prefix = "pg_walrus[5432:/data]";
+        assert_eq!(
+            tag_message("pg_walrus: grew max_wal_size", prefix).as_deref(),
+            Some("pg_walrus[5432:/data]: grew max_wal_size")
+        );
+        assert_eq!(
+            tag_message("pg_walrus worker started", prefix).as_deref(),
+            Some("pg_walrus[5432:/data] worker started")
+        );
+        assert_eq!(tag_message("could not open file", prefix), None);
+    }
+}
@@ -1,20 +1,171 @@
 //! ALTER SYSTEM execution for pg_walrus.
 //!
-//! This module handles modifying max_wal_size via ALTER SYSTEM SET,
+//! This module handles modifying max_wal_size (and other settings, such as
+//! checkpoint_completion_target and the walrus.* GUCs changed through
+//! walrus.set()) via ALTER SYSTEM SET,
 //! constructing the necessary AST nodes and executing within a transaction.
-//! Also provides cross-platform signaling to trigger configuration reloads.
+//! Also provides cross-platform signaling to trigger configuration reloads, or
+//! pg_reload_conf() with walrus.reload_method = 'sql'.
+//!
+//! ALTER SYSTEM raises a PostgreSQL ERROR on failure. Those errors are caught
+//! and returned as a `ConfigError`, with the failed change rolled back. A
+//! max_wal_size the reload then refuses or clamps is caught on the next cycle
+//! by `verify_applied_size()`.
+
+use crate::guc::{
+    ReloadMethod, WALRUS_COMPLETION_TARGET, WALRUS_MANAGE_COMPLETION_TARGET,
+    WALRUS_MAX_SIGHUPS_PER_HOUR_WARN, WALRUS_RELOAD_METHOD,
+};
+use crate::history;
+use crate::logging;
+use crate::rate_limit::window_expired;
+use crate::shmem::{self, now_unix};
+use crate::worker::in_transaction;
 
 use pgrx::pg_sys;
+use pgrx::pg_sys::panic::CaughtError;
+use pgrx::spi::Spi;
+use pgrx::{PgSqlErrorCode, PgTryBuilder};
+use serde_json::json;
 use std::ffi::CString;
+use std::fmt;
+use std::panic::AssertUnwindSafe;
 use std::ptr;
+use std::time::Instant;
+
+/// Why an ALTER SYSTEM call failed, with PostgreSQL's error message.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ConfigError {
+    /// The role may not change the parameter, or may not access postgresql.auto.conf
+    PermissionDenied(String),
+    /// Unknown parameter or a value PostgreSQL rejected
+    InvalidValue(String),
+    /// postgresql.auto.conf could not be written (disk full, I/O error, missing file)
+    FileWrite(String),
+    /// Any other error raised while running ALTER SYSTEM
+    Other(String),
+}
+
+impl ConfigError {
+    /// Classify a PostgreSQL error by its SQLSTATE.
+    pub(crate) fn from_sqlstate(code: PgSqlErrorCode, message: &str) -> Self {
+        let message = message.to_string();
+        match code {
+            PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE => {
+                ConfigError::PermissionDenied(message)
+            }
+            PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT
+            | PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE
+            | PgSqlErrorCode::ERRCODE_CANT_CHANGE_RUNTIME_PARAM => {
+                ConfigError::InvalidValue(message)
+            }
+            PgSqlErrorCode::ERRCODE_DISK_FULL
+            | PgSqlErrorCode::ERRCODE_IO_ERROR
+            | PgSqlErrorCode::ERRCODE_UNDEFINED_FILE => ConfigError::FileWrite(message),
+            _ => ConfigError::Other(message),
+        }
+    }
+
+    /// PostgreSQL's error message, without the cause.
+    pub fn message(&self) -> &str {
+        match self {
+            ConfigError::PermissionDenied(msg)
+            | ConfigError::InvalidValue(msg)
+            | ConfigError::FileWrite(msg)
+            | ConfigError::Other(msg) => msg,
+        }
+    }
+
+    fn from_caught(error: CaughtError) -> Self {
+        match error {
+            CaughtError::PostgresError(report)
+            | CaughtError::ErrorReport(report)
+            | CaughtError::RustPanic {
+                ereport: report, ..
+            } => Self::from_sqlstate(report.sql_error_code(), report.message()),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::PermissionDenied(msg) => write!(f, "permission denied: {}", msg),
+            ConfigError::InvalidValue(msg) => write!(f, "invalid setting: {}", msg),
+            ConfigError::FileWrite(msg) => write!(f, "could not write configuration: {}", msg),
+            ConfigError::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Ask the postmaster to reload the configuration.
+///
+/// This is used after ALTER SYSTEM to apply configuration changes. With
+/// walrus.reload_method = 'sql' it runs pg_reload_conf() through SPI, in the
+/// current transaction if there is one (SQL functions) or in a new one (the
+/// worker); otherwise it signals the postmaster directly. Either way the
+/// postmaster forwards SIGHUP to every backend, including the worker.
+///
+/// Returns the method used. A failed pg_reload_conf() is logged as a WARNING.
+/// Each request is counted by `record_sighup()`.
+pub fn signal_postmaster_reload() -> ReloadMethod {
+    record_sighup();
+    let method = WALRUS_RELOAD_METHOD.get();
+    match method {
+        ReloadMethod::Signal => send_reload_signal(),
+        ReloadMethod::Sql => {
+            let reload = || Spi::run("SELECT pg_catalog.pg_reload_conf()");
+            if let Err(e) = in_transaction(reload) {
+                pgrx::warning!("pg_walrus: pg_reload_conf() failed: {}", e);
+            }
+        }
+    }
+    method
+}
+
+/// Whether `sighups_last_hour` reloads exceed walrus.max_sighups_per_hour_warn.
+///
+/// A `warn_limit` of 0 disables the warning.
+#[inline]
+pub(crate) fn is_reload_storm(sighups_last_hour: i32, warn_limit: i32) -> bool {
+    warn_limit > 0 && sighups_last_hour > warn_limit
+}
+
+/// Count a configuration reload in shared memory and warn about a reload storm.
+///
+/// `sighups_this_hour` is kept in a rolling one-hour window, like the rate
+/// limiting counters. A flapping worker shows up here before the repeated
+/// reloads of every backend degrade the cluster.
+fn record_sighup() {
+    let now = now_unix();
+    let mut last_hour = 0;
+    shmem::update_state(|state| {
+        state.sighups_sent += 1;
+        if window_expired(state.sighup_hour_window_start, now, 3600) {
+            state.sighups_this_hour = 1;
+            state.sighup_hour_window_start = now;
+        } else {
+            state.sighups_this_hour += 1;
+        }
+        last_hour = state.sighups_this_hour;
+    });
+
+    let warn_limit = WALRUS_MAX_SIGHUPS_PER_HOUR_WARN.get();
+    if is_reload_storm(last_hour, warn_limit) {
+        pgrx::warning!(
+            "pg_walrus: {} configuration reloads in the last hour exceed walrus.max_sighups_per_hour_warn ({}); each one reloads every backend",
+            last_hour,
+            warn_limit
+        );
+    }
+}
 
 /// Send SIGHUP to the postmaster to trigger configuration reload.
 ///
-/// This is used after ALTER SYSTEM to apply configuration changes.
 /// On Unix, sends SIGHUP signal directly via libc.
 /// On Windows, signals via PostgreSQL's named event mechanism.
 #[cfg(unix)]
-pub fn signal_postmaster_reload() {
+fn send_reload_signal() {
     unsafe {
         libc::kill(pg_sys::PostmasterPid, libc::SIGHUP);
     }
@@ -25,7 +176,7 @@ pub fn signal_postmaster_reload() {
 /// On Windows, PostgreSQL uses named events for signal emulation.
 /// The event name format is `Global\PostgreSQL.SIGHUP.<pid>`.
 #[cfg(windows)]
-pub fn signal_postmaster_reload() {
+fn send_reload_signal() {
     // Windows API function declarations
     #[link(name = "kernel32")]
     unsafe extern "system" {
@@ -67,33 +218,21 @@ unsafe fn make_node<T>() -> *mut T {
     unsafe { pg_sys::palloc0(size) as *mut T }
 }
 
-/// Constructs the AST nodes for ALTER SYSTEM SET max_wal_size = <value>.
+/// Constructs the AST nodes for ALTER SYSTEM SET <name> = <useval> and executes it.
 ///
 /// # Safety
 /// Caller must ensure this is called within a valid transaction context
-/// and that PostgreSQL memory contexts are properly set up.
-unsafe fn alter_max_wal_size(new_value: i32) {
+/// and that `useval` was allocated in a PostgreSQL memory context.
+unsafe fn alter_system_set(name: &str, useval: *mut pg_sys::A_Const) {
     // SAFETY: All operations within this function are performed in a valid
     // PostgreSQL memory context and transaction.
     unsafe {
         // Allocate nodes in PostgreSQL memory context
         let alter_stmt: *mut pg_sys::AlterSystemStmt = make_node();
         let setstmt: *mut pg_sys::VariableSetStmt = make_node();
-        let useval: *mut pg_sys::A_Const = make_node();
-
-        // Configure A_Const with integer value (in MB)
-        (*useval).type_ = pg_sys::NodeTag::T_A_Const;
-        (*useval).isnull = false;
-        // location field only exists in pg18+
-        #[cfg(feature = "pg18")]
-        {
-            (*useval).location = -1;
-        }
-        (*useval).val.ival.type_ = pg_sys::NodeTag::T_Integer;
-        (*useval).val.ival.ival = new_value;
 
-        // Configure VariableSetStmt for max_wal_size
-        let name = CString::new("max_wal_size").expect("CString::new failed");
+        // Configure VariableSetStmt for the target parameter
+        let name = CString::new(name).expect("CString::new failed");
         (*setstmt).type_ = pg_sys::NodeTag::T_VariableSetStmt;
         (*setstmt).kind = pg_sys::VariableSetKind::VAR_SET_VALUE;
         (*setstmt).name = pg_sys::pstrdup(name.as_ptr());
@@ -118,23 +257,165 @@ unsafe fn alter_max_wal_size(new_value: i32) {
     }
 }
 
-/// Execute ALTER SYSTEM SET max_wal_size = <new_value>.
+/// Constructs the AST nodes for ALTER SYSTEM SET max_wal_size = <value>.
 ///
-/// This function detects the calling context:
-/// - From SQL function context: Calls AlterSystemSetConfigFile directly
-///   (we're already in a valid memory/transaction context)
-/// - From background worker: Sets up transaction, calls, then commits
+/// # Safety
+/// Caller must ensure this is called within a valid transaction context
+/// and that PostgreSQL memory contexts are properly set up.
+unsafe fn alter_max_wal_size(new_value: i32) {
+    // SAFETY: Called within a valid PostgreSQL memory context and transaction.
+    unsafe {
+        let useval: *mut pg_sys::A_Const = make_node();
+
+        // Configure A_Const with integer value (in MB)
+        (*useval).type_ = pg_sys::NodeTag::T_A_Const;
+        // pg13-14 wrap the value in a Value node; pg15+ use a union with isnull
+        #[cfg(any(feature = "pg13", feature = "pg14"))]
+        {
+            (*useval).location = -1;
+            (*useval).val.type_ = pg_sys::NodeTag::T_Integer;
+            (*useval).val.val.ival = new_value;
+        }
+        #[cfg(not(any(feature = "pg13", feature = "pg14")))]
+        {
+            (*useval).isnull = false;
+            // location field only exists in pg18+
+            #[cfg(feature = "pg18")]
+            {
+                (*useval).location = -1;
+            }
+            (*useval).val.ival.type_ = pg_sys::NodeTag::T_Integer;
+            (*useval).val.ival.ival = new_value;
+        }
+
+        alter_system_set("max_wal_size", useval);
+    }
+}
+
+/// Format a real value for a T_Float constant node.
+///
+/// PostgreSQL keeps float literals as strings in the parse tree; Debug
+/// formatting always includes a decimal point (e.g. "1.0", "0.9").
+#[inline]
+pub(crate) fn format_real(value: f64) -> String {
+    format!("{:?}", value)
+}
+
+/// Allocates an A_Const node holding a real (T_Float) value.
+///
+/// # Safety
+/// Must be called when PostgreSQL memory context is valid.
+pub(crate) unsafe fn make_real_const(value: f64) -> *mut pg_sys::A_Const {
+    // SAFETY: Called within a valid PostgreSQL memory context.
+    unsafe {
+        let useval: *mut pg_sys::A_Const = make_node();
+        let value = CString::new(format_real(value)).expect("CString::new failed");
+
+        (*useval).type_ = pg_sys::NodeTag::T_A_Const;
+        #[cfg(any(feature = "pg13", feature = "pg14"))]
+        {
+            (*useval).location = -1;
+            (*useval).val.type_ = pg_sys::NodeTag::T_Float;
+            (*useval).val.val.str_ = pg_sys::pstrdup(value.as_ptr());
+        }
+        #[cfg(not(any(feature = "pg13", feature = "pg14")))]
+        {
+            (*useval).isnull = false;
+            #[cfg(feature = "pg18")]
+            {
+                (*useval).location = -1;
+            }
+            (*useval).val.fval.type_ = pg_sys::NodeTag::T_Float;
+            (*useval).val.fval.fval = pg_sys::pstrdup(value.as_ptr());
+        }
+        useval
+    }
+}
+
+/// Constructs the AST nodes for ALTER SYSTEM SET <name> = <real value>.
+///
+/// # Safety
+/// Caller must ensure this is called within a valid transaction context
+/// and that PostgreSQL memory contexts are properly set up.
+unsafe fn alter_real_guc(name: &str, value: f64) {
+    // SAFETY: Called within a valid PostgreSQL memory context and transaction.
+    unsafe {
+        alter_system_set(name, make_real_const(value));
+    }
+}
+
+/// Constructs the AST nodes for ALTER SYSTEM SET <name> = '<value>'.
+///
+/// The value is passed as a string constant; PostgreSQL parses and validates
+/// it against the parameter's type, so this works for any GUC.
+///
+/// # Safety
+/// Caller must ensure this is called within a valid transaction context
+/// and that PostgreSQL memory contexts are properly set up.
+unsafe fn alter_setting(name: &str, value: &str) {
+    // SAFETY: Called within a valid PostgreSQL memory context and transaction.
+    unsafe {
+        let useval: *mut pg_sys::A_Const = make_node();
+        let value = CString::new(value).expect("CString::new failed");
+
+        // Configure A_Const with string value
+        (*useval).type_ = pg_sys::NodeTag::T_A_Const;
+        #[cfg(any(feature = "pg13", feature = "pg14"))]
+        {
+            (*useval).location = -1;
+            (*useval).val.type_ = pg_sys::NodeTag::T_String;
+            (*useval).val.val.str_ = pg_sys::pstrdup(value.as_ptr());
+        }
+        #[cfg(not(any(feature = "pg13", feature = "pg14")))]
+        {
+            (*useval).isnull = false;
+            #[cfg(feature = "pg18")]
+            {
+                (*useval).location = -1;
+            }
+            (*useval).val.sval.type_ = pg_sys::NodeTag::T_String;
+            (*useval).val.sval.sval = pg_sys::pstrdup(value.as_ptr());
+        }
+
+        alter_system_set(name, useval);
+    }
+}
+
+/// Run `alter`, returning any PostgreSQL ERROR it raises as a `ConfigError`.
+fn catch_config_error(alter: impl FnOnce()) -> Result<(), ConfigError> {
+    PgTryBuilder::new(AssertUnwindSafe(|| {
+        alter();
+        Ok(())
+    }))
+    .catch_others(|e| Err(ConfigError::from_caught(e)))
+    .execute()
+}
+
+/// Run an ALTER SYSTEM builder, starting a transaction if none is active.
 ///
-/// Returns Ok(()) on success, Err with a message on failure.
-pub fn execute_alter_system(new_value: i32) -> Result<(), &'static str> {
+/// - From SQL function context: Runs the builder in a subtransaction, so a
+///   failure is rolled back (releasing its locks) without aborting the caller
+/// - From background worker: Sets up transaction, calls, then commits, or
+///   aborts on failure
+fn with_alter_system_transaction(alter: impl FnOnce()) -> Result<(), ConfigError> {
     unsafe {
         // Check if we're already in a transaction (e.g., called from SQL function)
         let in_transaction = pg_sys::IsTransactionState();
 
         if in_transaction {
-            // SQL function context: call AlterSystemSetConfigFile directly
-            // No transaction handling needed - we're already in a valid context
-            alter_max_wal_size(new_value);
+            // SQL function context: subtransaction inside the caller's transaction
+            let old_context = pg_sys::CurrentMemoryContext;
+            let old_owner = pg_sys::CurrentResourceOwner;
+            pg_sys::BeginInternalSubTransaction(ptr::null());
+            let result = catch_config_error(alter);
+            if result.is_ok() {
+                pg_sys::ReleaseCurrentSubTransaction();
+            } else {
+                pg_sys::RollbackAndReleaseCurrentSubTransaction();
+            }
+            pg_sys::MemoryContextSwitchTo(old_context);
+            pg_sys::CurrentResourceOwner = old_owner;
+            result
         } else {
             // Background worker context: need to set up transaction
             if pg_sys::CurrentResourceOwner.is_null() {
@@ -143,10 +424,209 @@ pub fn execute_alter_system(new_value: i32) -> Result<(), &'static str> {
                     pg_sys::ResourceOwnerCreate(ptr::null_mut(), name.as_ptr());
             }
             pg_sys::StartTransactionCommand();
-            alter_max_wal_size(new_value);
-            pg_sys::CommitTransactionCommand();
+            let result = catch_config_error(alter);
+            if result.is_ok() {
+                pg_sys::CommitTransactionCommand();
+            } else {
+                pg_sys::AbortCurrentTransaction();
+            }
+            result
         }
     }
+}
 
+/// Execute ALTER SYSTEM SET max_wal_size = <new_value>.
+///
+/// This function detects the calling context:
+/// - From SQL function context: Calls AlterSystemSetConfigFile directly
+///   (we're already in a valid memory/transaction context)
+/// - From background worker: Sets up transaction, calls, then commits
+///
+/// Returns Ok(()) on success, or the `ConfigError` PostgreSQL raised.
+///
+/// The written value is remembered in shared memory for `verify_applied_size()`.
+pub fn execute_alter_system(new_value: i32) -> Result<(), ConfigError> {
+    with_alter_system_transaction(|| unsafe { alter_max_wal_size(new_value) })?;
+    if shmem::is_initialized() {
+        // SAFETY: GetCurrentTimestamp only reads the system clock.
+        let written_at = unsafe { pg_sys::GetCurrentTimestamp() };
+        shmem::update_state(|state| {
+            state.intended_size_mb = new_value;
+            state.intended_size_at = written_at;
+        });
+    }
     Ok(())
 }
+
+/// Whether an ALTER SYSTEM'd max_wal_size failed to take effect.
+///
+/// `intended_mb` is 0 when nothing is waiting to be verified.
+#[inline]
+pub(crate) fn applied_size_mismatch(intended_mb: i32, effective_mb: i32) -> bool {
+    intended_mb > 0 && intended_mb != effective_mb
+}
+
+/// Check that the last max_wal_size written with ALTER SYSTEM took effect.
+///
+/// Called at the start of each cycle. Once this process has re-read the
+/// configuration since the write (PgReloadTime), compares the effective
+/// `current_size` with the written value. If PostgreSQL clamped or rejected
+/// the value on reload, logs a WARNING and records an 'external_change' row
+/// from the intended to the effective size, so the audit chain matches
+/// reality. The pending value is cleared either way. Returns true on a mismatch.
+pub(crate) fn verify_applied_size(current_size: i32, checkpoint_timeout_sec: i32) -> bool {
+    let state = shmem::read_state();
+    // SAFETY: PgReloadTime is a process-global PostgreSQL variable, always valid.
+    let reloaded_at = unsafe { pg_sys::PgReloadTime };
+    if state.intended_size_mb == 0 || reloaded_at <= state.intended_size_at {
+        return false;
+    }
+    shmem::update_state(|state| state.intended_size_mb = 0);
+
+    let intended = state.intended_size_mb;
+    if !applied_size_mismatch(intended, current_size) {
+        return false;
+    }
+    pgrx::warning!(
+        "pg_walrus: max_wal_size was set to {} MB but is {} MB after the reload; PostgreSQL did not accept the value",
+        intended,
+        current_size
+    );
+    if let Err(e) = in_transaction(|| {
+        history::insert_history_record(
+            "external_change",
+            intended,
+            current_size,
+            0,
+            checkpoint_timeout_sec,
+            Some("max_wal_size did not take the value written by ALTER SYSTEM"),
+            Some(history::versioned_metadata(json!({
+                "intended_size_mb": intended,
+                "actual_size_mb": current_size,
+            }))),
+        )
+    }) {
+        pgrx::warning!("pg_walrus: failed to log rejected max_wal_size: {}", e);
+    }
+    true
+}
+
+/// Execute ALTER SYSTEM SET max_wal_size = <new_value> and measure how long it took.
+///
+/// Returns the elapsed milliseconds on success. The slowest apply is kept in
+/// shared memory as `max_apply_duration_ms`, so slow postgresql.auto.conf
+/// writes (e.g. a data directory on NFS) show up in walrus.status().
+pub fn execute_alter_system_timed(new_value: i32) -> Result<i64, ConfigError> {
+    let started = Instant::now();
+    execute_alter_system(new_value)?;
+    let elapsed_ms = i64::try_from(started.elapsed().as_millis()).unwrap_or(i64::MAX);
+
+    shmem::update_state(|state| {
+        state.max_apply_duration_ms = state.max_apply_duration_ms.max(elapsed_ms);
+    });
+    Ok(elapsed_ms)
+}
+
+/// Execute ALTER SYSTEM SET <name> = '<value>' for an arbitrary parameter.
+///
+/// PostgreSQL validates the parameter name and value, raising an ERROR for
+/// unknown parameters or out-of-range values. Transaction handling matches
+/// `execute_alter_system()`.
+///
+/// Returns Ok(()) on success, or the `ConfigError` PostgreSQL raised.
+pub fn execute_alter_system_setting(name: &str, value: &str) -> Result<(), ConfigError> {
+    with_alter_system_transaction(|| unsafe { alter_setting(name, value) })
+}
+
+/// Execute ALTER SYSTEM SET <name> = <value> for a real-valued parameter
+/// (e.g. checkpoint_completion_target).
+///
+/// Transaction handling matches `execute_alter_system()`.
+///
+/// Returns Ok(()) on success, or the `ConfigError` PostgreSQL raised.
+pub fn execute_alter_system_real(name: &str, value: f64) -> Result<(), ConfigError> {
+    with_alter_system_transaction(|| unsafe { alter_real_guc(name, value) })
+}
+
+/// Set checkpoint_completion_target alongside an applied grow, if enabled.
+///
+/// Controlled by walrus.manage_completion_target. Returns `(previous, new)`
+/// when the setting was changed, None if disabled, already at target, or failed.
+pub(crate) fn cotune_completion_target() -> Option<(f64, f64)> {
+    if !WALRUS_MANAGE_COMPLETION_TARGET.get() {
+        return None;
+    }
+    let target = WALRUS_COMPLETION_TARGET.get();
+    // SAFETY: CheckPointCompletionTarget is a global PostgreSQL variable, always valid.
+    let previous = unsafe { pg_sys::CheckPointCompletionTarget };
+    if (previous - target).abs() < f64::EPSILON {
+        return None;
+    }
+
+    if let Err(e) = execute_alter_system_real("checkpoint_completion_target", target) {
+        pgrx::warning!(
+            "pg_walrus: failed to set checkpoint_completion_target: {}",
+            e
+        );
+        return None;
+    }
+    logging::applied(format_args!(
+        "pg_walrus: setting checkpoint_completion_target from {} to {}",
+        previous, target
+    ));
+    Some((previous, target))
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test PostgreSQL errors are classified by SQLSTATE
+    #[test]
+    fn test_config_error_from_sqlstate() {
+        let denied =
+            ConfigError::from_sqlstate(PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE, "no");
+        assert_eq!(denied, ConfigError::PermissionDenied("no".to_string()));
+        assert!(matches!(
+            ConfigError::from_sqlstate(PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT, "x"),
+            ConfigError::InvalidValue(_)
+        ));
+        assert!(matches!(
+            ConfigError::from_sqlstate(PgSqlErrorCode::ERRCODE_DISK_FULL, "x"),
+            ConfigError::FileWrite(_)
+        ));
+        assert!(matches!(
+            ConfigError::from_sqlstate(PgSqlErrorCode::ERRCODE_INTERNAL_ERROR, "x"),
+            ConfigError::Other(_)
+        ));
+    }
+
+    /// Test the reload storm warning fires only above a nonzero limit
+    #[test]
+    fn test_is_reload_storm() {
+        assert!(!is_reload_storm(20, 20), "at the limit is not a storm");
+        assert!(is_reload_storm(21, 20));
+        assert!(!is_reload_storm(1000, 0), "0 disables the warning");
+    }
+
+    /// Test only a pending value that differs from the effective one is a mismatch
+    #[test]
+    fn test_applied_size_mismatch() {
+        assert!(applied_size_mismatch(4096, 2048), "clamped or refused");
+        assert!(!applied_size_mismatch(4096, 4096));
+        assert!(!applied_size_mismatch(0, 2048), "nothing pending");
+    }
+
+    /// Test the message keeps PostgreSQL's text after the cause
+    #[test]
+    fn test_config_error_display() {
+        let err = ConfigError::FileWrite("could not fsync file".to_string());
+        assert_eq!(
+            err.to_string(),
+            "could not write configuration: could not fsync file"
+        );
+        assert_eq!(ConfigError::Other("boom".to_string()).to_string(), "boom");
+        assert_eq!(err.message(), "could not fsync file");
+    }
+}
@@ -0,0 +1,692 @@
+// Integration tests for the pg_walrus monitoring cycle.
+//
+// These tests drive single cycles through walrus.tick() with prepared
+// shared-memory state, holding the cycle lock so the running worker cannot
+// start a cycle of its own in the meantime (hold_cycle_lock()). They cover
+// when walrus.tick() runs a cycle and when it leaves it to a running worker,
+// where a cycle reads checkpoint statistics and that they match the server's
+// statistics view, how it measures its interval and classifies its
+// checkpoints, how its changes are serialized, how its recorded decisions
+// replay, when a stale shrink is aborted, how a long-idle database shrinks,
+// and how a running worker recovers its baseline after walrus.reset(), plus
+// what a worker does as it starts: reading how the previous worker exited,
+// enforcing the walrus.min_size floor, and holding off decisions for
+// walrus.startup_grace_sec.
+
+/// Keep the running worker from starting a cycle until the test's transaction ends.
+///
+/// Waits out a worker cycle already in progress, so the shared-memory state a
+/// test prepares is not changed under it while walrus.tick() (which takes the
+/// same lock again) runs its cycle.
+fn hold_cycle_lock() {
+    Spi::run(&format!(
+        "SELECT pg_advisory_xact_lock({})",
+        crate::tick::CYCLE_LOCK_KEY
+    ))
+    .expect("cycle lock failed");
+}
+
+/// Test a cycle after a gap of many checkpoint intervals re-baselines instead of deciding
+#[pg_test]
+fn test_interval_drift_rebaselines() {
+    use crate::shmem;
+
+    hold_cycle_lock();
+    let saved = shmem::read_state();
+    let current = crate::stats::get_requested_checkpoints();
+    let timeout = crate::stats::checkpoint_timeout().as_secs() as i64;
+    // Ten intervals since the previous cycle, with a delta that would grow
+    shmem::update_state(|state| {
+        state.last_check_time = shmem::now_unix() - 10 * timeout;
+        state.prev_requested = current - 5;
+        state.quiet_intervals = 2;
+        state.pending_delta = 0;
+        state.sample_count = 0;
+        state.worker_pid = 0;
+    });
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.tick()")
+        .expect("query failed")
+        .expect("tick should return JSONB");
+    let after = shmem::read_state();
+    shmem::update_state(|state| *state = saved);
+
+    assert_eq!(result.0["baseline_established"], false);
+    assert_eq!(after.prev_requested, current, "the baseline moved past the gap");
+    assert_eq!(after.quiet_intervals, 2, "no decision was made");
+    assert_eq!(after.total_adjustments, saved.total_adjustments);
+}
+
+/// Test walrus.tick() establishes the baseline in shared memory, then measures from it
+#[pg_test]
+fn test_tick_runs_cycle_without_worker() {
+    hold_cycle_lock();
+    let saved = crate::shmem::read_state();
+    let current = crate::stats::get_requested_checkpoints();
+    // No worker pid published, as on a service that does not allow one
+    crate::shmem::update_state(|state| {
+        state.last_check_time = 0;
+        state.prev_requested = 0;
+        state.quiet_intervals = 0;
+        state.worker_pid = 0;
+    });
+    let tick = || {
+        Spi::get_one::<pgrx::JsonB>("SELECT walrus.tick()")
+            .expect("query failed")
+            .expect("tick should return JSONB")
+    };
+
+    let first = tick();
+    let baseline = crate::shmem::read_state();
+    // Move the baseline one checkpoint back, as if a checkpoint was forced since
+    crate::shmem::update_state(|state| state.prev_requested = current - 1);
+    let second = tick();
+    let after = crate::shmem::read_state();
+    crate::shmem::update_state(|state| *state = saved);
+
+    assert_eq!(first.0["ran"], true);
+    assert!(first.0["reason"].is_null());
+    assert_eq!(first.0["baseline_established"], true);
+    assert_eq!(baseline.prev_requested, current);
+    assert!(baseline.last_check_time > 0);
+    assert_eq!(second.0["baseline_established"], false);
+    assert_eq!(after.prev_requested, current, "the second tick advances prev_requested");
+    assert_eq!(second.0["prev_requested"], current);
+    assert_eq!(second.0["quiet_intervals"], 1, "one quiet interval was measured");
+}
+
+/// Test walrus.wal_pressure_only leaves out a checkpoint WAL volume cannot account for
+#[pg_test]
+fn test_wal_pressure_only_filters_other_checkpoints() {
+    let set = |name: &std::ffi::CStr, value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            name.as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    hold_cycle_lock();
+    let saved = crate::shmem::read_state();
+    let current = crate::stats::get_requested_checkpoints();
+    set(c"walrus.wal_pressure_only", c"on");
+    set(c"walrus.threshold", c"1");
+    // One requested checkpoint and almost no WAL since the last decision
+    crate::shmem::update_state(|state| {
+        state.last_check_time = crate::shmem::now_unix();
+        state.prev_requested = current - 1;
+        state.prev_lsn = crate::stats::get_current_wal_lsn();
+        state.quiet_intervals = 0;
+        state.worker_pid = 0;
+    });
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.tick()")
+        .expect("query failed")
+        .expect("tick should return JSONB");
+    let status = crate::status::status();
+    set(c"walrus.wal_pressure_only", c"off");
+    set(c"walrus.threshold", c"2");
+    crate::shmem::update_state(|state| *state = saved);
+
+    assert_eq!(
+        status.0["estimated_checkpoint_causes"],
+        serde_json::json!({ "wal_pressure": 0, "other": 1 })
+    );
+    assert_eq!(result.0["quiet_intervals"], 1, "the filtered delta of 0 is quiet");
+}
+
+/// Test a cycle leaves checkpoints set aside by walrus.ignore_next_checkpoints() out of its delta
+#[pg_test]
+fn test_ignore_next_checkpoints_reduces_effective_delta() {
+    use crate::shmem;
+
+    hold_cycle_lock();
+    let saved = shmem::read_state();
+    let current = crate::stats::get_requested_checkpoints();
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run("SELECT walrus.ignore_next_checkpoints(2)").expect("query failed");
+    // 3 forced checkpoints since the last cycle, 2 of them excluded: below the
+    // default walrus.threshold of 2, where all 3 would grow
+    shmem::update_state(|state| {
+        state.worker_pid = 0;
+        state.last_check_time = shmem::now_unix();
+        state.prev_requested = current - 3;
+        state.pending_delta = 0;
+        state.sample_count = 0;
+        state.quiet_intervals = 0;
+        state.needs_rebaseline = false;
+        state.last_adjustment_time = 0;
+    });
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.tick()")
+        .expect("query failed")
+        .expect("tick should return JSONB");
+    let after = shmem::read_state();
+    let grows = Spi::get_one::<i64>(
+        "SELECT count(*) FROM walrus.history WHERE action IN ('increase', 'capped', 'dry_run')",
+    )
+    .expect("query failed");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    shmem::update_state(|state| *state = saved);
+
+    assert_eq!(result.0["ran"], true);
+    assert_eq!(
+        after.ignore_checkpoints, 0,
+        "both ignored checkpoints were consumed"
+    );
+    assert_eq!(after.total_adjustments, saved.total_adjustments, "no grow");
+    assert_eq!(grows, Some(0));
+    assert_eq!(
+        result.0["quiet_intervals"], 1,
+        "the remaining delta of 1 is quiet"
+    );
+}
+
+/// Test walrus.tick() does not run a cycle while the worker's pid is live
+#[pg_test]
+fn test_tick_skips_while_worker_runs() {
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    // This backend's own pid stands in for a live worker
+    shmem::update_state(|state| state.worker_pid = unsafe { pg_sys::MyProcPid });
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.tick()")
+        .expect("query failed")
+        .expect("tick should return JSONB");
+    let after = shmem::read_state();
+    shmem::update_state(|state| *state = saved);
+
+    assert_eq!(result.0["ran"], false);
+    assert_eq!(result.0["reason"], "the background worker is running");
+    assert_eq!(after.last_check_time, saved.last_check_time, "no cycle ran");
+}
+
+/// Test the mirrored reload suppression flag shows in status() and can be cleared
+#[pg_test]
+fn test_clear_suppress() {
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    shmem::update_state(|state| state.suppress_next_sighup = true);
+    assert_eq!(crate::status::status().0["suppress_next_sighup"], true);
+
+    let cleared = Spi::get_one::<bool>("SELECT walrus.clear_suppress()").expect("query failed");
+    assert_eq!(cleared, Some(true));
+    assert_eq!(crate::status::status().0["suppress_next_sighup"], false);
+    let again = Spi::get_one::<bool>("SELECT walrus.clear_suppress()").expect("query failed");
+    assert_eq!(again, Some(false), "nothing left to clear");
+
+    shmem::update_state(|state| *state = saved);
+}
+
+/// Test a row with known decision inputs reproduces, and a tampered one diverges
+#[pg_test]
+fn test_reproduce_recorded_decision() {
+    let inputs = r#"{"current_size_mb": 1024, "delta": 3, "threshold": 2,
+        "quiet_intervals": 0, "changes_this_hour": 1, "max_changes_per_hour": 4,
+        "new_size_mb": 4096, "rule": "grow", "grow_exponent": 1.0,
+        "max_allowed_mb": 65536, "settling_step_mb": null}"#;
+    let insert = |new_size: i32| {
+        Spi::get_one::<i64>(&format!(
+            "INSERT INTO walrus.history (action, old_size_mb, new_size_mb, forced_checkpoints,
+                                         checkpoint_timeout_sec, metadata)
+             VALUES ('increase', 1024, {new_size}, 3, 300,
+                     jsonb_build_object('decision_inputs',
+                         jsonb_set('{inputs}'::jsonb, '{{new_size_mb}}', '{new_size}')))
+             RETURNING id"
+        ))
+        .expect("insert failed")
+        .expect("insert should return an id")
+    };
+
+    let id = insert(4096);
+    let report = Spi::get_one::<pgrx::JsonB>(&format!("SELECT walrus.reproduce({id})"))
+        .expect("query failed")
+        .expect("reproduce should return JSONB")
+        .0;
+    assert_eq!(report["matches"], true, "{report}");
+    assert_eq!(report["reproduced_new_size_mb"], 4096);
+    assert!(report["divergence"].is_null());
+
+    let id = insert(3000);
+    let report = Spi::get_one::<pgrx::JsonB>(&format!("SELECT walrus.reproduce({id})"))
+        .expect("query failed")
+        .expect("reproduce should return JSONB")
+        .0;
+    assert_eq!(report["matches"], false);
+    assert_eq!(report["recorded_new_size_mb"], 3000);
+    assert_eq!(report["reproduced_new_size_mb"], 4096);
+}
+
+/// Test replaying a missing row raises an error
+#[pg_test(error = "history row 0 does not exist")]
+fn test_reproduce_missing_row() {
+    Spi::get_one::<pgrx::JsonB>("SELECT walrus.reproduce(0)").expect("query failed");
+}
+
+/// Test two overlapping applies produce one change: the second waits its turn
+#[pg_test]
+fn test_concurrent_apply_is_serialized() {
+    use crate::apply::{ApplyError, ApplySource, SizeChange, apply_max_wal_size};
+    use pgrx::bgworkers::BackgroundWorkerBuilder;
+    use std::time::{Duration, Instant};
+
+    let saved = crate::shmem::read_state();
+    let original = crate::stats::get_current_max_wal_size();
+    let target = if original == 2048 { 3072 } else { 2048 };
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    let change = SizeChange {
+        action: "manual",
+        old_size_mb: original,
+        new_size_mb: target,
+        forced_checkpoints: 0,
+        checkpoint_timeout_sec: 300,
+        reason: "concurrent apply test",
+        abort_if_stale: false,
+    };
+    let apply = || {
+        apply_max_wal_size(
+            &change,
+            ApplySource::SetSize,
+            |_| {},
+            |_| serde_json::json!({ "source": "test" }),
+        )
+    };
+    let lock_held_elsewhere = || {
+        Spi::get_one::<bool>(
+            "SELECT EXISTS (SELECT 1 FROM pg_locks
+             WHERE locktype = 'advisory' AND granted AND pid <> pg_backend_pid())",
+        )
+        .expect("query failed")
+        .unwrap_or(false)
+    };
+    let history_rows = || {
+        Spi::get_one::<i64>("SELECT count(*) FROM walrus.history")
+            .expect("query failed")
+            .unwrap_or(0)
+    };
+    let wait_until = |done: &dyn Fn() -> bool, what: &str| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done() {
+            assert!(Instant::now() < deadline, "{what}");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    };
+
+    // Another session in the middle of an apply holds the adjustment lock
+    let db_oid = unsafe { pg_sys::MyDatabaseId };
+    let worker = BackgroundWorkerBuilder::new("pg_walrus lock holder")
+        .set_library("pg_walrus")
+        .set_function("walrus_test_lock_holder")
+        .set_argument(Some(pg_sys::Datum::from(u32::from(db_oid) as usize)))
+        .enable_spi_access()
+        .set_notify_pid(unsafe { pg_sys::MyProcPid })
+        .load_dynamic()
+        .expect("failed to start lock holder worker");
+    worker.wait_for_startup().expect("lock holder did not start");
+    wait_until(&lock_held_elsewhere, "lock holder never acquired the lock");
+
+    assert_eq!(apply(), Err(ApplyError::Busy));
+    let during = crate::shmem::read_state();
+    assert_eq!(during.total_adjustments, saved.total_adjustments);
+    assert_eq!(during.intended_size_mb, saved.intended_size_mb, "no ALTER SYSTEM ran");
+    assert_eq!(history_rows(), 0);
+
+    // Once the other transaction ends, the change applies exactly once
+    wait_until(&|| !lock_held_elsewhere(), "lock holder never released the lock");
+    assert!(apply().is_ok());
+    let after = crate::shmem::read_state();
+    assert_eq!(after.total_adjustments, saved.total_adjustments + 1);
+    assert_eq!(after.intended_size_mb, target);
+    assert_eq!(history_rows(), 1);
+
+    // Restore the original size for other tests
+    crate::config::execute_alter_system(original).expect("restore failed");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    crate::shmem::update_state(|state| *state = saved);
+}
+
+/// Test a restart after a crash is reported once, and one after a clean exit is not
+#[pg_test]
+fn test_unexpected_restart_detection() {
+    use crate::restart::{UNEXPECTED_RESTART_REASON, check_unexpected_restart, mark_clean_shutdown};
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    let notes = || {
+        Spi::get_one::<i64>(&format!(
+            "SELECT count(*) FROM walrus.history WHERE action = 'note' AND reason = '{}'",
+            UNEXPECTED_RESTART_REASON
+        ))
+        .expect("query failed")
+        .unwrap_or(0)
+    };
+
+    // A previous instance ran and exited without marking a clean shutdown
+    shmem::update_state(|state| {
+        state.worker_start_time = shmem::now_unix() - 600;
+        state.clean_shutdown = false;
+    });
+    assert!(check_unexpected_restart(), "a crash is detected");
+    assert_eq!(notes(), 1);
+
+    // This instance exits on SIGTERM; the next start is quiet and clears the flag
+    mark_clean_shutdown();
+    assert!(shmem::read_state().clean_shutdown);
+    assert!(!check_unexpected_restart(), "a clean exit is not a crash");
+    assert!(!shmem::read_state().clean_shutdown, "startup clears the flag");
+    assert_eq!(notes(), 1);
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    shmem::update_state(|state| *state = saved);
+}
+
+/// Test the startup floor grows to walrus.min_size when enabled and does nothing when disabled
+#[pg_test]
+fn test_enforce_min_on_start() {
+    use crate::min_floor::{MIN_FLOOR_REASON, enforce_min_on_start};
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    let original = crate::stats::get_current_max_wal_size();
+    let min_size = crate::guc::WALRUS_MIN_SIZE.get();
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    shmem::update_state(|state| {
+        state.growth_mb_this_day = 0;
+        state.day_window_start = 0;
+    });
+    let floor_row = || {
+        Spi::get_two::<i32, i32>(&format!(
+            "SELECT old_size_mb, new_size_mb FROM walrus.history
+             WHERE action = 'increase' AND reason = '{}'",
+            MIN_FLOOR_REASON
+        ))
+        .unwrap_or((None, None))
+    };
+
+    // The worker starts with max_wal_size below the floor
+    let below = min_size / 2;
+    assert!(!enforce_min_on_start(false, below), "disabled");
+    assert_eq!(floor_row(), (None, None));
+    assert_eq!(shmem::read_state().total_adjustments, saved.total_adjustments);
+
+    assert!(enforce_min_on_start(true, below));
+    let after = shmem::read_state();
+    assert_eq!(after.total_adjustments, saved.total_adjustments + 1);
+    assert_eq!(after.intended_size_mb, min_size);
+    assert_eq!(
+        after.growth_mb_this_day,
+        (min_size - below) as i64,
+        "counts toward the daily cap"
+    );
+    assert_eq!(floor_row(), (Some(below), Some(min_size)));
+    assert!(!enforce_min_on_start(true, min_size), "already at the floor");
+
+    // Restore the original size for other tests
+    crate::config::execute_alter_system(original).expect("restore failed");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    shmem::update_state(|state| *state = saved);
+}
+
+/// Test a worker shrink is aborted when walrus.set_size() ran after its target was computed
+#[pg_test]
+fn test_stale_shrink_aborts_after_set_size() {
+    use crate::apply::{ApplyError, ApplySource, SizeChange, apply_max_wal_size};
+
+    let saved = crate::shmem::read_state();
+    let original = crate::stats::get_current_max_wal_size();
+    let manual = if original == 2048 { 3072 } else { 2048 };
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+
+    // The cycle computes its shrink from the size it read
+    let shrink = SizeChange {
+        action: "decrease",
+        old_size_mb: original,
+        new_size_mb: 1024,
+        forced_checkpoints: 0,
+        checkpoint_timeout_sec: 300,
+        reason: "stale shrink test",
+        abort_if_stale: true,
+    };
+    // Before it applies, another session sets a size by hand
+    let result = Spi::get_one::<bool>(&format!("SELECT walrus.set_size({manual})"))
+        .expect("query failed");
+    assert_eq!(result, Some(true));
+    let applied = apply_max_wal_size(
+        &shrink,
+        ApplySource::Worker,
+        |_| {},
+        |_| serde_json::json!({}),
+    );
+
+    assert_eq!(applied, Err(ApplyError::Stale(manual)));
+    let state = crate::shmem::read_state();
+    assert_eq!(state.intended_size_mb, manual, "the manual size stands");
+    assert_eq!(state.total_adjustments, saved.total_adjustments + 1);
+    let decreases =
+        Spi::get_one::<i64>("SELECT count(*) FROM walrus.history WHERE action = 'decrease'")
+            .expect("query failed");
+    assert_eq!(decreases, Some(0));
+
+    // Restore the original size for other tests, reloading after the write so
+    // a reload requested by walrus.set_size() does not leave the manual size
+    crate::config::execute_alter_system(original).expect("restore failed");
+    crate::config::signal_postmaster_reload();
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    crate::shmem::update_state(|state| *state = saved);
+}
+
+/// Test shrinks past walrus.fast_shrink_after_intervals go to the floor, earlier ones by the factor
+#[pg_test]
+fn test_fast_shrink_after_long_idle() {
+    use crate::shmem;
+    use crate::shrink::maybe_shrink;
+
+    let set = |name: &std::ffi::CStr, value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            name.as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    let last_shrink = || {
+        Spi::get_two::<i32, pgrx::JsonB>(
+            "SELECT new_size_mb, metadata FROM walrus.history
+             WHERE action = 'decrease' ORDER BY id DESC LIMIT 1",
+        )
+        .expect("query failed")
+    };
+    // Prepare the quiet counts and a fresh rate-limit window; max_wal_size is
+    // 4096 MB again, since ALTER SYSTEM does not change the live value here
+    let idle_for = |quiet: i32, idle: i32| {
+        unsafe { pg_sys::max_wal_size_mb = 4096 };
+        shmem::update_state(|state| {
+            state.quiet_intervals = quiet;
+            state.idle_intervals = idle;
+            state.effective_shrink_intervals = 0;
+            state.last_adjustment_time = 0;
+            state.changes_this_hour = 0;
+            state.hour_window_start = 0;
+            state.pinned_until = 0;
+        });
+    };
+
+    let saved = shmem::read_state();
+    let saved_size = unsafe { pg_sys::max_wal_size_mb };
+    let original = crate::stats::get_current_max_wal_size();
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    set(c"walrus.shrink_enable", c"on");
+    set(c"walrus.shrink_factor", c"0.75");
+    set(c"walrus.shrink_intervals", c"5");
+    set(c"walrus.min_size", c"1024");
+    set(c"walrus.fast_shrink_after_intervals", c"100");
+
+    // Before the threshold: an ordinary factor shrink
+    idle_for(5, 100);
+    assert!(maybe_shrink(0, 0, 2, 5));
+    let (size, metadata) = last_shrink();
+    assert_eq!(size, Some(3072));
+    assert!(metadata.unwrap().0.get("fast_shrink").is_none());
+
+    // Past it: straight to the floor, even one quiet interval after a shrink
+    idle_for(0, 101);
+    assert!(maybe_shrink(0, 0, 2, 1));
+    let (size, metadata) = last_shrink();
+    assert_eq!(size, Some(1024));
+    let metadata = metadata.unwrap().0;
+    assert_eq!(metadata["fast_shrink"], true);
+    assert_eq!(metadata["idle_intervals"], 101);
+    assert_eq!(metadata["decision_inputs"]["rule"], "fast_shrink");
+
+    set(c"walrus.fast_shrink_after_intervals", c"0");
+    unsafe { pg_sys::max_wal_size_mb = saved_size };
+    crate::config::execute_alter_system(original).expect("restore failed");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    shmem::update_state(|state| *state = saved);
+}
+
+/// Test a running worker's first cycle after walrus.reset() re-baselines instead of deciding
+#[pg_test]
+fn test_reset_forces_rebaseline() {
+    use crate::shmem;
+    use crate::worker::process_checkpoint_stats;
+
+    hold_cycle_lock();
+    let saved = shmem::read_state();
+    let current = crate::stats::get_requested_checkpoints();
+    Spi::run("SELECT walrus.reset()").expect("reset failed");
+    let reset = shmem::read_state();
+    assert!(reset.needs_rebaseline);
+    assert_eq!(reset.prev_requested, 0);
+
+    // The worker is past its first iteration, so only the flag forces the baseline
+    let mut first_iteration = false;
+    process_checkpoint_stats(&mut first_iteration);
+    let after = shmem::read_state();
+    shmem::update_state(|state| *state = saved);
+
+    assert_eq!(after.prev_requested, current, "the baseline was re-established");
+    assert!(!after.needs_rebaseline, "the flag is cleared");
+    assert_eq!(after.total_adjustments, 0, "no delta against 0 was acted on");
+    assert_eq!(after.decision_intervals, 0, "no decision was made");
+}
+
+/// Test no delta decisions are made until the startup grace period elapses, while
+/// the cap-down to a lowered walrus.max is exempt from it
+#[pg_test]
+fn test_startup_grace_blocks_decisions() {
+    use crate::settling::startup_grace_active;
+    use crate::shmem::{self, now_unix};
+
+    let set = |name: &std::ffi::CStr, value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            name.as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    hold_cycle_lock();
+    let saved = shmem::read_state();
+
+    set(c"walrus.startup_grace_sec", c"600");
+    shmem::update_state(|state| state.worker_start_time = now_unix() - 30);
+    assert!(startup_grace_active(), "30 s after start is within a 600 s grace");
+
+    // A cycle within the grace still caps max_wal_size down to a lowered walrus.max
+    let current_size = crate::stats::get_current_max_wal_size();
+    let ceiling = std::ffi::CString::new((current_size / 2).to_string()).unwrap();
+    set(c"walrus.max", &ceiling);
+    set(c"walrus.min_size", c"64");
+    set(c"walrus.dry_run", c"on");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    shmem::update_state(|state| {
+        state.worker_pid = 0;
+        state.last_check_time = now_unix();
+        state.prev_requested = crate::stats::get_requested_checkpoints();
+        state.needs_rebaseline = false;
+        state.last_adjustment_time = 0;
+        state.changes_this_hour = 0;
+    });
+    Spi::run("SELECT walrus.tick()").expect("tick failed");
+    let cap_down = Spi::get_two::<i32, &str>(
+        "SELECT new_size_mb, metadata->>'would_apply' FROM walrus.history WHERE action = 'dry_run'",
+    )
+    .expect("query failed");
+    set(c"walrus.dry_run", c"off");
+    set(c"walrus.min_size", c"1024");
+    set(c"walrus.max", c"4096");
+    assert_eq!(cap_down, (Some(current_size / 2), Some("capped")));
+
+    shmem::update_state(|state| state.worker_start_time = now_unix() - 601);
+    assert!(!startup_grace_active(), "decisions resume once the grace elapses");
+
+    set(c"walrus.startup_grace_sec", c"0");
+    shmem::update_state(|state| state.worker_start_time = now_unix());
+    assert!(!startup_grace_active(), "a grace of 0 never blocks decisions");
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    shmem::update_state(|state| *state = saved);
+}
+
+/// Test walrus.stats_source = 'sql' reads the same forced checkpoint count as the C API
+#[pg_test]
+fn test_stats_source_sql_matches_direct() {
+    use crate::stats::{
+        direct_requested_checkpoints, get_requested_checkpoints, sql_requested_checkpoints,
+    };
+
+    let set = |value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.stats_source".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+
+    let direct = direct_requested_checkpoints();
+    assert!(direct >= 0, "the test cluster has checkpoint statistics");
+    assert_eq!(sql_requested_checkpoints(), direct);
+
+    set(c"sql");
+    let via_setting = get_requested_checkpoints();
+    set(c"direct");
+    assert_eq!(via_setting, direct, "the setting selects the SQL path");
+}
+
+/// Test that get_requested_checkpoints() matches pg_stat_bgwriter on PG 13-16.
+/// Before PG 17, forced checkpoints are reported as checkpoints_req.
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15", feature = "pg16"))]
+#[pg_test]
+fn test_get_requested_checkpoints_matches_bgwriter_view() {
+    let count = crate::stats::get_requested_checkpoints();
+    let view_count = Spi::get_one::<i64>("SELECT checkpoints_req FROM pg_stat_bgwriter")
+        .expect("query failed")
+        .expect("checkpoints_req should not be NULL");
+
+    assert!(count >= 0, "get_requested_checkpoints should return >= 0");
+    assert_eq!(
+        count, view_count,
+        "get_requested_checkpoints should match pg_stat_bgwriter.checkpoints_req"
+    );
+}
+
+/// Test that get_requested_checkpoints() matches pg_stat_checkpointer on PG 17+.
+#[cfg(any(feature = "pg17", feature = "pg18"))]
+#[pg_test]
+fn test_get_requested_checkpoints_matches_checkpointer_view() {
+    let count = crate::stats::get_requested_checkpoints();
+    let view_count = Spi::get_one::<i64>("SELECT num_requested FROM pg_stat_checkpointer")
+        .expect("query failed")
+        .expect("num_requested should not be NULL");
+
+    assert!(count >= 0, "get_requested_checkpoints should return >= 0");
+    assert_eq!(
+        count, view_count,
+        "get_requested_checkpoints should match pg_stat_checkpointer.num_requested"
+    );
+}
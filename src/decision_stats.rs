@@ -0,0 +1,69 @@
+//! Decision cadence statistics for pg_walrus.
+//!
+//! `walrus.decision_stats()` summarizes how often max_wal_size actually
+//! changes: the mean, median, and 95th percentile time between consecutive
+//! 'increase'/'decrease' rows within the history retention window. Short
+//! intervals between alternating grows and shrinks indicate flapping.
+
+use crate::guc::WALRUS_HISTORY_RETENTION_DAYS;
+use crate::history::{history_table, history_table_exists};
+
+use pgrx::JsonB;
+use pgrx::datum::DatumWithOid;
+use pgrx::prelude::*;
+use serde_json::json;
+
+/// Implementation for the decision cadence statistics.
+///
+/// Computed in one query: `lag(timestamp)` over the 'increase'/'decrease'
+/// rows of the last walrus.history_retention_days gives the gap before each
+/// decision, which is then aggregated. Returns a JSONB object with:
+/// - `retention_days`: Window the statistics cover
+/// - `increase_count`, `decrease_count`: Decisions in the window
+/// - `intervals`: Number of gaps measured (decisions - 1, or 0)
+/// - `mean_interval_sec`, `p50_interval_sec`, `p95_interval_sec`: Gap
+///   statistics in seconds (null with fewer than two decisions)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn decision_stats() -> Result<JsonB, spi::Error> {
+    let retention_days = WALRUS_HISTORY_RETENTION_DAYS.get();
+    if !history_table_exists()? {
+        return Ok(JsonB(json!({
+            "retention_days": retention_days,
+            "increase_count": 0,
+            "decrease_count": 0,
+            "intervals": 0,
+            "mean_interval_sec": null,
+            "p50_interval_sec": null,
+            "p95_interval_sec": null,
+        })));
+    }
+
+    let args: &[DatumWithOid<'_>] = &[retention_days.into()];
+    let stats = Spi::get_one_with_args::<JsonB>(
+        &format!(
+            "WITH decisions AS (
+                SELECT action,
+                       extract(epoch FROM timestamp - lag(timestamp) OVER (ORDER BY timestamp, id))::float8
+                           AS gap_sec
+                FROM {}
+                WHERE action IN ('increase', 'decrease')
+                  AND timestamp >= now() - $1 * interval '1 day'
+            )
+            SELECT jsonb_build_object(
+                'retention_days', $1,
+                'increase_count', count(*) FILTER (WHERE action = 'increase'),
+                'decrease_count', count(*) FILTER (WHERE action = 'decrease'),
+                'intervals', count(gap_sec),
+                'mean_interval_sec', round(avg(gap_sec)::numeric, 1),
+                'p50_interval_sec', round((percentile_cont(0.5) WITHIN GROUP (ORDER BY gap_sec))::numeric, 1),
+                'p95_interval_sec', round((percentile_cont(0.95) WITHIN GROUP (ORDER BY gap_sec))::numeric, 1)
+            )
+            FROM decisions",
+            history_table()
+        ),
+        args,
+    )?;
+
+    Ok(stats.unwrap_or_else(|| JsonB(json!({}))))
+}
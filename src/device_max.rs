@@ -0,0 +1,85 @@
+//! Ceiling relative to the WAL device for pg_walrus.
+//!
+//! On cloud volumes the sensible ceiling follows the size of the volume rather
+//! than a fixed number of MB. With `walrus.max_percent_of_device` set, the
+//! ceiling is that percentage of the total size of the filesystem holding
+//! pg_wal, read with statvfs at decision time so a resized volume takes effect
+//! without a configuration change. When walrus.max is also set (it always has
+//! a value), the lower of the two applies. If the filesystem size cannot be
+//! read, walrus.max applies alone.
+
+use crate::guc::{WALRUS_MAX, WALRUS_MAX_PERCENT_OF_DEVICE};
+
+/// Total size of the pg_wal filesystem, in MB.
+///
+/// Resolved like `wal_free_disk_mb()`, relative to the data directory.
+/// Returns None if statvfs fails.
+pub(crate) fn wal_device_total_mb() -> Option<i64> {
+    let mut buf: libc::statvfs = unsafe { std::mem::zeroed() };
+    let rc = unsafe { libc::statvfs(c"pg_wal".as_ptr(), &mut buf) };
+    if rc != 0 {
+        return None;
+    }
+    let total_bytes = (buf.f_blocks as u128).saturating_mul(buf.f_frsize as u128);
+    Some((total_bytes / (1024 * 1024)).min(i64::MAX as u128) as i64)
+}
+
+/// `percent` of a `device_mb` filesystem, in MB (rounded down, at least 2 MB).
+#[inline]
+pub(crate) fn percent_of_device_mb(device_mb: i64, percent: i32) -> i32 {
+    let mb = device_mb.max(0) as i128 * percent.clamp(0, 100) as i128 / 100;
+    mb.clamp(2, i32::MAX as i128) as i32
+}
+
+/// Ceiling from walrus.max and the device percentage.
+///
+/// walrus.max alone when `percent` is 0 or the device size is unknown.
+#[inline]
+pub(crate) fn device_capped_max(walrus_max: i32, percent: i32, device_mb: Option<i64>) -> i32 {
+    match device_mb {
+        Some(device_mb) if percent > 0 => walrus_max.min(percent_of_device_mb(device_mb, percent)),
+        _ => walrus_max,
+    }
+}
+
+/// Configured ceiling right now: walrus.max, limited by walrus.max_percent_of_device.
+///
+/// Before any walrus.temporary_max() override (see `effective_max()`).
+pub(crate) fn configured_max() -> i32 {
+    let percent = WALRUS_MAX_PERCENT_OF_DEVICE.get();
+    let device_mb = if percent > 0 {
+        wal_device_total_mb()
+    } else {
+        None
+    };
+    device_capped_max(WALRUS_MAX.get(), percent, device_mb)
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the percentage converts to MB of an injected device size
+    #[test]
+    fn test_percent_of_device_mb() {
+        assert_eq!(percent_of_device_mb(102_400, 10), 10_240);
+        assert_eq!(percent_of_device_mb(1_000, 33), 330);
+        assert_eq!(percent_of_device_mb(999, 1), 9, "rounds down");
+        assert_eq!(percent_of_device_mb(100, 1), 2, "never below 2 MB");
+        assert_eq!(
+            percent_of_device_mb(i64::MAX, 100),
+            i32::MAX,
+            "huge devices saturate"
+        );
+    }
+
+    /// Test the lower of walrus.max and the device share applies
+    #[test]
+    fn test_device_capped_max() {
+        assert_eq!(device_capped_max(4096, 10, Some(20_480)), 2048);
+        assert_eq!(device_capped_max(4096, 50, Some(20_480)), 4096);
+        assert_eq!(device_capped_max(4096, 0, Some(20_480)), 4096, "disabled");
+        assert_eq!(device_capped_max(4096, 10, None), 4096, "size unknown");
+    }
+}
@@ -0,0 +1,192 @@
+//! Support-bundle snapshot for pg_walrus.
+//!
+//! `walrus.diagnostics()` gathers everything usually asked for in a support
+//! request into one JSONB object: the full shared memory state, the effective
+//! configuration, the most recent history rows, and the build information
+//! from `walrus.version()`. It is read-only and needs no special privileges.
+//!
+//! `walrus.state()` returns just the shared memory state, every `WalrusState`
+//! field under its own name with its raw value (Unix timestamps as integers,
+//! codes as numbers), for white-box monitoring and regression tests.
+//! `walrus.status()` remains the human-friendly view.
+//!
+//! `walrus.guc_sources()` shows where each walrus.* setting comes from
+//! (default, configuration file, ALTER SYSTEM, ...), for troubleshooting.
+//!
+//! `walrus.clear_suppress()` cancels the worker's pending skip of its next
+//! cycle (`suppress_next_sighup` in walrus.status()), in case the reload it
+//! was waiting for never arrived.
+
+use crate::functions::{self, unix_timestamp_to_iso};
+use crate::history::{history_table, history_table_exists};
+use crate::shmem::{self, now_unix, read_state};
+
+use pgrx::JsonB;
+use pgrx::iter::TableIterator;
+use pgrx::name;
+use pgrx::prelude::*;
+use serde_json::{Value as JsonValue, json};
+
+/// Number of history rows included in `recent_history`.
+const RECENT_HISTORY_ROWS: i64 = 20;
+
+/// Core PostgreSQL settings included in `config` alongside the walrus.* GUCs.
+const CORE_SETTINGS: &str = "'max_wal_size', 'min_wal_size', 'checkpoint_timeout', \
+    'checkpoint_completion_target', 'wal_buffers', 'wal_segment_size'";
+
+/// walrus.* GUCs and related core settings, as shown by SHOW.
+///
+/// Settings the caller may not read (e.g. superuser-only GUCs for other
+/// users) are omitted by pg_settings.
+fn config_snapshot() -> Result<JsonValue, spi::Error> {
+    let config = Spi::get_one::<JsonB>(&format!(
+        "SELECT jsonb_object_agg(name, current_setting(name))
+         FROM pg_settings
+         WHERE name LIKE 'walrus.%' OR name IN ({CORE_SETTINGS})"
+    ))?;
+    Ok(config.map(|c| c.0).unwrap_or_else(|| json!({})))
+}
+
+/// The newest history rows, newest first (empty when the table is missing).
+fn recent_history() -> Result<JsonValue, spi::Error> {
+    if !history_table_exists()? {
+        return Ok(json!([]));
+    }
+    let rows = Spi::get_one::<JsonB>(&format!(
+        "SELECT coalesce(jsonb_agg(to_jsonb(h) ORDER BY h.id DESC), '[]'::jsonb)
+         FROM (SELECT * FROM {} ORDER BY id DESC LIMIT {RECENT_HISTORY_ROWS}) h",
+        history_table()
+    ))?;
+    Ok(rows.map(|r| r.0).unwrap_or_else(|| json!([])))
+}
+
+/// Every `WalrusState` field by name, as stored (null if shared memory is not
+/// initialized).
+fn state_snapshot() -> JsonValue {
+    if shmem::is_initialized() {
+        serde_json::to_value(read_state()).unwrap_or(JsonValue::Null)
+    } else {
+        JsonValue::Null
+    }
+}
+
+/// Implementation for the support-bundle snapshot.
+///
+/// Returns a JSONB object with:
+/// - `collected_at`: ISO 8601 time of the snapshot
+/// - `state`: every shared memory field (null if shared memory is not initialized)
+/// - `config`: walrus.* GUCs plus max_wal_size, min_wal_size, checkpoint_timeout,
+///   checkpoint_completion_target, wal_buffers, and wal_segment_size
+/// - `recent_history`: the last 20 history rows, newest first
+/// - `version`: the `walrus.version()` object
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn diagnostics() -> Result<JsonB, spi::Error> {
+    Ok(JsonB(json!({
+        "collected_at": unix_timestamp_to_iso(now_unix()),
+        "state": state_snapshot(),
+        "config": config_snapshot()?,
+        "recent_history": recent_history()?,
+        "version": functions::version().0,
+    })))
+}
+
+/// Implementation for dumping the raw shared memory state.
+///
+/// Returns a JSONB object with one key per `WalrusState` field, holding its
+/// value unconverted: integers (timestamps in Unix seconds, 0 for never),
+/// booleans, and arrays for `delta_ring` and the `cluster_id` bytes. Fields
+/// added to the struct appear automatically. Raises an error if shared memory
+/// is not initialized.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn state() -> JsonB {
+    if !shmem::is_initialized() {
+        pgrx::error!("{}", shmem::NOT_INITIALIZED_MESSAGE);
+    }
+    JsonB(state_snapshot())
+}
+
+/// Implementation for listing the provenance of each walrus.* setting.
+///
+/// Returns one row per walrus.* GUC in pg_settings, ordered by name:
+/// - name: TEXT
+/// - setting: TEXT (current value, as in pg_settings)
+/// - source: TEXT (pg_settings.source, or 'ALTER SYSTEM' for postgresql.auto.conf)
+/// - pending_restart: BOOL (changed in a file, but needs a restart, e.g. walrus.database)
+///
+/// pg_settings only shows `sourcefile` to superusers and pg_read_all_settings
+/// members, so other callers see 'configuration file' for ALTER SYSTEM too.
+/// Settings the caller may not read are omitted.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+#[allow(clippy::type_complexity)]
+pub fn guc_sources_srf() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(name, String),
+            name!(setting, Option<String>),
+            name!(source, Option<String>),
+            name!(pending_restart, bool),
+        ),
+    >,
+    spi::Error,
+> {
+    Spi::connect(|client| {
+        let results = client.select(
+            "SELECT name, setting,
+                    CASE WHEN sourcefile LIKE '%postgresql.auto.conf'
+                         THEN 'ALTER SYSTEM'
+                         ELSE source END AS source,
+                    pending_restart
+             FROM pg_catalog.pg_settings
+             WHERE name LIKE 'walrus.%'
+             ORDER BY name",
+            None,
+            &[],
+        )?;
+
+        let rows: Vec<_> = results
+            .filter_map(|row| {
+                let name: String = row.get_by_name("name").ok()??;
+                let setting: Option<String> = row.get_by_name("setting").ok()?;
+                let source: Option<String> = row.get_by_name("source").ok()?;
+                let pending_restart: bool = row.get_by_name("pending_restart").ok()??;
+                Some((name, setting, source, pending_restart))
+            })
+            .collect();
+
+        Ok(TableIterator::new(rows))
+    })
+}
+
+/// Implementation for clearing the worker's pending reload suppression.
+///
+/// Clears `suppress_next_sighup` in shared memory, so the worker processes its
+/// next cycle instead of skipping it as our own configuration reload.
+///
+/// # Returns
+///
+/// true if a skip was pending.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn clear_suppress() -> bool {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.clear_suppress() requires superuser");
+    }
+
+    let mut was_set = false;
+    shmem::update_state(|state| {
+        was_set = state.suppress_next_sighup;
+        state.suppress_next_sighup = false;
+    });
+    if was_set {
+        pgrx::log!("pg_walrus: pending reload suppression cleared, the next cycle runs");
+    }
+    was_set
+}
@@ -0,0 +1,198 @@
+//! Emergency shrink for pg_walrus when the WAL filesystem is nearly full.
+//!
+//! The normal shrink path waits for sustained quiet intervals and honors the
+//! cooldown and hourly limits. When free space on the pg_wal filesystem drops
+//! below `walrus.emergency_free_disk_mb`, the worker instead shrinks
+//! max_wal_size straight to the floor (the larger of walrus.min_size and
+//! min_wal_size), bypassing those gates.
+
+use crate::access::skip_read_only;
+use crate::algorithm::{records_only, shrink_floor};
+use crate::apply::{ApplySource, SizeChange, apply_max_wal_size};
+use crate::guc::{WALRUS_EMERGENCY_FREE_DISK_MB, WALRUS_MIN_SIZE, WALRUS_MODE};
+use crate::history;
+use crate::logging;
+use crate::pin::effective_dry_run;
+use crate::rate_limit::update_rate_limit_state_after_adjustment;
+use crate::shmem::{self, now_unix};
+use crate::stats::{checkpoint_timeout, get_current_max_wal_size, get_current_min_wal_size};
+use crate::worker::in_transaction;
+
+use serde_json::json;
+
+/// History reason for an emergency shrink.
+const EMERGENCY_REASON: &str = "emergency shrink: pg_wal filesystem nearly full";
+
+/// Outcome of an adjustment that overrides the normal grow/shrink cycle
+/// (emergency shrink, or capping down to a lowered walrus.max).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OverrideOutcome {
+    /// Applied through `apply_max_wal_size()`, which requested the reload
+    Applied,
+    /// Handled without a configuration change (dry-run, at floor, blocked, or failed)
+    Handled,
+}
+
+/// Decide whether free disk space warrants an emergency shrink.
+///
+/// Returns false when the feature is disabled (`threshold_mb` = 0) or the
+/// free space could not be determined.
+#[inline]
+pub(crate) fn should_emergency_shrink(free_mb: Option<i64>, threshold_mb: i32) -> bool {
+    threshold_mb > 0 && free_mb.is_some_and(|free| free < threshold_mb as i64)
+}
+
+/// Free space available to PostgreSQL on the pg_wal filesystem, in MB.
+///
+/// PostgreSQL processes run with the data directory as working directory,
+/// so the relative path resolves to the cluster's pg_wal (or its symlink target).
+/// Returns None if statvfs fails.
+pub(crate) fn wal_free_disk_mb() -> Option<i64> {
+    let mut buf: libc::statvfs = unsafe { std::mem::zeroed() };
+    let rc = unsafe { libc::statvfs(c"pg_wal".as_ptr(), &mut buf) };
+    if rc != 0 {
+        return None;
+    }
+    let free_bytes = (buf.f_bavail as u128).saturating_mul(buf.f_frsize as u128);
+    Some((free_bytes / (1024 * 1024)).min(i64::MAX as u128) as i64)
+}
+
+/// Shrink max_wal_size immediately if the pg_wal filesystem is nearly full.
+///
+/// Called at the top of each worker cycle. Returns None when no emergency is
+/// in progress, so normal processing continues. While an emergency persists
+/// the normal grow/shrink logic is skipped, since growing would only consume
+/// more of the remaining space.
+///
+/// The shrink ignores walrus.mode's direction restrictions, but still respects
+/// the min_size floor and dry-run (or observe) mode. It counts against the
+/// rate limit window, but is not blocked by it.
+pub(crate) fn check_emergency_shrink(current_requested: i64) -> Option<OverrideOutcome> {
+    let threshold_mb = WALRUS_EMERGENCY_FREE_DISK_MB.get();
+    if threshold_mb == 0 {
+        return None;
+    }
+
+    let free_mb = wal_free_disk_mb();
+    if !should_emergency_shrink(free_mb, threshold_mb) {
+        return None;
+    }
+    let free_mb = free_mb.unwrap_or(0);
+
+    let current_size = get_current_max_wal_size();
+    let min_size = shrink_floor(WALRUS_MIN_SIZE.get(), get_current_min_wal_size());
+
+    if current_size <= min_size {
+        pgrx::debug1!(
+            "pg_walrus: {} MB free on pg_wal filesystem, max_wal_size ({} MB) already at floor ({} MB)",
+            free_mb,
+            current_size,
+            min_size
+        );
+        return Some(OverrideOutcome::Handled);
+    }
+
+    if skip_read_only(current_size, min_size, "emergency shrink") {
+        return Some(OverrideOutcome::Handled);
+    }
+
+    let timeout_secs = checkpoint_timeout().as_secs() as i32;
+    let dry_run = records_only(WALRUS_MODE.get(), effective_dry_run());
+    let metadata = json!({
+        "emergency": true,
+        "free_disk_mb": free_mb,
+        "emergency_free_disk_mb": threshold_mb,
+        "calculated_size_mb": min_size
+    });
+
+    if dry_run {
+        logging::info(format_args!(
+            "pg_walrus [DRY-RUN]: would emergency shrink max_wal_size from {} MB to {} MB ({} MB free on pg_wal filesystem)",
+            current_size, min_size, free_mb
+        ));
+        // Emergency changes bypass the rate limit but still count against it
+        update_rate_limit_state_after_adjustment();
+
+        let mut metadata = metadata;
+        metadata["dry_run"] = json!(true);
+        metadata["would_apply"] = json!("decrease");
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "dry_run",
+                current_size,
+                min_size,
+                current_requested,
+                timeout_secs,
+                Some(EMERGENCY_REASON),
+                Some(history::versioned_metadata(metadata.clone())),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log emergency shrink history: {}", e);
+        }
+        return Some(OverrideOutcome::Handled);
+    }
+
+    pgrx::warning!(
+        "pg_walrus: emergency shrink of max_wal_size from {} MB to {} MB ({} MB free on pg_wal filesystem, below {} MB)",
+        current_size,
+        min_size,
+        free_mb,
+        threshold_mb
+    );
+
+    let change = SizeChange {
+        action: "decrease",
+        old_size_mb: current_size,
+        new_size_mb: min_size,
+        forced_checkpoints: current_requested,
+        checkpoint_timeout_sec: timeout_secs,
+        reason: EMERGENCY_REASON,
+        abort_if_stale: false,
+    };
+    let update = |state: &mut shmem::WalrusState| {
+        state.last_adjustment_time = now_unix();
+        state.quiet_intervals = 0;
+    };
+    let metadata = |_: i64| {
+        let key = history::decision_key(now_unix(), "decrease", current_requested, min_size);
+        history::with_idempotency_key(metadata, &key)
+    };
+    if let Err(e) = apply_max_wal_size(&change, ApplySource::Worker, update, metadata) {
+        pgrx::warning!(
+            "pg_walrus: failed to execute ALTER SYSTEM for emergency shrink, will retry next cycle: {}",
+            e
+        );
+        return Some(OverrideOutcome::Handled);
+    }
+    // Emergency changes bypass the rate limit but still count against it
+    update_rate_limit_state_after_adjustment();
+    Some(OverrideOutcome::Applied)
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test emergency shrink is disabled when the threshold is 0
+    #[test]
+    fn test_emergency_disabled() {
+        assert!(!should_emergency_shrink(Some(0), 0));
+        assert!(!should_emergency_shrink(Some(100), 0));
+    }
+
+    /// Test emergency shrink triggers only when free space is below the threshold
+    #[test]
+    fn test_emergency_trigger_below_threshold() {
+        assert!(should_emergency_shrink(Some(511), 512));
+        assert!(should_emergency_shrink(Some(0), 512));
+        assert!(!should_emergency_shrink(Some(512), 512));
+        assert!(!should_emergency_shrink(Some(100_000), 512));
+    }
+
+    /// Test emergency shrink does not trigger when free space is unknown
+    #[test]
+    fn test_emergency_unknown_free_space() {
+        assert!(!should_emergency_shrink(None, 512));
+    }
+}
@@ -0,0 +1,537 @@
+//! Decision explanation for pg_walrus.
+//!
+//! Implements `walrus.explain()`, which walks the same branches as the
+//! background worker's monitoring cycle against the live state and returns
+//! every evaluated condition in order, with its inputs and outcome. This lets
+//! operators see exactly which gate stopped an adjustment.
+//!
+//! Evaluation is read-only: shared memory, history, and configuration are not
+//! modified.
+
+use crate::algorithm::{
+    ModeGate, apply_ignored_checkpoints, calculate_new_size, calculate_shrink_size,
+    ceiling_excess_target, compute_confidence, current_shrink_intervals, grow_protected,
+    is_wal_quiet, lacks_real_baseline, meets_confidence_threshold, min_grow_delta, mode_gate,
+    next_quiet_intervals, peak_ratio_shrink_size, records_only, shrink_floor,
+};
+use crate::bulk_load::effective_threshold;
+use crate::emergency::{should_emergency_shrink, wal_free_disk_mb};
+use crate::functions::unix_timestamp_to_iso;
+use crate::guc::{
+    ShrinkTarget, WALRUS_COOLDOWN_SEC, WALRUS_DRY_RUN, WALRUS_EMERGENCY_FREE_DISK_MB,
+    WALRUS_ENABLE, WALRUS_GROW_EXPONENT, WALRUS_GROW_PROTECT_INTERVALS,
+    WALRUS_MAX_CHANGES_PER_HOUR, WALRUS_MAX_SHRINKS_PER_HOUR, WALRUS_MIN_APPLY_CONFIDENCE,
+    WALRUS_MIN_SIZE, WALRUS_MODE, WALRUS_PEAK_RATIO, WALRUS_SETTLING_MAX_STEP_MB,
+    WALRUS_SHRINK_BACKOFF, WALRUS_SHRINK_ENABLE, WALRUS_SHRINK_FACTOR, WALRUS_SHRINK_INTERVALS,
+    WALRUS_SHRINK_MAX_WAL_MB_PER_INTERVAL, WALRUS_SHRINK_ROUNDING, WALRUS_SHRINK_TARGET,
+    WALRUS_THRESHOLD_INCLUSIVE,
+};
+use crate::pin::is_pinned;
+use crate::rate_limit::{check_rate_limit, check_shrink_rate_limit};
+use crate::settling::{is_settling, settling_step_cap};
+use crate::shmem::{WalrusState, now_unix, read_state};
+use crate::stats::{
+    checkpoint_timeout, get_current_max_wal_size, get_current_min_wal_size, get_current_wal_lsn,
+    get_requested_checkpoints,
+};
+use crate::temporary_max::{ceiling_max, effective_max};
+
+use pgrx::JsonB;
+use serde_json::{Value as JsonValue, json};
+
+/// Ordered list of evaluated conditions.
+struct Explanation {
+    conditions: Vec<JsonValue>,
+}
+
+impl Explanation {
+    fn new() -> Self {
+        Self {
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Record an evaluated condition with its inputs and outcome.
+    fn check(&mut self, condition: &str, inputs: JsonValue, outcome: &str) {
+        self.conditions.push(json!({
+            "condition": condition,
+            "inputs": inputs,
+            "outcome": outcome,
+        }));
+    }
+
+    /// Append the final decision and return the full array.
+    fn decide(mut self, action: &str, reason: &str) -> JsonB {
+        self.conditions.push(json!({
+            "condition": "decision",
+            "inputs": {},
+            "outcome": action,
+            "reason": reason,
+        }));
+        JsonB(JsonValue::Array(self.conditions))
+    }
+}
+
+/// Outcome string for a boolean gate.
+#[inline]
+fn pass_fail(passed: bool) -> &'static str {
+    if passed { "pass" } else { "fail" }
+}
+
+/// Record the cooldown and hourly-limit conditions.
+///
+/// A shrink (`shrink = true`) is checked against walrus.max_shrinks_per_hour
+/// when that gives shrinks their own budget. Returns true if the rate limit
+/// allows an adjustment.
+fn explain_rate_limit(ex: &mut Explanation, state: &WalrusState, shrink: bool) -> bool {
+    let own_shrink_budget = shrink && WALRUS_MAX_SHRINKS_PER_HOUR.get() >= 0;
+    let result = if shrink {
+        check_shrink_rate_limit()
+    } else {
+        check_rate_limit()
+    };
+    let blocked_by = result.blocked_by.as_deref();
+    let remaining = result
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("cooldown_remaining_sec"))
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0);
+
+    ex.check(
+        "cooldown",
+        json!({
+            "cooldown_sec": WALRUS_COOLDOWN_SEC.get(),
+            "last_adjustment_time": state.last_adjustment_time,
+            "cooldown_remaining_sec": remaining,
+        }),
+        if blocked_by == Some("cooldown") {
+            "blocked"
+        } else {
+            "pass"
+        },
+    );
+    if blocked_by == Some("cooldown") {
+        return false;
+    }
+
+    if own_shrink_budget {
+        ex.check(
+            "shrink_hourly_limit",
+            json!({
+                "max_shrinks_per_hour": WALRUS_MAX_SHRINKS_PER_HOUR.get(),
+                "shrinks_this_hour": state.shrinks_this_hour,
+                "shrink_hour_window_start": state.shrink_hour_window_start,
+            }),
+            if blocked_by == Some("shrink_hourly_limit") {
+                "blocked"
+            } else {
+                "pass"
+            },
+        );
+        return blocked_by.is_none();
+    }
+
+    ex.check(
+        "hourly_limit",
+        json!({
+            "max_changes_per_hour": WALRUS_MAX_CHANGES_PER_HOUR.get(),
+            "changes_this_hour": state.changes_this_hour,
+            "hour_window_start": state.hour_window_start,
+        }),
+        if blocked_by == Some("hourly_limit") {
+            "blocked"
+        } else {
+            "pass"
+        },
+    );
+    blocked_by.is_none()
+}
+
+/// Record the dry-run condition and return the final action name.
+///
+/// walrus.mode = observe and a walrus.pin() pin log only, like walrus.dry_run.
+fn explain_dry_run<'a>(ex: &mut Explanation, action: &'a str) -> &'a str {
+    let dry_run = WALRUS_DRY_RUN.get();
+    let pinned_until = read_state().pinned_until;
+    let pinned = is_pinned(now_unix(), pinned_until);
+    let mode = WALRUS_MODE.get();
+    let log_only = records_only(mode, dry_run || pinned);
+    ex.check(
+        "dry_run",
+        json!({
+            "walrus.dry_run": dry_run,
+            "walrus.mode": mode.name(),
+            "pinned_until": unix_timestamp_to_iso(pinned_until),
+        }),
+        if log_only { "log_only" } else { "apply" },
+    );
+    if log_only { "dry_run" } else { action }
+}
+
+/// Record the walrus.mode direction check.
+///
+/// Returns true if walrus.mode allows a grow (`grow = true`) or shrink.
+fn explain_mode(ex: &mut Explanation, grow: bool) -> bool {
+    let mode = WALRUS_MODE.get();
+    let allowed = mode_gate(mode, grow, false) != ModeGate::Skip;
+    ex.check(
+        "mode",
+        json!({ "walrus.mode": mode.name() }),
+        pass_fail(allowed),
+    );
+    allowed
+}
+
+/// Implementation for explaining the next worker decision.
+///
+/// Returns a JSONB array of condition objects in evaluation order, each with:
+/// - `condition`: Name of the gate (e.g. "enable", "delta_vs_threshold", "cooldown")
+/// - `inputs`: Values the gate was evaluated against
+/// - `outcome`: Result of the gate (e.g. "pass", "fail", "blocked", "grow", "quiet")
+///
+/// The last element has `condition = "decision"`, the resulting action as its
+/// outcome, and a `reason`. Conditions after the first blocking gate are not evaluated,
+/// mirroring the worker's early returns.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn explain() -> JsonB {
+    let mut ex = Explanation::new();
+    let state = read_state();
+
+    // Enable check
+    let enabled = WALRUS_ENABLE.get();
+    ex.check(
+        "enable",
+        json!({ "walrus.enable": enabled }),
+        pass_fail(enabled),
+    );
+    if !enabled {
+        return ex.decide("none", "extension is disabled");
+    }
+
+    // Emergency shrink (bypasses all other gates)
+    let emergency_threshold = WALRUS_EMERGENCY_FREE_DISK_MB.get();
+    let free_mb = if emergency_threshold > 0 {
+        wal_free_disk_mb()
+    } else {
+        None
+    };
+    let emergency = should_emergency_shrink(free_mb, emergency_threshold);
+    ex.check(
+        "emergency",
+        json!({
+            "walrus.emergency_free_disk_mb": emergency_threshold,
+            "free_disk_mb": free_mb,
+        }),
+        if emergency_threshold == 0 {
+            "disabled"
+        } else if emergency {
+            "triggered"
+        } else {
+            "pass"
+        },
+    );
+
+    let current_size = get_current_max_wal_size();
+    let floor = shrink_floor(WALRUS_MIN_SIZE.get(), get_current_min_wal_size());
+    if emergency {
+        return if current_size > floor {
+            let action = explain_dry_run(&mut ex, "decrease");
+            ex.decide(action, "emergency shrink: pg_wal filesystem nearly full")
+        } else {
+            ex.decide("none", "emergency, but max_wal_size already at floor")
+        };
+    }
+
+    // Stats availability
+    let current_requested = get_requested_checkpoints();
+    ex.check(
+        "stats_available",
+        json!({ "requested_checkpoints": current_requested }),
+        pass_fail(current_requested >= 0),
+    );
+    if current_requested < 0 {
+        return ex.decide("none", "checkpoint statistics unavailable");
+    }
+
+    // Baseline, and a delta that is not the counter's lifetime total
+    let lifetime_delta = lacks_real_baseline(
+        state.prev_requested,
+        current_requested - state.prev_requested + state.pending_delta,
+        checkpoint_timeout().as_secs() as i64,
+    );
+    let has_baseline = state.prev_requested > 0 && !lifetime_delta;
+    ex.check(
+        "baseline",
+        json!({
+            "prev_requested": state.prev_requested,
+            "requested_checkpoints": current_requested,
+        }),
+        pass_fail(has_baseline),
+    );
+    if !has_baseline {
+        return ex.decide("none", "awaiting baseline checkpoint count");
+    }
+
+    // walrus.max lowered below the current size (cap-down, subject to rate limits)
+    let ceiling = ceiling_max();
+    let ceiling_target = ceiling_excess_target(current_size, ceiling, floor);
+    ex.check(
+        "ceiling_excess",
+        json!({ "current_size_mb": current_size, "walrus.max": ceiling, "floor_mb": floor }),
+        if ceiling_target.is_some() {
+            "capped"
+        } else {
+            "pass"
+        },
+    );
+    if let Some(target) = ceiling_target {
+        if !explain_rate_limit(&mut ex, &state, false) {
+            return ex.decide("skipped", "rate limited");
+        }
+        let action = explain_dry_run(&mut ex, "capped");
+        return ex.decide(
+            action,
+            &format!(
+                "current exceeds lowered walrus.max: cap max_wal_size from {} MB to {} MB",
+                current_size, target
+            ),
+        );
+    }
+
+    // Delta vs threshold
+    let raw_delta = current_requested - state.prev_requested + state.pending_delta;
+    let (delta, ignored) = apply_ignored_checkpoints(raw_delta, state.ignore_checkpoints);
+    let threshold = effective_threshold();
+    let inclusive = WALRUS_THRESHOLD_INCLUSIVE.get();
+    let grow = delta >= min_grow_delta(threshold, inclusive);
+    ex.check(
+        "delta_vs_threshold",
+        json!({
+            "requested_checkpoints": current_requested,
+            "prev_requested": state.prev_requested,
+            "pending_delta": state.pending_delta,
+            "ignored_checkpoints": ignored,
+            "delta": delta,
+            "threshold": threshold,
+            "walrus.threshold_inclusive": inclusive,
+            "comparison": if inclusive { "delta >= threshold" } else { "delta > threshold" },
+        }),
+        if grow { "grow" } else { "quiet" },
+    );
+
+    if grow {
+        if !explain_mode(&mut ex, true) {
+            return ex.decide("none", "grow disabled by walrus.mode");
+        }
+
+        // Ceiling clamp (walrus.max, or an active walrus.temporary_max() override)
+        let max_allowed = effective_max();
+        let calculated_size = calculate_new_size(current_size, delta, WALRUS_GROW_EXPONENT.get());
+        let is_capped = calculated_size > max_allowed;
+        let new_size = calculated_size.min(max_allowed);
+        ex.check(
+            "ceiling_clamp",
+            json!({
+                "current_size_mb": current_size,
+                "calculated_size_mb": calculated_size,
+                "walrus.max": max_allowed,
+            }),
+            if is_capped { "capped" } else { "pass" },
+        );
+
+        // Step limit while settling after startup or promotion
+        let max_step = WALRUS_SETTLING_MAX_STEP_MB.get();
+        let settled_size = if is_settling(now_unix(), state.settling_until) {
+            settling_step_cap(current_size, new_size, max_step)
+        } else {
+            new_size
+        };
+        ex.check(
+            "settling",
+            json!({
+                "settling_until": state.settling_until,
+                "walrus.settling_max_step_mb": max_step,
+            }),
+            if settled_size < new_size {
+                "limited"
+            } else {
+                "pass"
+            },
+        );
+        let is_capped = is_capped && settled_size == new_size;
+        let new_size = settled_size;
+
+        ex.check(
+            "already_at_target",
+            json!({ "current_size_mb": current_size, "target_size_mb": new_size }),
+            pass_fail(current_size < new_size),
+        );
+        if current_size >= new_size {
+            return ex.decide("none", "max_wal_size already at maximum");
+        }
+
+        // Confidence gate (grow only, like the worker)
+        let confidence = compute_confidence(&state, current_requested);
+        let min_confidence = WALRUS_MIN_APPLY_CONFIDENCE.get();
+        let confident = meets_confidence_threshold(confidence, min_confidence);
+        ex.check(
+            "confidence_gate",
+            json!({ "confidence": confidence, "walrus.min_apply_confidence": min_confidence }),
+            pass_fail(confident),
+        );
+        if !confident {
+            return ex.decide("skipped", "confidence below threshold");
+        }
+
+        if !explain_rate_limit(&mut ex, &state, false) {
+            return ex.decide("skipped", "rate limited");
+        }
+
+        let action = explain_dry_run(&mut ex, if is_capped { "capped" } else { "increase" });
+        return ex.decide(
+            action,
+            &format!(
+                "grow max_wal_size from {} MB to {} MB",
+                current_size, new_size
+            ),
+        );
+    }
+
+    // WAL volume check for quiet intervals
+    let wal_bytes = if state.prev_lsn > 0 {
+        (get_current_wal_lsn() - state.prev_lsn).max(0)
+    } else {
+        0
+    };
+    let max_wal_mb = WALRUS_SHRINK_MAX_WAL_MB_PER_INTERVAL.get();
+    let wal_quiet = is_wal_quiet(wal_bytes, max_wal_mb);
+    ex.check(
+        "wal_volume",
+        json!({
+            "wal_bytes_since_last_check": wal_bytes,
+            "walrus.shrink_max_wal_mb_per_interval": max_wal_mb,
+        }),
+        if max_wal_mb == 0 {
+            "disabled"
+        } else {
+            pass_fail(wal_quiet)
+        },
+    );
+    let projected_quiet = next_quiet_intervals(state.quiet_intervals, wal_quiet);
+    if !wal_quiet {
+        return ex.decide("none", "WAL volume too high to count interval as quiet");
+    }
+
+    // Shrink gates
+    let shrink_enable = WALRUS_SHRINK_ENABLE.get();
+    ex.check(
+        "shrink_enable",
+        json!({ "walrus.shrink_enable": shrink_enable }),
+        pass_fail(shrink_enable),
+    );
+    if !shrink_enable {
+        return ex.decide("none", "shrink disabled");
+    }
+    if !explain_mode(&mut ex, false) {
+        return ex.decide("none", "shrink disabled by walrus.mode");
+    }
+
+    let shrink_intervals = current_shrink_intervals(state);
+    ex.check(
+        "quiet_intervals",
+        json!({
+            "quiet_intervals": state.quiet_intervals,
+            "projected_quiet_intervals": projected_quiet,
+            "walrus.shrink_intervals": WALRUS_SHRINK_INTERVALS.get(),
+            "walrus.shrink_backoff": WALRUS_SHRINK_BACKOFF.get(),
+            "required_quiet_intervals": shrink_intervals,
+        }),
+        pass_fail(projected_quiet >= shrink_intervals),
+    );
+    if projected_quiet < shrink_intervals {
+        return ex.decide("none", "not enough quiet intervals");
+    }
+
+    // Floor clamp
+    ex.check(
+        "floor_clamp",
+        json!({
+            "current_size_mb": current_size,
+            "walrus.min_size": WALRUS_MIN_SIZE.get(),
+            "min_wal_size": get_current_min_wal_size(),
+            "floor_mb": floor,
+        }),
+        pass_fail(current_size > floor),
+    );
+    if current_size <= floor {
+        return ex.decide("none", "max_wal_size already at minimum");
+    }
+
+    let shrink_target = WALRUS_SHRINK_TARGET.get();
+    let peak_mb = match shrink_target {
+        ShrinkTarget::Factor => None,
+        ShrinkTarget::PeakRatio => crate::history::recent_peak_size_mb().unwrap_or(None),
+    };
+    let shrink_factor = WALRUS_SHRINK_FACTOR.get();
+    let rounding = WALRUS_SHRINK_ROUNDING.get();
+    let peak_ratio = WALRUS_PEAK_RATIO.get();
+    let new_size = match peak_mb {
+        Some(peak) => peak_ratio_shrink_size(current_size, peak, peak_ratio, floor),
+        None => calculate_shrink_size(current_size, shrink_factor, floor, rounding),
+    };
+    ex.check(
+        "shrink_target",
+        json!({
+            "current_size_mb": current_size,
+            "walrus.shrink_target": shrink_target.name(),
+            "walrus.shrink_factor": shrink_factor,
+            "walrus.shrink_rounding": rounding.name(),
+            "walrus.peak_ratio": peak_ratio,
+            "recent_peak_mb": peak_mb,
+            "target_size_mb": new_size,
+        }),
+        pass_fail(new_size < current_size),
+    );
+    if new_size >= current_size {
+        return ex.decide("none", "shrink target not less than current size");
+    }
+
+    // The next decision interval is the one being explained
+    let protect_intervals = WALRUS_GROW_PROTECT_INTERVALS.get();
+    let next_interval = state.decision_intervals + 1;
+    let protected = grow_protected(next_interval, state.last_grow_interval, protect_intervals);
+    ex.check(
+        "grow_protect",
+        json!({
+            "walrus.grow_protect_intervals": protect_intervals,
+            "intervals_since_grow": (state.last_grow_interval > 0)
+                .then(|| next_interval - state.last_grow_interval),
+        }),
+        if protect_intervals == 0 {
+            "disabled"
+        } else {
+            pass_fail(!protected)
+        },
+    );
+    if protected {
+        return ex.decide(
+            "skipped",
+            "shrink within walrus.grow_protect_intervals of a grow",
+        );
+    }
+
+    if !explain_rate_limit(&mut ex, &state, true) {
+        return ex.decide("skipped", "rate limited");
+    }
+
+    let action = explain_dry_run(&mut ex, "decrease");
+    ex.decide(
+        action,
+        &format!(
+            "shrink max_wal_size from {} MB to {} MB",
+            current_size, new_size
+        ),
+    )
+}
@@ -0,0 +1,867 @@
+// Integration tests for pg_walrus informational SQL functions.
+//
+// These tests cover the smaller SQL functions in the walrus schema that
+// report extension and worker metadata.
+
+// =========================================================================
+// walrus.version() Tests
+// =========================================================================
+
+/// Test walrus.version() reports a non-empty extension_version
+#[pg_test]
+fn test_version_extension_version() {
+    let version = Spi::get_one::<&str>("SELECT walrus.version()->>'extension_version'")
+        .expect("query failed");
+    assert!(
+        version.is_some_and(|v| !v.is_empty()),
+        "extension_version should be non-empty"
+    );
+}
+
+/// Test walrus.version() pg_major matches the running server
+#[pg_test]
+fn test_version_pg_major_matches_server() {
+    let pg_major =
+        Spi::get_one::<i32>("SELECT (walrus.version()->>'pg_major')::int").expect("query failed");
+    let server_major =
+        Spi::get_one::<i32>("SELECT current_setting('server_version_num')::int / 10000")
+            .expect("query failed");
+    assert!(pg_major.is_some(), "version should have 'pg_major' field");
+    assert_eq!(
+        pg_major, server_major,
+        "pg_major should match the test cluster major version"
+    );
+}
+
+/// Test walrus.version() reports a known build_profile
+#[pg_test]
+fn test_version_build_profile() {
+    let profile =
+        Spi::get_one::<&str>("SELECT walrus.version()->>'build_profile'").expect("query failed");
+    assert!(
+        matches!(profile, Some("debug") | Some("release")),
+        "build_profile should be 'debug' or 'release', got {:?}",
+        profile
+    );
+}
+
+// =========================================================================
+// walrus.worker_running() and walrus.heartbeat() Tests
+// =========================================================================
+
+/// Test walrus.worker_running() reports the background worker
+#[pg_test]
+fn test_worker_running_returns_true() {
+    let running = Spi::get_one::<bool>("SELECT walrus.worker_running()").expect("query failed");
+    assert_eq!(running, Some(true), "worker should be running");
+}
+
+/// Test walrus.heartbeat() is NULL when no cycle has completed
+#[pg_test]
+fn test_heartbeat_null_before_first_cycle() {
+    use crate::shmem;
+
+    shmem::update_state(|state| {
+        state.last_check_time = 0;
+    });
+
+    let is_null = Spi::get_one::<bool>("SELECT walrus.heartbeat() IS NULL").expect("query failed");
+    assert_eq!(
+        is_null,
+        Some(true),
+        "heartbeat should be NULL when last_check_time is 0"
+    );
+}
+
+/// Test walrus.heartbeat() advances when last_check_time is updated
+#[pg_test]
+fn test_heartbeat_advances() {
+    use crate::shmem;
+
+    let now = shmem::now_unix();
+    shmem::update_state(|state| {
+        state.last_check_time = now - 600;
+    });
+
+    let first = Spi::get_one::<i64>("SELECT extract(epoch FROM walrus.heartbeat())::bigint")
+        .expect("query failed");
+    assert_eq!(
+        first,
+        Some(now - 600),
+        "heartbeat should match last_check_time"
+    );
+
+    shmem::update_state(|state| {
+        state.last_check_time = now;
+    });
+
+    let second = Spi::get_one::<i64>("SELECT extract(epoch FROM walrus.heartbeat())::bigint")
+        .expect("query failed");
+    assert_eq!(
+        second,
+        Some(now),
+        "heartbeat should match updated last_check_time"
+    );
+    assert!(second > first, "heartbeat should advance");
+}
+
+// =========================================================================
+// walrus.recommendation() Hypothetical Override Tests
+// =========================================================================
+
+/// Test hypothetical delta above threshold recommends growth
+#[pg_test]
+fn test_recommendation_hypothetical_grow() {
+    let current = stats::get_current_max_wal_size();
+    let max = crate::guc::WALRUS_MAX.get();
+
+    let action =
+        Spi::get_one::<&str>("SELECT walrus.recommendation(hypothetical_delta := 100)->>'action'")
+            .expect("query failed");
+    let recommended = Spi::get_one::<i32>(
+        "SELECT (walrus.recommendation(hypothetical_delta := 100)->>'recommended_size_mb')::int",
+    )
+    .expect("query failed");
+
+    if current < max {
+        assert_eq!(
+            action,
+            Some("increase"),
+            "delta 100 should recommend increase"
+        );
+        assert_eq!(
+            recommended,
+            Some(max),
+            "large delta should be capped at walrus.max"
+        );
+    } else {
+        assert_eq!(action, Some("none"), "at walrus.max growth is not possible");
+    }
+}
+
+/// Test hypothetical quiet intervals drive the shrink path
+#[pg_test]
+fn test_recommendation_hypothetical_shrink() {
+    let current = stats::get_current_max_wal_size();
+    let min_size = crate::guc::WALRUS_MIN_SIZE.get();
+
+    let action = Spi::get_one::<&str>(
+        "SELECT walrus.recommendation(hypothetical_delta := 0, hypothetical_quiet_intervals := 100)->>'action'",
+    )
+    .expect("query failed");
+
+    if current > min_size {
+        assert_eq!(
+            action,
+            Some("decrease"),
+            "100 quiet intervals should recommend decrease"
+        );
+    } else {
+        let reason = Spi::get_one::<String>(
+            "SELECT walrus.recommendation(hypothetical_delta := 0, hypothetical_quiet_intervals := 100)->>'reason'",
+        )
+        .expect("query failed")
+        .unwrap_or_default();
+        assert_eq!(
+            action,
+            Some("none"),
+            "at walrus.min_size shrink is not possible"
+        );
+        assert!(
+            reason.contains("already at minimum"),
+            "unexpected reason: {}",
+            reason
+        );
+    }
+}
+
+/// Test hypothetical quiet intervals below walrus.shrink_intervals do not shrink
+#[pg_test]
+fn test_recommendation_hypothetical_quiet_below_threshold() {
+    let reason = Spi::get_one::<String>(
+        "SELECT walrus.recommendation(hypothetical_delta := 0, hypothetical_quiet_intervals := 1)->>'reason'",
+    )
+    .expect("query failed")
+    .unwrap_or_default();
+    assert!(
+        reason.contains("1 of 5 quiet intervals"),
+        "reason should reflect hypothetical quiet intervals: {}",
+        reason
+    );
+}
+
+/// Test forced_checkpoints_per_minute is the delta normalized by checkpoint_timeout
+#[pg_test]
+fn test_recommendation_forced_checkpoints_per_minute() {
+    let timeout_sec = Spi::get_one::<i32>(
+        "SELECT (walrus.recommendation(hypothetical_delta := 10)->>'checkpoint_timeout_sec')::int",
+    )
+    .expect("query failed")
+    .expect("checkpoint_timeout_sec should be present");
+    let live_timeout_sec = Spi::get_one::<i32>(
+        "SELECT setting::int FROM pg_settings WHERE name = 'checkpoint_timeout'",
+    )
+    .expect("query failed")
+    .expect("checkpoint_timeout should exist");
+    assert_eq!(timeout_sec, live_timeout_sec);
+
+    let rate = Spi::get_one::<f64>(
+        "SELECT (walrus.recommendation(hypothetical_delta := 10)->>'forced_checkpoints_per_minute')::float8",
+    )
+    .expect("query failed")
+    .expect("forced_checkpoints_per_minute should be present");
+    let expected = 10.0 / (timeout_sec as f64 / 60.0);
+    assert!(
+        (rate - expected).abs() < 1e-9,
+        "rate {} should equal delta / (timeout / 60) = {}",
+        rate,
+        expected
+    );
+}
+
+/// Test walrus.recommendation() rejects a negative hypothetical delta
+#[pg_test(error = "hypothetical_delta must be >= 0")]
+fn test_recommendation_hypothetical_negative_delta() {
+    Spi::run("SELECT walrus.recommendation(hypothetical_delta := -1)").expect("query failed");
+}
+
+// =========================================================================
+// walrus.ignore_next_checkpoints() Tests
+// =========================================================================
+
+/// Test walrus.ignore_next_checkpoints() records the count in shared memory
+#[pg_test]
+fn test_ignore_next_checkpoints_sets_shmem() {
+    use crate::shmem;
+
+    let result =
+        Spi::get_one::<i64>("SELECT walrus.ignore_next_checkpoints(3)").expect("query failed");
+    assert_eq!(result, Some(3), "function should return the pending count");
+    assert_eq!(shmem::read_state().ignore_checkpoints, 3);
+
+    let pending =
+        Spi::get_one::<i64>("SELECT (walrus.status()->>'ignore_checkpoints_pending')::bigint")
+            .expect("query failed");
+    assert_eq!(
+        pending,
+        Some(3),
+        "status should report pending ignored checkpoints"
+    );
+
+    Spi::run("SELECT walrus.ignore_next_checkpoints(0)").expect("query failed");
+    assert_eq!(shmem::read_state().ignore_checkpoints, 0);
+}
+
+/// Test walrus.reset() clears pending ignored checkpoints
+#[pg_test]
+fn test_reset_clears_ignore_checkpoints() {
+    use crate::shmem;
+
+    Spi::run("SELECT walrus.ignore_next_checkpoints(5)").expect("query failed");
+    Spi::run("SELECT walrus.reset()").expect("reset failed");
+    assert_eq!(shmem::read_state().ignore_checkpoints, 0);
+}
+
+// =========================================================================
+// walrus.set() Tests
+// =========================================================================
+
+/// Test walrus.set() writes the setting that the triggered reload applies
+#[pg_test]
+fn test_set_threshold() {
+    let result =
+        Spi::get_one::<bool>("SELECT walrus.set('walrus.threshold', '7')").expect("query failed");
+    assert_eq!(result, Some(true), "walrus.set() should return true");
+
+    // pg_file_settings re-reads the config files, showing what the reload applies
+    let setting = Spi::get_one::<&str>(
+        "SELECT setting FROM pg_file_settings
+         WHERE name = 'walrus.threshold' AND error IS NULL
+         ORDER BY seqno DESC LIMIT 1",
+    )
+    .expect("query failed");
+    assert_eq!(
+        setting,
+        Some("7"),
+        "walrus.threshold should be 7 in postgresql.auto.conf"
+    );
+
+    // Restore the default for other tests
+    Spi::run("SELECT walrus.set('walrus.threshold', '2')").expect("restore failed");
+}
+
+/// Test walrus.set() rejects parameters outside the walrus namespace
+#[pg_test(error = "walrus.set() only accepts walrus.* parameters: max_wal_size")]
+fn test_set_rejects_non_walrus_param() {
+    Spi::run("SELECT walrus.set('max_wal_size', '2GB')").expect("query failed");
+}
+
+/// Test walrus.set() surfaces PostgreSQL's validation of out-of-range values
+#[pg_test(error = "0 is outside the valid range for parameter \"walrus.threshold\" (1 .. 1000)")]
+fn test_set_rejects_invalid_value() {
+    Spi::run("SELECT walrus.set('walrus.threshold', '0')").expect("query failed");
+}
+
+// =========================================================================
+// walrus.analyze() Advisory Lock Tests
+// =========================================================================
+
+/// Test-only background worker that holds the adjustment advisory lock.
+///
+/// Connects to the database passed as the worker argument, takes the lock in
+/// a transaction, and holds it for a few seconds before committing.
+#[pg_guard]
+#[unsafe(no_mangle)]
+pub extern "C-unwind" fn walrus_test_lock_holder(arg: pg_sys::Datum) {
+    use pgrx::bgworkers::{BackgroundWorker, SignalWakeFlags};
+
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGTERM);
+    let db_oid = pg_sys::Oid::from(arg.value() as u32);
+    BackgroundWorker::connect_worker_to_spi_by_oid(Some(db_oid), None);
+
+    BackgroundWorker::transaction(|| {
+        Spi::run(&format!(
+            "SELECT pg_advisory_xact_lock({})",
+            crate::apply::ADJUSTMENT_LOCK_KEY
+        ))
+        .expect("failed to take advisory lock");
+        Spi::run("SELECT pg_sleep(5)").expect("pg_sleep failed");
+    });
+}
+
+/// Test analyze(apply := true) declines to apply while another session holds the lock
+#[pg_test]
+fn test_analyze_apply_declines_when_lock_held() {
+    use pgrx::bgworkers::BackgroundWorkerBuilder;
+    use std::time::{Duration, Instant};
+
+    let db_oid = unsafe { pg_sys::MyDatabaseId };
+    let worker = BackgroundWorkerBuilder::new("pg_walrus lock holder")
+        .set_library("pg_walrus")
+        .set_function("walrus_test_lock_holder")
+        .set_argument(Some(pg_sys::Datum::from(u32::from(db_oid) as usize)))
+        .enable_spi_access()
+        .set_notify_pid(unsafe { pg_sys::MyProcPid })
+        .load_dynamic()
+        .expect("failed to start lock holder worker");
+    worker.wait_for_startup().expect("lock holder did not start");
+
+    // Wait until the other session has actually been granted the lock
+    let deadline = Instant::now() + Duration::from_secs(4);
+    loop {
+        let held = Spi::get_one::<bool>(
+            "SELECT EXISTS (SELECT 1 FROM pg_locks
+             WHERE locktype = 'advisory' AND granted AND pid <> pg_backend_pid())",
+        )
+        .expect("query failed")
+        .unwrap_or(false);
+        if held {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "lock holder never acquired the lock"
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.analyze(apply := true)")
+        .expect("query failed")
+        .expect("analyze should return JSONB");
+    assert_eq!(
+        result.0.get("applied"),
+        Some(&serde_json::json!(false)),
+        "analyze should not apply while the lock is held"
+    );
+    assert_eq!(
+        result.0.get("reason"),
+        Some(&serde_json::json!("another adjustment in progress"))
+    );
+}
+
+// =========================================================================
+// walrus.explain() Tests
+// =========================================================================
+
+/// Test walrus.explain() walks the quiet path and reports each gate in order
+#[pg_test]
+fn test_explain_quiet_state_conditions() {
+    use crate::shmem;
+
+    // Quiet state: baseline established, no new forced checkpoints, no quiet intervals yet
+    let saved = shmem::read_state();
+    let current = stats::get_requested_checkpoints();
+    shmem::update_state(|state| {
+        state.prev_requested = current.max(1);
+        state.quiet_intervals = 0;
+        state.ignore_checkpoints = 0;
+    });
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.explain()")
+        .expect("query failed")
+        .expect("explain should return JSONB");
+    shmem::update_state(|state| *state = saved);
+
+    let conditions = result.0.as_array().expect("explain should return an array");
+    let names: Vec<&str> = conditions
+        .iter()
+        .map(|c| c["condition"].as_str().unwrap_or_default())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "enable",
+            "emergency",
+            "stats_available",
+            "baseline",
+            "ceiling_excess",
+            "delta_vs_threshold",
+            "wal_volume",
+            "shrink_enable",
+            "mode",
+            "quiet_intervals",
+            "decision",
+        ]
+    );
+
+    for condition in conditions {
+        assert!(condition.get("inputs").is_some(), "missing inputs: {}", condition);
+        assert!(condition.get("outcome").is_some(), "missing outcome: {}", condition);
+    }
+    assert_eq!(conditions[4]["outcome"], "pass");
+    assert_eq!(conditions[5]["outcome"], "quiet");
+    assert_eq!(conditions[8]["outcome"], "pass");
+    assert_eq!(conditions[9]["outcome"], "fail");
+    assert_eq!(conditions[10]["outcome"], "none");
+}
+
+// =========================================================================
+// walrus.metadata_keys() Tests
+// =========================================================================
+
+/// Test walrus.metadata_keys() surveys keys per action across history rows
+#[pg_test]
+fn test_metadata_keys_lists_keys_per_action() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run(
+        "INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec, reason, metadata)
+         VALUES
+            ('increase', 1024, 2048, 3, 300, 'test',
+             '{\"delta\": 3, \"multiplier\": 4, \"calculated_size_mb\": 4096}'),
+            ('capped', 2048, 4096, 5, 300, 'test',
+             '{\"delta\": 5, \"multiplier\": 6, \"calculated_size_mb\": 12288, \"walrus_max_mb\": 4096}'),
+            ('decrease', 4096, 3072, 0, 300, 'test',
+             '{\"shrink_factor\": 0.75, \"quiet_intervals\": 5, \"calculated_size_mb\": 3072}'),
+            ('skipped', 1024, 2048, 3, 300, 'test', '{\"blocked_by\": \"cooldown\"}'),
+            ('increase', 2048, 3072, 2, 300, 'test', NULL)",
+    )
+    .expect("insert failed");
+
+    let keys_for = |action: &str| -> Vec<String> {
+        Spi::connect(|client| {
+            client
+                .select(
+                    "SELECT key FROM walrus.metadata_keys() WHERE action = $1 ORDER BY key",
+                    None,
+                    &[action.into()],
+                )
+                .expect("query failed")
+                .filter_map(|row| row.get::<String>(1).ok().flatten())
+                .collect()
+        })
+    };
+
+    assert_eq!(
+        keys_for("increase"),
+        vec!["calculated_size_mb", "delta", "multiplier"]
+    );
+    assert_eq!(
+        keys_for("capped"),
+        vec!["calculated_size_mb", "delta", "multiplier", "walrus_max_mb"]
+    );
+    assert_eq!(
+        keys_for("decrease"),
+        vec!["calculated_size_mb", "quiet_intervals", "shrink_factor"]
+    );
+    assert_eq!(keys_for("skipped"), vec!["blocked_by"]);
+
+    let sample = Spi::get_one::<String>(
+        "SELECT sample_value FROM walrus.metadata_keys()
+         WHERE action = 'skipped' AND key = 'blocked_by'",
+    )
+    .expect("query failed");
+    assert_eq!(sample.as_deref(), Some("cooldown"));
+}
+
+// =========================================================================
+// walrus.history_after() Tests
+// =========================================================================
+
+/// Test keyset pagination over 250 rows returns every row exactly once, in order
+#[pg_test]
+fn test_history_after_pages_without_duplicates_or_gaps() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run(
+        "INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec, reason)
+         SELECT 'increase', 1024, 2048, g, 300, 'page test'
+         FROM generate_series(1, 250) AS g",
+    )
+    .expect("insert failed");
+
+    let expected: Vec<i64> = Spi::connect(|client| {
+        client
+            .select("SELECT id FROM walrus.history ORDER BY id", None, &[])
+            .expect("query failed")
+            .filter_map(|row| row.get::<i64>(1).ok().flatten())
+            .collect()
+    });
+    assert_eq!(expected.len(), 250);
+
+    let mut seen: Vec<i64> = Vec::new();
+    let mut after_id = 0i64;
+    let mut pages = 0;
+    loop {
+        let page: Vec<i64> = Spi::connect(|client| {
+            client
+                .select(
+                    "SELECT id FROM walrus.history_after($1, 100)",
+                    None,
+                    &[after_id.into()],
+                )
+                .expect("query failed")
+                .filter_map(|row| row.get::<i64>(1).ok().flatten())
+                .collect()
+        });
+        if page.is_empty() {
+            break;
+        }
+        assert!(page.len() <= 100, "page exceeds limit_rows");
+        after_id = *page.last().expect("page is not empty");
+        seen.extend(page);
+        pages += 1;
+    }
+
+    assert_eq!(pages, 3, "250 rows should span 3 pages of 100");
+    assert_eq!(seen, expected, "pages should cover all ids once, in order");
+}
+
+/// Test limit_rows defaults to 100
+#[pg_test]
+fn test_history_after_default_limit() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run(
+        "INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         SELECT 'decrease', 2048, 1024, 0, 300 FROM generate_series(1, 150)",
+    )
+    .expect("insert failed");
+
+    let count =
+        Spi::get_one::<i64>("SELECT count(*) FROM walrus.history_after(0)").expect("query failed");
+    assert_eq!(count, Some(100));
+}
+
+/// Test walrus.kick() wakes the running worker
+#[pg_test]
+fn test_kick_returns_true_when_worker_running() {
+    let pid = crate::shmem::read_state().worker_pid;
+    assert!(pid > 0, "worker should publish its pid at startup");
+
+    let result = Spi::get_one::<bool>("SELECT walrus.kick()").expect("query failed");
+    assert_eq!(result, Some(true), "kick should set the worker's latch");
+}
+
+/// Test walrus.kick() and walrus.worker_running() report no worker when no
+/// pid is published or the published pid has no live backend
+#[pg_test]
+fn test_kick_returns_false_without_worker() {
+    use crate::shmem;
+
+    let saved = shmem::read_state().worker_pid;
+    for pid in [0, i32::MAX] {
+        shmem::update_state(|state| state.worker_pid = pid);
+        let kicked = Spi::get_one::<bool>("SELECT walrus.kick()").expect("query failed");
+        let running =
+            Spi::get_one::<bool>("SELECT walrus.worker_running()").expect("query failed");
+        shmem::update_state(|state| state.worker_pid = saved);
+
+        assert_eq!(kicked, Some(false), "pid {pid}: kick");
+        assert_eq!(running, Some(false), "pid {pid}: worker_running");
+    }
+}
+
+/// Test history insert, read, and cleanup against a custom walrus.schema_name
+#[pg_test]
+fn test_history_in_custom_schema() {
+    use crate::history::{cleanup_old_history, insert_history_record};
+
+    // walrus.schema_name is postmaster-context; override it for this backend only
+    let set_schema = |name: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.schema_name".as_ptr(),
+            name.as_ptr(),
+            pg_sys::GucContext::PGC_POSTMASTER,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+
+    Spi::run("CREATE SCHEMA \"Tenant Walrus\"").expect("create schema failed");
+    Spi::run("CREATE TABLE \"Tenant Walrus\".history (LIKE walrus.history INCLUDING ALL)")
+        .expect("create table failed");
+    let default_rows =
+        Spi::get_one::<i64>("SELECT count(*) FROM walrus.history").expect("query failed");
+
+    set_schema(c"Tenant Walrus");
+
+    insert_history_record("increase", 1024, 2048, 3, 300, Some("custom schema"), None)
+        .expect("insert failed");
+    Spi::run(
+        "INSERT INTO \"Tenant Walrus\".history
+         (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES (now() - interval '30 days', 'increase', 512, 1024, 1, 300)",
+    )
+    .expect("insert failed");
+
+    let read = Spi::get_one::<String>(
+        "SELECT reason FROM walrus.history() WHERE action = 'increase' AND new_size_mb = 2048",
+    )
+    .expect("query failed");
+    let deleted = cleanup_old_history().expect("cleanup failed");
+    let remaining = Spi::get_one::<i64>("SELECT count(*) FROM \"Tenant Walrus\".history")
+        .expect("query failed");
+
+    set_schema(c"walrus");
+
+    assert_eq!(read.as_deref(), Some("custom schema"), "walrus.history() reads the custom schema");
+    assert_eq!(deleted, 1, "cleanup deletes expired rows in the custom schema");
+    assert_eq!(remaining, Some(1));
+    let after = Spi::get_one::<i64>("SELECT count(*) FROM walrus.history").expect("query failed");
+    assert_eq!(after, default_rows, "default schema is untouched");
+}
+
+/// Test walrus.begin_bulk_load() scales the effective threshold until it expires
+#[pg_test]
+fn test_bulk_load_scales_threshold_until_expiry() {
+    use crate::bulk_load::effective_threshold;
+    use crate::shmem::{self, now_unix};
+
+    let saved = shmem::read_state().bulk_load_until;
+    let threshold = crate::guc::WALRUS_THRESHOLD.get() as i64;
+    let multiplier = crate::guc::WALRUS_BULK_LOAD_THRESHOLD_MULTIPLIER.get() as i64;
+    assert_eq!(effective_threshold(), threshold, "not scaled before bulk load");
+
+    Spi::run("SELECT walrus.begin_bulk_load(30)").expect("begin_bulk_load failed");
+    let scaled = effective_threshold();
+    let active = Spi::get_one::<bool>("SELECT (walrus.status()->>'bulk_load_active')::boolean")
+        .expect("query failed");
+
+    // Expired: the end time has passed
+    shmem::update_state(|state| state.bulk_load_until = now_unix() - 1);
+    let expired = effective_threshold();
+    let active_after = Spi::get_one::<bool>("SELECT (walrus.status()->>'bulk_load_active')::boolean")
+        .expect("query failed");
+
+    shmem::update_state(|state| state.bulk_load_until = saved);
+
+    assert_eq!(scaled, threshold * multiplier, "scaled while active");
+    assert_eq!(active, Some(true));
+    assert_eq!(expired, threshold, "restored after expiry");
+    assert_eq!(active_after, Some(false));
+}
+
+/// Test walrus.end_bulk_load() reports whether bulk-load mode was active
+#[pg_test]
+fn test_end_bulk_load() {
+    let saved = crate::shmem::read_state().bulk_load_until;
+
+    Spi::run("SELECT walrus.begin_bulk_load(5)").expect("begin_bulk_load failed");
+    let ended = Spi::get_one::<bool>("SELECT walrus.end_bulk_load()").expect("query failed");
+    let again = Spi::get_one::<bool>("SELECT walrus.end_bulk_load()").expect("query failed");
+
+    crate::shmem::update_state(|state| state.bulk_load_until = saved);
+
+    assert_eq!(ended, Some(true));
+    assert_eq!(again, Some(false), "already ended");
+}
+
+/// Test walrus.begin_bulk_load() rejects a non-positive duration
+#[pg_test(error = "minutes must be > 0")]
+fn test_begin_bulk_load_rejects_zero_minutes() {
+    Spi::run("SELECT walrus.begin_bulk_load(0)").expect("begin_bulk_load failed");
+}
+
+// =========================================================================
+// walrus.export_history() / walrus.import_history() Tests
+// =========================================================================
+
+/// Test export then import restores the same rows and metadata
+#[pg_test]
+fn test_export_import_history_round_trip() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run(
+        r#"INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec, reason, metadata)
+         VALUES
+            ('increase', 1024, 2048, 5, 300, 'Forced checkpoints exceeded threshold', '{"delta": 5}'),
+            ('skipped', 2048, 4096, 7, 300, NULL, '{"blocked_by": "cooldown", "note": "a, \"quoted\"\nvalue"}'),
+            ('decrease', 2048, 1843, 0, 300, '', NULL)"#,
+    )
+    .expect("insert failed");
+
+    let csv = Spi::get_one::<String>("SELECT walrus.export_history()")
+        .expect("query failed")
+        .expect("export returned NULL");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+
+    let imported = Spi::get_one_with_args::<i64>(
+        "SELECT walrus.import_history($1)",
+        &[csv.as_str().into()],
+    )
+    .expect("query failed");
+    let count = Spi::get_one::<i64>("SELECT count(*) FROM walrus.history").expect("query failed");
+    let note = Spi::get_one::<String>(
+        "SELECT metadata->>'note' FROM walrus.history WHERE action = 'skipped'",
+    )
+    .expect("query failed");
+    let empty_reason = Spi::get_one::<String>(
+        "SELECT reason FROM walrus.history WHERE action = 'decrease'",
+    )
+    .expect("query failed");
+
+    assert_eq!(imported, Some(3), "all exported rows should import");
+    assert_eq!(count, Some(3));
+    assert_eq!(note.as_deref(), Some("a, \"quoted\"\nvalue"));
+    assert_eq!(empty_reason.as_deref(), Some(""), "empty reason is not NULL");
+}
+
+/// Test malformed lines are skipped and valid ones still imported
+#[pg_test]
+fn test_import_history_skips_malformed_lines() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    let csv = "timestamp,action,old_size_mb,new_size_mb,forced_checkpoints,checkpoint_timeout_sec,reason,metadata\n\
+               2025-01-15T10:30:00.000000Z,increase,1024,2048,5,300,,\n\
+               2025-01-15T10:31:00.000000Z,resize,1024,2048,5,300,,\n\
+               2025-01-15T10:32:00.000000Z,increase,0,2048,5,300,,\n\
+               2025-01-15T10:33:00.000000Z,increase,1024,2048,5,300,,{broken\n\
+               not,enough,fields\n";
+
+    let imported =
+        Spi::get_one_with_args::<i64>("SELECT walrus.import_history($1)", &[csv.into()])
+            .expect("query failed");
+    let timestamp = Spi::get_one::<bool>(
+        "SELECT timestamp = '2025-01-15 10:30:00+00' FROM walrus.history",
+    )
+    .expect("query failed");
+
+    assert_eq!(imported, Some(1), "only the valid line should import");
+    assert_eq!(timestamp, Some(true), "original timestamp is kept");
+}
+
+// =========================================================================
+// Shared memory initialization Tests
+// =========================================================================
+
+/// Test walrus.status() reports shared memory as initialized under shared_preload_libraries
+#[pg_test]
+fn test_status_reports_shmem_initialized() {
+    assert!(crate::shmem::is_initialized());
+    let initialized =
+        Spi::get_one::<bool>("SELECT (walrus.status()->>'shmem_initialized')::boolean")
+            .expect("query failed");
+    let error = Spi::get_one::<bool>("SELECT walrus.status() ? 'error'").expect("query failed");
+    assert_eq!(initialized, Some(true));
+    assert_eq!(error, Some(false), "no error when initialized");
+}
+
+// =========================================================================
+// walrus.diagnostics() Tests
+// =========================================================================
+
+/// Test walrus.diagnostics() contains every top-level section
+#[pg_test]
+fn test_diagnostics_top_level_keys() {
+    Spi::run(
+        "INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES ('increase', 1024, 2048, 3, 300)",
+    )
+    .expect("insert failed");
+
+    let has_keys = Spi::get_one::<bool>(
+        "SELECT walrus.diagnostics() ?& array['state', 'config', 'recent_history', 'version']",
+    )
+    .expect("query failed");
+    let state_has_quiet = Spi::get_one::<bool>(
+        "SELECT walrus.diagnostics()->'state' ? 'quiet_intervals'",
+    )
+    .expect("query failed");
+    let config_max = Spi::get_one::<bool>(
+        "SELECT walrus.diagnostics()->'config' ?& array['walrus.max', 'max_wal_size']",
+    )
+    .expect("query failed");
+    let history_len = Spi::get_one::<i32>(
+        "SELECT jsonb_array_length(walrus.diagnostics()->'recent_history')",
+    )
+    .expect("query failed");
+
+    assert_eq!(has_keys, Some(true));
+    assert_eq!(state_has_quiet, Some(true), "state includes shared memory fields");
+    assert_eq!(config_max, Some(true), "config includes walrus and core settings");
+    assert!(history_len.is_some_and(|n| (1..=20).contains(&n)));
+}
+
+// =========================================================================
+// walrus.set_size() Tests
+// =========================================================================
+
+/// Test walrus.set_size() writes max_wal_size and records a 'manual' history row
+#[pg_test]
+fn test_set_size_records_manual_history() {
+    let saved = crate::shmem::read_state();
+    let original = crate::stats::get_current_max_wal_size();
+    let target = if original == 2048 { 3072 } else { 2048 };
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    // Counting in the hourly window is covered by test_manual_apply_resets_cooldown
+    set_manual_resets_cooldown(c"off");
+
+    let result = Spi::get_one::<bool>(&format!("SELECT walrus.set_size({target})"))
+        .expect("query failed");
+    assert_eq!(result, Some(true), "walrus.set_size() should return true");
+
+    let (old_size, new_size) = Spi::get_two::<i32, i32>(
+        "SELECT old_size_mb, new_size_mb FROM walrus.history
+         WHERE action = 'manual' AND metadata->>'source' = 'walrus.set_size'",
+    )
+    .expect("query failed");
+    assert_eq!((old_size, new_size), (Some(original), Some(target)));
+
+    // pg_file_settings re-reads the config files, showing what the reload applies
+    let setting = Spi::get_one::<&str>(
+        "SELECT setting FROM pg_file_settings
+         WHERE name = 'max_wal_size' AND error IS NULL
+         ORDER BY seqno DESC LIMIT 1",
+    )
+    .expect("query failed");
+    let expected = format!("{target}MB");
+    assert_eq!(setting, Some(expected.as_str()));
+
+    // Manual changes start the cooldown but do not use up the hourly limit
+    let state = crate::shmem::read_state();
+    set_manual_resets_cooldown(c"on");
+    assert!(state.last_adjustment_time > 0);
+    assert_eq!(state.changes_this_hour, saved.changes_this_hour);
+
+    // Restore the original size for other tests. This backend has not reloaded
+    // yet, so walrus.set_size(original) would see no change; write it directly.
+    crate::config::execute_alter_system(original).expect("restore failed");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    crate::shmem::update_state(|state| *state = saved);
+}
+
+/// Test walrus.set_size() rejects sizes above walrus.max
+#[pg_test(error = "walrus.set_size(): 8192 MB is outside the allowed range (80 MB min_wal_size .. 4096 MB walrus.max)")]
+fn test_set_size_rejects_above_max() {
+    Spi::run("SELECT walrus.set_size(8192)").expect("query failed");
+}
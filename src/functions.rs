@@ -1,42 +1,47 @@
 //! SQL-callable functions for pg_walrus observability.
 //!
-//! This module implements the five SQL functions in the `walrus` schema:
-//! - `walrus.status()`: JSONB with extension state
+//! This module implements the SQL functions in the `walrus` schema:
 //! - `walrus.history()`: SETOF RECORD with adjustment history
-//! - `walrus.recommendation()`: JSONB with sizing recommendation
-//! - `walrus.analyze(apply)`: JSONB with analysis and optional execution
+//! - `walrus.recommendation(hypothetical_delta, hypothetical_quiet_intervals)`: JSONB with sizing recommendation
+//! - `walrus.analyze(apply, force)`: JSONB with analysis and optional execution
 //! - `walrus.reset()`: Clear state and history (superuser only)
 //! - `walrus.cleanup_history()`: Delete old history records (moved from lib.rs)
-
-use crate::algorithm::compute_recommendation;
-use crate::config::{execute_alter_system, signal_postmaster_reload};
-use crate::guc::{
-    WALRUS_COOLDOWN_SEC, WALRUS_ENABLE, WALRUS_MAX, WALRUS_MAX_CHANGES_PER_HOUR, WALRUS_MIN_SIZE,
-    WALRUS_SHRINK_ENABLE, WALRUS_SHRINK_FACTOR, WALRUS_SHRINK_INTERVALS, WALRUS_THRESHOLD,
+//! - `walrus.version()`: JSONB with extension version and build information
+//! - `walrus.worker_running()`: Boolean liveness check for the background worker
+//! - `walrus.heartbeat()`: Timestamp of the worker's last analysis cycle
+//! - `walrus.ignore_next_checkpoints(n)`: Exclude upcoming manual checkpoints from delta
+//! - `walrus.set(param, value)`: ALTER SYSTEM + reload for a walrus.* parameter
+//! - `walrus.set_size(mb)`: Set max_wal_size once, recorded as a 'manual' history row
+//! - `walrus.kick()`: Wake the background worker for an immediate cycle
+//!
+//! `walrus.status()` is implemented in `status`, `walrus.begin_bulk_load()` /
+//! `walrus.end_bulk_load()` in `bulk_load`, `walrus.next_allowed_at()` in `rate_limit`, and
+//! `walrus.tick()` in `tick`.
+
+use crate::access;
+use crate::algorithm::{
+    ModeGate, Recommendation, compute_recommendation, compute_recommendation_for_delta,
+    meets_confidence_threshold, mode_gate,
 };
+use crate::apply::{ApplyError, ApplySource, SizeChange, apply_max_wal_size, try_adjustment_lock};
+use crate::config::{execute_alter_system_setting, signal_postmaster_reload};
+use crate::guc::{WALRUS_ENABLE, WALRUS_MAX, WALRUS_MIN_APPLY_CONFIDENCE, WALRUS_MODE};
 use crate::history;
+use crate::rate_limit::{
+    automatic_rate_limit, cooldown_active, update_rate_limit_state_after_manual,
+};
 use crate::shmem::{self, now_unix, read_state};
-use crate::stats::{checkpoint_timeout, get_current_max_wal_size};
+use crate::stats::{checkpoint_timeout, get_current_max_wal_size, get_current_min_wal_size};
 
-use pgrx::datum::TimestampWithTimeZone;
+use pgrx::datum::{DatumWithOid, TimestampWithTimeZone};
 use pgrx::prelude::*;
 use pgrx::{JsonB, pg_sys};
 use serde_json::json;
 
-/// Check if the pg_walrus background worker is running.
-///
-/// Queries pg_stat_activity for a backend with backend_type = 'pg_walrus'.
-fn check_worker_running() -> bool {
-    let result = Spi::get_one::<bool>(
-        "SELECT EXISTS(SELECT 1 FROM pg_stat_activity WHERE backend_type = 'pg_walrus')",
-    );
-    result.unwrap_or(Some(false)).unwrap_or(false)
-}
-
 /// Convert a Unix timestamp (seconds since epoch) to ISO 8601 format.
 ///
 /// Returns None if the timestamp is 0 (indicating "never").
-fn unix_timestamp_to_iso(timestamp: i64) -> Option<String> {
+pub(crate) fn unix_timestamp_to_iso(timestamp: i64) -> Option<String> {
     if timestamp == 0 {
         return None;
     }
@@ -99,117 +104,101 @@ fn is_leap_year(year: i32) -> bool {
     (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
 }
 
-/// Implementation for getting current extension status.
-///
-/// Returns JSONB with 15 fields covering configuration, worker state, and counters.
-///
-/// # Fields
-///
-/// Configuration:
-/// - `enabled`: Whether auto-sizing is enabled
-/// - `current_max_wal_size_mb`: Current max_wal_size in MB
-/// - `configured_maximum_mb`: walrus.max setting in MB
-/// - `threshold`: walrus.threshold setting
-/// - `checkpoint_timeout_sec`: PostgreSQL checkpoint_timeout in seconds
-///
-/// Shrink configuration:
-/// - `shrink_enabled`: Whether auto-shrink is enabled
-/// - `shrink_factor`: walrus.shrink_factor setting
-/// - `shrink_intervals`: walrus.shrink_intervals setting
-/// - `min_size_mb`: walrus.min_size in MB
-///
-/// Worker state:
-/// - `worker_running`: Whether background worker is active
-/// - `last_check_time`: ISO 8601 timestamp of last analysis (null if never)
-/// - `last_adjustment_time`: ISO 8601 timestamp of last resize (null if never)
+/// Implementation for getting adjustment history.
 ///
-/// Counters:
-/// - `total_adjustments`: Number of sizing changes since PostgreSQL start
-/// - `quiet_intervals`: Consecutive low-activity intervals
+/// Returns SETOF RECORD from walrus.history table with columns:
+/// - timestamp: TIMESTAMPTZ
+/// - action: TEXT (increase/decrease/capped)
+/// - old_size_mb: INTEGER
+/// - new_size_mb: INTEGER
+/// - forced_checkpoints: BIGINT
+/// - reason: TEXT (nullable)
+/// - database_name: TEXT (database whose connection recorded the row)
 ///
-/// Derived:
-/// - `at_ceiling`: Whether current_max_wal_size_mb >= configured_maximum_mb
+/// With `database_name` set, only rows recorded from that database.
 ///
 /// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
-pub fn status() -> JsonB {
-    let state = read_state();
-    let now = now_unix();
-    let current_size = get_current_max_wal_size();
-    let configured_max = WALRUS_MAX.get();
-    let timeout_secs = checkpoint_timeout().as_secs() as i32;
-
-    // Rate limiting GUC values
-    let cooldown_sec = WALRUS_COOLDOWN_SEC.get();
-    let max_changes_per_hour = WALRUS_MAX_CHANGES_PER_HOUR.get();
-
-    // Compute cooldown status
-    let cooldown_active = cooldown_sec > 0
-        && state.last_adjustment_time > 0
-        && now < state.last_adjustment_time.saturating_add(cooldown_sec as i64);
-
-    let cooldown_remaining_sec = if cooldown_active {
-        state
-            .last_adjustment_time
-            .saturating_add(cooldown_sec as i64)
-            .saturating_sub(now) as i32
-    } else {
-        0
-    };
+#[allow(clippy::type_complexity)]
+pub fn history_srf(
+    database_name: Option<String>,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(timestamp, TimestampWithTimeZone),
+            name!(action, String),
+            name!(old_size_mb, i32),
+            name!(new_size_mb, i32),
+            name!(forced_checkpoints, i64),
+            name!(reason, Option<String>),
+            name!(database_name, String),
+        ),
+    >,
+    spi::Error,
+> {
+    if !history::history_table_exists()? {
+        return Err(spi::Error::InvalidPosition);
+    }
 
-    // Compute hourly limit status
-    let hour_expired = if state.hour_window_start > 0 {
-        now >= state.hour_window_start.saturating_add(3600)
-    } else {
-        true
-    };
+    Spi::connect(|client| {
+        let args: &[DatumWithOid<'_>] = &[database_name.into()];
+        let results = client.select(
+            &format!(
+                "SELECT timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, reason,
+                        database_name
+                 FROM {} WHERE $1::text IS NULL OR database_name = $1 ORDER BY timestamp ASC",
+                history::history_table()
+            ),
+            None,
+            args,
+        )?;
 
-    let hourly_limit_reached = !hour_expired
-        && max_changes_per_hour > 0
-        && state.changes_this_hour >= max_changes_per_hour;
+        let rows: Vec<_> = results
+            .filter_map(|row| {
+                let timestamp: TimestampWithTimeZone = row.get_by_name("timestamp").ok()??;
+                let action: String = row.get_by_name("action").ok()??;
+                let old_size: i32 = row.get_by_name("old_size_mb").ok()??;
+                let new_size: i32 = row.get_by_name("new_size_mb").ok()??;
+                let checkpoints: i64 = row.get_by_name("forced_checkpoints").ok()??;
+                let reason: Option<String> = row.get_by_name("reason").ok()?;
+                let database: String = row.get_by_name("database_name").ok()??;
+                Some((
+                    timestamp,
+                    action,
+                    old_size,
+                    new_size,
+                    checkpoints,
+                    reason,
+                    database,
+                ))
+            })
+            .collect();
 
-    JsonB(json!({
-        "enabled": WALRUS_ENABLE.get(),
-        "current_max_wal_size_mb": current_size,
-        "configured_maximum_mb": configured_max,
-        "threshold": WALRUS_THRESHOLD.get(),
-        "checkpoint_timeout_sec": timeout_secs,
-        "shrink_enabled": WALRUS_SHRINK_ENABLE.get(),
-        "shrink_factor": WALRUS_SHRINK_FACTOR.get(),
-        "shrink_intervals": WALRUS_SHRINK_INTERVALS.get(),
-        "min_size_mb": WALRUS_MIN_SIZE.get(),
-        "worker_running": check_worker_running(),
-        "last_check_time": unix_timestamp_to_iso(state.last_check_time),
-        "last_adjustment_time": unix_timestamp_to_iso(state.last_adjustment_time),
-        "total_adjustments": state.total_adjustments,
-        "quiet_intervals": state.quiet_intervals,
-        "at_ceiling": current_size >= configured_max,
-        // Rate limiting fields (7 new fields per FR-012)
-        "cooldown_sec": cooldown_sec,
-        "max_changes_per_hour": max_changes_per_hour,
-        "cooldown_active": cooldown_active,
-        "cooldown_remaining_sec": cooldown_remaining_sec,
-        "changes_this_hour": state.changes_this_hour,
-        "hourly_window_start": unix_timestamp_to_iso(state.hour_window_start),
-        "hourly_limit_reached": hourly_limit_reached,
-    }))
+        Ok(TableIterator::new(rows))
+    })
 }
 
-/// Implementation for getting adjustment history.
+/// Implementation for keyset pagination over adjustment history.
 ///
-/// Returns SETOF RECORD from walrus.history table with columns:
-/// - timestamp: TIMESTAMPTZ
-/// - action: TEXT (increase/decrease/capped)
-/// - old_size_mb: INTEGER
-/// - new_size_mb: INTEGER
-/// - forced_checkpoints: BIGINT
-/// - reason: TEXT (nullable)
+/// Returns up to `limit_rows` rows with `id > after_id`, ordered by id ascending,
+/// with columns:
+/// - id: BIGINT
+/// - timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, reason
+///   (as in `history_srf()`)
+///
+/// Pass the last returned id as `after_id` to fetch the next page; start with 0.
+/// Unlike OFFSET, pages stay stable while new rows are inserted.
 ///
 /// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
 #[allow(clippy::type_complexity)]
-pub fn history_srf() -> Result<
+pub fn history_after_srf(
+    after_id: i64,
+    limit_rows: i32,
+) -> Result<
     TableIterator<
         'static,
         (
+            name!(id, i64),
             name!(timestamp, TimestampWithTimeZone),
             name!(action, String),
             name!(old_size_mb, i32),
@@ -220,36 +209,43 @@ pub fn history_srf() -> Result<
     >,
     spi::Error,
 > {
-    // Check if history table exists
-    let table_exists = Spi::get_one::<bool>(
-        "SELECT EXISTS (
-            SELECT 1 FROM pg_catalog.pg_class c
-            JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
-            WHERE n.nspname = 'walrus' AND c.relname = 'history'
-        )",
-    )?;
-
-    if table_exists != Some(true) {
+    if limit_rows < 0 {
+        pgrx::error!("limit_rows must be non-negative: {}", limit_rows);
+    }
+    if !history::history_table_exists()? {
         return Err(spi::Error::InvalidPosition);
     }
 
     Spi::connect(|client| {
+        let args: &[DatumWithOid<'_>] = &[after_id.into(), (limit_rows as i64).into()];
         let results = client.select(
-            "SELECT timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, reason
-             FROM walrus.history ORDER BY timestamp ASC",
+            &format!(
+                "SELECT id, timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, reason
+                 FROM {} WHERE id > $1 ORDER BY id ASC LIMIT $2",
+                history::history_table()
+            ),
             None,
-            &[],
+            args,
         )?;
 
         let rows: Vec<_> = results
             .filter_map(|row| {
+                let id: i64 = row.get_by_name("id").ok()??;
                 let timestamp: TimestampWithTimeZone = row.get_by_name("timestamp").ok()??;
                 let action: String = row.get_by_name("action").ok()??;
                 let old_size: i32 = row.get_by_name("old_size_mb").ok()??;
                 let new_size: i32 = row.get_by_name("new_size_mb").ok()??;
                 let checkpoints: i64 = row.get_by_name("forced_checkpoints").ok()??;
                 let reason: Option<String> = row.get_by_name("reason").ok()?;
-                Some((timestamp, action, old_size, new_size, checkpoints, reason))
+                Some((
+                    id,
+                    timestamp,
+                    action,
+                    old_size,
+                    new_size,
+                    checkpoints,
+                    reason,
+                ))
             })
             .collect();
 
@@ -257,6 +253,90 @@ pub fn history_srf() -> Result<
     })
 }
 
+/// Implementation for surveying metadata keys in the history table.
+///
+/// Returns one row per distinct (action, key) pair found in walrus.history.metadata:
+/// - action: TEXT
+/// - key: TEXT (top-level metadata key, e.g. delta, calculated_size_mb, blocked_by)
+/// - sample_value: TEXT (value from the most recent row containing the key)
+///
+/// Rows with NULL or non-object metadata are ignored.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+#[allow(clippy::type_complexity)]
+pub fn metadata_keys_srf() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(action, String),
+            name!(key, String),
+            name!(sample_value, Option<String>),
+        ),
+    >,
+    spi::Error,
+> {
+    Spi::connect(|client| {
+        let results = client.select(
+            &format!(
+                "SELECT h.action, k.key,
+                        (array_agg(h.metadata->>k.key ORDER BY h.id DESC))[1] AS sample_value
+                 FROM {} h,
+                      LATERAL jsonb_object_keys(h.metadata) AS k(key)
+                 WHERE jsonb_typeof(h.metadata) = 'object'
+                 GROUP BY h.action, k.key
+                 ORDER BY h.action, k.key",
+                history::history_table()
+            ),
+            None,
+            &[],
+        )?;
+
+        let rows: Vec<_> = results
+            .filter_map(|row| {
+                let action: String = row.get_by_name("action").ok()??;
+                let key: String = row.get_by_name("key").ok()??;
+                let sample_value: Option<String> = row.get_by_name("sample_value").ok()?;
+                Some((action, key, sample_value))
+            })
+            .collect();
+
+        Ok(TableIterator::new(rows))
+    })
+}
+
+/// Whether a recommendation is a real signal rather than a placeholder.
+///
+/// True for an 'increase' or 'decrease' whose confidence passes
+/// walrus.min_apply_confidence; 'none', 'error', and low-confidence results are not.
+#[inline]
+pub(crate) fn is_actionable(action: &str, confidence: i32, min_confidence: i32) -> bool {
+    matches!(action, "increase" | "decrease")
+        && meets_confidence_threshold(confidence, min_confidence)
+}
+
+/// Recommendation fields shared by walrus.recommendation() and walrus.analyze().
+///
+/// Adds whether the worker's rate limits would block applying it right now.
+/// Manual applies bypass those limits except the cooldown, which
+/// `manual_apply_bypasses_rate_limits` states explicitly.
+fn recommendation_json(rec: &Recommendation) -> serde_json::Value {
+    let rate_limit =
+        automatic_rate_limit(&rec.action, rec.current_size_mb, rec.recommended_size_mb);
+    json!({
+        "current_size_mb": rec.current_size_mb,
+        "recommended_size_mb": rec.recommended_size_mb,
+        "action": rec.action,
+        "reason": rec.reason,
+        "confidence": rec.confidence,
+        "actionable": is_actionable(&rec.action, rec.confidence, WALRUS_MIN_APPLY_CONFIDENCE.get()),
+        "checkpoint_timeout_sec": rec.checkpoint_timeout_sec,
+        "forced_checkpoints_per_minute": rec.forced_checkpoints_per_minute,
+        "would_be_rate_limited": rate_limit.is_some(),
+        "rate_limit_reason": rate_limit,
+        "manual_apply_bypasses_rate_limits": rate_limit.as_deref() != Some("cooldown"),
+    })
+}
+
 /// Implementation for getting sizing recommendation.
 ///
 /// Returns JSONB with:
@@ -265,19 +345,44 @@ pub fn history_srf() -> Result<
 /// - `action`: "increase" | "decrease" | "none" | "error"
 /// - `reason`: Human-readable explanation
 /// - `confidence`: 0-100 confidence score
+/// - `actionable`: Whether `action` is "increase" or "decrease" with `confidence`
+///   at or above walrus.min_apply_confidence (see `is_actionable()`)
+/// - `checkpoint_timeout_sec`: Live checkpoint_timeout the delta was measured over
+/// - `forced_checkpoints_per_minute`: Delta normalized by checkpoint_timeout
+///   (null when no delta is available)
+/// - `would_be_rate_limited`: Whether the worker's rate limits would block
+///   applying the action automatically right now
+/// - `rate_limit_reason`: The blocking limit ("cooldown", "hourly_limit",
+///   "shrink_hourly_limit", or "daily_growth_cap"), or null
+/// - `manual_apply_bypasses_rate_limits`: Whether walrus.analyze(apply := true)
+///   could apply it anyway; false only during the cooldown, which needs `force`
+///
+/// # Arguments
+///
+/// * `hypothetical_delta` - If set, used instead of the live forced checkpoint delta
+/// * `hypothetical_quiet_intervals` - If set, replaces quiet_intervals in a synthetic state
+///
+/// With both arguments NULL, the recommendation reflects live stats and state.
 ///
 /// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
-pub fn recommendation() -> JsonB {
-    let state = read_state();
-    let rec = compute_recommendation(&state);
+pub fn recommendation(
+    hypothetical_delta: Option<i64>,
+    hypothetical_quiet_intervals: Option<i32>,
+) -> JsonB {
+    if hypothetical_delta.is_some_and(|d| d < 0) {
+        pgrx::error!("hypothetical_delta must be >= 0");
+    }
+    if hypothetical_quiet_intervals.is_some_and(|q| q < 0) {
+        pgrx::error!("hypothetical_quiet_intervals must be >= 0");
+    }
 
-    JsonB(json!({
-        "current_size_mb": rec.current_size_mb,
-        "recommended_size_mb": rec.recommended_size_mb,
-        "action": rec.action,
-        "reason": rec.reason,
-        "confidence": rec.confidence,
-    }))
+    let mut state = read_state();
+    if let Some(quiet_intervals) = hypothetical_quiet_intervals {
+        state.quiet_intervals = quiet_intervals;
+    }
+    let rec = compute_recommendation_for_delta(&state, hypothetical_delta);
+
+    JsonB(recommendation_json(&rec))
 }
 
 /// Implementation for immediate analysis with optional execution.
@@ -285,14 +390,31 @@ pub fn recommendation() -> JsonB {
 /// # Arguments
 ///
 /// * `apply` - If true and recommendation action != "none", execute ALTER SYSTEM
+/// * `force` - Apply even while walrus.cooldown_sec since the last adjustment
+///   is still running
 ///
 /// # Returns
 ///
 /// JSONB with:
 /// - `analyzed`: true if analysis completed
-/// - `recommendation`: The recommendation object
+/// - `recommendation`: The recommendation object, with the same fields as
+///   walrus.recommendation() (including `would_be_rate_limited`)
 /// - `applied`: true only if apply=true AND change was executed
-/// - `reason`: Error reason if analyzed=false
+/// - `reason`: Error reason if analyzed=false, "confidence below threshold"
+///   if the recommendation was not applied due to walrus.min_apply_confidence,
+///   "another adjustment in progress" if a concurrent apply holds the lock,
+///   "cooldown active" if the last adjustment is within walrus.cooldown_sec
+///   and `force` is false,
+///   "disabled by walrus.mode" if walrus.mode excludes the direction,
+///   "observe mode" if the decision was only recorded as 'dry_run', or the
+///   error text (also logged as a WARNING) if ALTER SYSTEM failed
+///
+/// # Concurrency
+///
+/// With `apply = true`, the adjustment lock (`apply::ADJUSTMENT_LOCK_KEY`)
+/// is taken before reading state and held by `apply_max_wal_size()` until the
+/// transaction ends, so neither a concurrent caller nor the worker can execute
+/// ALTER SYSTEM and double-count adjustments meanwhile.
 ///
 /// # Authorization
 ///
@@ -301,11 +423,15 @@ pub fn recommendation() -> JsonB {
 ///
 /// Note: This is not marked #[pg_extern] because the walrus.analyze function
 /// is defined in lib.rs walrus module to ensure proper schema placement.
-pub fn analyze(apply: bool) -> Result<JsonB, spi::Error> {
+pub fn analyze(apply: bool, force: bool) -> Result<JsonB, spi::Error> {
     // Check superuser requirement for apply=true
     if apply && unsafe { !pg_sys::superuser() } {
         pgrx::error!("permission denied: walrus.analyze(apply := true) requires superuser");
     }
+    if apply {
+        access::require_apply_allowed("walrus.analyze(apply := true)");
+        access::require_writable("walrus.analyze(apply := true)");
+    }
 
     // Check if extension is enabled
     if !WALRUS_ENABLE.get() {
@@ -315,68 +441,108 @@ pub fn analyze(apply: bool) -> Result<JsonB, spi::Error> {
         })));
     }
 
+    // Serialize concurrent apply calls before reading state; held until transaction end
+    let lock_acquired = !apply || try_adjustment_lock()?;
+
     // Compute recommendation
     let state = read_state();
     let rec = compute_recommendation(&state);
     let mut applied = false;
+    let mut blocked_reason: Option<String> = None;
 
-    // Apply if requested and action warrants change
-    if apply && rec.action != "none" && rec.action != "error" {
+    if apply && !lock_acquired {
+        // Another session is applying an adjustment right now
+        blocked_reason = Some("another adjustment in progress".into());
+    } else if apply && rec.action != "none" && rec.action != "error" {
+        // Apply if requested and action warrants change
         // Check if we're already at the recommended size
         let current = get_current_max_wal_size();
-        if rec.action == "increase" && current >= rec.recommended_size_mb {
+        // walrus.mode applies to manual adjustments too (walrus.dry_run does not)
+        let gate = mode_gate(WALRUS_MODE.get(), rec.action == "increase", false);
+        if !force && cooldown_active(&state) {
+            // Don't fight the worker: a change it just made stands for walrus.cooldown_sec
+            blocked_reason = Some("cooldown active".into());
+        } else if !meets_confidence_threshold(rec.confidence, WALRUS_MIN_APPLY_CONFIDENCE.get()) {
+            // Confidence gate: refuse to apply low-confidence recommendations
+            blocked_reason = Some("confidence below threshold".into());
+        } else if gate == ModeGate::Skip {
+            blocked_reason = Some("disabled by walrus.mode".into());
+        } else if gate == ModeGate::Record {
+            // Observe mode: record the decision without applying it
+            let timeout_secs = checkpoint_timeout().as_secs() as i32;
+            let current_requested = crate::stats::get_requested_checkpoints();
+            let metadata = history::versioned_metadata(json!({
+                "dry_run": true,
+                "would_apply": rec.action,
+                "source": "walrus.analyze",
+                "confidence": rec.confidence,
+            }));
+            let _ = history::insert_history_record(
+                "dry_run",
+                rec.current_size_mb,
+                rec.recommended_size_mb,
+                current_requested.max(0),
+                timeout_secs,
+                Some(&rec.reason),
+                Some(metadata),
+            );
+            blocked_reason = Some("observe mode".into());
+        } else if rec.action == "increase" && current >= rec.recommended_size_mb {
             // Already at or above target, don't apply
         } else if rec.action == "decrease" && current <= rec.recommended_size_mb {
             // Already at or below target, don't apply
         } else {
-            // Execute ALTER SYSTEM
-            if execute_alter_system(rec.recommended_size_mb).is_ok() {
-                applied = true;
-
-                // Log to history
-                let timeout_secs = checkpoint_timeout().as_secs() as i32;
-                let current_requested = crate::stats::get_requested_checkpoints();
-                let metadata = serde_json::json!({
-                    "source": "walrus.analyze",
+            // Execute ALTER SYSTEM, log to history, and reload
+            let change = SizeChange {
+                action: &rec.action,
+                old_size_mb: rec.current_size_mb,
+                new_size_mb: rec.recommended_size_mb,
+                forced_checkpoints: crate::stats::get_requested_checkpoints(),
+                checkpoint_timeout_sec: checkpoint_timeout().as_secs() as i32,
+                reason: &rec.reason,
+                abort_if_stale: false,
+            };
+            let is_grow = rec.action == "increase";
+            let update = |s: &mut shmem::WalrusState| {
+                s.last_adjustment_time = now_unix();
+                if is_grow {
+                    s.quiet_intervals = 0;
+                    s.idle_intervals = 0;
+                    s.effective_shrink_intervals = 0;
+                }
+            };
+            let metadata = |_: i64| {
+                json!({
+                    "source": ApplySource::Analyze.name(),
                     "confidence": rec.confidence,
-                });
-
-                let _ = history::insert_history_record(
-                    &rec.action,
-                    rec.current_size_mb,
-                    rec.recommended_size_mb,
-                    current_requested,
-                    timeout_secs,
-                    Some(&rec.reason),
-                    Some(metadata),
-                );
-
-                // Update shmem state
-                shmem::update_state(|s| {
-                    s.total_adjustments += 1;
-                    s.last_adjustment_time = now_unix();
-                    if rec.action == "increase" {
-                        s.quiet_intervals = 0;
+                })
+            };
+            match apply_max_wal_size(&change, ApplySource::Analyze, update, metadata) {
+                Ok(_) => {
+                    applied = true;
+                    update_rate_limit_state_after_manual();
+                }
+                Err(e) => {
+                    // Losing the lock to another session is expected; a failed apply is not
+                    if e != ApplyError::Busy {
+                        pgrx::warning!("pg_walrus: walrus.analyze(apply := true) failed: {}", e);
                     }
-                });
-
-                // Send SIGHUP to apply configuration
-                signal_postmaster_reload();
+                    blocked_reason = Some(e.to_string());
+                }
             }
         }
     }
 
-    Ok(JsonB(json!({
+    let mut result = json!({
         "analyzed": true,
-        "recommendation": {
-            "current_size_mb": rec.current_size_mb,
-            "recommended_size_mb": rec.recommended_size_mb,
-            "action": rec.action,
-            "reason": rec.reason,
-            "confidence": rec.confidence,
-        },
+        "recommendation": recommendation_json(&rec),
         "applied": applied,
-    })))
+    });
+    if let Some(reason) = blocked_reason {
+        result["reason"] = json!(reason);
+    }
+
+    Ok(JsonB(result))
 }
 
 /// Implementation for resetting extension state.
@@ -404,21 +570,15 @@ pub fn reset() -> Result<bool, spi::Error> {
     if unsafe { !pg_sys::superuser() } {
         pgrx::error!("permission denied: walrus.reset() requires superuser");
     }
+    access::require_apply_allowed("walrus.reset()");
+    access::require_writable("walrus.reset()");
 
     // Reset shared memory state
     shmem::reset_state();
 
     // Clear history table (with graceful handling if dropped)
-    let table_exists = Spi::get_one::<bool>(
-        "SELECT EXISTS (
-            SELECT 1 FROM pg_catalog.pg_class c
-            JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
-            WHERE n.nspname = 'walrus' AND c.relname = 'history'
-        )",
-    )?;
-
-    if table_exists == Some(true) {
-        Spi::run("DELETE FROM walrus.history")?;
+    if history::history_table_exists()? {
+        Spi::run(&format!("DELETE FROM {}", history::history_table()))?;
     } else {
         pgrx::warning!("pg_walrus: history table does not exist");
     }
@@ -428,7 +588,9 @@ pub fn reset() -> Result<bool, spi::Error> {
 
 /// Implementation for deleting old history records.
 ///
-/// This is a re-export of the history module's cleanup function.
+/// Runs the history module's cleanup immediately, regardless of
+/// walrus.cleanup_interval_sec, and records the run time so the worker's
+/// next scheduled cleanup is deferred.
 /// Retention period controlled by walrus.history_retention_days GUC.
 ///
 /// # Returns
@@ -437,5 +599,255 @@ pub fn reset() -> Result<bool, spi::Error> {
 ///
 /// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
 pub fn cleanup_history() -> Result<i64, spi::Error> {
-    history::cleanup_old_history()
+    let deleted = history::cleanup_old_history()?;
+    let now = now_unix();
+    shmem::update_state(|state| {
+        state.last_cleanup_time = now;
+    });
+    Ok(deleted)
+}
+
+/// PostgreSQL major version this binary was compiled against.
+///
+/// Derived from the pgXX cargo feature selected at build time.
+pub(crate) fn compiled_pg_major() -> i32 {
+    if cfg!(feature = "pg18") {
+        18
+    } else if cfg!(feature = "pg17") {
+        17
+    } else if cfg!(feature = "pg16") {
+        16
+    } else if cfg!(feature = "pg15") {
+        15
+    } else if cfg!(feature = "pg14") {
+        14
+    } else {
+        13
+    }
+}
+
+/// Implementation for reporting extension build information.
+///
+/// Returns JSONB with:
+/// - `extension_version`: Cargo package version of pg_walrus
+/// - `pg_major`: PostgreSQL major version the binary was compiled against
+/// - `build_profile`: "debug" or "release"
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn version() -> JsonB {
+    let build_profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+
+    JsonB(json!({
+        "extension_version": env!("CARGO_PKG_VERSION"),
+        "pg_major": compiled_pg_major(),
+        "build_profile": build_profile,
+    }))
+}
+
+/// Look up the background worker's PGPROC from the pid it publishes in
+/// shared memory.
+///
+/// Returns NULL if no pid is published or no live backend has that pid, for
+/// example after the worker exited without clearing it.
+pub(crate) fn worker_proc() -> *mut pg_sys::PGPROC {
+    let pid = read_state().worker_pid;
+    if pid <= 0 {
+        return std::ptr::null_mut();
+    }
+    // SAFETY: BackendPidGetProc takes ProcArrayLock and returns NULL if no live
+    // process has this pid.
+    unsafe { pg_sys::BackendPidGetProc(pid) }
+}
+
+/// Implementation for the lightweight worker liveness check.
+///
+/// Returns only the worker_running flag, without computing the rest of
+/// the status document. Liveness comes from shared memory: the worker must
+/// have published its pid and a live backend must still have it. NULL when
+/// shared memory is not initialized.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn worker_running() -> Option<bool> {
+    if !shmem::is_initialized() {
+        return None;
+    }
+    Some(!worker_proc().is_null())
+}
+
+/// Implementation for the worker heartbeat.
+///
+/// Returns `last_check_time` from shared memory as a timestamp, or NULL if
+/// the worker has not completed a cycle yet. Reads only shared memory, so it
+/// is cheap enough for frequent polling by external monitors.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn heartbeat() -> Option<TimestampWithTimeZone> {
+    let last_check_time = read_state().last_check_time;
+    if last_check_time == 0 {
+        return None;
+    }
+    Some(pgrx::datum::to_timestamp(last_check_time as f64))
+}
+
+/// Implementation for excluding upcoming forced checkpoints from delta.
+///
+/// Records in shared memory a count of forced checkpoints that the worker
+/// subtracts from its next delta computation(s), so that manual `CHECKPOINT`
+/// commands run during maintenance do not trigger growth. Replaces any
+/// previously pending count; `n = 0` clears it.
+///
+/// # Returns
+///
+/// The pending count after the update.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn ignore_next_checkpoints(n: i32) -> i64 {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.ignore_next_checkpoints() requires superuser");
+    }
+    if n < 0 {
+        pgrx::error!("n must be >= 0");
+    }
+
+    shmem::update_state(|state| {
+        state.ignore_checkpoints = n as i64;
+    });
+    n as i64
+}
+
+/// Implementation for waking the background worker immediately.
+///
+/// Sets the latch of the worker process, whose pid the worker publishes in
+/// shared memory at startup, so its `wait_latch` returns and it runs a cycle
+/// now instead of waiting up to checkpoint_timeout.
+///
+/// # Returns
+///
+/// true if the worker's latch was set, false if the worker is not running.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn kick() -> bool {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.kick() requires superuser");
+    }
+
+    let proc_ = worker_proc();
+    if proc_.is_null() {
+        return false;
+    }
+    // SAFETY: procLatch is valid for as long as the PGPROC is in use.
+    unsafe { pg_sys::SetLatch(&mut (*proc_).procLatch) };
+    true
+}
+
+/// Implementation for setting a walrus.* parameter persistently.
+///
+/// Runs ALTER SYSTEM SET `param` = `value` and signals the postmaster to
+/// reload the configuration, so the change takes effect without a separate
+/// `pg_reload_conf()`. PostgreSQL validates the value against the
+/// parameter's type and range.
+///
+/// # Returns
+///
+/// true once the setting has been written and the reload signaled.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn set(param: &str, value: &str) -> bool {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.set() requires superuser");
+    }
+    access::require_writable("walrus.set()");
+    if !param.to_ascii_lowercase().starts_with("walrus.") {
+        pgrx::error!("walrus.set() only accepts walrus.* parameters: {}", param);
+    }
+
+    // Re-raise PostgreSQL's own validation message, as plain ALTER SYSTEM would
+    if let Err(e) = execute_alter_system_setting(param, value) {
+        pgrx::error!("{}", e.message());
+    }
+    signal_postmaster_reload();
+    true
+}
+
+/// Implementation for setting max_wal_size once, with a history record.
+///
+/// `mb` must lie between the live min_wal_size and walrus.max. Runs ALTER
+/// SYSTEM SET max_wal_size, records a 'manual' history row, and signals the
+/// postmaster to reload. Like `walrus.analyze(apply := true)`, it updates
+/// `last_adjustment_time` (so the cooldown applies to the worker's next
+/// change), and with walrus.manual_resets_cooldown on it counts in the hourly
+/// window (see `update_rate_limit_state_after_manual()`).
+///
+/// # Returns
+///
+/// true if max_wal_size was changed, false if it already was `mb`.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise), and only in databases allowed by
+/// walrus.apply_allowed_databases
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn set_size(mb: i32) -> bool {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.set_size() requires superuser");
+    }
+    access::require_apply_allowed("walrus.set_size()");
+    access::require_writable("walrus.set_size()");
+    let min_size = get_current_min_wal_size();
+    let max_size = WALRUS_MAX.get();
+    if mb < min_size || mb > max_size {
+        pgrx::error!(
+            "walrus.set_size(): {} MB is outside the allowed range ({} MB min_wal_size .. {} MB walrus.max)",
+            mb,
+            min_size,
+            max_size
+        );
+    }
+
+    let current = get_current_max_wal_size();
+    if mb == current {
+        return false;
+    }
+    let change = SizeChange {
+        action: "manual",
+        old_size_mb: current,
+        new_size_mb: mb,
+        forced_checkpoints: crate::stats::get_requested_checkpoints().max(0),
+        checkpoint_timeout_sec: checkpoint_timeout().as_secs() as i32,
+        reason: "set by walrus.set_size()",
+        abort_if_stale: false,
+    };
+    let update = |s: &mut shmem::WalrusState| {
+        s.last_adjustment_time = now_unix();
+        if mb > current {
+            s.quiet_intervals = 0;
+            s.idle_intervals = 0;
+            s.effective_shrink_intervals = 0;
+        }
+    };
+    let metadata = |_: i64| json!({ "source": ApplySource::SetSize.name() });
+    match apply_max_wal_size(&change, ApplySource::SetSize, update, metadata) {
+        Ok(_) => {}
+        Err(ApplyError::Config(e)) => pgrx::error!("{}", e.message()),
+        Err(e) => pgrx::error!("walrus.set_size(): {}", e),
+    }
+    update_rate_limit_state_after_manual();
+    true
 }
@@ -3,17 +3,68 @@
 //! This module defines the runtime configuration parameters:
 //! - `walrus.enable`: Enable/disable automatic WAL size adjustment
 //! - `walrus.max`: Maximum allowed max_wal_size (in MB)
+//! - `walrus.max_percent_of_device`: Ceiling as a percentage of the pg_wal filesystem size
 //! - `walrus.threshold`: Forced checkpoint count threshold before resize
+//! - `walrus.threshold_inclusive`: Grow when the delta equals the threshold (>=) or only above it (>)
+//! - `walrus.bulk_load_threshold_multiplier`: Threshold multiplier while bulk-load mode is active
+//! - `walrus.grow_exponent`: Exponent applied to the delta in the grow formula
+//! - `walrus.sample_interval_sec`: Sub-interval for sampling checkpoint counts
+//! - `walrus.use_rolling_delta`: Compare the rolling average delta against the threshold
+//! - `walrus.lookback_intervals`: Intervals of forced checkpoints compared against the threshold
+//! - `walrus.wal_pressure_only`: Count only checkpoints forced by WAL volume toward the threshold
+//! - `walrus.settling_max_step_mb`: Largest grow step during the post-startup settling period
+//! - `walrus.startup_grace_sec`: Seconds after worker start without grow/shrink decisions
+//! - `walrus.max_interval_drift`: Intervals since the previous cycle that count as a stall
 //! - `walrus.shrink_enable`: Enable/disable automatic shrinking
 //! - `walrus.shrink_factor`: Multiplication factor when shrinking (0.01-0.99)
 //! - `walrus.shrink_intervals`: Quiet intervals before triggering shrink
 //! - `walrus.min_size`: Minimum floor for max_wal_size (in MB)
+//! - `walrus.enforce_min_on_start`: Grow to walrus.min_size once at worker startup
+//! - `walrus.shrink_max_wal_mb_per_interval`: WAL volume above which an interval is not quiet
+//! - `walrus.force_min_shrink`: Shrink by one WAL segment when shrink_factor rounding stalls
+//! - `walrus.shrink_rounding`: Rounding of the shrink target (up, down, nearest)
+//! - `walrus.shrink_backoff`: Double the quiet intervals required after each shrink
+//! - `walrus.shrink_target`: How the shrink target is chosen (factor, peak_ratio)
+//! - `walrus.peak_ratio`: Multiple of the recent peak size that peak_ratio shrinks toward
+//! - `walrus.grow_protect_intervals`: Intervals after a grow during which no shrink is made
+//! - `walrus.fast_shrink_after_intervals`: Quiet intervals after which shrinks go straight to the floor
+//! - `walrus.mode`: Operating mode (both, grow_only, shrink_only, observe)
+//! - `walrus.reload_method`: How a configuration reload is requested (signal, sql)
+//! - `walrus.log_level`: Worker log verbosity (quiet, normal, verbose)
 //! - `walrus.history_retention_days`: Days to retain history records before cleanup
-
-use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting};
+//! - `walrus.cleanup_interval_sec`: Minimum seconds between worker history cleanups
+//! - `walrus.history_max_rows`: Maximum history rows kept by cleanup (0 = unlimited)
+//! - `walrus.archive_before_delete`: Roll deleted history rows up into walrus.history_archive
+//! - `walrus.record_stats_unavailable`: Record a history note when checkpoint stats go missing
+//! - `walrus.stats_source`: Where the forced checkpoint count is read from (direct, sql)
+//! - `walrus.verbose_history`: Record a history note classifying every interval
+//! - `walrus.mirror_table`: Table that also receives every recorded decision
+//! - `walrus.max_growth_mb_per_day`: Cumulative growth allowed per rolling 24 hours
+//! - `walrus.max_sighups_per_hour_warn`: Configuration reloads per hour before a WARNING
+//! - `walrus.manual_resets_cooldown`: Manual changes count in the hourly window
+//! - `walrus.min_apply_confidence`: Minimum confidence required to apply a recommendation
+//! - `walrus.min_shrink_confidence`: Minimum confidence required for an automatic shrink
+//! - `walrus.shrink_confidence_penalty`: Points subtracted from the confidence of a shrink
+//! - `walrus.emergency_free_disk_mb`: Free pg_wal disk space that triggers an emergency shrink
+//! - `walrus.manage_completion_target`: Also set checkpoint_completion_target on grow
+//! - `walrus.completion_target`: checkpoint_completion_target value applied on grow
+//! - `walrus.warn_wal_buffers`: Warn when wal_buffers looks undersized after a grow
+//! - `walrus.database`: Database the background worker connects to (postmaster)
+//! - `walrus.schema_name`: Schema holding the history table (postmaster)
+//! - `walrus.apply_allowed_databases`: Databases where apply functions may run (postmaster)
+//! - `walrus.read_only`: Never run ALTER SYSTEM or write history (postmaster)
+
+use crate::algorithm::DELTA_RING_SIZE;
+
+use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting, PostgresGucEnum};
 use pgrx::pg_sys;
 use std::ffi::CString;
 
+mod shrink;
+mod stats;
+pub use shrink::*;
+pub use stats::*;
+
 // =========================================================================
 // Grow GUC Parameters
 // =========================================================================
@@ -28,35 +79,68 @@ pub static WALRUS_ENABLE: GucSetting<bool> = GucSetting::<bool>::new(true);
 /// Default: 4096 (4GB), Min: 2 MB, Max: i32::MAX MB
 pub static WALRUS_MAX: GucSetting<i32> = GucSetting::<i32>::new(4096);
 
+/// Ceiling as a percentage of the pg_wal filesystem's total size.
+/// When set, the effective maximum is the lower of this and walrus.max.
+/// Default: 0 (walrus.max only), Min: 0, Max: 100
+pub static WALRUS_MAX_PERCENT_OF_DEVICE: GucSetting<i32> = GucSetting::<i32>::new(0);
+
 /// Forced checkpoints per checkpoint_timeout interval before increasing max_wal_size.
 /// Higher values ignore occasional WAL spikes from batch jobs.
 /// Default: 2, Min: 1, Max: 1000
 pub static WALRUS_THRESHOLD: GucSetting<i32> = GucSetting::<i32>::new(2);
 
-// =========================================================================
-// Shrink GUC Parameters
-// =========================================================================
-
-/// Enable automatic shrinking of max_wal_size parameter.
-/// When enabled (and walrus.enable is also true), pg_walrus shrinks max_wal_size
-/// after sustained periods of low checkpoint activity.
+/// Whether a delta equal to walrus.threshold grows (delta >= threshold) or
+/// only a delta above it does (delta > threshold).
 /// Default: true
-pub static WALRUS_SHRINK_ENABLE: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static WALRUS_THRESHOLD_INCLUSIVE: GucSetting<bool> = GucSetting::<bool>::new(true);
+
+/// Multiplier applied to walrus.threshold while bulk-load mode is active
+/// (walrus.begin_bulk_load()), so only sustained pressure grows the size.
+/// Default: 4, Min: 1, Max: 1000
+pub static WALRUS_BULK_LOAD_THRESHOLD_MULTIPLIER: GucSetting<i32> = GucSetting::<i32>::new(4);
 
-/// Multiplication factor when shrinking max_wal_size.
-/// Lower values shrink more aggressively. Must be between 0.01 and 0.99 (exclusive).
-/// Default: 0.75 (reduces by 25%)
-pub static WALRUS_SHRINK_FACTOR: GucSetting<f64> = GucSetting::<f64>::new(0.75);
+/// Exponent applied to the forced checkpoint delta when growing:
+/// new size = current * (1 + delta^exponent). Below 1.0 dampens large bursts.
+/// Default: 1.0 (current * (delta + 1)), Min: 0.1, Max: 4.0
+pub static WALRUS_GROW_EXPONENT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
 
-/// Number of consecutive quiet checkpoint intervals before triggering shrink.
-/// A quiet interval is one where forced checkpoints < threshold.
-/// Default: 5, Min: 1, Max: 1000
-pub static WALRUS_SHRINK_INTERVALS: GucSetting<i32> = GucSetting::<i32>::new(5);
+/// Seconds between checkpoint count samples within a checkpoint_timeout interval.
+/// Deltas accumulate across samples; 0 samples once per checkpoint_timeout.
+/// Default: 0 (use checkpoint_timeout), Min: 0, Max: 86400 (24 hours)
+pub static WALRUS_SAMPLE_INTERVAL_SEC: GucSetting<i32> = GucSetting::<i32>::new(0);
 
-/// Minimum floor for max_wal_size in MB.
-/// pg_walrus will never shrink max_wal_size below this value.
-/// Default: 1024 (1GB), Min: 2 MB, Max: i32::MAX MB
-pub static WALRUS_MIN_SIZE: GucSetting<i32> = GucSetting::<i32>::new(1024);
+/// Decide on the average of the last 8 interval deltas instead of the latest one,
+/// so a brief dip does not immediately start the shrink countdown.
+/// Default: false
+pub static WALRUS_USE_ROLLING_DELTA: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Decision intervals whose forced checkpoints are added up and compared
+/// against walrus.threshold, catching slow but steady pressure. Bounded by the
+/// ring of recent deltas. walrus.use_rolling_delta takes precedence.
+/// Default: 1 (latest interval only), Min: 1, Max: 8
+pub static WALRUS_LOOKBACK_INTERVALS: GucSetting<i32> = GucSetting::<i32>::new(1);
+
+/// Count only the forced checkpoints the interval's WAL volume accounts for,
+/// leaving explicit CHECKPOINTs and base backups out of the delta.
+/// Default: false (every requested checkpoint counts)
+pub static WALRUS_WAL_PRESSURE_ONLY: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Largest single grow step while settling after worker startup or standby promotion.
+/// Settling lasts walrus.shrink_intervals checkpoint_timeout intervals.
+/// Default: 1024 (1GB), Min: 1 MB, Max: i32::MAX MB
+pub static WALRUS_SETTLING_MAX_STEP_MB: GucSetting<i32> = GucSetting::<i32>::new(1024);
+
+/// Seconds after the worker starts during which it keeps its checkpoint
+/// baseline current but makes no grow or shrink decisions, so transient
+/// startup checkpoints never reach a delta. Emergency shrinks and the cap-down
+/// to a lowered walrus.max are not delta decisions and still apply.
+/// Default: 0 (no grace period), Min: 0, Max: 86400 (24 hours)
+pub static WALRUS_STARTUP_GRACE_SEC: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Checkpoint intervals since the previous cycle after which the worker treats
+/// the gap as a stall: no decision, and the baseline is re-established.
+/// Default: 3, Min: 0 (disabled), Max: 1000
+pub static WALRUS_MAX_INTERVAL_DRIFT: GucSetting<i32> = GucSetting::<i32>::new(3);
 
 // =========================================================================
 // History GUC Parameters
@@ -67,6 +151,38 @@ pub static WALRUS_MIN_SIZE: GucSetting<i32> = GucSetting::<i32>::new(1024);
 /// Default: 7, Min: 0 (delete all), Max: 3650 (10 years)
 pub static WALRUS_HISTORY_RETENTION_DAYS: GucSetting<i32> = GucSetting::<i32>::new(7);
 
+/// Minimum seconds between history cleanups run by the background worker.
+/// walrus.cleanup_history() always runs immediately regardless.
+/// Default: 3600 (1 hour), Min: 0 (every cycle), Max: 604800 (7 days)
+pub static WALRUS_CLEANUP_INTERVAL_SEC: GucSetting<i32> = GucSetting::<i32>::new(3600);
+
+/// Maximum number of history rows kept; cleanup trims to N rows, deleting the
+/// oldest skipped/dry_run/note rows before any real decision.
+/// Applied in addition to history_retention_days.
+/// Default: 0 (unlimited), Min: 0, Max: i32::MAX
+pub static WALRUS_HISTORY_MAX_ROWS: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Before cleanup deletes history rows, add them to the per-day rollup in
+/// walrus.history_archive, which is kept indefinitely.
+/// Default: false
+pub static WALRUS_ARCHIVE_BEFORE_DELETE: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Record a history row (action 'note') when checkpoint statistics become unavailable.
+/// At most one row is written per contiguous outage, so monitoring gaps are auditable.
+/// Default: false
+pub static WALRUS_RECORD_STATS_UNAVAILABLE: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Record a 'note' row every cycle with the delta, threshold, quiet_intervals,
+/// and whether the interval was classified active or quiet. Verbose; meant for tuning.
+/// Default: false
+pub static WALRUS_VERBOSE_HISTORY: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Schema-qualified table that also receives every recorded decision.
+/// Lets alerting hang triggers off a table whose shape pg_walrus does not own.
+/// Default: "" (off)
+pub static WALRUS_MIRROR_TABLE: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(Some(c""));
+
 // =========================================================================
 // Dry-Run GUC Parameters
 // =========================================================================
@@ -77,6 +193,94 @@ pub static WALRUS_HISTORY_RETENTION_DAYS: GucSetting<i32> = GucSetting::<i32>::n
 /// Default: false
 pub static WALRUS_DRY_RUN: GucSetting<bool> = GucSetting::<bool>::new(false);
 
+// =========================================================================
+// Operating Mode GUC Parameters
+// =========================================================================
+
+/// Operating mode for automatic and manual adjustments (walrus.mode).
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WalrusMode {
+    /// Grow and shrink
+    #[name = c"both"]
+    Both,
+    /// Only ever increase max_wal_size
+    #[name = c"grow_only"]
+    GrowOnly,
+    /// Only ever decrease max_wal_size
+    #[name = c"shrink_only"]
+    ShrinkOnly,
+    /// Record decisions without applying anything (like walrus.dry_run)
+    #[name = c"observe"]
+    Observe,
+}
+
+impl WalrusMode {
+    /// Setting value as shown by SHOW walrus.mode.
+    pub fn name(self) -> &'static str {
+        match self {
+            WalrusMode::Both => "both",
+            WalrusMode::GrowOnly => "grow_only",
+            WalrusMode::ShrinkOnly => "shrink_only",
+            WalrusMode::Observe => "observe",
+        }
+    }
+}
+
+/// Restrict adjustments to one direction, or record decisions without applying.
+/// Emergency shrinks and cap-downs to a lowered walrus.max ignore the direction.
+/// Default: both
+pub static WALRUS_MODE: GucSetting<WalrusMode> = GucSetting::<WalrusMode>::new(WalrusMode::Both);
+
+/// How a configuration reload is requested after ALTER SYSTEM (walrus.reload_method).
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReloadMethod {
+    /// Send SIGHUP to the postmaster directly
+    #[name = c"signal"]
+    Signal,
+    /// Run SELECT pg_reload_conf() through SPI
+    #[name = c"sql"]
+    Sql,
+}
+
+impl ReloadMethod {
+    /// Setting value as shown by SHOW walrus.reload_method.
+    pub fn name(self) -> &'static str {
+        match self {
+            ReloadMethod::Signal => "signal",
+            ReloadMethod::Sql => "sql",
+        }
+    }
+}
+
+/// Reload mechanism, for platforms that restrict signalling the postmaster
+/// but allow pg_reload_conf().
+/// Default: signal
+pub static WALRUS_RELOAD_METHOD: GucSetting<ReloadMethod> =
+    GucSetting::<ReloadMethod>::new(ReloadMethod::Signal);
+
+// =========================================================================
+// Logging GUC Parameters
+// =========================================================================
+
+/// Verbosity of the worker's informational log messages (walrus.log_level).
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WalrusLogLevel {
+    /// Only applied changes (plus warnings and errors)
+    #[name = c"quiet"]
+    Quiet,
+    /// Applied changes and decisions that were blocked, held, or dry-run
+    #[name = c"normal"]
+    Normal,
+    /// Also a line per cycle with the delta and quiet intervals
+    #[name = c"verbose"]
+    Verbose,
+}
+
+/// Worker log verbosity. Warnings and errors are always logged.
+/// Default: normal
+pub static WALRUS_LOG_LEVEL: GucSetting<WalrusLogLevel> =
+    GucSetting::<WalrusLogLevel>::new(WalrusLogLevel::Normal);
+
 // =========================================================================
 // Rate Limiting GUC Parameters
 // =========================================================================
@@ -93,6 +297,76 @@ pub static WALRUS_COOLDOWN_SEC: GucSetting<i32> = GucSetting::<i32>::new(300);
 /// Default: 4, Min: 0, Max: 1000
 pub static WALRUS_MAX_CHANGES_PER_HOUR: GucSetting<i32> = GucSetting::<i32>::new(4);
 
+/// Maximum number of automatic shrinks allowed per rolling one-hour window.
+/// When set, shrinks have their own budget and no longer count toward
+/// walrus.max_changes_per_hour, so grows cannot starve them.
+/// walrus.max_changes_per_hour = 0 still blocks shrinks.
+/// Default: -1 (shrinks share walrus.max_changes_per_hour), Min: -1, Max: 1000
+pub static WALRUS_MAX_SHRINKS_PER_HOUR: GucSetting<i32> = GucSetting::<i32>::new(-1);
+
+/// Maximum cumulative growth (MB) applied per rolling 24-hour window.
+/// Bounds the magnitude of growth, which cooldown and hourly limits do not.
+/// Default: 0 (unlimited), Min: 0, Max: i32::MAX MB
+pub static WALRUS_MAX_GROWTH_MB_PER_DAY: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Configuration reloads per rolling one-hour window above which a WARNING is
+/// logged. Every reload makes all backends re-read their configuration.
+/// Default: 20, Min: 0 (no warning), Max: 10000
+pub static WALRUS_MAX_SIGHUPS_PER_HOUR_WARN: GucSetting<i32> = GucSetting::<i32>::new(20);
+
+/// Whether manual changes (walrus.analyze(apply := true), walrus.set_size())
+/// count in the hourly window shared with the worker. They start the cooldown
+/// either way.
+/// Default: true
+pub static WALRUS_MANUAL_RESETS_COOLDOWN: GucSetting<bool> = GucSetting::<bool>::new(true);
+
+// =========================================================================
+// Confidence GUC Parameters
+// =========================================================================
+
+/// Minimum recommendation confidence (0-100) required to apply a change.
+/// Gates walrus.analyze(apply := true) and the worker's automatic grows.
+/// Default: 0 (no gate), Min: 0, Max: 100
+pub static WALRUS_MIN_APPLY_CONFIDENCE: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Minimum confidence (0-100) required for the worker to apply an automatic shrink.
+/// Low-confidence shrinks are held so headroom is not reduced on thin evidence.
+/// Default: 0 (no gate), Min: 0, Max: 100
+pub static WALRUS_MIN_SHRINK_CONFIDENCE: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Points subtracted from the confidence of a shrink, whose evidence (an absence
+/// of activity) is weaker than the checkpoint bursts behind a grow.
+/// Default: 0 (shrinks and grows scored alike), Min: 0, Max: 100
+pub static WALRUS_SHRINK_CONFIDENCE_PENALTY: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+// =========================================================================
+// Emergency GUC Parameters
+// =========================================================================
+
+/// Free space (in MB) on the pg_wal filesystem below which pg_walrus shrinks
+/// max_wal_size to the floor immediately, bypassing quiet intervals and rate limits.
+/// Default: 0 (disabled), Min: 0, Max: i32::MAX MB
+pub static WALRUS_EMERGENCY_FREE_DISK_MB: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+// =========================================================================
+// Checkpoint Co-Tuning GUC Parameters
+// =========================================================================
+
+/// Also set checkpoint_completion_target when a grow is applied.
+/// Spreading checkpoint I/O avoids spikes once max_wal_size is large.
+/// Default: false
+pub static WALRUS_MANAGE_COMPLETION_TARGET: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// checkpoint_completion_target applied alongside a grow when
+/// walrus.manage_completion_target is enabled.
+/// Default: 0.9, Min: 0.0, Max: 1.0
+pub static WALRUS_COMPLETION_TARGET: GucSetting<f64> = GucSetting::<f64>::new(0.9);
+
+/// Warn once when wal_buffers looks undersized for the max_wal_size just applied.
+/// Advisory only: wal_buffers is never changed.
+/// Default: true
+pub static WALRUS_WARN_WAL_BUFFERS: GucSetting<bool> = GucSetting::<bool>::new(true);
+
 // =========================================================================
 // Database GUC Parameter (Postmaster context - requires restart)
 // =========================================================================
@@ -104,6 +378,28 @@ pub static WALRUS_MAX_CHANGES_PER_HOUR: GucSetting<i32> = GucSetting::<i32>::new
 pub static WALRUS_DATABASE: GucSetting<Option<CString>> =
     GucSetting::<Option<CString>>::new(Some(c"postgres"));
 
+/// Schema holding the pg_walrus history table.
+/// Lets multi-tenant clusters keep the table in a renamed schema.
+/// Must be set in postgresql.conf and requires restart to change.
+/// Default: "walrus"
+pub static WALRUS_SCHEMA_NAME: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(Some(c"walrus"));
+
+/// Comma-separated databases where walrus.analyze(apply := true),
+/// walrus.set_size(), walrus.tick(), and walrus.reset() may run, in addition
+/// to the superuser check.
+/// Must be set in postgresql.conf and requires restart to change.
+/// Default: "" (all databases)
+pub static WALRUS_APPLY_ALLOWED_DATABASES: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+
+/// Observe-only installs (e.g. read replicas): the worker never runs ALTER
+/// SYSTEM or writes history, logging its decisions at DEBUG1 instead, and the
+/// apply functions raise an error.
+/// Must be set in postgresql.conf and requires restart to change.
+/// Default: false
+pub static WALRUS_READ_ONLY: GucSetting<bool> = GucSetting::<bool>::new(false);
+
 /// Register all pg_walrus GUC parameters with PostgreSQL.
 ///
 /// This function registers all eight GUC parameters using GucContext::Sighup,
@@ -133,6 +429,17 @@ pub fn register_gucs() {
         GucFlags::UNIT_MB,
     );
 
+    GucRegistry::define_int_guc(
+        c"walrus.max_percent_of_device",
+        c"Maximum max_wal_size as a percentage of the pg_wal filesystem size.",
+        c"The lower of this and walrus.max applies. Set to 0 to use walrus.max only.",
+        &WALRUS_MAX_PERCENT_OF_DEVICE,
+        0,
+        100,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
     GucRegistry::define_int_guc(
         c"walrus.threshold",
         c"Forced checkpoints per timeout before increasing max_wal_size.",
@@ -144,52 +451,122 @@ pub fn register_gucs() {
         GucFlags::default(),
     );
 
-    // =========================================================================
-    // Shrink GUCs
-    // =========================================================================
-
     GucRegistry::define_bool_guc(
-        c"walrus.shrink_enable",
-        c"Enable automatic shrinking of max_wal_size parameter.",
-        c"When enabled, pg_walrus shrinks max_wal_size after sustained low activity.",
-        &WALRUS_SHRINK_ENABLE,
+        c"walrus.threshold_inclusive",
+        c"Grow when forced checkpoints equal walrus.threshold.",
+        c"When on, a delta equal to the threshold grows (delta >= threshold); when off, only a delta above it does (delta > threshold).",
+        &WALRUS_THRESHOLD_INCLUSIVE,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.bulk_load_threshold_multiplier",
+        c"Threshold multiplier while bulk-load mode is active.",
+        c"Between walrus.begin_bulk_load() and its expiry (or walrus.end_bulk_load()), the worker grows only when forced checkpoints reach walrus.threshold times this value.",
+        &WALRUS_BULK_LOAD_THRESHOLD_MULTIPLIER,
+        1,
+        1000,
         GucContext::Sighup,
         GucFlags::default(),
     );
 
     GucRegistry::define_float_guc(
-        c"walrus.shrink_factor",
-        c"Multiplication factor when shrinking max_wal_size.",
-        c"Lower values shrink more aggressively. Must be between 0.01 and 0.99.",
-        &WALRUS_SHRINK_FACTOR,
-        0.01,
-        0.99,
+        c"walrus.grow_exponent",
+        c"Exponent applied to the forced checkpoint delta when growing.",
+        c"New size = current * (1 + delta^exponent). Values below 1.0 dampen large bursts; above 1.0 amplify them.",
+        &WALRUS_GROW_EXPONENT,
+        0.1,
+        4.0,
         GucContext::Sighup,
         GucFlags::default(),
     );
 
     GucRegistry::define_int_guc(
-        c"walrus.shrink_intervals",
-        c"Quiet checkpoint intervals before triggering shrink.",
-        c"A quiet interval is one where forced checkpoints are below threshold.",
-        &WALRUS_SHRINK_INTERVALS,
+        c"walrus.sample_interval_sec",
+        c"Seconds between checkpoint count samples.",
+        c"Deltas accumulate across samples until a full checkpoint_timeout elapses. Set to 0 to sample once per checkpoint_timeout.",
+        &WALRUS_SAMPLE_INTERVAL_SEC,
+        0,
+        86400,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.use_rolling_delta",
+        c"Compare the rolling average of recent deltas against the threshold.",
+        c"Uses the average of the last 8 interval deltas instead of the latest delta, smoothing out brief dips and spikes.",
+        &WALRUS_USE_ROLLING_DELTA,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.lookback_intervals",
+        c"Intervals of forced checkpoints compared against the threshold.",
+        c"Above 1, the threshold applies to the forced checkpoints of the last N intervals combined, and the grow formula uses their per-interval rate. Ignored with walrus.use_rolling_delta.",
+        &WALRUS_LOOKBACK_INTERVALS,
         1,
-        1000,
+        DELTA_RING_SIZE as i32,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.wal_pressure_only",
+        c"Count only checkpoints forced by WAL volume toward the threshold.",
+        c"Requested checkpoints the interval's WAL volume cannot account for (explicit CHECKPOINT, base backups) are left out of the delta. Every checkpoint counts while the split is unknown.",
+        &WALRUS_WAL_PRESSURE_ONLY,
         GucContext::Sighup,
         GucFlags::default(),
     );
 
     GucRegistry::define_int_guc(
-        c"walrus.min_size",
-        c"Minimum floor for max_wal_size in MB.",
-        c"pg_walrus will never shrink max_wal_size below this value.",
-        &WALRUS_MIN_SIZE,
-        2,
+        c"walrus.settling_max_step_mb",
+        c"Largest grow step while settling after startup or promotion.",
+        c"For the first walrus.shrink_intervals intervals after the worker starts, each grow is limited to this many MB.",
+        &WALRUS_SETTLING_MAX_STEP_MB,
+        1,
         i32::MAX,
         GucContext::Sighup,
         GucFlags::UNIT_MB,
     );
 
+    GucRegistry::define_int_guc(
+        c"walrus.startup_grace_sec",
+        c"Seconds after worker start without grow or shrink decisions.",
+        c"The worker keeps its checkpoint baseline current but makes no grow or shrink decisions until this many seconds after it starts; emergency shrinks and the cap-down to a lowered walrus.max still apply. Set to 0 to disable.",
+        &WALRUS_STARTUP_GRACE_SEC,
+        0,
+        86400,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.max_interval_drift",
+        c"Checkpoint intervals between cycles that count as a stall.",
+        c"When more than this many checkpoint_timeout intervals passed since the previous cycle, the worker makes no decision and re-establishes its checkpoint baseline. Set to 0 to disable.",
+        &WALRUS_MAX_INTERVAL_DRIFT,
+        0,
+        1000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    // =========================================================================
+    // Shrink GUCs (guc/shrink.rs)
+    // =========================================================================
+
+    shrink::register_shrink_gucs();
+
+    // =========================================================================
+    // Checkpoint Statistics GUCs (guc/stats.rs)
+    // =========================================================================
+
+    stats::register_stats_gucs();
+
     // =========================================================================
     // History GUCs
     // =========================================================================
@@ -205,6 +582,64 @@ pub fn register_gucs() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_int_guc(
+        c"walrus.cleanup_interval_sec",
+        c"Minimum seconds between automatic history cleanups.",
+        c"The worker deletes expired history at most this often. Set to 0 to run every cycle.",
+        &WALRUS_CLEANUP_INTERVAL_SEC,
+        0,
+        604800,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.history_max_rows",
+        c"Maximum number of history rows to keep.",
+        c"Cleanup trims the history table to N rows, deleting skipped, dry_run, and note rows before real decisions, in addition to day-based retention. Set to 0 for unlimited.",
+        &WALRUS_HISTORY_MAX_ROWS,
+        0,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.archive_before_delete",
+        c"Archive daily summaries of history rows before cleanup deletes them.",
+        c"Cleanup adds the rows it deletes to per-day counts and sizes in walrus.history_archive, which is never cleaned up.",
+        &WALRUS_ARCHIVE_BEFORE_DELETE,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.record_stats_unavailable",
+        c"Record a history note when checkpoint statistics are unavailable.",
+        c"Writes one 'note' row per contiguous outage so monitoring gaps are auditable.",
+        &WALRUS_RECORD_STATS_UNAVAILABLE,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.verbose_history",
+        c"Record a history note classifying every interval as active or quiet.",
+        c"Writes one 'note' row per cycle with delta, threshold, and quiet_intervals. Bounded by history retention.",
+        &WALRUS_VERBOSE_HISTORY,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"walrus.mirror_table",
+        c"Table that also receives every recorded decision.",
+        c"Schema-qualified name. Each decision written to the history table is also inserted there if the table exists and has the core history columns; failures are logged as warnings. Empty disables mirroring.",
+        &WALRUS_MIRROR_TABLE,
+        GucContext::Sighup,
+        GucFlags::SUPERUSER_ONLY,
+    );
+
     // =========================================================================
     // Dry-Run GUCs
     // =========================================================================
@@ -218,6 +653,41 @@ pub fn register_gucs() {
         GucFlags::default(),
     );
 
+    // =========================================================================
+    // Operating Mode GUCs
+    // =========================================================================
+
+    GucRegistry::define_enum_guc(
+        c"walrus.mode",
+        c"Operating mode: both, grow_only, shrink_only, or observe.",
+        c"grow_only and shrink_only disable the other direction; observe records decisions without applying them.",
+        &WALRUS_MODE,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        c"walrus.reload_method",
+        c"How to reload the configuration after a change: signal or sql.",
+        c"signal sends SIGHUP to the postmaster; sql runs pg_reload_conf() for platforms that restrict signals.",
+        &WALRUS_RELOAD_METHOD,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    // =========================================================================
+    // Logging GUCs
+    // =========================================================================
+
+    GucRegistry::define_enum_guc(
+        c"walrus.log_level",
+        c"Worker log verbosity: quiet, normal, or verbose.",
+        c"quiet logs only applied changes; verbose adds a line per cycle. Warnings and errors are always logged.",
+        &WALRUS_LOG_LEVEL,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
     // =========================================================================
     // Rate Limiting GUCs
     // =========================================================================
@@ -244,6 +714,133 @@ pub fn register_gucs() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_int_guc(
+        c"walrus.max_shrinks_per_hour",
+        c"Maximum automatic shrinks per rolling one-hour window.",
+        c"Shrinks are counted separately from walrus.max_changes_per_hour, though walrus.max_changes_per_hour = 0 still blocks them. Set to -1 to share that limit, or 0 to block automatic shrinks.",
+        &WALRUS_MAX_SHRINKS_PER_HOUR,
+        -1,
+        1000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.max_growth_mb_per_day",
+        c"Maximum cumulative growth per rolling 24-hour window.",
+        c"Grows that would take the total growth applied in the window past this many MB are skipped. Set to 0 for unlimited.",
+        &WALRUS_MAX_GROWTH_MB_PER_DAY,
+        0,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_MB,
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.max_sighups_per_hour_warn",
+        c"Configuration reloads per hour above which a warning is logged.",
+        c"Each applied change reloads the configuration of every backend. More reloads than this in a rolling one-hour window log a WARNING about a reload storm. Set to 0 to disable the warning.",
+        &WALRUS_MAX_SIGHUPS_PER_HOUR_WARN,
+        0,
+        10000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.manual_resets_cooldown",
+        c"Count manual changes in the hourly window shared with the worker.",
+        c"When on, walrus.analyze(apply := true) and walrus.set_size() count against walrus.max_changes_per_hour like automatic changes. Manual changes start the cooldown either way.",
+        &WALRUS_MANUAL_RESETS_COOLDOWN,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    // =========================================================================
+    // Confidence GUCs
+    // =========================================================================
+
+    GucRegistry::define_int_guc(
+        c"walrus.min_apply_confidence",
+        c"Minimum recommendation confidence required to apply a change.",
+        c"Recommendations below this confidence (0-100) are not applied. Set to 0 to disable.",
+        &WALRUS_MIN_APPLY_CONFIDENCE,
+        0,
+        100,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.min_shrink_confidence",
+        c"Minimum confidence required to apply an automatic shrink.",
+        c"Shrinks below this confidence (0-100) are held and recorded as skipped. Set to 0 to disable.",
+        &WALRUS_MIN_SHRINK_CONFIDENCE,
+        0,
+        100,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.shrink_confidence_penalty",
+        c"Confidence points subtracted from a shrink.",
+        c"Shrink recommendations and the walrus.min_shrink_confidence gate use the confidence minus this penalty (never below 0).",
+        &WALRUS_SHRINK_CONFIDENCE_PENALTY,
+        0,
+        100,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    // =========================================================================
+    // Emergency GUCs
+    // =========================================================================
+
+    GucRegistry::define_int_guc(
+        c"walrus.emergency_free_disk_mb",
+        c"Free pg_wal disk space that triggers an emergency shrink.",
+        c"Below this, max_wal_size shrinks to the floor ignoring cooldown and hourly limits. Set to 0 to disable.",
+        &WALRUS_EMERGENCY_FREE_DISK_MB,
+        0,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_MB,
+    );
+
+    // =========================================================================
+    // Checkpoint Co-Tuning GUCs
+    // =========================================================================
+
+    GucRegistry::define_bool_guc(
+        c"walrus.manage_completion_target",
+        c"Also set checkpoint_completion_target when growing max_wal_size.",
+        c"When enabled, applied grows also set checkpoint_completion_target to walrus.completion_target.",
+        &WALRUS_MANAGE_COMPLETION_TARGET,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"walrus.completion_target",
+        c"checkpoint_completion_target applied alongside a grow.",
+        c"Only used when walrus.manage_completion_target is enabled. Range: 0.0-1.0.",
+        &WALRUS_COMPLETION_TARGET,
+        0.0,
+        1.0,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.warn_wal_buffers",
+        c"Warn when wal_buffers looks undersized after a grow.",
+        c"Advisory only: logs a one-time WARNING suggesting a wal_buffers value and flags the grow history record.",
+        &WALRUS_WARN_WAL_BUFFERS,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
     // =========================================================================
     // Database GUC (Postmaster context - requires restart)
     // =========================================================================
@@ -257,9 +854,42 @@ pub fn register_gucs() {
         GucFlags::SUPERUSER_ONLY,
     );
 
+    GucRegistry::define_string_guc(
+        c"walrus.schema_name",
+        c"Schema holding the pg_walrus history table.",
+        c"All history reads, writes, and cleanup use <schema_name>.history. Requires restart to change.",
+        &WALRUS_SCHEMA_NAME,
+        GucContext::Postmaster,
+        GucFlags::SUPERUSER_ONLY,
+    );
+
+    GucRegistry::define_string_guc(
+        c"walrus.apply_allowed_databases",
+        c"Databases where pg_walrus apply functions may run.",
+        c"Comma-separated list checked by walrus.analyze(apply := true), walrus.set_size(), walrus.tick(), and walrus.reset(). Empty allows all databases. Requires restart to change.",
+        &WALRUS_APPLY_ALLOWED_DATABASES,
+        GucContext::Postmaster,
+        GucFlags::SUPERUSER_ONLY,
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.read_only",
+        c"Never change max_wal_size or write history.",
+        c"For observe-only installs such as read replicas: the worker only logs its decisions at DEBUG1, and the apply functions raise an error. Requires restart to change.",
+        &WALRUS_READ_ONLY,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+
     // Reserve the "walrus" GUC prefix to prevent other extensions from using it.
     // This matches pg_walsizer's behavior with MarkGUCPrefixReserved("walsizer").
+    // MarkGUCPrefixReserved was added in PG 15; older versions only warn on placeholders.
+    #[cfg(not(any(feature = "pg13", feature = "pg14")))]
     unsafe {
         pg_sys::MarkGUCPrefixReserved(c"walrus".as_ptr());
     }
+    #[cfg(any(feature = "pg13", feature = "pg14"))]
+    unsafe {
+        pg_sys::EmitWarningsOnPlaceholders(c"walrus".as_ptr());
+    }
 }
@@ -0,0 +1,258 @@
+//! Shrink GUC parameters for pg_walrus.
+//!
+//! Split out of `guc.rs` to keep it under the file size limit. The statics
+//! are re-exported from `crate::guc`, and `register_shrink_gucs()` is called
+//! from `register_gucs()`.
+
+use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting, PostgresGucEnum};
+
+/// Enable automatic shrinking of max_wal_size parameter.
+/// When enabled (and walrus.enable is also true), pg_walrus shrinks max_wal_size
+/// after sustained periods of low checkpoint activity.
+/// Default: true
+pub static WALRUS_SHRINK_ENABLE: GucSetting<bool> = GucSetting::<bool>::new(true);
+
+/// Multiplication factor when shrinking max_wal_size.
+/// Lower values shrink more aggressively. Must be between 0.01 and 0.99 (exclusive).
+/// Default: 0.75 (reduces by 25%)
+pub static WALRUS_SHRINK_FACTOR: GucSetting<f64> = GucSetting::<f64>::new(0.75);
+
+/// Number of consecutive quiet checkpoint intervals before triggering shrink.
+/// A quiet interval is one where forced checkpoints < threshold.
+/// Default: 5, Min: 1, Max: 1000
+pub static WALRUS_SHRINK_INTERVALS: GucSetting<i32> = GucSetting::<i32>::new(5);
+
+/// Minimum floor for max_wal_size in MB.
+/// pg_walrus will never shrink max_wal_size below this value.
+/// Default: 1024 (1GB), Min: 2 MB, Max: i32::MAX MB
+pub static WALRUS_MIN_SIZE: GucSetting<i32> = GucSetting::<i32>::new(1024);
+
+/// Grow max_wal_size to walrus.min_size once at worker startup when it is below it.
+/// Default: false (min_size only bounds shrinks)
+pub static WALRUS_ENFORCE_MIN_ON_START: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Maximum WAL (in MB) generated per interval for it to count as quiet.
+/// Intervals with more WAL reset quiet_intervals even when forced checkpoints are low.
+/// Default: 0 (disabled), Min: 0, Max: i32::MAX MB
+pub static WALRUS_SHRINK_MAX_WAL_MB_PER_INTERVAL: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Shrink by at least one WAL segment when rounding up current * shrink_factor
+/// leaves the target at the current size (e.g. a factor near 0.99 on a small size).
+/// Default: false (log once and keep waiting)
+pub static WALRUS_FORCE_MIN_SHRINK: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Rounding of current * shrink_factor to whole MB (walrus.shrink_rounding).
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShrinkRounding {
+    /// Round up (ceil), never under-sizing
+    #[name = c"up"]
+    Up,
+    /// Round down (floor), reclaiming more space
+    #[name = c"down"]
+    Down,
+    /// Round to the nearest MB
+    #[name = c"nearest"]
+    Nearest,
+}
+
+impl ShrinkRounding {
+    /// Setting value as shown by SHOW walrus.shrink_rounding.
+    pub fn name(self) -> &'static str {
+        match self {
+            ShrinkRounding::Up => "up",
+            ShrinkRounding::Down => "down",
+            ShrinkRounding::Nearest => "nearest",
+        }
+    }
+}
+
+/// Rounding direction for the shrink target. The result is still clamped to
+/// the shrink floor.
+/// Default: up
+pub static WALRUS_SHRINK_ROUNDING: GucSetting<ShrinkRounding> =
+    GucSetting::<ShrinkRounding>::new(ShrinkRounding::Up);
+
+/// Multiply the quiet intervals required for the next shrink by
+/// SHRINK_BACKOFF_FACTOR after each shrink, back to walrus.shrink_intervals
+/// after a grow. Avoids grow-shrink ping-pong near the edge.
+/// Default: false
+pub static WALRUS_SHRINK_BACKOFF: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// How the shrink target is chosen (walrus.shrink_target).
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShrinkTarget {
+    /// Multiply the current size by walrus.shrink_factor
+    #[name = c"factor"]
+    Factor,
+    /// Shrink toward walrus.peak_ratio times the recent peak size in history
+    #[name = c"peak_ratio"]
+    PeakRatio,
+}
+
+impl ShrinkTarget {
+    /// Setting value as shown by SHOW walrus.shrink_target.
+    pub fn name(self) -> &'static str {
+        match self {
+            ShrinkTarget::Factor => "factor",
+            ShrinkTarget::PeakRatio => "peak_ratio",
+        }
+    }
+}
+
+/// Shrink by a fixed factor, or toward a multiple of demonstrated need.
+/// 'peak_ratio' falls back to walrus.shrink_factor while history has no grow
+/// within walrus.history_retention_days.
+/// Default: factor
+pub static WALRUS_SHRINK_TARGET: GucSetting<ShrinkTarget> =
+    GucSetting::<ShrinkTarget>::new(ShrinkTarget::Factor);
+
+/// Multiple of the recent peak size that walrus.shrink_target = 'peak_ratio'
+/// shrinks toward.
+/// Default: 1.5, Min: 1.0, Max: 10.0
+pub static WALRUS_PEAK_RATIO: GucSetting<f64> = GucSetting::<f64>::new(1.5);
+
+/// Intervals after an applied grow during which no shrink is made, however
+/// many quiet intervals have accumulated. A hard minimum, not an addition to
+/// walrus.shrink_intervals.
+/// Default: 0 (off), Min: 0, Max: 1000
+pub static WALRUS_GROW_PROTECT_INTERVALS: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Consecutive quiet intervals after which the worker shrinks straight to the
+/// shrink floor instead of by walrus.shrink_factor. Counted across shrinks, so
+/// it is meant to be much larger than walrus.shrink_intervals.
+/// Default: 0 (off), Min: 0, Max: 1000000
+pub static WALRUS_FAST_SHRINK_AFTER_INTERVALS: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+/// Register the shrink GUC parameters. Called from `register_gucs()`.
+pub(super) fn register_shrink_gucs() {
+    GucRegistry::define_bool_guc(
+        c"walrus.shrink_enable",
+        c"Enable automatic shrinking of max_wal_size parameter.",
+        c"When enabled, pg_walrus shrinks max_wal_size after sustained low activity.",
+        &WALRUS_SHRINK_ENABLE,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"walrus.shrink_factor",
+        c"Multiplication factor when shrinking max_wal_size.",
+        c"Lower values shrink more aggressively. Must be between 0.01 and 0.99.",
+        &WALRUS_SHRINK_FACTOR,
+        0.01,
+        0.99,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.shrink_intervals",
+        c"Quiet checkpoint intervals before triggering shrink.",
+        c"A quiet interval is one where forced checkpoints are below threshold.",
+        &WALRUS_SHRINK_INTERVALS,
+        1,
+        1000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.min_size",
+        c"Minimum floor for max_wal_size in MB.",
+        c"pg_walrus will never shrink max_wal_size below this value.",
+        &WALRUS_MIN_SIZE,
+        2,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_MB,
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.enforce_min_on_start",
+        c"Grow max_wal_size to walrus.min_size at worker startup.",
+        c"When max_wal_size is below walrus.min_size as the worker starts, grow it to walrus.min_size once instead of waiting for checkpoint pressure.",
+        &WALRUS_ENFORCE_MIN_ON_START,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.shrink_max_wal_mb_per_interval",
+        c"Maximum WAL generated per interval for it to count as quiet.",
+        c"Intervals generating more WAL reset quiet intervals. Set to 0 to disable.",
+        &WALRUS_SHRINK_MAX_WAL_MB_PER_INTERVAL,
+        0,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_MB,
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.force_min_shrink",
+        c"Shrink by one WAL segment when shrink_factor rounding cannot make progress.",
+        c"When ceil(max_wal_size * walrus.shrink_factor) equals the current size, shrink by one WAL segment instead (never below the floor).",
+        &WALRUS_FORCE_MIN_SHRINK,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        c"walrus.shrink_rounding",
+        c"Rounding of the shrink target: up, down, or nearest.",
+        c"up (ceil) never under-sizes; down (floor) reclaims more space. The target is still clamped to the shrink floor.",
+        &WALRUS_SHRINK_ROUNDING,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"walrus.shrink_backoff",
+        c"Require more quiet intervals after each shrink.",
+        c"Each shrink doubles the quiet intervals required for the next one; a grow resets the requirement to walrus.shrink_intervals.",
+        &WALRUS_SHRINK_BACKOFF,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        c"walrus.shrink_target",
+        c"How the shrink target is chosen: factor or peak_ratio.",
+        c"factor multiplies max_wal_size by walrus.shrink_factor; peak_ratio shrinks toward walrus.peak_ratio times the largest grow in history.",
+        &WALRUS_SHRINK_TARGET,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"walrus.peak_ratio",
+        c"Multiple of the recent peak size that peak_ratio shrinks toward.",
+        c"Used when walrus.shrink_target = 'peak_ratio'. The target is never below the shrink floor or above max_wal_size.",
+        &WALRUS_PEAK_RATIO,
+        1.0,
+        10.0,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.grow_protect_intervals",
+        c"Intervals after a grow during which no shrink is made.",
+        c"Shrinks due within this many intervals of an applied grow are skipped with blocked_by = grow_protect. Set to 0 to disable.",
+        &WALRUS_GROW_PROTECT_INTERVALS,
+        0,
+        1000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"walrus.fast_shrink_after_intervals",
+        c"Quiet intervals after which max_wal_size shrinks straight to the floor.",
+        c"Counted across shrinks and reset by activity, for databases that sit idle for days. Set to 0 to disable.",
+        &WALRUS_FAST_SHRINK_AFTER_INTERVALS,
+        0,
+        1000000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+}
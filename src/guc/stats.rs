@@ -0,0 +1,46 @@
+//! Checkpoint statistics GUC parameters for pg_walrus.
+//!
+//! Split out of `guc.rs` to keep it under the file size limit. The statics
+//! are re-exported from `crate::guc`, and `register_stats_gucs()` is called
+//! from `register_gucs()`.
+
+use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting, PostgresGucEnum};
+
+/// Where the forced checkpoint count is read from (walrus.stats_source).
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StatsSource {
+    /// The pgstat C API (pgstat_fetch_stat_checkpointer, pgstat_fetch_global)
+    #[name = c"direct"]
+    Direct,
+    /// pg_stat_checkpointer (PG 17+) or pg_stat_bgwriter, queried through SPI
+    #[name = c"sql"]
+    Sql,
+}
+
+impl StatsSource {
+    /// Setting value as shown by SHOW walrus.stats_source.
+    pub fn name(self) -> &'static str {
+        match self {
+            StatsSource::Direct => "direct",
+            StatsSource::Sql => "sql",
+        }
+    }
+}
+
+/// Source of the forced checkpoint count. 'sql' costs a query per read but
+/// does not depend on the pgstat C API returning a snapshot.
+/// Default: direct
+pub static WALRUS_STATS_SOURCE: GucSetting<StatsSource> =
+    GucSetting::<StatsSource>::new(StatsSource::Direct);
+
+/// Register the checkpoint statistics GUC parameters. Called from `register_gucs()`.
+pub(super) fn register_stats_gucs() {
+    GucRegistry::define_enum_guc(
+        c"walrus.stats_source",
+        c"Where the forced checkpoint count is read from: direct or sql.",
+        c"direct uses the pgstat C API; sql queries pg_stat_checkpointer (pg_stat_bgwriter before PostgreSQL 17), for platforms where the C API returns no statistics intermittently.",
+        &WALRUS_STATS_SOURCE,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+}
@@ -3,18 +3,288 @@
 //! This module provides functions for recording sizing decisions to the walrus.history
 //! table and managing automatic cleanup based on the retention period.
 //!
+//! The table's schema is `walrus.schema_name` (default `walrus`). All SQL that
+//! touches the table builds its name with `history_table()`.
+//!
 //! The history table stores:
 //! - Timestamp of each sizing decision
-//! - Action type: 'increase', 'decrease', or 'capped'
+//! - Action type: 'increase', 'decrease', 'capped', 'dry_run', 'skipped', 'note',
+//!   'external_change', or 'manual'
 //! - Old and new max_wal_size values
 //! - Checkpoint statistics at decision time
 //! - Optional reason and metadata (JSONB)
-
-use crate::guc::WALRUS_HISTORY_RETENTION_DAYS;
+//! - Name of the database whose connection recorded the row
+//!
+//! With `walrus.archive_before_delete` on, cleanup first adds the rows it
+//! deletes to a per-day rollup in the `history_archive` table of the same
+//! schema, so long-term trends survive retention.
+//!
+//! Metadata objects produced by pg_walrus carry a `schema_version` key (see
+//! `METADATA_SCHEMA_VERSION`) so downstream parsers can detect shape changes.
+
+use crate::algorithm::grow_multiplier;
+use crate::guc::{
+    WALRUS_ARCHIVE_BEFORE_DELETE, WALRUS_HISTORY_MAX_ROWS, WALRUS_HISTORY_RETENTION_DAYS,
+    WALRUS_READ_ONLY, WALRUS_SCHEMA_NAME,
+};
+use crate::mirror;
+use crate::shmem;
 use pgrx::JsonB;
 use pgrx::datum::DatumWithOid;
 use pgrx::prelude::*;
-use serde_json::Value as JsonValue;
+use serde_json::{Value as JsonValue, json};
+
+/// Actions allowed by the history table's CHECK constraint.
+pub const HISTORY_ACTIONS: &[&str] = &[
+    "increase",
+    "decrease",
+    "capped",
+    "dry_run",
+    "skipped",
+    "note",
+    "external_change",
+    "manual",
+];
+
+/// Informational actions, trimmed before real decisions by the row cap.
+pub const INFORMATIONAL_ACTIONS: &[&str] = &["skipped", "dry_run", "note"];
+
+/// Actions that count as a sizing decision for `last_action` in shared memory.
+///
+/// 'note' and 'external_change' rows record observations, not decisions, and
+/// 'manual' rows an operator's walrus.set_size().
+/// The shared-memory code is the 1-based position in this list (0 = none).
+pub const DECISION_ACTIONS: &[&str] = &["increase", "decrease", "capped", "skipped", "dry_run"];
+
+/// Shared-memory code for a decision action, or 0 if `action` is not a decision.
+#[inline]
+pub fn decision_action_code(action: &str) -> i32 {
+    DECISION_ACTIONS
+        .iter()
+        .position(|&a| a == action)
+        .map_or(0, |i| i as i32 + 1)
+}
+
+/// Decision action for a shared-memory code (None for 0 or an unknown code).
+#[inline]
+pub fn decision_action_name(code: i32) -> Option<&'static str> {
+    usize::try_from(code)
+        .ok()
+        .and_then(|c| c.checked_sub(1))
+        .and_then(|i| DECISION_ACTIONS.get(i).copied())
+}
+
+/// Version of the metadata JSON shape written to walrus.history.
+///
+/// Bump whenever fields are added, removed, or change meaning, and document
+/// the new shape in README.md ("Metadata Schema").
+///
+/// Version 1 fields by action:
+/// - `increase`: delta, multiplier, calculated_size_mb
+/// - `capped`: delta, multiplier, calculated_size_mb, walrus_max_mb
+/// - `decrease`: shrink_factor, quiet_intervals, calculated_size_mb
+/// - `dry_run`: dry_run, would_apply, plus the fields of the would-be action
+/// - `skipped`: blocked_by plus the rate limit fields that blocked the change
+/// - manual (`walrus.analyze`): source, confidence
+///
+/// Version 2 adds emergency shrinks: `decrease` (or `dry_run`) rows with
+/// emergency, free_disk_mb, emergency_free_disk_mb, calculated_size_mb.
+///
+/// Version 3 adds completion_target and previous_completion_target to
+/// `increase`/`capped` rows when checkpoint_completion_target was co-tuned.
+///
+/// Version 4 adds cap-downs after walrus.max is lowered: `capped` (or
+/// `dry_run`) rows with only calculated_size_mb and walrus_max_mb.
+///
+/// Version 5 adds grow_exponent to `increase`/`capped` rows when
+/// walrus.grow_exponent is not 1.0; multiplier is then fractional.
+///
+/// Version 6 adds per-cycle `note` rows (walrus.verbose_history) with delta,
+/// threshold, quiet_intervals, classification, wal_bytes.
+///
+/// Version 7 adds `external_change` rows with recorded_size_mb, actual_size_mb.
+///
+/// Version 8 adds wal_buffers_warning to `increase`/`capped` rows when
+/// wal_buffers looked undersized for the new size.
+///
+/// Version 9 adds apply_duration_ms to `increase`/`capped`/`decrease` rows
+/// applied by the worker's grow and shrink paths.
+///
+/// Version 10 adds recent_peak_mb and peak_ratio to `decrease` (or `dry_run`)
+/// rows whose target came from walrus.shrink_target = 'peak_ratio'.
+///
+/// Version 11 adds idempotency_key to `increase`/`capped`/`decrease` rows
+/// applied by the worker (see `decision_key()`).
+///
+/// Version 12 adds lookback_intervals and window_delta to `increase`/`capped`
+/// (or `dry_run`) rows decided on a walrus.lookback_intervals window.
+///
+/// Version 13 adds `external_change` rows with intended_size_mb, actual_size_mb
+/// when a max_wal_size written by ALTER SYSTEM did not take effect.
+///
+/// Version 14 adds `decision_inputs` to the `increase`, `capped`, and
+/// `decrease` rows the worker's grow and shrink rules apply, the snapshot
+/// `walrus.reproduce()` replays (not to analyze, ceiling, emergency, or
+/// min-floor rows).
+///
+/// Version 15 adds effective_ceiling_mb to `increase`/`capped` (or `dry_run`)
+/// rows: the ceiling in effect, after walrus.max_percent_of_device and any
+/// walrus.temporary_max() override.
+///
+/// Version 16 adds `note` rows recording an unexpected worker restart, with
+/// previous_worker_start_time.
+///
+/// Version 17 adds `increase` (or `dry_run`) rows growing to walrus.min_size at
+/// startup (walrus.enforce_min_on_start) with calculated_size_mb, min_size_mb,
+/// enforced_on_start.
+///
+/// Version 18 adds fast_shrink, idle_intervals to `decrease` (or `dry_run`)
+/// rows shrinking straight to the floor (walrus.fast_shrink_after_intervals),
+/// whose `decision_inputs` use the `fast_shrink` rule.
+///
+/// Version 19 documents `manual` rows written by walrus.set_size(), whose
+/// metadata has only source ('walrus.set_size'). Manual rows from
+/// walrus.analyze(apply := true) keep source ('walrus.analyze') and confidence.
+///
+/// Version 20 documents `skipped` rows held by walrus.max_growth_mb_per_day:
+/// blocked_by ('daily_growth_cap'), max_growth_mb_per_day, growth_mb_this_day,
+/// requested_growth_mb, day_window_start.
+///
+/// Version 21 documents `skipped` rows held by a confidence gate: blocked_by
+/// ('low_confidence') and confidence, plus min_apply_confidence for a grow
+/// (walrus.min_apply_confidence) or min_shrink_confidence for a shrink
+/// (walrus.min_shrink_confidence).
+///
+/// Version 22 documents `skipped` rows held by walrus.max_shrinks_per_hour:
+/// blocked_by ('shrink_hourly_limit'), max_shrinks_per_hour, shrinks_this_hour,
+/// and shrink_hour_window_start (absent when max_shrinks_per_hour is 0).
+///
+/// Version 23 documents `skipped` rows held by walrus.grow_protect_intervals:
+/// blocked_by ('grow_protect'), intervals_since_grow, grow_protect_intervals.
+pub const METADATA_SCHEMA_VERSION: i64 = 23;
+
+/// Stamp a metadata object with the current `schema_version`.
+///
+/// All history metadata is passed through this helper before insertion so
+/// the version is set in one place. Non-object values are returned unchanged.
+pub fn versioned_metadata(mut metadata: JsonValue) -> JsonValue {
+    if let JsonValue::Object(map) = &mut metadata {
+        map.insert(
+            "schema_version".to_string(),
+            JsonValue::from(METADATA_SCHEMA_VERSION),
+        );
+    }
+    metadata
+}
+
+/// Build metadata for a grow ('increase' or 'capped') decision.
+///
+/// `walrus_max_mb` is Some for capped grows. With the default grow_exponent
+/// of 1.0, multiplier is the integer `delta + 1`; otherwise it is the
+/// effective fractional multiplier and grow_exponent is included.
+pub fn grow_metadata(
+    delta: i64,
+    calculated_size: i32,
+    walrus_max_mb: Option<i32>,
+    grow_exponent: f64,
+) -> JsonValue {
+    let mut metadata = json!({
+        "delta": delta,
+        "multiplier": delta + 1,
+        "calculated_size_mb": calculated_size,
+    });
+    if grow_exponent != 1.0 {
+        metadata["multiplier"] = json!(grow_multiplier(delta, grow_exponent));
+        metadata["grow_exponent"] = json!(grow_exponent);
+    }
+    if let Some(max_mb) = walrus_max_mb {
+        metadata["walrus_max_mb"] = json!(max_mb);
+    }
+    metadata
+}
+
+/// Record the ceiling a grow or cap-down decision was made against.
+pub fn with_effective_ceiling(mut metadata: JsonValue, ceiling_mb: i32) -> JsonValue {
+    if let JsonValue::Object(map) = &mut metadata {
+        map.insert(
+            "effective_ceiling_mb".to_string(),
+            JsonValue::from(ceiling_mb),
+        );
+    }
+    metadata
+}
+
+/// Record how long ALTER SYSTEM took for an applied change.
+pub fn with_apply_duration(mut metadata: JsonValue, apply_duration_ms: i64) -> JsonValue {
+    if let JsonValue::Object(map) = &mut metadata {
+        map.insert(
+            "apply_duration_ms".to_string(),
+            JsonValue::from(apply_duration_ms),
+        );
+    }
+    metadata
+}
+
+/// Record the peak a walrus.shrink_target = 'peak_ratio' shrink was sized from.
+///
+/// Unchanged when `peak_mb` is None (the target came from walrus.shrink_factor).
+pub fn with_peak_ratio(
+    mut metadata: JsonValue,
+    peak_mb: Option<i32>,
+    peak_ratio: f64,
+) -> JsonValue {
+    if let (JsonValue::Object(map), Some(peak_mb)) = (&mut metadata, peak_mb) {
+        map.insert("recent_peak_mb".to_string(), JsonValue::from(peak_mb));
+        map.insert("peak_ratio".to_string(), JsonValue::from(peak_ratio));
+    }
+    metadata
+}
+
+/// Idempotency key for a change the worker applied in one cycle.
+///
+/// Deterministic in the cycle's Unix time, the action, the forced checkpoint
+/// count, and the new size, so inserting the same decision twice (e.g. a retry
+/// after the history transaction failed) leaves one row.
+pub fn decision_key(
+    cycle_time: i64,
+    action: &str,
+    forced_checkpoints: i64,
+    new_size_mb: i32,
+) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        cycle_time, action, forced_checkpoints, new_size_mb
+    )
+}
+
+/// Record the idempotency key of an applied change in its metadata.
+///
+/// The history table has a unique index on `metadata->>'idempotency_key'`, and
+/// `insert_history_record()` skips a row whose key is already present.
+pub fn with_idempotency_key(mut metadata: JsonValue, key: &str) -> JsonValue {
+    if let JsonValue::Object(map) = &mut metadata {
+        map.insert("idempotency_key".to_string(), JsonValue::from(key));
+    }
+    metadata
+}
+
+/// Record a checkpoint_completion_target co-change in grow metadata.
+///
+/// `completion_target` is `(previous, new)` when the worker also set
+/// checkpoint_completion_target; None leaves the metadata unchanged.
+pub fn with_completion_target(
+    mut metadata: JsonValue,
+    completion_target: Option<(f64, f64)>,
+) -> JsonValue {
+    if let (Some((previous, target)), JsonValue::Object(map)) = (completion_target, &mut metadata) {
+        map.insert("completion_target".to_string(), JsonValue::from(target));
+        map.insert(
+            "previous_completion_target".to_string(),
+            JsonValue::from(previous),
+        );
+    }
+    metadata
+}
 
 /// Insert a history record into walrus.history table.
 ///
@@ -34,12 +304,19 @@ use serde_json::Value as JsonValue;
 ///
 /// # Returns
 ///
-/// `Ok(())` on success, `Err(spi::Error)` on failure
+/// `Ok(())` on success, `Err(spi::Error)` on failure. A row whose metadata
+/// carries an `idempotency_key` already in the table is silently not inserted.
 ///
 /// # Error Handling
 ///
 /// The caller (worker) wraps this in BackgroundWorker::transaction() and logs
 /// warnings on failure without aborting the monitoring cycle.
+///
+/// Every decision branch records its row here, so decision actions
+/// (`DECISION_ACTIONS`) also set `last_action` and `last_decision_time` in
+/// shared memory, even when the history table is missing or walrus.read_only
+/// is on (in which case nothing is written). A decision row that was written
+/// is also copied to walrus.mirror_table (see `mirror::mirror_decision()`).
 pub fn insert_history_record(
     action: &str,
     old_size_mb: i32,
@@ -49,18 +326,26 @@ pub fn insert_history_record(
     reason: Option<&str>,
     metadata: Option<JsonValue>,
 ) -> Result<(), spi::Error> {
+    let code = decision_action_code(action);
+    if code > 0 && shmem::is_initialized() {
+        shmem::update_state(|state| {
+            state.last_action = code;
+            state.last_decision_time = shmem::now_unix();
+        });
+    }
+
+    if WALRUS_READ_ONLY.get() {
+        pgrx::debug1!(
+            "pg_walrus [read-only]: not recording '{}' history row",
+            action
+        );
+        return Ok(());
+    }
+
     // Check if history table exists before attempting insert
     // This handles the edge case where the table was dropped
-    let table_exists = Spi::get_one::<bool>(
-        "SELECT EXISTS (
-            SELECT 1 FROM pg_catalog.pg_class c
-            JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
-            WHERE n.nspname = 'walrus' AND c.relname = 'history'
-        )",
-    )?;
-
-    if table_exists != Some(true) {
-        pgrx::warning!("pg_walrus: history table does not exist, skipping history insert");
+    if !history_table_exists()? {
+        log_table_missing("pg_walrus: history table does not exist, skipping history insert");
         return Ok(());
     }
 
@@ -78,687 +363,435 @@ pub fn insert_history_record(
         jsonb_metadata.into(),
     ];
 
-    Spi::run_with_args(
-        "INSERT INTO walrus.history
-         (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec, reason, metadata)
-         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    let inserted = Spi::get_one_with_args::<i64>(
+        &format!(
+            "WITH inserted AS (
+                INSERT INTO {}
+                (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec, reason, metadata,
+                 database_name)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, current_database())
+                ON CONFLICT DO NOTHING
+                RETURNING 1
+             )
+             SELECT count(*) FROM inserted",
+            history_table()
+        ),
         &args,
-    )
-}
-
-/// Delete history records older than the configured retention period.
-///
-/// This function is called by the background worker at the end of each monitoring
-/// cycle and by the SQL-callable walrus.cleanup_history() function.
-///
-/// # Returns
-///
-/// The number of deleted records, or `Err(spi::Error)` on failure
-///
-/// # Retention Policy
-///
-/// - Uses `walrus.history_retention_days` GUC value
-/// - If retention_days = 0, all records are deleted
-/// - Records with timestamp < now() - interval 'N days' are deleted
-/// - The timestamp index ensures efficient DELETE performance
-pub fn cleanup_old_history() -> Result<i64, spi::Error> {
-    let retention_days = WALRUS_HISTORY_RETENTION_DAYS.get();
-
-    // Check if history table exists before attempting cleanup
-    let table_exists = Spi::get_one::<bool>(
-        "SELECT EXISTS (
-            SELECT 1 FROM pg_catalog.pg_class c
-            JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
-            WHERE n.nspname = 'walrus' AND c.relname = 'history'
-        )",
     )?;
 
-    if table_exists != Some(true) {
-        pgrx::warning!("pg_walrus: history table does not exist, skipping cleanup");
-        return Ok(0);
+    // A decision that was not a duplicate also goes to walrus.mirror_table
+    if code > 0 && inserted.unwrap_or(0) > 0 {
+        mirror::mirror_decision(
+            action,
+            old_size_mb,
+            new_size_mb,
+            forced_checkpoints,
+            checkpoint_timeout_sec,
+            reason,
+        );
     }
+    Ok(())
+}
 
-    // Use parameterized query with interval arithmetic
-    // $1 * interval '1 day' computes the retention window
-    let args: &[DatumWithOid<'_>] = &[retention_days.into()];
-    let deleted = Spi::get_one_with_args::<i64>(
-        "WITH deleted AS (
-            DELETE FROM walrus.history
-            WHERE timestamp < now() - $1 * interval '1 day'
-            RETURNING 1
-        )
-        SELECT count(*) FROM deleted",
-        args,
-    )?;
-
-    Ok(deleted.unwrap_or(0))
+/// Schema holding the history table (walrus.schema_name, default "walrus").
+pub fn history_schema() -> String {
+    WALRUS_SCHEMA_NAME
+        .get()
+        .and_then(|s| s.to_str().ok().map(|s| s.to_owned()))
+        .unwrap_or_else(|| "walrus".to_owned())
 }
 
-// PostgreSQL integration tests for history module
-#[cfg(any(test, feature = "pg_test"))]
-#[pgrx::pg_schema]
-mod tests {
-    use super::*;
-    use serde_json::json;
-
-    // =========================================================================
-    // History Table Schema Tests (T011-T015)
-    // =========================================================================
-
-    /// Test that walrus.history table exists after CREATE EXTENSION (T011)
-    #[pg_test]
-    fn test_history_table_exists() {
-        let exists = Spi::get_one::<bool>(
-            "SELECT EXISTS (
-                SELECT 1 FROM pg_catalog.pg_class c
-                JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
-                WHERE n.nspname = 'walrus' AND c.relname = 'history'
-            )",
-        )
-        .expect("query failed");
-        assert_eq!(
-            exists,
-            Some(true),
-            "walrus.history table should exist after CREATE EXTENSION"
-        );
-    }
+/// Quoted, schema-qualified name of the history table for building SQL.
+///
+/// Quoted with quote_identifier rules, so any walrus.schema_name is safe to
+/// interpolate into a statement.
+pub fn history_table() -> String {
+    spi::quote_qualified_identifier(history_schema().as_str(), "history")
+}
 
-    /// Test that walrus.history table has 9 columns with correct types (T012)
-    #[pg_test]
-    fn test_history_table_columns() {
-        let col_count = Spi::get_one::<i64>(
-            "SELECT count(*) FROM information_schema.columns
-             WHERE table_schema = 'walrus' AND table_name = 'history'",
-        )
-        .expect("query failed");
-        assert_eq!(col_count, Some(9), "walrus.history should have 9 columns");
-    }
+/// Quoted, schema-qualified name of the daily archive table for building SQL.
+pub fn history_archive_table() -> String {
+    spi::quote_qualified_identifier(history_schema().as_str(), "history_archive")
+}
 
-    /// Test that walrus_history_timestamp_idx index exists (T013)
-    #[pg_test]
-    fn test_history_timestamp_index_exists() {
-        let exists = Spi::get_one::<bool>(
-            "SELECT EXISTS (
-                SELECT 1 FROM pg_indexes
-                WHERE schemaname = 'walrus'
-                  AND tablename = 'history'
-                  AND indexname = 'walrus_history_timestamp_idx'
-            )",
-        )
-        .expect("query failed");
-        assert_eq!(
-            exists,
-            Some(true),
-            "walrus_history_timestamp_idx index should exist"
-        );
-    }
+/// Columns a cleanup DELETE returns so the deleted rows can be archived.
+const DELETED_COLUMNS: &str = "timestamp, action, old_size_mb, new_size_mb";
 
-    /// Test that walrus.history_retention_days GUC has correct default (T014)
-    #[pg_test]
-    fn test_guc_history_retention_days_default() {
-        let result =
-            Spi::get_one::<&str>("SHOW walrus.history_retention_days").expect("SHOW failed");
-        assert_eq!(
-            result,
-            Some("7"),
-            "walrus.history_retention_days should default to '7'"
-        );
+/// CTE that adds the rows of a preceding `deleted` CTE to the daily archive.
+///
+/// Empty unless `walrus.archive_before_delete` is on. Rows are grouped by UTC
+/// day and merged into existing days, so a day split across cleanup runs
+/// accumulates. The peak and minimum cover the size each row started from
+/// (`old_size_mb`) and, for applied changes, the size it ended at. If the
+/// archive table is missing, warns and returns an empty CTE so retention
+/// still bounds the history table.
+fn archive_cte() -> Result<String, spi::Error> {
+    if !WALRUS_ARCHIVE_BEFORE_DELETE.get() {
+        return Ok(String::new());
     }
 
-    /// Test that walrus.history_retention_days GUC has correct range 0-3650 (T015)
-    #[pg_test]
-    fn test_guc_history_retention_days_range() {
-        let min_val = Spi::get_one::<&str>(
-            "SELECT min_val FROM pg_settings WHERE name = 'walrus.history_retention_days'",
-        )
-        .expect("query failed");
-        assert_eq!(min_val, Some("0"), "history_retention_days min should be 0");
-
-        let max_val = Spi::get_one::<&str>(
-            "SELECT max_val FROM pg_settings WHERE name = 'walrus.history_retention_days'",
-        )
-        .expect("query failed");
-        assert_eq!(
-            max_val,
-            Some("3650"),
-            "history_retention_days max should be 3650"
+    let args: &[DatumWithOid<'_>] = &[history_archive_table().into()];
+    if Spi::get_one_with_args::<bool>("SELECT to_regclass($1) IS NOT NULL", args)? != Some(true) {
+        pgrx::warning!(
+            "pg_walrus: {} does not exist, deleting history without archiving",
+            history_archive_table()
         );
+        return Ok(String::new());
     }
 
-    // =========================================================================
-    // insert_history_record Tests (T030-T034)
-    // =========================================================================
-
-    /// Test insert_history_record with action='increase' (T030)
-    #[pg_test]
-    fn test_insert_history_record_increase() {
-        let result = insert_history_record(
-            "increase",
-            1024,
-            2048,
-            5,
-            300,
-            Some("Forced checkpoints exceeded threshold"),
-            Some(json!({"delta": 5, "multiplier": 6, "calculated_size_mb": 6144})),
-        );
-        assert!(result.is_ok(), "Insert should succeed");
-
-        // Verify the record was inserted
-        let count =
-            Spi::get_one::<i64>("SELECT count(*) FROM walrus.history WHERE action = 'increase'")
-                .expect("query failed");
-        assert!(
-            count.unwrap_or(0) >= 1,
-            "Should have at least one increase record"
-        );
+    Ok(format!(
+        ", archived AS (
+            INSERT INTO {archive} AS a
+                (day, increases, decreases, capped, peak_size_mb, min_size_mb)
+            SELECT (timestamp AT TIME ZONE 'UTC')::date,
+                   count(*) FILTER (WHERE action = 'increase'),
+                   count(*) FILTER (WHERE action = 'decrease'),
+                   count(*) FILTER (WHERE action = 'capped'),
+                   max(greatest(old_size_mb, {applied})),
+                   min(least(old_size_mb, {applied}))
+            FROM deleted
+            GROUP BY 1
+            ON CONFLICT (day) DO UPDATE SET
+                increases = a.increases + EXCLUDED.increases,
+                decreases = a.decreases + EXCLUDED.decreases,
+                capped = a.capped + EXCLUDED.capped,
+                peak_size_mb = greatest(a.peak_size_mb, EXCLUDED.peak_size_mb),
+                min_size_mb = least(a.min_size_mb, EXCLUDED.min_size_mb)
+        )",
+        archive = history_archive_table(),
+        applied = "CASE WHEN action IN ('increase', 'decrease', 'capped', 'external_change', \
+                   'manual') THEN new_size_mb END",
+    ))
+}
 
-        // Verify values
-        let record = Spi::get_one::<i32>(
-            "SELECT new_size_mb FROM walrus.history WHERE action = 'increase' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(record, Some(2048), "new_size_mb should be 2048");
+/// Largest size a grow reached within walrus.history_retention_days.
+///
+/// Taken from the new_size_mb of 'increase' and 'capped' rows, the sizes the
+/// workload has demonstrably needed. Returns None when there is no such row or
+/// no history table. Must be called within a transaction.
+pub fn recent_peak_size_mb() -> Result<Option<i32>, spi::Error> {
+    if !history_table_exists()? {
+        return Ok(None);
     }
+    let args: &[DatumWithOid<'_>] = &[WALRUS_HISTORY_RETENTION_DAYS.get().into()];
+    Spi::get_one_with_args::<i32>(
+        &format!(
+            "SELECT max(new_size_mb) FROM {}
+             WHERE action IN ('increase', 'capped')
+               AND timestamp >= now() - $1 * interval '1 day'",
+            history_table()
+        ),
+        args,
+    )
+}
 
-    /// Test insert_history_record with action='decrease' (T031)
-    #[pg_test]
-    fn test_insert_history_record_decrease() {
-        let result = insert_history_record(
-            "decrease",
-            4096,
-            3072,
-            0,
-            300,
-            Some("Sustained low activity"),
-            Some(json!({"shrink_factor": 0.75, "quiet_intervals": 5, "calculated_size_mb": 3072})),
-        );
-        assert!(result.is_ok(), "Insert should succeed");
-
-        // Verify the record was inserted
-        let action = Spi::get_one::<&str>(
-            "SELECT action FROM walrus.history WHERE old_size_mb = 4096 AND new_size_mb = 3072 ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(action, Some("decrease"), "Action should be 'decrease'");
-    }
+/// Check if the history table exists in the walrus.schema_name schema.
+pub fn history_table_exists() -> Result<bool, spi::Error> {
+    let args: &[DatumWithOid<'_>] = &[history_schema().into()];
+    let table_exists = Spi::get_one_with_args::<bool>(
+        "SELECT EXISTS (
+            SELECT 1 FROM pg_catalog.pg_class c
+            JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = $1 AND c.relname = 'history'
+        )",
+        args,
+    )?;
+    Ok(table_exists == Some(true))
+}
 
-    /// Test insert_history_record with action='capped' (T032)
-    #[pg_test]
-    fn test_insert_history_record_capped() {
-        let result = insert_history_record(
-            "capped",
-            2048,
-            4096,
-            10,
-            300,
-            Some("Calculated size exceeded walrus.max"),
-            Some(
-                json!({"delta": 10, "multiplier": 11, "calculated_size_mb": 22528, "walrus_max_mb": 4096}),
-            ),
+/// Check that the history table exists in the worker's database.
+///
+/// walrus.database can name a database where CREATE EXTENSION pg_walrus was
+/// never run. The result is stored as `schema_missing` in shared memory, and a
+/// WARNING explaining the fix is logged only when the table goes missing, not
+/// on every check. Returns true if the WARNING was logged.
+pub fn check_history_schema() -> Result<bool, spi::Error> {
+    let missing = !history_table_exists()?;
+    let mut newly_missing = false;
+    shmem::update_state(|state| {
+        newly_missing = missing && !state.schema_missing;
+        state.schema_missing = missing;
+    });
+
+    if newly_missing {
+        let database =
+            Spi::get_one::<String>("SELECT current_database()::text")?.unwrap_or_default();
+        pgrx::warning!(
+            "pg_walrus: history table {} not found in database \"{}\"; run CREATE EXTENSION pg_walrus in that database (walrus.database) to record history",
+            history_table(),
+            database
         );
-        assert!(result.is_ok(), "Insert should succeed");
-
-        // Verify the record was inserted with correct action
-        let action = Spi::get_one::<&str>(
-            "SELECT action FROM walrus.history WHERE old_size_mb = 2048 AND new_size_mb = 4096 ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(action, Some("capped"), "Action should be 'capped'");
     }
+    Ok(newly_missing)
+}
 
-    /// Test insert_history_record with metadata JSONB stored correctly (T033)
-    #[pg_test]
-    fn test_insert_history_record_with_metadata() {
-        let metadata = json!({
-            "delta": 3,
-            "multiplier": 4,
-            "calculated_size_mb": 4096,
-            "custom_field": "test_value"
-        });
-
-        let result = insert_history_record(
-            "increase",
-            1024,
-            4096,
-            3,
-            300,
-            Some("Test with metadata"),
-            Some(metadata),
-        );
-        assert!(result.is_ok(), "Insert should succeed");
-
-        // Verify JSONB metadata was stored correctly
-        let stored_delta = Spi::get_one::<i64>(
-            "SELECT (metadata->>'delta')::bigint FROM walrus.history
-             WHERE reason = 'Test with metadata' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(stored_delta, Some(3), "Metadata delta should be 3");
-
-        let stored_custom = Spi::get_one::<&str>(
-            "SELECT metadata->>'custom_field' FROM walrus.history
-             WHERE reason = 'Test with metadata' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(
-            stored_custom,
-            Some("test_value"),
-            "Custom field should be preserved"
-        );
+/// Log that the history table is missing.
+///
+/// Once the worker has reported it (`schema_missing`), repeats are logged at
+/// DEBUG1 so every cycle doesn't produce a WARNING.
+fn log_table_missing(message: &str) {
+    if shmem::is_initialized() && shmem::read_state().schema_missing {
+        pgrx::debug1!("{}", message);
+    } else {
+        pgrx::warning!("{}", message);
     }
+}
 
-    /// Test insert_history_record with NULL metadata (T034)
-    #[pg_test]
-    fn test_insert_history_record_null_metadata() {
-        let result = insert_history_record(
-            "increase", 512, 1024, 2, 300, None, // NULL reason
-            None, // NULL metadata
-        );
-        assert!(result.is_ok(), "Insert with NULL metadata should succeed");
-
-        // Verify NULL values were stored
-        let is_null = Spi::get_one::<bool>(
-            "SELECT metadata IS NULL FROM walrus.history
-             WHERE old_size_mb = 512 AND new_size_mb = 1024 ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(is_null, Some(true), "Metadata should be NULL");
-    }
+/// Size recorded by the newest applied change, if it differs from the actual size.
+///
+/// Returns None when there is no recorded change or it matches `current_size_mb`.
+#[inline]
+pub fn startup_drift(last_recorded_mb: Option<i32>, current_size_mb: i32) -> Option<i32> {
+    last_recorded_mb.filter(|&recorded| recorded != current_size_mb)
+}
 
-    // =========================================================================
-    // cleanup_old_history Tests (T046-T049)
-    // =========================================================================
-
-    /// Test that cleanup deletes old records (T046)
-    #[pg_test]
-    fn test_cleanup_history_deletes_old_records() {
-        // Insert a record with an old timestamp (8 days ago, default retention is 7)
-        Spi::run(
-            "INSERT INTO walrus.history
-             (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
-             VALUES (now() - interval '8 days', 'increase', 1024, 2048, 5, 300)"
-        ).expect("insert failed");
-
-        // Run cleanup
-        let deleted = cleanup_old_history().expect("cleanup failed");
-
-        // Should have deleted at least the one old record
-        assert!(
-            deleted >= 1,
-            "Should delete old records, deleted: {}",
-            deleted
-        );
+/// Record an 'external_change' row if max_wal_size changed while the worker was down.
+///
+/// Called once by the worker at startup, before the first cycle. Compares the
+/// new_size_mb of the newest row that changed configuration ('increase',
+/// 'decrease', 'capped', 'external_change', or 'manual') with the actual max_wal_size and,
+/// if they differ, inserts a row bridging the gap so old_size_mb values in the
+/// audit chain stay continuous. Returns Ok(true) if a row was written. Must be
+/// called within a transaction.
+pub fn reconcile_startup_drift(
+    current_size: i32,
+    checkpoint_timeout_sec: i32,
+) -> Result<bool, spi::Error> {
+    if !history_table_exists()? {
+        return Ok(false);
     }
+    let last_recorded = Spi::get_one::<i32>(&format!(
+        "SELECT (
+            SELECT new_size_mb FROM {}
+            WHERE action IN ('increase', 'decrease', 'capped', 'external_change', 'manual')
+            ORDER BY id DESC LIMIT 1
+        )",
+        history_table()
+    ))?;
+    let Some(recorded) = startup_drift(last_recorded, current_size) else {
+        return Ok(false);
+    };
+
+    pgrx::log!(
+        "pg_walrus: max_wal_size changed from {} MB to {} MB while not running, recording external change",
+        recorded,
+        current_size
+    );
+    insert_history_record(
+        "external_change",
+        recorded,
+        current_size,
+        0,
+        checkpoint_timeout_sec,
+        Some("max_wal_size changed while pg_walrus was not running"),
+        Some(versioned_metadata(json!({
+            "recorded_size_mb": recorded,
+            "actual_size_mb": current_size,
+        }))),
+    )?;
+    Ok(true)
+}
 
-    /// Test that cleanup preserves recent records (T047)
-    #[pg_test]
-    fn test_cleanup_history_preserves_recent_records() {
-        // Insert a recent record (1 day ago, well within 7-day retention)
-        Spi::run(
-            "INSERT INTO walrus.history
-             (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
-             VALUES (now() - interval '1 day', 'increase', 2048, 4096, 3, 300)"
-        ).expect("insert failed");
-
-        // Count before cleanup
-        let count_before = Spi::get_one::<i64>(
-            "SELECT count(*) FROM walrus.history WHERE timestamp > now() - interval '2 days'",
-        )
-        .expect("query failed")
-        .unwrap_or(0);
-
-        // Run cleanup
-        cleanup_old_history().expect("cleanup failed");
-
-        // Count after cleanup
-        let count_after = Spi::get_one::<i64>(
-            "SELECT count(*) FROM walrus.history WHERE timestamp > now() - interval '2 days'",
-        )
-        .expect("query failed")
-        .unwrap_or(0);
-
-        assert_eq!(
-            count_before, count_after,
-            "Recent records should be preserved"
-        );
-    }
+/// Delete history records older than the configured retention period.
+///
+/// This function is called by the background worker at the end of a monitoring
+/// cycle (at most once per `walrus.cleanup_interval_sec`, see `cleanup_due()`)
+/// and by the SQL-callable walrus.cleanup_history() function, which always runs.
+///
+/// # Returns
+///
+/// The number of deleted records, or `Err(spi::Error)` on failure
+///
+/// # Retention Policy
+///
+/// - Uses `walrus.history_retention_days` GUC value
+/// - If retention_days = 0, all records are deleted
+/// - Records with timestamp < now() - interval 'N days' are deleted
+/// - The timestamp index ensures efficient DELETE performance
+/// - If `walrus.history_max_rows` > 0, the table is then trimmed to N rows,
+///   informational rows first (see `trim_history_to_max_rows()`)
+/// - If `walrus.archive_before_delete` is on, every deleted row is first added
+///   to the daily rollup in `history_archive` (see `archive_cte()`)
+pub fn cleanup_old_history() -> Result<i64, spi::Error> {
+    let retention_days = WALRUS_HISTORY_RETENTION_DAYS.get();
 
-    /// Test that cleanup returns correct count (T048)
-    #[pg_test]
-    fn test_cleanup_history_returns_count() {
-        // Clean up any existing old records first
-        Spi::run("DELETE FROM walrus.history WHERE timestamp < now() - interval '7 days'")
-            .expect("delete failed");
-
-        // Insert exactly 3 old records
-        for i in 0..3 {
-            Spi::run(&format!(
-                "INSERT INTO walrus.history
-                 (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
-                 VALUES (now() - interval '{} days', 'increase', {}, {}, 1, 300)",
-                10 + i,  // 10, 11, 12 days ago (all older than 7-day retention)
-                1000 + i,
-                2000 + i
-            ))
-            .expect("insert failed");
-        }
-
-        // Run cleanup
-        let deleted = cleanup_old_history().expect("cleanup failed");
-
-        // Should return exactly 3
-        assert_eq!(deleted, 3, "Should return count of deleted records");
+    // Check if history table exists before attempting cleanup
+    if !history_table_exists()? {
+        log_table_missing("pg_walrus: history table does not exist, skipping cleanup");
+        return Ok(0);
     }
 
-    /// Test cleanup with retention_days = 0 deletes all records (T049)
-    #[pg_test]
-    fn test_cleanup_history_retention_zero() {
-        // Insert a record with a past timestamp (1 second ago)
-        // This tests that retention_days=0 (timestamp < now() - 0 days) deletes old records
-        Spi::run(
-            "INSERT INTO walrus.history
-             (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
-             VALUES (now() - interval '1 second', 'increase', 1024, 2048, 1, 300)",
-        )
-        .expect("insert failed");
-
-        // Verify that the cleanup query with 0 retention would delete the record
-        // The query is: timestamp < now() - 0 days = timestamp < now()
-        // A record 1 second in the past should match this condition
-        let would_delete = Spi::get_one::<i64>(
-            "SELECT count(*) FROM walrus.history WHERE timestamp < now() - 0 * interval '1 day'",
-        )
-        .expect("query failed")
-        .unwrap_or(0);
-
-        assert!(
-            would_delete >= 1,
-            "Retention 0 should mark records older than now() for deletion"
-        );
-    }
+    // Use parameterized query with interval arithmetic
+    // $1 * interval '1 day' computes the retention window
+    let archive = archive_cte()?;
+    let args: &[DatumWithOid<'_>] = &[retention_days.into()];
+    let deleted = Spi::get_one_with_args::<i64>(
+        &format!(
+            "WITH deleted AS (
+                DELETE FROM {}
+                WHERE timestamp < now() - $1 * interval '1 day'
+                RETURNING {DELETED_COLUMNS}
+            ){archive}
+            SELECT count(*) FROM deleted",
+            history_table()
+        ),
+        args,
+    )?;
 
-    // =========================================================================
-    // Dry-Run History Tests (T021-T023, T026)
-    // =========================================================================
-
-    /// Test insert_history_record with action='dry_run' for grow decision (T021)
-    #[pg_test]
-    fn test_dry_run_history_grow() {
-        let metadata = json!({
-            "dry_run": true,
-            "would_apply": "increase",
-            "delta": 5,
-            "multiplier": 6,
-            "calculated_size_mb": 6144
-        });
+    let trimmed = trim_rows(WALRUS_HISTORY_MAX_ROWS.get(), &archive)?;
 
-        let result = insert_history_record(
-            "dry_run",
-            1024,
-            2048,
-            5,
-            300,
-            Some("threshold exceeded"),
-            Some(metadata),
-        );
-        assert!(result.is_ok(), "Dry-run history insert should succeed");
-
-        // Verify the record was inserted with correct action and would_apply
-        let action = Spi::get_one::<&str>(
-            "SELECT action FROM walrus.history WHERE reason = 'threshold exceeded' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(action, Some("dry_run"), "Action should be 'dry_run'");
-
-        let would_apply = Spi::get_one::<&str>(
-            "SELECT metadata->>'would_apply' FROM walrus.history WHERE reason = 'threshold exceeded' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(
-            would_apply,
-            Some("increase"),
-            "would_apply should be 'increase'"
-        );
+    Ok(deleted.unwrap_or(0) + trimmed)
+}
 
-        let dry_run_flag = Spi::get_one::<bool>(
-            "SELECT (metadata->>'dry_run')::boolean FROM walrus.history WHERE reason = 'threshold exceeded' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(dry_run_flag, Some(true), "dry_run flag should be true");
+/// Trim the history table to `max_rows` rows.
+///
+/// Bounds storage regardless of time-based retention. A `max_rows` of 0 means
+/// unlimited and deletes nothing. The oldest informational rows
+/// (`INFORMATIONAL_ACTIONS`) go first, so a chatty run of skipped or dry-run
+/// rows cannot push out real decisions. If the table is still over the cap,
+/// the remaining rows are trimmed to the newest N by id: the cutoff is the id
+/// of the Nth-newest row, found via the primary key index; with N or fewer
+/// rows it is NULL and nothing is deleted. Returns the number of deleted records.
+pub fn trim_history_to_max_rows(max_rows: i32) -> Result<i64, spi::Error> {
+    if max_rows <= 0 {
+        return Ok(0);
     }
+    trim_rows(max_rows, &archive_cte()?)
+}
 
-    /// Test insert_history_record with action='dry_run' for shrink decision (T022)
-    #[pg_test]
-    fn test_dry_run_history_shrink() {
-        let metadata = json!({
-            "dry_run": true,
-            "would_apply": "decrease",
-            "shrink_factor": 0.75,
-            "quiet_intervals": 5,
-            "calculated_size_mb": 3072
-        });
-
-        let result = insert_history_record(
-            "dry_run",
-            4096,
-            3072,
-            0,
-            300,
-            Some("sustained low activity"),
-            Some(metadata),
-        );
-        assert!(
-            result.is_ok(),
-            "Dry-run shrink history insert should succeed"
-        );
-
-        // Verify the record was inserted with correct action and would_apply
-        let action = Spi::get_one::<&str>(
-            "SELECT action FROM walrus.history WHERE reason = 'sustained low activity' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(action, Some("dry_run"), "Action should be 'dry_run'");
-
-        let would_apply = Spi::get_one::<&str>(
-            "SELECT metadata->>'would_apply' FROM walrus.history WHERE reason = 'sustained low activity' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(
-            would_apply,
-            Some("decrease"),
-            "would_apply should be 'decrease'"
-        );
+/// Trim to `max_rows` rows, running the `archive` CTE on each DELETE.
+fn trim_rows(max_rows: i32, archive: &str) -> Result<i64, spi::Error> {
+    if max_rows <= 0 {
+        return Ok(0);
     }
 
-    /// Test that dry_run history metadata contains all algorithm fields (T023)
-    #[pg_test]
-    fn test_dry_run_history_metadata_complete() {
-        // Test grow decision metadata
-        let grow_metadata = json!({
-            "dry_run": true,
-            "would_apply": "increase",
-            "delta": 3,
-            "multiplier": 4,
-            "calculated_size_mb": 4096
-        });
+    let args: &[DatumWithOid<'_>] = &[(max_rows as i64).into()];
+    let table = history_table();
+    let informational = INFORMATIONAL_ACTIONS
+        .iter()
+        .map(|action| format!("'{action}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let informational_deleted = Spi::get_one_with_args::<i64>(
+        &format!(
+            "WITH deleted AS (
+                DELETE FROM {table}
+                WHERE id IN (
+                    SELECT id FROM {table}
+                    WHERE action IN ({informational})
+                    ORDER BY id
+                    LIMIT greatest((SELECT count(*) FROM {table}) - $1, 0)
+                )
+                RETURNING {DELETED_COLUMNS}
+            ){archive}
+            SELECT count(*) FROM deleted"
+        ),
+        args,
+    )?;
 
-        let result = insert_history_record(
-            "dry_run",
-            1024,
-            4096,
-            3,
-            300,
-            Some("metadata test grow"),
-            Some(grow_metadata),
-        );
-        assert!(result.is_ok(), "Insert should succeed");
-
-        // Verify all fields are present
-        let delta = Spi::get_one::<i64>(
-            "SELECT (metadata->>'delta')::bigint FROM walrus.history WHERE reason = 'metadata test grow' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(delta, Some(3), "delta should be present");
-
-        let multiplier = Spi::get_one::<i64>(
-            "SELECT (metadata->>'multiplier')::bigint FROM walrus.history WHERE reason = 'metadata test grow' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(multiplier, Some(4), "multiplier should be present");
-
-        let calculated = Spi::get_one::<i64>(
-            "SELECT (metadata->>'calculated_size_mb')::bigint FROM walrus.history WHERE reason = 'metadata test grow' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(
-            calculated,
-            Some(4096),
-            "calculated_size_mb should be present"
-        );
+    let deleted = Spi::get_one_with_args::<i64>(
+        &format!(
+            "WITH deleted AS (
+                DELETE FROM {table}
+                WHERE id < (
+                    SELECT id FROM {table}
+                    ORDER BY id DESC
+                    OFFSET $1 - 1 LIMIT 1
+                )
+                RETURNING {DELETED_COLUMNS}
+            ){archive}
+            SELECT count(*) FROM deleted"
+        ),
+        args,
+    )?;
 
-        // Test shrink decision metadata
-        let shrink_metadata = json!({
-            "dry_run": true,
-            "would_apply": "decrease",
-            "shrink_factor": 0.75,
-            "quiet_intervals": 5,
-            "calculated_size_mb": 3072
-        });
+    Ok(informational_deleted.unwrap_or(0) + deleted.unwrap_or(0))
+}
 
-        let result = insert_history_record(
-            "dry_run",
-            4096,
-            3072,
-            0,
-            300,
-            Some("metadata test shrink"),
-            Some(shrink_metadata),
-        );
-        assert!(result.is_ok(), "Insert should succeed");
-
-        let shrink_factor = Spi::get_one::<f64>(
-            "SELECT (metadata->>'shrink_factor')::float FROM walrus.history WHERE reason = 'metadata test shrink' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert!(
-            (shrink_factor.unwrap_or(0.0) - 0.75).abs() < 0.01,
-            "shrink_factor should be present"
-        );
+/// Check whether the worker's scheduled history cleanup is due.
+///
+/// Cleanup runs at most once per `walrus.cleanup_interval_sec`. A
+/// `last_cleanup_time` of 0 (never run, or after reset) is always due.
+#[inline]
+pub fn cleanup_due(last_cleanup_time: i64, now: i64, interval_sec: i32) -> bool {
+    last_cleanup_time == 0 || now - last_cleanup_time >= interval_sec as i64
+}
 
-        let quiet_intervals = Spi::get_one::<i64>(
-            "SELECT (metadata->>'quiet_intervals')::bigint FROM walrus.history WHERE reason = 'metadata test shrink' ORDER BY id DESC LIMIT 1",
-        )
-        .expect("query failed");
-        assert_eq!(
-            quiet_intervals,
-            Some(5),
-            "quiet_intervals should be present"
-        );
+/// Decide whether to record a 'note' row for unavailable checkpoint statistics.
+///
+/// Returns `(record, noted)`: whether to write the note this cycle, and the new
+/// value of the shared memory outage flag. At most one note is written per
+/// contiguous outage; a successful read (`current_requested >= 0`) clears the flag.
+#[inline]
+pub fn stats_unavailable_note(
+    noted: bool,
+    current_requested: i64,
+    record_enabled: bool,
+) -> (bool, bool) {
+    if current_requested >= 0 {
+        return (false, false);
     }
-
-    /// Test graceful handling when history table does not exist during dry-run (T026)
-    #[pg_test]
-    fn test_dry_run_missing_history_table() {
-        // This test verifies the table existence check handles the edge case gracefully
-        // The insert_history_record function checks if the table exists before inserting
-        // and returns Ok(()) with a warning if it doesn't exist.
-
-        // First, verify the dry_run action works with the table present
-        let metadata = json!({
-            "dry_run": true,
-            "would_apply": "increase",
-            "delta": 2,
-            "multiplier": 3,
-            "calculated_size_mb": 3072
-        });
-
-        let result = insert_history_record(
-            "dry_run",
-            1024,
-            3072,
-            2,
-            300,
-            Some("missing table test"),
-            Some(metadata),
-        );
-        assert!(
-            result.is_ok(),
-            "Dry-run insert should succeed with table present"
-        );
-
-        // The actual "missing table" handling is already tested in test_insert_fails_gracefully_on_error
-        // This test specifically verifies that dry_run action works when table exists
-        let exists = Spi::get_one::<bool>(
-            "SELECT EXISTS(SELECT 1 FROM walrus.history WHERE reason = 'missing table test' AND action = 'dry_run')",
-        )
-        .expect("query failed");
-        assert_eq!(exists, Some(true), "Dry-run record should be inserted");
+    if noted || !record_enabled {
+        return (false, noted);
     }
+    (true, true)
+}
 
-    // =========================================================================
-    // Edge Case Tests (T055, T058, T069)
-    // =========================================================================
-
-    /// Test insert fails gracefully when history table has issues (T055)
-    #[pg_test]
-    fn test_insert_fails_gracefully_on_error() {
-        // This tests the graceful handling path - the function should return Ok(())
-        // and log a warning when the table doesn't exist, not panic or abort.
-        // We test this by verifying the function handles the table existence check.
-
-        // First, verify normal insert works
-        let result = insert_history_record("increase", 100, 200, 1, 300, None, None);
-        assert!(result.is_ok(), "Normal insert should succeed");
-
-        // The actual "table dropped" scenario is tested by the table existence check
-        // in insert_history_record() which returns Ok(()) with a warning instead of failing.
-        // We verify this check exists by examining that the function queries pg_class.
-    }
+/// Metadata for a per-cycle interval classification note.
+///
+/// `classification` is "active" (delta reached the threshold, or WAL volume was
+/// too high for the interval to count as quiet) or "quiet". `quiet_intervals`
+/// is the count after this interval.
+pub fn interval_note_metadata(
+    delta: i64,
+    threshold: i64,
+    quiet_intervals: i32,
+    classification: &str,
+    wal_bytes: i64,
+) -> JsonValue {
+    versioned_metadata(json!({
+        "delta": delta,
+        "threshold": threshold,
+        "quiet_intervals": quiet_intervals,
+        "classification": classification,
+        "wal_bytes": wal_bytes,
+    }))
+}
 
-    /// Test concurrent insert during cleanup preserves new records (T058)
-    #[pg_test]
-    fn test_concurrent_insert_during_cleanup_preserves_new_records() {
-        // Insert a new record (recent timestamp)
-        insert_history_record("increase", 512, 1024, 2, 300, Some("Concurrent test"), None)
-            .expect("insert failed");
-
-        // Run cleanup immediately after
-        cleanup_old_history().expect("cleanup failed");
-
-        // Verify the new record still exists (it's recent, so should not be deleted)
-        let exists = Spi::get_one::<bool>(
-            "SELECT EXISTS(SELECT 1 FROM walrus.history WHERE reason = 'Concurrent test')",
-        )
-        .expect("query failed");
-        assert_eq!(
-            exists,
-            Some(true),
-            "New record should not be deleted by concurrent cleanup"
-        );
+/// Insert a per-cycle 'note' row describing how the interval was classified.
+///
+/// Controlled by walrus.verbose_history (`enabled`); returns Ok(false) without
+/// touching the table when disabled. Must be called within a transaction.
+pub fn insert_interval_note(
+    enabled: bool,
+    current_size: i32,
+    current_requested: i64,
+    checkpoint_timeout_sec: i32,
+    metadata: JsonValue,
+) -> Result<bool, spi::Error> {
+    if !enabled {
+        return Ok(false);
     }
+    let reason = match metadata.get("classification").and_then(|c| c.as_str()) {
+        Some("active") => "interval classified as active",
+        _ => "interval classified as quiet",
+    };
+    insert_history_record(
+        "note",
+        current_size,
+        current_size,
+        current_requested.max(0),
+        checkpoint_timeout_sec,
+        Some(reason),
+        Some(metadata),
+    )?;
+    Ok(true)
+}
 
-    /// Test insert completes within acceptable time (T069)
-    #[pg_test]
-    fn test_insert_completes_within_one_second() {
-        use std::time::Instant;
-
-        let start = Instant::now();
-        let result = insert_history_record(
-            "increase",
-            1024,
-            2048,
-            5,
-            300,
-            Some("Performance test"),
-            Some(json!({"delta": 5, "multiplier": 6})),
-        );
-        let elapsed = start.elapsed();
-
-        assert!(result.is_ok(), "Insert should succeed");
-        assert!(
-            elapsed.as_millis() < 1000,
-            "Insert should complete in < 1 second, took {}ms",
-            elapsed.as_millis()
-        );
-    }
+// PostgreSQL integration tests for history module
+// Tests are in a separate file to keep history.rs under 900 LOC
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    include!("history_tests.rs");
 }
@@ -0,0 +1,455 @@
+//! CSV export and import of walrus.history.
+//!
+//! `walrus.export_history()` renders the history table as CSV (RFC 4180
+//! quoting, one header line) and `walrus.import_history(csv)` loads that text
+//! back, so tuning history survives a cluster migration or a pg_upgrade that
+//! rebuilds the extension. Ids are not carried over; imported rows get new ids
+//! in export order and keep their original timestamps and `database_name`.
+//! Exports from 0.1.0, which have no `database_name` column, import with the
+//! importing database, like rows written there.
+//!
+//! Import skips malformed lines instead of failing the whole load: a wrong
+//! field count, an action outside the history CHECK constraint, a size or
+//! count that would violate the table constraints, unparseable metadata, or a
+//! timestamp not in the export format.
+
+use crate::history::{HISTORY_ACTIONS, history_table, history_table_exists};
+
+use pgrx::JsonB;
+use pgrx::datum::DatumWithOid;
+use pgrx::prelude::*;
+use serde_json::Value as JsonValue;
+
+/// Column order of the CSV header line.
+pub(crate) const CSV_COLUMNS: [&str; 9] = [
+    "timestamp",
+    "action",
+    "old_size_mb",
+    "new_size_mb",
+    "forced_checkpoints",
+    "checkpoint_timeout_sec",
+    "reason",
+    "metadata",
+    "database_name",
+];
+
+/// Columns of a 0.1.0 export, which ends before `database_name`.
+const LEGACY_CSV_COLUMNS: usize = 8;
+
+/// Render one CSV field.
+///
+/// NULL is an empty unquoted field; an empty string is quoted ("") so the two
+/// round-trip distinctly. Values containing a comma, quote, or line break are
+/// quoted with embedded quotes doubled.
+pub(crate) fn csv_field(value: Option<&str>) -> String {
+    match value {
+        None => String::new(),
+        Some(v) if v.is_empty() || v.contains([',', '"', '\n', '\r']) => {
+            format!("\"{}\"", v.replace('"', "\"\""))
+        }
+        Some(v) => v.to_owned(),
+    }
+}
+
+/// Split CSV text into records of fields.
+///
+/// Quoted fields may contain commas, doubled quotes, and line breaks. An
+/// unquoted empty field is `None` (NULL); a quoted empty field is `Some("")`.
+/// Blank lines produce no record.
+pub(crate) fn parse_csv_records(text: &str) -> Vec<Vec<Option<String>>> {
+    let mut records = Vec::new();
+    let mut record: Vec<Option<String>> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_quotes = true;
+                quoted = true;
+            }
+            ',' => record.push(finish_field(&mut field, &mut quoted)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                if !record.is_empty() || !field.is_empty() || quoted {
+                    record.push(finish_field(&mut field, &mut quoted));
+                    records.push(std::mem::take(&mut record));
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+    if !record.is_empty() || !field.is_empty() || quoted {
+        record.push(finish_field(&mut field, &mut quoted));
+        records.push(record);
+    }
+    records
+}
+
+fn finish_field(field: &mut String, quoted: &mut bool) -> Option<String> {
+    let value = std::mem::take(field);
+    let was_quoted = std::mem::replace(quoted, false);
+    if value.is_empty() && !was_quoted {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Whether `ts` is a UTC timestamp in the export format.
+///
+/// `YYYY-MM-DDTHH:MM:SS[.ffffff]Z`, with the date and time in range, so the
+/// timestamptz cast on insert cannot fail.
+pub(crate) fn is_export_timestamp(ts: &str) -> bool {
+    let Some(body) = ts.strip_suffix('Z') else {
+        return false;
+    };
+    let (datetime, fraction) = body.split_once('.').unwrap_or((body, ""));
+    let well_formed = datetime.len() == 19
+        && datetime.bytes().enumerate().all(|(i, c)| match i {
+            4 | 7 => c == b'-',
+            10 => c == b'T',
+            13 | 16 => c == b':',
+            _ => c.is_ascii_digit(),
+        });
+    if !well_formed || fraction.len() > 6 || !fraction.bytes().all(|d| d.is_ascii_digit()) {
+        return false;
+    }
+
+    // All digits, so each part parses
+    let [year, month, day, hour, minute, second] = [0..4, 5..7, 8..10, 11..13, 14..16, 17..19]
+        .map(|range| datetime[range].parse::<u32>().unwrap_or(0));
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    year >= 1 && (1..=days_in_month).contains(&day) && hour < 24 && minute < 60 && second < 60
+}
+
+/// A CSV record validated against the history table constraints.
+#[derive(Debug, PartialEq)]
+pub(crate) struct HistoryCsvRow {
+    pub(crate) timestamp: String,
+    pub(crate) action: String,
+    pub(crate) old_size_mb: i32,
+    pub(crate) new_size_mb: i32,
+    pub(crate) forced_checkpoints: i64,
+    pub(crate) checkpoint_timeout_sec: i32,
+    pub(crate) reason: Option<String>,
+    pub(crate) metadata: Option<JsonValue>,
+    /// None for a 0.1.0 record or an empty field
+    pub(crate) database_name: Option<String>,
+}
+
+fn parse_number<T: std::str::FromStr>(field: &Option<String>) -> Option<T> {
+    field.as_deref()?.parse::<T>().ok()
+}
+
+/// Validate one CSV record, returning None if it is malformed.
+///
+/// Accepts the current columns and the 0.1.0 ones (no `database_name`).
+pub(crate) fn parse_history_row(record: &[Option<String>]) -> Option<HistoryCsvRow> {
+    let (record, database_name) = match record {
+        [legacy @ .., database_name] if record.len() == CSV_COLUMNS.len() => (
+            legacy,
+            database_name.clone().filter(|name| !name.is_empty()),
+        ),
+        _ => (record, None),
+    };
+    let [
+        timestamp,
+        action,
+        old_size_mb,
+        new_size_mb,
+        forced_checkpoints,
+        checkpoint_timeout_sec,
+        reason,
+        metadata,
+    ] = record
+    else {
+        return None;
+    };
+
+    let timestamp = timestamp.as_deref().filter(|ts| is_export_timestamp(ts))?;
+    let action = action.as_deref().filter(|a| HISTORY_ACTIONS.contains(a))?;
+    let old_size_mb = parse_number::<i32>(old_size_mb).filter(|v| *v > 0)?;
+    let new_size_mb = parse_number::<i32>(new_size_mb).filter(|v| *v > 0)?;
+    let forced_checkpoints = parse_number::<i64>(forced_checkpoints).filter(|v| *v >= 0)?;
+    let checkpoint_timeout_sec = parse_number::<i32>(checkpoint_timeout_sec).filter(|v| *v > 0)?;
+    let metadata = match metadata {
+        Some(text) => Some(serde_json::from_str::<JsonValue>(text).ok()?),
+        None => None,
+    };
+
+    Some(HistoryCsvRow {
+        timestamp: timestamp.to_owned(),
+        action: action.to_owned(),
+        old_size_mb,
+        new_size_mb,
+        forced_checkpoints,
+        checkpoint_timeout_sec,
+        reason: reason.clone(),
+        metadata,
+        database_name,
+    })
+}
+
+/// Implementation for exporting the history table as CSV.
+///
+/// Rows are ordered by id. Timestamps are rendered in UTC with microseconds
+/// (`2025-01-15T10:30:00.123456Z`).
+///
+/// # Returns
+///
+/// The CSV text, header line first. Only the header when the table does not exist.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn export_history() -> Result<String, spi::Error> {
+    let mut csv = CSV_COLUMNS.join(",");
+    csv.push('\n');
+
+    if !history_table_exists()? {
+        return Ok(csv);
+    }
+
+    let query = format!(
+        "SELECT to_char(timestamp AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS.US\"Z\"'),
+                action, old_size_mb::text, new_size_mb::text, forced_checkpoints::text,
+                checkpoint_timeout_sec::text, reason, metadata::text, database_name
+         FROM {}
+         ORDER BY id",
+        history_table()
+    );
+
+    Spi::connect(|client| {
+        for row in client.select(&query, None, &[])? {
+            let fields = (1..=CSV_COLUMNS.len())
+                .map(|i| row.get::<String>(i).map(|v| csv_field(v.as_deref())))
+                .collect::<Result<Vec<_>, _>>()?;
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        Ok::<_, spi::Error>(())
+    })?;
+
+    Ok(csv)
+}
+
+/// Implementation for importing history rows from CSV.
+///
+/// Accepts the output of `export_history()`, including from 0.1.0. The header
+/// line is skipped, as are malformed lines (see the module docs). Valid rows
+/// are inserted in file order with their original timestamps and
+/// `database_name` (the current database when absent). Rows whose metadata
+/// `idempotency_key` is already in the table are skipped, so re-importing an
+/// export does not duplicate applied changes.
+///
+/// # Returns
+///
+/// The number of rows imported.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn import_history(csv: &str) -> Result<i64, spi::Error> {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.import_history() requires superuser");
+    }
+    if !history_table_exists()? {
+        pgrx::error!("walrus.import_history(): history table does not exist");
+    }
+
+    let query = format!(
+        "WITH inserted AS (
+             INSERT INTO {}
+             (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec, reason, metadata, database_name)
+             VALUES ($1::timestamptz, $2, $3, $4, $5, $6, $7, $8, coalesce($9, current_database()))
+             ON CONFLICT DO NOTHING
+             RETURNING 1
+         )
+         SELECT count(*) FROM inserted",
+        history_table()
+    );
+
+    let header: Vec<Option<String>> = CSV_COLUMNS.iter().map(|c| Some(c.to_string())).collect();
+    let mut imported = 0i64;
+    let mut skipped = 0i64;
+    for record in parse_csv_records(csv) {
+        if record == header || record[..] == header[..LEGACY_CSV_COLUMNS] {
+            continue;
+        }
+        let Some(row) = parse_history_row(&record) else {
+            skipped += 1;
+            continue;
+        };
+        let args: Vec<DatumWithOid<'_>> = vec![
+            row.timestamp.as_str().into(),
+            row.action.as_str().into(),
+            row.old_size_mb.into(),
+            row.new_size_mb.into(),
+            row.forced_checkpoints.into(),
+            row.checkpoint_timeout_sec.into(),
+            row.reason.as_deref().into(),
+            row.metadata.map(JsonB).into(),
+            row.database_name.as_deref().into(),
+        ];
+        imported += Spi::get_one_with_args::<i64>(&query, &args)?.unwrap_or(0);
+    }
+
+    if skipped > 0 {
+        pgrx::warning!(
+            "pg_walrus: import_history skipped {} malformed lines",
+            skipped
+        );
+    }
+    Ok(imported)
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(values: &[Option<&str>]) -> Vec<Option<String>> {
+        values.iter().map(|v| v.map(str::to_owned)).collect()
+    }
+
+    /// Test fields are quoted only when needed and NULL stays distinct from ''
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field(None), "");
+        assert_eq!(csv_field(Some("")), "\"\"");
+        assert_eq!(csv_field(Some("increase")), "increase");
+        assert_eq!(csv_field(Some("a,b")), "\"a,b\"");
+        assert_eq!(csv_field(Some("{\"k\": 1}")), "\"{\"\"k\"\": 1}\"");
+        assert_eq!(csv_field(Some("line\nbreak")), "\"line\nbreak\"");
+    }
+
+    /// Test rendered fields parse back to the same values
+    #[test]
+    fn test_csv_round_trip() {
+        let values = [
+            None,
+            Some(""),
+            Some("plain"),
+            Some("a,b"),
+            Some("{\"delta\": 5, \"note\": \"x, \\\"y\\\"\"}"),
+            Some("two\r\nlines"),
+        ];
+        let line: Vec<String> = values.iter().map(|v| csv_field(*v)).collect();
+        let text = format!("{}\n{}\n", line.join(","), line.join(","));
+
+        let records = parse_csv_records(&text);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], fields(&values));
+        assert_eq!(records[1], fields(&values));
+    }
+
+    /// Test CRLF line endings and blank lines
+    #[test]
+    fn test_parse_csv_records_line_endings() {
+        let records = parse_csv_records("a,b\r\n\r\nc,\r\n");
+        assert_eq!(
+            records,
+            vec![fields(&[Some("a"), Some("b")]), fields(&[Some("c"), None])]
+        );
+    }
+
+    /// Test only the export timestamp format with in-range values is accepted
+    #[test]
+    fn test_is_export_timestamp() {
+        assert!(is_export_timestamp("2025-01-15T10:30:00.123456Z"));
+        assert!(is_export_timestamp("2025-01-15T10:30:00Z"));
+        assert!(is_export_timestamp("2024-02-29T23:59:59Z"));
+
+        assert!(!is_export_timestamp("2025-02-29T00:00:00Z"));
+        assert!(!is_export_timestamp("2025-13-01T00:00:00Z"));
+        assert!(!is_export_timestamp("2025-01-15T24:00:00Z"));
+        assert!(!is_export_timestamp("2025-01-15 10:30:00+00"));
+        assert!(!is_export_timestamp("2025-01-15T10:30:00.1234567Z"));
+        assert!(!is_export_timestamp("not a timestamp"));
+    }
+
+    /// Test a valid record parses and constraint violations are rejected
+    #[test]
+    fn test_parse_history_row() {
+        let valid = [
+            Some("2025-01-15T10:30:00.000000Z"),
+            Some("increase"),
+            Some("1024"),
+            Some("2048"),
+            Some("5"),
+            Some("300"),
+            None,
+            Some("{\"delta\": 5}"),
+        ];
+        let row = parse_history_row(&fields(&valid)).expect("valid row");
+        assert_eq!(row.action, "increase");
+        assert_eq!(row.new_size_mb, 2048);
+        assert_eq!(row.reason, None);
+        assert_eq!(row.metadata, Some(serde_json::json!({"delta": 5})));
+
+        let with = |index: usize, value: Option<&str>| {
+            let mut record = valid;
+            record[index] = value;
+            parse_history_row(&fields(&record))
+        };
+        assert!(with(1, Some("resize")).is_none(), "unknown action");
+        assert!(with(2, Some("0")).is_none(), "old_size_mb must be > 0");
+        assert!(with(3, Some("big")).is_none(), "non-numeric size");
+        assert!(with(4, Some("-1")).is_none(), "negative forced_checkpoints");
+        assert!(with(5, None).is_none(), "missing checkpoint_timeout_sec");
+        assert!(with(7, Some("{broken")).is_none(), "invalid metadata");
+        assert!(with(7, None).is_some(), "metadata is optional");
+        assert!(
+            parse_history_row(&fields(&valid[..7])).is_none(),
+            "field count"
+        );
+    }
+
+    /// Test database_name is read from current exports and absent from 0.1.0 ones
+    #[test]
+    fn test_parse_history_row_database_name() {
+        let legacy = [
+            Some("2025-01-15T10:30:00Z"),
+            Some("manual"),
+            Some("1024"),
+            Some("2048"),
+            Some("0"),
+            Some("300"),
+            None,
+            None,
+        ];
+        let row = parse_history_row(&fields(&legacy)).expect("0.1.0 row");
+        assert_eq!(row.database_name, None);
+
+        let mut current = fields(&legacy);
+        current.push(Some("analytics".to_string()));
+        let row = parse_history_row(&current).expect("current row");
+        assert_eq!(row.database_name.as_deref(), Some("analytics"));
+        assert_eq!(row.action, "manual");
+
+        current[8] = None;
+        let row = parse_history_row(&current).expect("empty database_name");
+        assert_eq!(row.database_name, None);
+    }
+}
@@ -0,0 +1,58 @@
+//! Newline-delimited JSON export of walrus.history.
+//!
+//! `walrus.history_ndjson(since)` returns one text line per history row, each
+//! a self-contained JSON object including metadata, so log shippers can ingest
+//! the output line by line instead of parsing the `walrus.history()` SRF or the
+//! CSV export.
+
+use crate::history::{history_table, history_table_exists};
+
+use pgrx::datum::{DatumWithOid, TimestampWithTimeZone};
+use pgrx::prelude::*;
+
+/// Implementation for streaming history as newline-delimited JSON.
+///
+/// Returns one JSON object per row, ordered by id, with keys `id`,
+/// `timestamp` (UTC, as in `export_history()`), `action`, `old_size_mb`,
+/// `new_size_mb`, `forced_checkpoints`, `checkpoint_timeout_sec`, `reason`,
+/// `metadata`, and `database_name`. With `since`, only rows at or after that time are returned.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn history_ndjson(
+    since: Option<TimestampWithTimeZone>,
+) -> Result<SetOfIterator<'static, String>, spi::Error> {
+    if !history_table_exists()? {
+        return Err(spi::Error::InvalidPosition);
+    }
+
+    Spi::connect(|client| {
+        let args: &[DatumWithOid<'_>] = &[since.into()];
+        let lines = client
+            .select(
+                &format!(
+                    "SELECT json_build_object(
+                         'id', id,
+                         'timestamp', to_char(timestamp AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS.US\"Z\"'),
+                         'action', action,
+                         'old_size_mb', old_size_mb,
+                         'new_size_mb', new_size_mb,
+                         'forced_checkpoints', forced_checkpoints,
+                         'checkpoint_timeout_sec', checkpoint_timeout_sec,
+                         'reason', reason,
+                         'metadata', metadata,
+                         'database_name', database_name
+                     )::text
+                     FROM {}
+                     WHERE $1::timestamptz IS NULL OR timestamp >= $1
+                     ORDER BY id",
+                    history_table()
+                ),
+                None,
+                args,
+            )?
+            .filter_map(|row| row.get::<String>(1).ok().flatten())
+            .collect::<Vec<_>>();
+
+        Ok(SetOfIterator::new(lines))
+    })
+}
@@ -3,13 +3,43 @@
 //! This extension monitors checkpoint activity and automatically adjusts
 //! `max_wal_size` to prevent performance-degrading forced checkpoints.
 
+mod access;
 mod algorithm;
+mod apply;
+mod bulk_load;
+mod ceiling;
+mod checkpoint_causes;
+mod checkpoint_stats;
+mod cluster_id;
 mod config;
+mod decision_stats;
+mod device_max;
+mod diagnostics;
+mod emergency;
+mod explain;
 mod functions;
 mod guc;
 mod history;
+mod history_csv;
+mod history_ndjson;
+mod logging;
+mod lookback;
+mod min_floor;
+mod mirror;
+mod pin;
+mod rate_limit;
+mod recommend_min;
+mod reproduce;
+mod restart;
+mod settling;
 mod shmem;
+mod shrink;
 mod stats;
+mod status;
+mod temporary_max;
+mod tick;
+mod validate;
+mod wal_buffers;
 mod worker;
 
 use pgrx::bgworkers::{BackgroundWorkerBuilder, BgWorkerStartTime};
@@ -34,29 +64,54 @@ CREATE SCHEMA IF NOT EXISTS walrus;
 CREATE TABLE walrus.history (
     id BIGSERIAL PRIMARY KEY,
     timestamp TIMESTAMPTZ NOT NULL DEFAULT now(),
-    action TEXT NOT NULL CHECK (action IN ('increase', 'decrease', 'capped', 'dry_run', 'skipped')),
+    action TEXT NOT NULL CHECK (action IN ('increase', 'decrease', 'capped', 'dry_run', 'skipped', 'note', 'external_change', 'manual')),
     old_size_mb INTEGER NOT NULL CHECK (old_size_mb > 0),
     new_size_mb INTEGER NOT NULL CHECK (new_size_mb > 0),
     forced_checkpoints BIGINT NOT NULL CHECK (forced_checkpoints >= 0),
     checkpoint_timeout_sec INTEGER NOT NULL CHECK (checkpoint_timeout_sec > 0),
     reason TEXT,
-    metadata JSONB
+    metadata JSONB,
+    database_name TEXT NOT NULL DEFAULT current_database()
 );
 
 -- Index for efficient range queries and cleanup
 CREATE INDEX walrus_history_timestamp_idx ON walrus.history (timestamp);
 
+-- Deduplicates retried inserts of the same applied decision
+CREATE UNIQUE INDEX walrus_history_idempotency_key_idx
+    ON walrus.history ((metadata->>'idempotency_key'))
+    WHERE metadata ? 'idempotency_key';
+
 -- Documentation comments
 COMMENT ON TABLE walrus.history IS 'Audit trail of pg_walrus sizing decisions';
 COMMENT ON COLUMN walrus.history.id IS 'Unique identifier for each history record';
 COMMENT ON COLUMN walrus.history.timestamp IS 'When the sizing decision was made';
-COMMENT ON COLUMN walrus.history.action IS 'Decision type: increase, decrease, capped, dry_run, or skipped';
+COMMENT ON COLUMN walrus.history.action IS 'Decision type: increase, decrease, capped, dry_run, skipped, note, external_change, or manual';
 COMMENT ON COLUMN walrus.history.old_size_mb IS 'max_wal_size before the change (in MB)';
 COMMENT ON COLUMN walrus.history.new_size_mb IS 'max_wal_size after the change (in MB)';
 COMMENT ON COLUMN walrus.history.forced_checkpoints IS 'Checkpoint count at decision time';
 COMMENT ON COLUMN walrus.history.checkpoint_timeout_sec IS 'checkpoint_timeout value in seconds at decision time';
 COMMENT ON COLUMN walrus.history.reason IS 'Human-readable explanation of the decision';
 COMMENT ON COLUMN walrus.history.metadata IS 'Algorithm-specific details in JSON format';
+COMMENT ON COLUMN walrus.history.database_name IS 'Database whose connection recorded the row';
+
+-- Daily rollup of deleted history rows (walrus.archive_before_delete)
+CREATE TABLE walrus.history_archive (
+    day DATE PRIMARY KEY,
+    increases INTEGER NOT NULL DEFAULT 0,
+    decreases INTEGER NOT NULL DEFAULT 0,
+    capped INTEGER NOT NULL DEFAULT 0,
+    peak_size_mb INTEGER,
+    min_size_mb INTEGER
+);
+
+COMMENT ON TABLE walrus.history_archive IS 'Per-day summary of history rows removed by cleanup; never cleaned up';
+COMMENT ON COLUMN walrus.history_archive.day IS 'UTC day of the archived history rows';
+COMMENT ON COLUMN walrus.history_archive.increases IS 'Number of increase rows';
+COMMENT ON COLUMN walrus.history_archive.decreases IS 'Number of decrease rows';
+COMMENT ON COLUMN walrus.history_archive.capped IS 'Number of capped rows';
+COMMENT ON COLUMN walrus.history_archive.peak_size_mb IS 'Largest max_wal_size seen that day (in MB)';
+COMMENT ON COLUMN walrus.history_archive.min_size_mb IS 'Smallest max_wal_size seen that day (in MB)';
 "#,
     name = "create_walrus_schema_and_history",
     bootstrap,
@@ -72,7 +127,23 @@ COMMENT ON COLUMN walrus.history.metadata IS 'Algorithm-specific details in JSON
 /// rather than the default public schema.
 #[pg_schema]
 mod walrus {
+    use crate::bulk_load;
+    use crate::checkpoint_stats;
+    use crate::decision_stats;
+    use crate::diagnostics;
+    use crate::explain;
     use crate::functions;
+    use crate::history_csv;
+    use crate::history_ndjson;
+    use crate::pin;
+    use crate::rate_limit;
+    use crate::recommend_min;
+    use crate::reproduce;
+    use crate::shrink;
+    use crate::status;
+    use crate::temporary_max;
+    use crate::tick;
+    use crate::validate;
     use pgrx::JsonB;
     use pgrx::datum::TimestampWithTimeZone;
     use pgrx::prelude::*;
@@ -86,19 +157,26 @@ mod walrus {
     /// ```
     #[pg_extern]
     fn status() -> JsonB {
-        functions::status()
+        status::status()
     }
 
     /// Returns adjustment history as SETOF RECORD.
     ///
+    /// # Arguments
+    ///
+    /// * `database_name` - Only rows recorded from this database (all when NULL)
+    ///
     /// # Example
     ///
     /// ```sql
     /// SELECT * FROM walrus.history();
+    /// SELECT * FROM walrus.history(database_name := 'postgres');
     /// ```
     #[allow(clippy::type_complexity)]
     #[pg_extern]
-    fn history() -> Result<
+    fn history(
+        database_name: pgrx::default!(Option<String>, "NULL"),
+    ) -> Result<
         pgrx::iter::TableIterator<
             'static,
             (
@@ -108,23 +186,156 @@ mod walrus {
                 pgrx::name!(new_size_mb, i32),
                 pgrx::name!(forced_checkpoints, i64),
                 pgrx::name!(reason, Option<String>),
+                pgrx::name!(database_name, String),
+            ),
+        >,
+        spi::Error,
+    > {
+        functions::history_srf(database_name)
+    }
+
+    /// Returns history rows with id greater than `after_id`, for keyset pagination.
+    ///
+    /// # Arguments
+    ///
+    /// * `after_id` - Last id seen (0 for the first page)
+    /// * `limit_rows` - Maximum rows to return (default 100)
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT * FROM walrus.history_after(0);
+    /// SELECT * FROM walrus.history_after(after_id := 100, limit_rows := 50);
+    /// ```
+    #[allow(clippy::type_complexity)]
+    #[pg_extern]
+    fn history_after(
+        after_id: i64,
+        limit_rows: pgrx::default!(i32, 100),
+    ) -> Result<
+        pgrx::iter::TableIterator<
+            'static,
+            (
+                pgrx::name!(id, i64),
+                pgrx::name!(timestamp, TimestampWithTimeZone),
+                pgrx::name!(action, String),
+                pgrx::name!(old_size_mb, i32),
+                pgrx::name!(new_size_mb, i32),
+                pgrx::name!(forced_checkpoints, i64),
+                pgrx::name!(reason, Option<String>),
+            ),
+        >,
+        spi::Error,
+    > {
+        functions::history_after_srf(after_id, limit_rows)
+    }
+
+    /// Streams history rows as newline-delimited JSON, one object per row.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Only return rows at or after this time (default: all rows)
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT * FROM walrus.history_ndjson();
+    /// SELECT * FROM walrus.history_ndjson(since := now() - interval '1 hour');
+    /// ```
+    #[pg_extern]
+    fn history_ndjson(
+        since: pgrx::default!(Option<TimestampWithTimeZone>, "NULL"),
+    ) -> Result<SetOfIterator<'static, String>, spi::Error> {
+        history_ndjson::history_ndjson(since)
+    }
+
+    /// Lists the metadata keys present in the history table, per action.
+    ///
+    /// Lets dashboard authors discover which `metadata->>'key'` lookups are
+    /// available (delta, calculated_size_mb, blocked_by, etc.).
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT * FROM walrus.metadata_keys() WHERE action = 'capped';
+    /// ```
+    #[allow(clippy::type_complexity)]
+    #[pg_extern]
+    fn metadata_keys() -> Result<
+        pgrx::iter::TableIterator<
+            'static,
+            (
+                pgrx::name!(action, String),
+                pgrx::name!(key, String),
+                pgrx::name!(sample_value, Option<String>),
+            ),
+        >,
+        spi::Error,
+    > {
+        functions::metadata_keys_srf()
+    }
+
+    /// Lists each walrus.* setting with where its value comes from.
+    ///
+    /// `source` is pg_settings' source ('default', 'configuration file', ...),
+    /// or 'ALTER SYSTEM' for values set in postgresql.auto.conf.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT * FROM walrus.guc_sources() WHERE source <> 'default';
+    /// ```
+    #[allow(clippy::type_complexity)]
+    #[pg_extern]
+    fn guc_sources() -> Result<
+        pgrx::iter::TableIterator<
+            'static,
+            (
+                pgrx::name!(name, String),
+                pgrx::name!(setting, Option<String>),
+                pgrx::name!(source, Option<String>),
+                pgrx::name!(pending_restart, bool),
             ),
         >,
         spi::Error,
     > {
-        functions::history_srf()
+        diagnostics::guc_sources_srf()
+    }
+
+    /// Cancels the worker's pending skip of its next cycle (superuser only).
+    ///
+    /// Returns true if a skip was pending (`suppress_next_sighup` in status()).
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.clear_suppress();
+    /// ```
+    #[pg_extern]
+    fn clear_suppress() -> bool {
+        diagnostics::clear_suppress()
     }
 
     /// Returns sizing recommendation as JSONB.
     ///
+    /// # Arguments
+    ///
+    /// * `hypothetical_delta` - Preview the decision for this forced checkpoint delta
+    /// * `hypothetical_quiet_intervals` - Preview the decision for this quiet interval count
+    ///
     /// # Example
     ///
     /// ```sql
     /// SELECT walrus.recommendation();
+    /// SELECT walrus.recommendation(hypothetical_delta := 10);
+    /// SELECT walrus.recommendation(hypothetical_delta := 0, hypothetical_quiet_intervals := 5);
     /// ```
     #[pg_extern]
-    fn recommendation() -> JsonB {
-        functions::recommendation()
+    fn recommendation(
+        hypothetical_delta: pgrx::default!(Option<i64>, "NULL"),
+        hypothetical_quiet_intervals: pgrx::default!(Option<i32>, "NULL"),
+    ) -> JsonB {
+        functions::recommendation(hypothetical_delta, hypothetical_quiet_intervals)
     }
 
     /// Triggers immediate analysis with optional execution.
@@ -132,16 +343,21 @@ mod walrus {
     /// # Arguments
     ///
     /// * `apply` - If true, execute the recommendation (superuser only)
+    /// * `force` - If true, apply even within walrus.cooldown_sec of the last adjustment
     ///
     /// # Example
     ///
     /// ```sql
     /// SELECT walrus.analyze();
     /// SELECT walrus.analyze(apply := true);
+    /// SELECT walrus.analyze(apply := true, force := true);
     /// ```
     #[pg_extern]
-    fn analyze(apply: pgrx::default!(bool, false)) -> Result<JsonB, spi::Error> {
-        functions::analyze(apply)
+    fn analyze(
+        apply: pgrx::default!(bool, false),
+        force: pgrx::default!(bool, false),
+    ) -> Result<JsonB, spi::Error> {
+        functions::analyze(apply, force)
     }
 
     /// Resets extension state (superuser only).
@@ -177,6 +393,393 @@ mod walrus {
     fn cleanup_history() -> Result<i64, spi::Error> {
         functions::cleanup_history()
     }
+
+    /// Exports the history table as CSV text, header line first.
+    ///
+    /// Pairs with `walrus.import_history()` to carry tuning history across a
+    /// cluster migration.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.export_history();
+    /// -- From a shell: psql -Atc 'SELECT walrus.export_history()' > history.csv
+    /// ```
+    #[pg_extern]
+    fn export_history() -> Result<String, spi::Error> {
+        history_csv::export_history()
+    }
+
+    /// Imports history rows from CSV produced by `walrus.export_history()`.
+    ///
+    /// Malformed lines are skipped. Requires superuser.
+    ///
+    /// # Returns
+    ///
+    /// The number of rows imported.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.import_history(pg_read_file('/tmp/history.csv'));
+    /// -- Returns: 128 (number of imported records)
+    /// ```
+    #[pg_extern]
+    fn import_history(csv: &str) -> Result<i64, spi::Error> {
+        history_csv::import_history(csv)
+    }
+
+    /// Returns extension version and build information as JSONB.
+    ///
+    /// Includes the PostgreSQL major version the binary was compiled against,
+    /// which is useful when diagnosing deployment mismatches.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.version();
+    /// -- Returns: {"extension_version": "0.2.0", "pg_major": 18, "build_profile": "release"}
+    /// ```
+    #[pg_extern]
+    fn version() -> JsonB {
+        functions::version()
+    }
+
+    /// Returns a one-call support snapshot as JSONB.
+    ///
+    /// Combines the full shared memory state, the effective configuration,
+    /// the last 20 history rows, and `walrus.version()`. Read-only.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT jsonb_pretty(walrus.diagnostics());
+    /// ```
+    #[pg_extern]
+    fn diagnostics() -> Result<JsonB, spi::Error> {
+        diagnostics::diagnostics()
+    }
+
+    /// Returns every shared memory field verbatim as JSONB.
+    ///
+    /// Timestamps stay Unix seconds and nothing is omitted; use
+    /// `walrus.status()` for the human-friendly view.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT (walrus.state()->>'prev_requested')::bigint;
+    /// ```
+    #[pg_extern]
+    fn state() -> JsonB {
+        diagnostics::state()
+    }
+
+    /// Returns the time between consecutive increase/decrease decisions as JSONB.
+    ///
+    /// Mean, median, and 95th percentile gaps over the history retention
+    /// window, plus decision counts. Short gaps indicate flapping.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.decision_stats();
+    /// -- Returns: {"intervals": 5, "mean_interval_sec": 5400.0, "p50_interval_sec": 3600.0, ...}
+    /// ```
+    #[pg_extern]
+    fn decision_stats() -> Result<JsonB, spi::Error> {
+        decision_stats::decision_stats()
+    }
+
+    /// Checks the current configuration against recent history and live stats.
+    ///
+    /// Advisory only: returns findings such as a threshold that never fires or
+    /// a walrus.max that is hit repeatedly, each with a severity.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.validate_config();
+    /// -- Returns: {"ok": false, "history_days": 7, "findings": [{"check": "max_frequently_hit", ...}]}
+    /// ```
+    #[pg_extern]
+    fn validate_config() -> Result<JsonB, spi::Error> {
+        validate::validate_config()
+    }
+
+    /// Returns the raw checkpoint counters the worker decides on as JSONB.
+    ///
+    /// Forced and timed checkpoint counts, the worker's `prev_requested`
+    /// baseline, and the resulting delta, for comparing with pg_stat_checkpointer.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.checkpoint_stats();
+    /// -- Returns: {"requested": 42, "timed": 310, "prev_requested": 40, "delta": 2, ...}
+    /// ```
+    #[pg_extern]
+    fn checkpoint_stats() -> JsonB {
+        checkpoint_stats::checkpoint_stats()
+    }
+
+    /// Previews the sizes successive shrinks would take with a shrink factor.
+    ///
+    /// Step 0 is the current max_wal_size; each step applies `factor` with the
+    /// current shrink floor and walrus.shrink_rounding. Nothing is changed.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT * FROM walrus.preview_shrink_path(0.5, 5);
+    /// ```
+    #[pg_extern]
+    fn preview_shrink_path(
+        factor: f64,
+        steps: pgrx::default!(i32, 10),
+    ) -> TableIterator<'static, (name!(step, i32), name!(size_mb, i32))> {
+        shrink::preview_shrink_path(factor, steps)
+    }
+
+    /// Suggests a walrus.min_size from shrinks that were followed by a grow.
+    ///
+    /// Advisory only: the smallest size a shrink reached before the workload
+    /// forced a grow, plus a margin.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.recommend_min();
+    /// -- Returns: {"suggested_min_mb": 1127, "observed_shrink_then_grow_events": 2, "rationale": "..."}
+    /// ```
+    #[pg_extern]
+    fn recommend_min() -> Result<JsonB, spi::Error> {
+        recommend_min::recommend_min()
+    }
+
+    /// Returns whether the pg_walrus background worker is running.
+    ///
+    /// Returns NULL if shared memory is not initialized.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.worker_running();
+    /// ```
+    #[pg_extern]
+    fn worker_running() -> Option<bool> {
+        functions::worker_running()
+    }
+
+    /// Returns the time of the worker's last analysis cycle.
+    ///
+    /// Reads shared memory only. Returns NULL if the worker has not yet
+    /// completed a cycle.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// -- Alert if the worker has stalled for more than one checkpoint_timeout
+    /// SELECT now() - walrus.heartbeat() > current_setting('checkpoint_timeout')::interval;
+    /// ```
+    #[pg_extern]
+    fn heartbeat() -> Option<TimestampWithTimeZone> {
+        functions::heartbeat()
+    }
+
+    /// Excludes the next `n` forced checkpoints from growth decisions (superuser only).
+    ///
+    /// Call before running manual `CHECKPOINT` commands during maintenance so
+    /// they do not push the forced checkpoint delta over `walrus.threshold`.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.ignore_next_checkpoints(1);
+    /// CHECKPOINT;
+    /// ```
+    #[pg_extern]
+    fn ignore_next_checkpoints(n: i32) -> i64 {
+        functions::ignore_next_checkpoints(n)
+    }
+
+    /// Wakes the background worker to run a cycle now (superuser only).
+    ///
+    /// Useful after changing walrus.* settings, instead of waiting up to
+    /// `checkpoint_timeout` for the next cycle. Returns false if the worker is
+    /// not running.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.set('walrus.threshold', '5');
+    /// SELECT walrus.kick();
+    /// ```
+    #[pg_extern]
+    fn kick() -> bool {
+        functions::kick()
+    }
+
+    /// Runs one monitoring cycle in this session (superuser only).
+    ///
+    /// For services that do not allow background workers: schedule it every
+    /// `checkpoint_timeout` instead. The first call only records the baseline.
+    /// Does not run a cycle while the background worker is running.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT cron.schedule('walrus-tick', '*/5 * * * *', 'SELECT walrus.tick()');
+    /// ```
+    #[pg_extern]
+    fn tick() -> JsonB {
+        tick::tick()
+    }
+
+    /// Returns when the next automatic adjustment could be applied.
+    ///
+    /// Considers the cooldown and the hourly limit. Returns
+    /// `{earliest_at, blocked_by}`, or NULL if an adjustment is allowed now.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.next_allowed_at();
+    /// -- {"blocked_by": "cooldown", "earliest_at": "2025-01-01T12:05:00.000000+00:00"}
+    /// ```
+    #[pg_extern]
+    fn next_allowed_at() -> Option<JsonB> {
+        rate_limit::next_allowed_at()
+    }
+
+    /// Starts bulk-load mode for `minutes` (superuser only).
+    ///
+    /// Until it expires, the worker multiplies walrus.threshold by
+    /// walrus.bulk_load_threshold_multiplier, so the forced checkpoints of a
+    /// known COPY or pg_restore do not grow max_wal_size permanently. Returns
+    /// the time bulk-load mode ends.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.begin_bulk_load(60);
+    /// -- pg_restore ...
+    /// SELECT walrus.end_bulk_load();
+    /// ```
+    #[pg_extern]
+    fn begin_bulk_load(minutes: i32) -> TimestampWithTimeZone {
+        bulk_load::begin_bulk_load(minutes)
+    }
+
+    /// Ends bulk-load mode early (superuser only).
+    ///
+    /// Returns true if bulk-load mode was active.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.end_bulk_load();
+    /// ```
+    #[pg_extern]
+    fn end_bulk_load() -> bool {
+        bulk_load::end_bulk_load()
+    }
+
+    /// Pins max_wal_size for `minutes` (superuser only); 0 releases the pin.
+    ///
+    /// Until it expires, the worker records its decisions as 'dry_run' history
+    /// rows without applying them. Returns true if a pin was already in effect.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.pin(120);
+    /// -- benchmark ...
+    /// SELECT walrus.pin(0);
+    /// ```
+    #[pg_extern]
+    fn pin(minutes: i32) -> bool {
+        pin::pin(minutes)
+    }
+
+    /// Raises walrus.max to `mb` for `minutes` (superuser only).
+    ///
+    /// Until it expires, grows are capped at the higher of walrus.max and `mb`.
+    /// Returns true if the override raises the cap above walrus.max.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.temporary_max(16384, 240);
+    /// -- run the migration ...
+    /// ```
+    #[pg_extern]
+    fn temporary_max(mb: i32, minutes: i32) -> bool {
+        temporary_max::temporary_max(mb, minutes)
+    }
+
+    /// Sets a walrus.* parameter via ALTER SYSTEM and reloads (superuser only).
+    ///
+    /// Equivalent to `ALTER SYSTEM SET` followed by `pg_reload_conf()`, so the
+    /// new value takes effect without a second statement.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.set('walrus.threshold', '5');
+    /// ```
+    #[pg_extern]
+    fn set(param: &str, value: &str) -> bool {
+        functions::set(param, value)
+    }
+
+    /// Sets max_wal_size once and records it in history (superuser only).
+    ///
+    /// `mb` must be between min_wal_size and walrus.max. The change is logged
+    /// as a 'manual' history row and does not count against the hourly limits.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.set_size(4096);
+    /// ```
+    #[pg_extern]
+    fn set_size(mb: i32) -> bool {
+        functions::set_size(mb)
+    }
+
+    /// Explains the worker's next decision as an ordered array of conditions.
+    ///
+    /// Each element names a gate (enable, stats availability, delta vs threshold,
+    /// cooldown, hourly limit, shrink gates, floor/ceiling clamps) with its inputs
+    /// and outcome. The last element is the final decision. Read-only.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT e->>'condition', e->>'outcome'
+    /// FROM jsonb_array_elements(walrus.explain()) AS e;
+    /// ```
+    #[pg_extern]
+    fn explain() -> JsonB {
+        explain::explain()
+    }
+
+    /// Replays a recorded sizing decision with today's code (superuser only).
+    ///
+    /// Runs the sizing rule on the inputs recorded in the row's metadata and
+    /// reports whether it still produces the recorded size.
+    ///
+    /// # Example
+    ///
+    /// ```sql
+    /// SELECT walrus.reproduce(id)
+    /// FROM walrus.history WHERE action = 'increase' ORDER BY id DESC LIMIT 1;
+    /// ```
+    #[pg_extern]
+    fn reproduce(history_id: i64) -> Result<JsonB, spi::Error> {
+        reproduce::reproduce(history_id)
+    }
 }
 
 /// Extension initialization entry point.
@@ -202,11 +805,8 @@ pub extern "C-unwind" fn _PG_init() {
     }
 
     // Initialize shared memory for worker state (must be before BackgroundWorkerBuilder)
-    // Allow unexpected_cfgs from pgrx macro that checks pg13/pg14 features we don't support
-    #[allow(unexpected_cfgs)]
-    {
-        pg_shmem_init!(WALRUS_STATE);
-    }
+    pg_shmem_init!(WALRUS_STATE);
+    shmem::mark_initialized();
 
     // Register the background worker
     // Restart time matches pg_walsizer: use checkpoint_timeout so if worker crashes,
@@ -249,4 +849,10 @@ pub mod pg_test {
 mod tests {
     include!("tests.rs");
     include!("rate_limit_tests.rs");
+    include!("function_tests.rs");
+    include!("safety_tests.rs");
+    include!("report_tests.rs");
+    include!("cycle_tests.rs");
+    include!("schema_tests.rs");
+    include!("access_tests.rs");
 }
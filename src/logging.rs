@@ -0,0 +1,84 @@
+//! Worker log verbosity for pg_walrus (walrus.log_level).
+//!
+//! Informational worker messages go through `applied()`, `info()`, or
+//! `cycle()` instead of `pgrx::log!` directly:
+//! - 'quiet' logs only applied changes
+//! - 'normal' also logs decisions that were blocked, held, or dry-run
+//! - 'verbose' also logs a line per cycle with the delta and quiet intervals
+//!
+//! Warnings and errors always log; they keep using `pgrx::warning!`/`pgrx::error!`.
+
+use crate::guc::{WALRUS_LOG_LEVEL, WalrusLogLevel};
+use std::fmt::Arguments;
+
+/// Class of an informational worker message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum LogKind {
+    /// A change to max_wal_size (or a co-tuned setting) was applied
+    Applied,
+    /// A decision that did not apply: blocked, held, or dry-run
+    Info,
+    /// Per-cycle detail
+    Cycle,
+}
+
+/// Whether a message of `kind` is logged at `level`.
+#[inline]
+pub(crate) fn should_log(level: WalrusLogLevel, kind: LogKind) -> bool {
+    match kind {
+        LogKind::Applied => true,
+        LogKind::Info => level != WalrusLogLevel::Quiet,
+        LogKind::Cycle => level == WalrusLogLevel::Verbose,
+    }
+}
+
+fn emit(kind: LogKind, message: Arguments) {
+    if should_log(WALRUS_LOG_LEVEL.get(), kind) {
+        pgrx::log!("{}", message);
+    }
+}
+
+/// Log an applied change (always logged).
+pub(crate) fn applied(message: Arguments) {
+    emit(LogKind::Applied, message);
+}
+
+/// Log a decision that did not apply (suppressed at 'quiet').
+pub(crate) fn info(message: Arguments) {
+    emit(LogKind::Info, message);
+}
+
+/// Log per-cycle detail (only at 'verbose').
+pub(crate) fn cycle(message: Arguments) {
+    emit(LogKind::Cycle, message);
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test 'quiet' suppresses informational messages but keeps applied changes
+    #[test]
+    fn test_quiet_suppresses_info() {
+        assert!(should_log(WalrusLogLevel::Quiet, LogKind::Applied));
+        assert!(!should_log(WalrusLogLevel::Quiet, LogKind::Info));
+        assert!(!should_log(WalrusLogLevel::Quiet, LogKind::Cycle));
+    }
+
+    /// Test 'normal' keeps today's messages without per-cycle detail
+    #[test]
+    fn test_normal_logs_info_only() {
+        assert!(should_log(WalrusLogLevel::Normal, LogKind::Applied));
+        assert!(should_log(WalrusLogLevel::Normal, LogKind::Info));
+        assert!(!should_log(WalrusLogLevel::Normal, LogKind::Cycle));
+    }
+
+    /// Test 'verbose' logs everything
+    #[test]
+    fn test_verbose_logs_cycle_detail() {
+        assert!(should_log(WalrusLogLevel::Verbose, LogKind::Applied));
+        assert!(should_log(WalrusLogLevel::Verbose, LogKind::Info));
+        assert!(should_log(WalrusLogLevel::Verbose, LogKind::Cycle));
+    }
+}
@@ -0,0 +1,132 @@
+//! Multi-interval lookback for pg_walrus.
+//!
+//! A single interval's delta misses slow, steady pressure such as one forced
+//! checkpoint every other interval. With `walrus.lookback_intervals` above 1,
+//! the worker compares walrus.threshold against the forced checkpoints of the
+//! last N decision intervals combined, taken from the ring of recent interval
+//! deltas in shared memory (`delta_ring`, shared with walrus.use_rolling_delta).
+//! The grow formula then uses the window's per-interval rate, rounded up, so a
+//! window's worth of checkpoints does not produce an N-times larger grow. The
+//! window never reaches back past the last applied grow (`window_span()`).
+
+use crate::algorithm::DELTA_RING_SIZE;
+
+use serde_json::{Value as JsonValue, json};
+
+/// Forced checkpoints over the newest `intervals` slots of the delta ring.
+///
+/// `pos` is the next slot to write and `len` the number of filled slots (see
+/// `push_delta()`). Until the ring holds `intervals` deltas, sums the ones it has.
+#[inline]
+pub(crate) fn window_delta(
+    ring: &[i32; DELTA_RING_SIZE],
+    pos: i32,
+    len: i32,
+    intervals: i32,
+) -> i64 {
+    let filled = len.clamp(0, DELTA_RING_SIZE as i32) as usize;
+    let newest = pos.max(0) as usize % DELTA_RING_SIZE;
+    (1..=(intervals.max(1) as usize).min(filled))
+        .map(|back| ring[(newest + DELTA_RING_SIZE - back) % DELTA_RING_SIZE] as i64)
+        .sum()
+}
+
+/// Intervals the window spans in the current decision interval.
+///
+/// walrus.lookback_intervals, but never reaching back past the interval of the
+/// last applied grow (`last_grow_interval`, 0 = none): those checkpoints were
+/// already acted on and must not grow the size again in the following intervals.
+#[inline]
+pub(crate) fn window_span(lookback: i32, decision_intervals: i64, last_grow_interval: i64) -> i32 {
+    if last_grow_interval <= 0 {
+        return lookback.max(1);
+    }
+    let since_grow = (decision_intervals - last_grow_interval).clamp(1, i32::MAX as i64) as i32;
+    lookback.clamp(1, since_grow)
+}
+
+/// Per-interval rate of a window of `intervals` intervals, rounded up.
+///
+/// Used as the delta in the grow formula, so it matches a single interval's delta
+/// when `intervals` is 1.
+#[inline]
+pub(crate) fn per_interval_delta(window_delta: i64, intervals: i32) -> i64 {
+    let intervals = intervals.max(1) as i64;
+    window_delta.max(0).div_ceil(intervals)
+}
+
+/// Record the window a grow was decided on, as `(intervals spanned, window_delta)`.
+///
+/// Unchanged when `window` is None (walrus.lookback_intervals = 1 or
+/// walrus.use_rolling_delta). `delta` in the metadata is then the per-interval rate.
+pub(crate) fn with_lookback(mut metadata: JsonValue, window: Option<(i32, i64)>) -> JsonValue {
+    if let Some((intervals, window_delta)) = window {
+        metadata["lookback_intervals"] = json!(intervals);
+        metadata["window_delta"] = json!(window_delta);
+    }
+    metadata
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::{min_grow_delta, push_delta};
+
+    /// Test the window sums the newest deltas, wrapping around the ring
+    #[test]
+    fn test_window_delta() {
+        let mut ring = [0; DELTA_RING_SIZE];
+        let (mut pos, mut len) = (0, 0);
+        for delta in 1..=10 {
+            (pos, len) = push_delta(&mut ring, pos, len, delta);
+        }
+        assert_eq!(window_delta(&ring, pos, len, 1), 10);
+        assert_eq!(window_delta(&ring, pos, len, 3), 10 + 9 + 8);
+        assert_eq!(window_delta(&ring, pos, len, 8), (3..=10).sum::<i64>());
+
+        // Fewer deltas recorded than the window: sum what there is
+        let mut short = [0; DELTA_RING_SIZE];
+        let (pos, len) = push_delta(&mut short, 0, 0, 5);
+        let (pos, len) = push_delta(&mut short, pos, len, 2);
+        assert_eq!(window_delta(&short, pos, len, 4), 7);
+    }
+
+    /// Test one forced checkpoint every other interval grows with lookback 4 but not 1
+    #[test]
+    fn test_steady_pattern_needs_lookback() {
+        let grow_at = min_grow_delta(2, true);
+        let mut ring = [0; DELTA_RING_SIZE];
+        let (mut pos, mut len) = (0, 0);
+        let (mut grows_with_1, mut grows_with_4) = (false, false);
+        for interval in 0..12 {
+            (pos, len) = push_delta(&mut ring, pos, len, (interval % 2 == 0) as i64);
+            grows_with_1 |= window_delta(&ring, pos, len, 1) >= grow_at;
+            grows_with_4 |= window_delta(&ring, pos, len, 4) >= grow_at;
+        }
+        assert!(
+            !grows_with_1,
+            "a single interval never reaches the threshold"
+        );
+        assert!(grows_with_4, "two checkpoints in four intervals reach it");
+    }
+
+    /// Test the window restarts after an applied grow
+    #[test]
+    fn test_window_span() {
+        assert_eq!(window_span(4, 10, 0), 4, "no grow yet");
+        assert_eq!(window_span(4, 11, 10), 1, "first interval after the grow");
+        assert_eq!(window_span(4, 12, 10), 2);
+        assert_eq!(window_span(4, 20, 10), 4);
+        assert_eq!(window_span(1, 20, 19), 1);
+    }
+
+    /// Test the grow formula sees the window's per-interval rate
+    #[test]
+    fn test_per_interval_delta() {
+        assert_eq!(per_interval_delta(7, 1), 7);
+        assert_eq!(per_interval_delta(2, 4), 1, "rounded up");
+        assert_eq!(per_interval_delta(8, 4), 2);
+        assert_eq!(per_interval_delta(0, 4), 0);
+    }
+}
@@ -0,0 +1,144 @@
+//! Startup enforcement of the walrus.min_size floor for pg_walrus.
+//!
+//! walrus.min_size only bounds shrinks: if an installation starts with
+//! max_wal_size already below it, nothing raises max_wal_size until checkpoint
+//! pressure causes a grow. With `walrus.enforce_min_on_start` on, the worker
+//! grows max_wal_size to walrus.min_size once at startup (capped at the
+//! ceiling) and records an 'increase' row with reason
+//! 'enforcing min_size floor'. The grow bypasses the rate limit but counts
+//! against it, including the walrus.max_growth_mb_per_day budget, and follows
+//! walrus.mode and dry-run like other grows.
+
+use crate::access::skip_read_only;
+use crate::algorithm::{ModeGate, mode_gate};
+use crate::apply::{ApplySource, SizeChange, apply_max_wal_size};
+use crate::guc::{WALRUS_MIN_SIZE, WALRUS_MODE};
+use crate::history;
+use crate::logging;
+use crate::pin::effective_dry_run;
+use crate::rate_limit::{record_growth, update_rate_limit_state_after_adjustment};
+use crate::shmem::{self, now_unix};
+use crate::stats::{checkpoint_timeout, get_requested_checkpoints};
+use crate::temporary_max::ceiling_max;
+use crate::worker::in_transaction;
+
+use serde_json::json;
+
+/// History reason for the startup grow to walrus.min_size.
+pub(crate) const MIN_FLOOR_REASON: &str = "enforcing min_size floor";
+
+/// Size to grow to at startup, or None when max_wal_size is already at the floor.
+///
+/// The floor is capped at `ceiling`, so a walrus.min_size above walrus.max
+/// does not grow past what the ceiling check would cap back down.
+#[inline]
+pub(crate) fn min_floor_target(current_size: i32, min_size: i32, ceiling: i32) -> Option<i32> {
+    let target = min_size.min(ceiling);
+    (target > current_size).then_some(target)
+}
+
+/// Grow max_wal_size to walrus.min_size if it is below it.
+///
+/// Called once at worker startup with walrus.enforce_min_on_start and the live
+/// max_wal_size. Returns whether a change was applied.
+pub(crate) fn enforce_min_on_start(enabled: bool, current_size: i32) -> bool {
+    if !enabled {
+        return false;
+    }
+    let min_size = WALRUS_MIN_SIZE.get();
+    let Some(new_size) = min_floor_target(current_size, min_size, ceiling_max()) else {
+        return false;
+    };
+    if skip_read_only(current_size, new_size, MIN_FLOOR_REASON) {
+        return false;
+    }
+
+    let gate = mode_gate(WALRUS_MODE.get(), true, effective_dry_run());
+    if gate == ModeGate::Skip {
+        pgrx::debug1!("pg_walrus: min_size enforcement skipped, walrus.mode disables grows");
+        return false;
+    }
+
+    let growth_mb = (new_size - current_size) as i64;
+    let forced_checkpoints = get_requested_checkpoints().max(0);
+    let timeout_secs = checkpoint_timeout().as_secs() as i32;
+    let metadata = json!({
+        "calculated_size_mb": new_size,
+        "min_size_mb": min_size,
+        "enforced_on_start": true
+    });
+
+    if gate == ModeGate::Record {
+        logging::info(format_args!(
+            "pg_walrus [DRY-RUN]: would change max_wal_size from {} MB to {} MB ({})",
+            current_size, new_size, MIN_FLOOR_REASON
+        ));
+        // The grow bypasses the rate limit but still counts against it
+        update_rate_limit_state_after_adjustment();
+        record_growth(growth_mb);
+
+        let mut metadata = metadata;
+        metadata["dry_run"] = json!(true);
+        metadata["would_apply"] = json!("increase");
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "dry_run",
+                current_size,
+                new_size,
+                forced_checkpoints,
+                timeout_secs,
+                Some(MIN_FLOOR_REASON),
+                Some(history::versioned_metadata(metadata.clone())),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log history: {}", e);
+        }
+        return false;
+    }
+
+    logging::applied(format_args!(
+        "pg_walrus: max_wal_size ({} MB) is below walrus.min_size ({} MB), resizing to {} MB",
+        current_size, min_size, new_size
+    ));
+    let change = SizeChange {
+        action: "increase",
+        old_size_mb: current_size,
+        new_size_mb: new_size,
+        forced_checkpoints,
+        checkpoint_timeout_sec: timeout_secs,
+        reason: MIN_FLOOR_REASON,
+        abort_if_stale: false,
+    };
+    let update = |state: &mut shmem::WalrusState| state.last_adjustment_time = now_unix();
+    let metadata = |apply_duration_ms| history::with_apply_duration(metadata, apply_duration_ms);
+    if let Err(e) = apply_max_wal_size(&change, ApplySource::Worker, update, metadata) {
+        pgrx::warning!("pg_walrus: failed to enforce walrus.min_size: {}", e);
+        return false;
+    }
+    update_rate_limit_state_after_adjustment();
+    record_growth(growth_mb);
+    true
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test only a size below the floor grows, and never past the ceiling
+    #[test]
+    fn test_min_floor_target() {
+        assert_eq!(min_floor_target(512, 1024, 4096), Some(1024));
+        assert_eq!(
+            min_floor_target(1024, 1024, 4096),
+            None,
+            "already at the floor"
+        );
+        assert_eq!(min_floor_target(2048, 1024, 4096), None);
+        assert_eq!(
+            min_floor_target(512, 8192, 4096),
+            Some(4096),
+            "capped at the ceiling"
+        );
+    }
+}
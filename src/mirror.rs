@@ -0,0 +1,155 @@
+//! Decision mirroring for pg_walrus.
+//!
+//! With `walrus.mirror_table` set, every decision written to the history
+//! table is also inserted into that table, so teams can attach triggers and
+//! alerting to a table they own instead of depending on walrus.history's
+//! exact schema. Only `MIRROR_COLUMNS` are written; the table may have more
+//! columns as long as they have defaults.
+//!
+//! Mirroring never disrupts the caller: a missing or incompatible table is
+//! logged and skipped, and a failing INSERT (a trigger error, a CHECK
+//! constraint) runs in its own subtransaction, so it is rolled back and
+//! logged as a WARNING without losing the history row.
+
+use crate::guc::WALRUS_MIRROR_TABLE;
+
+use pgrx::datum::DatumWithOid;
+use pgrx::pg_sys::panic::CaughtError;
+use pgrx::prelude::*;
+use pgrx::{PgTryBuilder, pg_sys};
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+
+/// Core history columns copied to the mirror table, in INSERT order.
+pub const MIRROR_COLUMNS: &[&str] = &[
+    "timestamp",
+    "action",
+    "old_size_mb",
+    "new_size_mb",
+    "forced_checkpoints",
+    "checkpoint_timeout_sec",
+    "reason",
+];
+
+/// The configured walrus.mirror_table, or None when mirroring is off.
+pub(crate) fn mirror_table() -> Option<String> {
+    WALRUS_MIRROR_TABLE
+        .get()
+        .and_then(|s| s.to_str().ok().map(|s| s.trim().to_owned()))
+        .filter(|s| !s.is_empty())
+}
+
+/// Message of a PostgreSQL error caught while mirroring.
+fn caught_message(error: CaughtError) -> String {
+    match error {
+        CaughtError::PostgresError(report)
+        | CaughtError::ErrorReport(report)
+        | CaughtError::RustPanic {
+            ereport: report, ..
+        } => report.message().to_owned(),
+    }
+}
+
+/// Run `insert` in a subtransaction, rolling it back if it fails.
+///
+/// Errors raised by PostgreSQL (including from triggers on the mirror table)
+/// are caught, so the surrounding transaction stays usable.
+fn in_subtransaction(insert: impl FnOnce() -> Result<(), spi::Error>) -> Result<(), String> {
+    unsafe {
+        let old_context = pg_sys::CurrentMemoryContext;
+        let old_owner = pg_sys::CurrentResourceOwner;
+        pg_sys::BeginInternalSubTransaction(ptr::null());
+        let result = PgTryBuilder::new(AssertUnwindSafe(|| insert().map_err(|e| e.to_string())))
+            .catch_others(|e| Err(caught_message(e)))
+            .execute();
+        if result.is_ok() {
+            pg_sys::ReleaseCurrentSubTransaction();
+        } else {
+            pg_sys::RollbackAndReleaseCurrentSubTransaction();
+        }
+        pg_sys::MemoryContextSwitchTo(old_context);
+        pg_sys::CurrentResourceOwner = old_owner;
+        result
+    }
+}
+
+/// Resolve the mirror table and check it has every `MIRROR_COLUMNS` entry.
+///
+/// Returns the quoted table name, or None (after logging why) if the table
+/// does not exist or lacks columns.
+fn resolve_mirror_table(name: &str) -> Result<Option<String>, spi::Error> {
+    let columns: Vec<String> = MIRROR_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let args: &[DatumWithOid<'_>] = &[name.into(), columns.into()];
+    let (table, missing) = Spi::get_two_with_args::<String, Vec<String>>(
+        "SELECT to_regclass($1)::text,
+                ARRAY(SELECT c FROM unnest($2::text[]) AS c
+                      WHERE NOT EXISTS (
+                          SELECT 1 FROM pg_catalog.pg_attribute
+                          WHERE attrelid = to_regclass($1) AND attname = c
+                            AND attnum > 0 AND NOT attisdropped))",
+        args,
+    )?;
+    let Some(table) = table else {
+        pgrx::debug1!(
+            "pg_walrus: walrus.mirror_table {} does not exist, not mirroring",
+            name
+        );
+        return Ok(None);
+    };
+    let missing = missing.unwrap_or_default();
+    if !missing.is_empty() {
+        pgrx::warning!(
+            "pg_walrus: walrus.mirror_table {} lacks column(s) {}, not mirroring",
+            table,
+            missing.join(", ")
+        );
+        return Ok(None);
+    }
+    Ok(Some(table))
+}
+
+/// Insert a decision into walrus.mirror_table, if one is configured.
+///
+/// Called after the history row is written, in the same transaction. Every
+/// failure is logged and swallowed.
+pub(crate) fn mirror_decision(
+    action: &str,
+    old_size_mb: i32,
+    new_size_mb: i32,
+    forced_checkpoints: i64,
+    checkpoint_timeout_sec: i32,
+    reason: Option<&str>,
+) {
+    let Some(name) = mirror_table() else {
+        return;
+    };
+    let result = in_subtransaction(|| {
+        let Some(table) = resolve_mirror_table(&name)? else {
+            return Ok(());
+        };
+        let args: &[DatumWithOid<'_>] = &[
+            action.into(),
+            old_size_mb.into(),
+            new_size_mb.into(),
+            forced_checkpoints.into(),
+            checkpoint_timeout_sec.into(),
+            reason.into(),
+        ];
+        Spi::run_with_args(
+            &format!(
+                "INSERT INTO {} ({}) VALUES (now(), $1, $2, $3, $4, $5, $6)",
+                table,
+                MIRROR_COLUMNS.join(", ")
+            ),
+            args,
+        )
+    });
+    if let Err(e) = result {
+        pgrx::warning!(
+            "pg_walrus: failed to mirror '{}' decision to {}: {}",
+            action,
+            name,
+            e
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Pinning max_wal_size for pg_walrus.
+//!
+//! Some periods (a benchmark, an incident) need a fixed max_wal_size while
+//! still collecting pg_walrus's analysis and history. walrus.dry_run does
+//! that, but it is a GUC that needs ALTER SYSTEM and has to be turned off
+//! again. `walrus.pin(minutes)` sets `pinned_until` in shared memory instead:
+//! until it expires, every worker decision is handled as in dry-run mode,
+//! recorded as a 'dry_run' history row and never applied.
+
+use crate::guc::WALRUS_DRY_RUN;
+use crate::shmem::{self, now_unix};
+
+use pgrx::pg_sys;
+
+/// Whether a pin is in effect at `now`.
+///
+/// A `pinned_until` of 0 (never set, released, or cleared by walrus.reset()) is not pinned.
+#[inline]
+pub(crate) fn is_pinned(now: i64, pinned_until: i64) -> bool {
+    now < pinned_until
+}
+
+/// Whether worker decisions are only recorded: walrus.dry_run, or an unexpired pin.
+pub(crate) fn effective_dry_run() -> bool {
+    WALRUS_DRY_RUN.get() || is_pinned(now_unix(), shmem::read_state().pinned_until)
+}
+
+/// Implementation for pinning max_wal_size.
+///
+/// With `minutes` > 0, sets `pinned_until` to `minutes` from now, replacing
+/// any earlier expiry. With `minutes` = 0, releases the pin.
+///
+/// # Returns
+///
+/// true if max_wal_size was already pinned before the call.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn pin(minutes: i32) -> bool {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.pin() requires superuser");
+    }
+    if minutes < 0 {
+        pgrx::error!("minutes must be >= 0");
+    }
+
+    let now = now_unix();
+    let until = if minutes == 0 {
+        0
+    } else {
+        now.saturating_add(minutes as i64 * 60)
+    };
+    let mut was_pinned = false;
+    shmem::update_state(|state| {
+        was_pinned = is_pinned(now, state.pinned_until);
+        state.pinned_until = until;
+    });
+    if minutes > 0 {
+        pgrx::log!(
+            "pg_walrus: max_wal_size pinned for {} minutes, decisions are recorded only",
+            minutes
+        );
+    } else if was_pinned {
+        pgrx::log!("pg_walrus: max_wal_size pin released");
+    }
+    was_pinned
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test a pin holds only before its expiry
+    #[test]
+    fn test_is_pinned() {
+        assert!(is_pinned(1000, 1600));
+        assert!(is_pinned(1599, 1600));
+        assert!(!is_pinned(1600, 1600));
+        assert!(!is_pinned(1000, 0));
+    }
+}
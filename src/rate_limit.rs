@@ -0,0 +1,498 @@
+//! Rate limiting for automatic max_wal_size adjustments.
+//!
+//! This module enforces the two rate limits applied by the background worker
+//! before any automatic adjustment:
+//! - `walrus.cooldown_sec`: minimum seconds between adjustments
+//! - `walrus.max_changes_per_hour`: maximum adjustments per rolling hour
+//!
+//! Shrinks can instead be given their own hourly budget with
+//! `walrus.max_shrinks_per_hour`, counted in `shrinks_this_hour` and
+//! `shrink_hour_window_start`.
+//!
+//! Grows are additionally bounded by `walrus.max_growth_mb_per_day`, the
+//! cumulative growth allowed per rolling 24-hour window.
+//!
+//! Rate limiting state (`changes_this_hour`, `hour_window_start`,
+//! `growth_mb_this_day`, `day_window_start`) lives in shared memory so it is
+//! visible to `walrus.status()` and `walrus.next_allowed_at()`.
+
+use crate::functions::unix_timestamp_to_iso;
+use crate::guc::{
+    WALRUS_COOLDOWN_SEC, WALRUS_MANUAL_RESETS_COOLDOWN, WALRUS_MAX_CHANGES_PER_HOUR,
+    WALRUS_MAX_GROWTH_MB_PER_DAY, WALRUS_MAX_SHRINKS_PER_HOUR,
+};
+use crate::logging;
+use crate::shmem::{self, WalrusState, now_unix};
+
+use pgrx::JsonB;
+use serde_json::json;
+
+/// Result of a rate limit check.
+///
+/// If the adjustment is blocked, contains the reason and metadata for history logging.
+/// If allowed, the `blocked_by` field is None.
+pub(crate) struct RateLimitResult {
+    /// If Some, the adjustment is blocked. The string describes which limit blocked it.
+    pub blocked_by: Option<String>,
+    /// Reason text for history record (only set when blocked).
+    pub reason: Option<String>,
+    /// Metadata for history record (only set when blocked).
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl RateLimitResult {
+    /// Create an allowed result (not blocked).
+    fn allowed() -> Self {
+        Self {
+            blocked_by: None,
+            reason: None,
+            metadata: None,
+        }
+    }
+
+    /// Create a blocked result with the specified reason and metadata.
+    fn blocked(blocked_by: &str, reason: &str, metadata: serde_json::Value) -> Self {
+        Self {
+            blocked_by: Some(blocked_by.to_string()),
+            reason: Some(reason.to_string()),
+            metadata: Some(metadata),
+        }
+    }
+
+    /// Check if the adjustment is blocked.
+    pub(crate) fn is_blocked(&self) -> bool {
+        self.blocked_by.is_some()
+    }
+
+    /// Log why the adjustment was blocked.
+    ///
+    /// `what` names the blocked change in the message ("adjustment", "shrink").
+    pub(crate) fn log_blocked(&self, what: &str) {
+        let field = |name: &str| {
+            self.metadata
+                .as_ref()
+                .and_then(|m| m.get(name))
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0)
+        };
+        match self.blocked_by.as_deref() {
+            Some("cooldown") => logging::info(format_args!(
+                "pg_walrus: {} blocked - cooldown active ({} seconds remaining)",
+                what,
+                field("cooldown_remaining_sec")
+            )),
+            Some("hourly_limit") => logging::info(format_args!(
+                "pg_walrus: {} blocked - hourly limit reached ({} of {})",
+                what,
+                field("changes_this_hour"),
+                field("max_changes_per_hour")
+            )),
+            Some("shrink_hourly_limit") => logging::info(format_args!(
+                "pg_walrus: {} blocked - shrink hourly limit reached ({} of {})",
+                what,
+                field("shrinks_this_hour"),
+                field("max_shrinks_per_hour")
+            )),
+            _ => logging::info(format_args!(
+                "pg_walrus: {} blocked - {}",
+                what,
+                self.reason.as_deref().unwrap_or("rate limit blocked")
+            )),
+        }
+    }
+}
+
+/// Length of the rolling window for walrus.max_growth_mb_per_day.
+pub(crate) const GROWTH_WINDOW_SECS: i64 = 86400;
+
+/// Whether a rolling window that started at `window_start` has expired at `now`.
+///
+/// A `window_start` of 0 (no window yet) counts as expired.
+#[inline]
+pub(crate) fn window_expired(window_start: i64, now: i64, length_secs: i64) -> bool {
+    window_start <= 0 || now >= window_start.saturating_add(length_secs)
+}
+
+/// Whether a grow of `growth_mb` would take the day's cumulative growth past `cap_mb`.
+///
+/// Growth recorded in an expired window counts as zero. A `cap_mb` of 0 means
+/// unlimited.
+#[inline]
+pub(crate) fn exceeds_daily_growth(
+    growth_this_day: i64,
+    window_start: i64,
+    now: i64,
+    growth_mb: i64,
+    cap_mb: i32,
+) -> bool {
+    if cap_mb <= 0 {
+        return false;
+    }
+    let used = if window_expired(window_start, now, GROWTH_WINDOW_SECS) {
+        0
+    } else {
+        growth_this_day
+    };
+    used.saturating_add(growth_mb) > cap_mb as i64
+}
+
+/// Growth window after applying a grow of `growth_mb` at `now`.
+///
+/// Returns `(growth_this_day, window_start)`, starting a new window when the
+/// current one has expired.
+#[inline]
+pub(crate) fn next_growth_window(
+    growth_this_day: i64,
+    window_start: i64,
+    now: i64,
+    growth_mb: i64,
+) -> (i64, i64) {
+    if window_expired(window_start, now, GROWTH_WINDOW_SECS) {
+        (growth_mb.max(0), now)
+    } else {
+        (
+            growth_this_day.saturating_add(growth_mb.max(0)),
+            window_start,
+        )
+    }
+}
+
+/// Check rate limiting constraints before applying an adjustment.
+///
+/// Checks two rate limits in order (FR-014 specifies cooldown is checked first):
+/// 1. Cooldown period: minimum seconds between adjustments (walrus.cooldown_sec)
+/// 2. Hourly limit: maximum adjustments per rolling one-hour window (walrus.max_changes_per_hour)
+///
+/// Special cases:
+/// - If cooldown_sec = 0, cooldown check is skipped entirely
+/// - If max_changes_per_hour = 0, all automatic adjustments are blocked
+///
+/// Returns:
+/// - RateLimitResult with blocked_by=None if adjustment is allowed
+/// - RateLimitResult with blocked_by=Some("cooldown"|"hourly_limit") if blocked
+pub(crate) fn check_rate_limit() -> RateLimitResult {
+    let now = now_unix();
+    let cooldown_sec = WALRUS_COOLDOWN_SEC.get();
+    let max_changes_per_hour = WALRUS_MAX_CHANGES_PER_HOUR.get();
+    let state = shmem::read_state();
+
+    // Edge case: max_changes_per_hour = 0 blocks all automatic adjustments
+    if max_changes_per_hour == 0 {
+        return RateLimitResult::blocked(
+            "hourly_limit",
+            "automatic adjustments disabled (max_changes_per_hour = 0)",
+            json!({
+                "blocked_by": "hourly_limit",
+                "max_changes_per_hour": 0,
+                "changes_this_hour": state.changes_this_hour
+            }),
+        );
+    }
+
+    // Check 1: Cooldown period (skip if cooldown_sec = 0)
+    if let Some(blocked) = check_cooldown(&state, now, cooldown_sec) {
+        return blocked;
+    }
+
+    // Check 2: Hourly limit (only after cooldown passes)
+    // First, check if the current hour window has expired
+    let hour_expired = if state.hour_window_start > 0 {
+        now >= state.hour_window_start.saturating_add(3600)
+    } else {
+        // No previous window, will start fresh
+        true
+    };
+
+    // If window hasn't expired, check if we're at the limit
+    if !hour_expired && state.changes_this_hour >= max_changes_per_hour {
+        return RateLimitResult::blocked(
+            "hourly_limit",
+            "hourly limit reached",
+            json!({
+                "blocked_by": "hourly_limit",
+                "max_changes_per_hour": max_changes_per_hour,
+                "changes_this_hour": state.changes_this_hour,
+                "hour_window_start": state.hour_window_start
+            }),
+        );
+    }
+
+    // Adjustment is allowed
+    RateLimitResult::allowed()
+}
+
+/// Blocked result if the cooldown after the last adjustment is still active.
+///
+/// Never blocks when cooldown_sec = 0 or no adjustment has been made.
+fn check_cooldown(state: &WalrusState, now: i64, cooldown_sec: i32) -> Option<RateLimitResult> {
+    if cooldown_sec <= 0 || state.last_adjustment_time <= 0 {
+        return None;
+    }
+    let cooldown_end = state
+        .last_adjustment_time
+        .saturating_add(cooldown_sec as i64);
+    // Use strict inequality: blocked if now < cooldown_end (not <=)
+    // This means adjustment is allowed when now >= cooldown_end
+    if now >= cooldown_end {
+        return None;
+    }
+    Some(RateLimitResult::blocked(
+        "cooldown",
+        "cooldown active",
+        json!({
+            "blocked_by": "cooldown",
+            "cooldown_sec": cooldown_sec,
+            "cooldown_remaining_sec": cooldown_end.saturating_sub(now),
+            "last_adjustment_time": state.last_adjustment_time
+        }),
+    ))
+}
+
+/// Whether walrus.cooldown_sec after the last adjustment is still running.
+///
+/// walrus.analyze(apply := true) honors this unless called with `force`, so a
+/// manual apply does not undo a change the worker has just made.
+pub(crate) fn cooldown_active(state: &WalrusState) -> bool {
+    check_cooldown(state, now_unix(), WALRUS_COOLDOWN_SEC.get()).is_some()
+}
+
+/// Check rate limiting constraints before applying a shrink.
+///
+/// With walrus.max_shrinks_per_hour = -1 (the default) shrinks share
+/// walrus.max_changes_per_hour with grows, and this is `check_rate_limit()`.
+/// Otherwise the cooldown still applies, but the hourly limit is
+/// walrus.max_shrinks_per_hour, counted in its own window, so grows cannot
+/// use up the shrink budget. walrus.max_changes_per_hour = 0 still blocks
+/// shrinks whatever their own budget, as the stop for all automatic changes.
+///
+/// Returns blocked_by=Some("shrink_hourly_limit") when that budget is spent.
+pub(crate) fn check_shrink_rate_limit() -> RateLimitResult {
+    let max_shrinks_per_hour = WALRUS_MAX_SHRINKS_PER_HOUR.get();
+    if max_shrinks_per_hour < 0 || WALRUS_MAX_CHANGES_PER_HOUR.get() == 0 {
+        return check_rate_limit();
+    }
+
+    let now = now_unix();
+    let state = shmem::read_state();
+
+    if max_shrinks_per_hour == 0 {
+        return RateLimitResult::blocked(
+            "shrink_hourly_limit",
+            "automatic shrinks disabled (max_shrinks_per_hour = 0)",
+            json!({
+                "blocked_by": "shrink_hourly_limit",
+                "max_shrinks_per_hour": 0,
+                "shrinks_this_hour": state.shrinks_this_hour
+            }),
+        );
+    }
+
+    if let Some(blocked) = check_cooldown(&state, now, WALRUS_COOLDOWN_SEC.get()) {
+        return blocked;
+    }
+
+    if !window_expired(state.shrink_hour_window_start, now, 3600)
+        && state.shrinks_this_hour >= max_shrinks_per_hour
+    {
+        return RateLimitResult::blocked(
+            "shrink_hourly_limit",
+            "shrink hourly limit reached",
+            json!({
+                "blocked_by": "shrink_hourly_limit",
+                "max_shrinks_per_hour": max_shrinks_per_hour,
+                "shrinks_this_hour": state.shrinks_this_hour,
+                "shrink_hour_window_start": state.shrink_hour_window_start
+            }),
+        );
+    }
+
+    RateLimitResult::allowed()
+}
+
+/// Earliest time an adjustment could be applied, given the rate-limit state.
+///
+/// Uses the same math as `check_rate_limit()`: the cooldown ends at
+/// last_adjustment_time + cooldown_sec, and an hourly window at the limit
+/// frees up at hour_window_start + 3600. When both block, the later time wins.
+///
+/// Returns None when an adjustment is allowed at `now`, otherwise
+/// `(earliest_at, blocked_by)`. `earliest_at` is None when
+/// max_changes_per_hour = 0, since automatic adjustments never resume.
+pub(crate) fn next_allowed_time(
+    state: &WalrusState,
+    now: i64,
+    cooldown_sec: i32,
+    max_changes_per_hour: i32,
+) -> Option<(Option<i64>, &'static str)> {
+    if max_changes_per_hour == 0 {
+        return Some((None, "hourly_limit"));
+    }
+
+    let last_adjustment = state.last_adjustment_time;
+    let cooldown_end = last_adjustment.saturating_add(cooldown_sec as i64);
+    let in_cooldown = cooldown_sec > 0 && last_adjustment > 0 && now < cooldown_end;
+
+    let hour_end = state.hour_window_start.saturating_add(3600);
+    let at_hourly_limit = !window_expired(state.hour_window_start, now, 3600)
+        && state.changes_this_hour >= max_changes_per_hour;
+
+    match (in_cooldown, at_hourly_limit) {
+        (true, true) if hour_end > cooldown_end => Some((Some(hour_end), "hourly_limit")),
+        (true, _) => Some((Some(cooldown_end), "cooldown")),
+        (false, true) => Some((Some(hour_end), "hourly_limit")),
+        (false, false) => None,
+    }
+}
+
+/// Implementation for the time of the next allowed automatic adjustment.
+///
+/// Returns JSONB `{earliest_at, blocked_by}` (ISO 8601 timestamp, and
+/// "cooldown" or "hourly_limit"), or NULL if an adjustment could be applied
+/// now. `earliest_at` is null when max_changes_per_hour = 0. The daily growth
+/// cap depends on the size of the grow, so it is not considered.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn next_allowed_at() -> Option<JsonB> {
+    let (earliest_at, blocked_by) = next_allowed_time(
+        &shmem::read_state(),
+        now_unix(),
+        WALRUS_COOLDOWN_SEC.get(),
+        WALRUS_MAX_CHANGES_PER_HOUR.get(),
+    )?;
+    Some(JsonB(json!({
+        "earliest_at": earliest_at.and_then(unix_timestamp_to_iso),
+        "blocked_by": blocked_by
+    })))
+}
+
+/// Update rate limiting state after a successful adjustment.
+///
+/// Called after an adjustment is applied (or would be applied in dry-run mode).
+/// Updates changes_this_hour and hour_window_start in shared memory.
+pub(crate) fn update_rate_limit_state_after_adjustment() {
+    let now = now_unix();
+    shmem::update_state(|state| {
+        // Check if hour window has expired
+        let hour_expired = if state.hour_window_start > 0 {
+            now >= state.hour_window_start.saturating_add(3600)
+        } else {
+            true
+        };
+
+        if hour_expired {
+            // Start new window
+            state.changes_this_hour = 1;
+            state.hour_window_start = now;
+        } else {
+            // Increment count in current window
+            state.changes_this_hour += 1;
+        }
+    });
+}
+
+/// Update rate limiting state after a manual change.
+///
+/// Called after walrus.analyze(apply := true) or walrus.set_size() applies a
+/// change. The caller has already set `last_adjustment_time`, so the cooldown
+/// delays the worker's next change either way. With
+/// walrus.manual_resets_cooldown on, the manual change is also counted in the
+/// hourly window like an automatic one (`update_rate_limit_state_after_adjustment()`),
+/// so automatic and manual changes draw on one budget. With it off, the
+/// hourly window is left alone.
+pub(crate) fn update_rate_limit_state_after_manual() {
+    if WALRUS_MANUAL_RESETS_COOLDOWN.get() {
+        update_rate_limit_state_after_adjustment();
+    }
+}
+
+/// Update rate limiting state after a shrink.
+///
+/// Called after a shrink is applied (or would be applied in dry-run mode).
+/// Counts it in the shrink window when walrus.max_shrinks_per_hour is set,
+/// otherwise in the shared window like any other adjustment.
+pub(crate) fn update_rate_limit_state_after_shrink() {
+    if WALRUS_MAX_SHRINKS_PER_HOUR.get() < 0 {
+        update_rate_limit_state_after_adjustment();
+        return;
+    }
+
+    let now = now_unix();
+    shmem::update_state(|state| {
+        if window_expired(state.shrink_hour_window_start, now, 3600) {
+            state.shrinks_this_hour = 1;
+            state.shrink_hour_window_start = now;
+        } else {
+            state.shrinks_this_hour += 1;
+        }
+    });
+}
+
+/// Check rate limiting constraints before applying a grow of `growth_mb`.
+///
+/// Runs `check_rate_limit()` first, then walrus.max_growth_mb_per_day.
+/// Returns blocked_by=Some("daily_growth_cap") if the grow would exceed the
+/// cumulative growth allowed in the current 24-hour window.
+pub(crate) fn check_grow_rate_limit(growth_mb: i64) -> RateLimitResult {
+    let result = check_rate_limit();
+    if result.is_blocked() {
+        return result;
+    }
+
+    let cap_mb = WALRUS_MAX_GROWTH_MB_PER_DAY.get();
+    let state = shmem::read_state();
+    if exceeds_daily_growth(
+        state.growth_mb_this_day,
+        state.day_window_start,
+        now_unix(),
+        growth_mb,
+        cap_mb,
+    ) {
+        return RateLimitResult::blocked(
+            "daily_growth_cap",
+            "daily growth cap reached",
+            json!({
+                "blocked_by": "daily_growth_cap",
+                "max_growth_mb_per_day": cap_mb,
+                "growth_mb_this_day": state.growth_mb_this_day,
+                "requested_growth_mb": growth_mb,
+                "day_window_start": state.day_window_start
+            }),
+        );
+    }
+
+    result
+}
+
+/// Rate limit an automatic `action` from `current_mb` to `target_mb` would hit now.
+///
+/// Read-only: runs the worker's checks (`check_grow_rate_limit()` for a grow,
+/// `check_shrink_rate_limit()` for a shrink) without recording anything.
+/// Returns the blocking limit's `blocked_by`, or None if the change would be
+/// allowed or `action` is not a change.
+pub(crate) fn automatic_rate_limit(
+    action: &str,
+    current_mb: i32,
+    target_mb: i32,
+) -> Option<String> {
+    let result = match action {
+        "increase" | "capped" => check_grow_rate_limit(target_mb.saturating_sub(current_mb) as i64),
+        "decrease" => check_shrink_rate_limit(),
+        _ => return None,
+    };
+    result.blocked_by
+}
+
+/// Add a grow of `growth_mb` to the rolling day window in shared memory.
+///
+/// Called after a grow is applied (or would be applied in dry-run mode).
+pub(crate) fn record_growth(growth_mb: i64) {
+    let now = now_unix();
+    shmem::update_state(|state| {
+        (state.growth_mb_this_day, state.day_window_start) = next_growth_window(
+            state.growth_mb_this_day,
+            state.day_window_start,
+            now,
+            growth_mb,
+        );
+    });
+}
@@ -346,8 +346,8 @@ fn test_manual_adjust_bypasses_rate_limits() {
     //
     // The design is verified by code inspection:
     // - walrus.analyze(apply := true) in functions.rs does NOT call update_rate_limit_state_after_adjustment()
-    // - It only updates last_adjustment_time, not changes_this_hour/hour_window_start
-    // - This means manual adjustments bypass the rate limiting counters
+    // - It calls update_rate_limit_state_after_manual(), which counts it in the window
+    // - This means manual adjustments never block themselves on the hourly limits
 }
 
 /// Test clock skew handling: backward clock jump extends cooldown (T050)
@@ -386,3 +386,381 @@ fn test_clock_skew_extends_cooldown() {
         state.last_adjustment_time = 0;
     });
 }
+
+// =========================================================================
+// Daily Growth Cap Tests (walrus.max_growth_mb_per_day)
+// =========================================================================
+
+/// Test that walrus.max_growth_mb_per_day defaults to 0 (unlimited)
+#[pg_test]
+fn test_guc_max_growth_mb_per_day_default() {
+    let result =
+        Spi::get_one::<&str>("SHOW walrus.max_growth_mb_per_day").expect("SHOW failed");
+    assert_eq!(result, Some("0"), "walrus.max_growth_mb_per_day should default to '0'");
+}
+
+/// Test the growth window rolls over after 24 hours
+#[pg_test]
+fn test_daily_growth_window_rollover() {
+    use crate::rate_limit::{GROWTH_WINDOW_SECS, next_growth_window, window_expired};
+
+    let start = 1_000_000;
+    assert!(window_expired(0, start, GROWTH_WINDOW_SECS), "no window yet");
+    assert!(!window_expired(start, start + GROWTH_WINDOW_SECS - 1, GROWTH_WINDOW_SECS));
+    assert!(window_expired(start, start + GROWTH_WINDOW_SECS, GROWTH_WINDOW_SECS));
+
+    // First grow starts a window; later grows in the window accumulate
+    let (growth, window) = next_growth_window(0, 0, start, 1024);
+    assert_eq!((growth, window), (1024, start));
+    let (growth, window) = next_growth_window(growth, window, start + 3600, 2048);
+    assert_eq!((growth, window), (3072, start));
+
+    // After 24 hours the next grow starts a fresh window
+    let later = start + GROWTH_WINDOW_SECS;
+    assert_eq!(next_growth_window(growth, window, later, 512), (512, later));
+}
+
+/// Test cap enforcement arithmetic
+#[pg_test]
+fn test_daily_growth_cap_enforcement() {
+    use crate::rate_limit::{GROWTH_WINDOW_SECS, exceeds_daily_growth};
+
+    let start = 1_000_000;
+    let now = start + 3600;
+    // 3072 MB already grown today with a 4096 MB cap
+    assert!(!exceeds_daily_growth(3072, start, now, 1024, 4096), "exactly at cap is allowed");
+    assert!(exceeds_daily_growth(3072, start, now, 1025, 4096), "one MB over is blocked");
+    // Unlimited when the cap is 0
+    assert!(!exceeds_daily_growth(i64::MAX, start, now, 1 << 20, 0));
+    // Growth from an expired window does not count
+    let next_day = start + GROWTH_WINDOW_SECS;
+    assert!(!exceeds_daily_growth(3072, start, next_day, 4096, 4096));
+    // A single grow larger than the cap is blocked even in a fresh window
+    assert!(exceeds_daily_growth(0, 0, now, 8192, 4096));
+}
+
+/// Test status() reports growth in the current window only
+#[pg_test]
+fn test_status_growth_mb_this_day() {
+    use crate::rate_limit::GROWTH_WINDOW_SECS;
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    let now = shmem::now_unix();
+    shmem::update_state(|state| {
+        state.growth_mb_this_day = 2048;
+        state.day_window_start = now - 60;
+    });
+    let growth = Spi::get_one::<i64>("SELECT (walrus.status()->>'growth_mb_this_day')::bigint")
+        .expect("query failed");
+    assert_eq!(growth, Some(2048));
+
+    shmem::update_state(|state| state.day_window_start = now - GROWTH_WINDOW_SECS - 1);
+    let growth = Spi::get_one::<i64>("SELECT (walrus.status()->>'growth_mb_this_day')::bigint")
+        .expect("query failed");
+    assert_eq!(growth, Some(0), "expired window reports no growth");
+
+    shmem::update_state(|state| *state = saved);
+}
+
+// =========================================================================
+// walrus.next_allowed_at() Tests
+// =========================================================================
+
+/// Test the next allowed time is the end of the cooldown
+#[pg_test]
+fn test_next_allowed_time_cooldown_bound() {
+    use crate::rate_limit::next_allowed_time;
+    use crate::shmem::WalrusState;
+
+    let now = 1_000_000;
+    let state = WalrusState {
+        last_adjustment_time: now - 100,
+        changes_this_hour: 1,
+        hour_window_start: now - 100,
+        ..Default::default()
+    };
+    assert_eq!(
+        next_allowed_time(&state, now, 300, 4),
+        Some((Some(now + 200), "cooldown"))
+    );
+
+    // Cooldown over and hourly limit not reached: allowed now
+    assert_eq!(next_allowed_time(&state, now + 200, 300, 4), None);
+    assert_eq!(next_allowed_time(&state, now, 0, 4), None, "cooldown disabled");
+}
+
+/// Test the next allowed time is the end of the hourly window when at the limit
+#[pg_test]
+fn test_next_allowed_time_hourly_bound() {
+    use crate::rate_limit::next_allowed_time;
+    use crate::shmem::WalrusState;
+
+    let now = 1_000_000;
+    let state = WalrusState {
+        last_adjustment_time: now - 600,
+        changes_this_hour: 4,
+        hour_window_start: now - 1000,
+        ..Default::default()
+    };
+    // Cooldown (300s) already over, hourly window frees up in 2600s
+    assert_eq!(
+        next_allowed_time(&state, now, 300, 4),
+        Some((Some(now + 2600), "hourly_limit"))
+    );
+
+    // Both block: the later hourly window wins
+    assert_eq!(
+        next_allowed_time(&state, now, 900, 4),
+        Some((Some(now + 2600), "hourly_limit"))
+    );
+
+    // Window expired: allowed again
+    assert_eq!(next_allowed_time(&state, now + 2600, 300, 4), None);
+
+    // max_changes_per_hour = 0 never allows
+    assert_eq!(
+        next_allowed_time(&WalrusState::default(), now, 300, 0),
+        Some((None, "hourly_limit"))
+    );
+}
+
+/// Test walrus.next_allowed_at() reports a cooldown after an adjustment and NULL otherwise
+#[pg_test]
+fn test_next_allowed_at_sql() {
+    use crate::shmem::{self, now_unix};
+
+    let saved = shmem::read_state();
+    shmem::update_state(|state| {
+        state.last_adjustment_time = 0;
+        state.changes_this_hour = 0;
+        state.hour_window_start = 0;
+    });
+    let allowed = Spi::get_one::<pgrx::JsonB>("SELECT walrus.next_allowed_at()")
+        .expect("query failed");
+
+    shmem::update_state(|state| state.last_adjustment_time = now_unix());
+    let blocked_by = Spi::get_one::<&str>("SELECT walrus.next_allowed_at()->>'blocked_by'")
+        .expect("query failed");
+    let has_time =
+        Spi::get_one::<bool>("SELECT (walrus.next_allowed_at()->>'earliest_at') IS NOT NULL")
+            .expect("query failed");
+
+    shmem::update_state(|state| *state = saved);
+
+    assert!(allowed.is_none(), "NULL when an adjustment is allowed now");
+    assert_eq!(blocked_by, Some("cooldown"));
+    assert_eq!(has_time, Some(true));
+}
+
+// =========================================================================
+// walrus.max_shrinks_per_hour Tests
+// =========================================================================
+
+/// Test grows use up the shared budget without blocking a shrink with its own budget
+#[pg_test]
+fn test_shrink_budget_independent_of_grows() {
+    use crate::rate_limit::{
+        check_grow_rate_limit, check_shrink_rate_limit, update_rate_limit_state_after_adjustment,
+        update_rate_limit_state_after_shrink,
+    };
+    use crate::shmem;
+
+    let set = |name: &std::ffi::CStr, value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            name.as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    let saved = shmem::read_state();
+    shmem::reset_state();
+    set(c"walrus.cooldown_sec", c"0");
+    set(c"walrus.max_changes_per_hour", c"2");
+
+    // Two grows use up the shared hourly budget
+    update_rate_limit_state_after_adjustment();
+    update_rate_limit_state_after_adjustment();
+    let grow = check_grow_rate_limit(1024);
+    assert_eq!(grow.blocked_by.as_deref(), Some("hourly_limit"));
+
+    // Sharing the limit (-1), the shrink is starved too
+    set(c"walrus.max_shrinks_per_hour", c"-1");
+    let shared = check_shrink_rate_limit();
+    assert_eq!(shared.blocked_by.as_deref(), Some("hourly_limit"));
+
+    // With its own budget the shrink is allowed, and counted separately
+    set(c"walrus.max_shrinks_per_hour", c"1");
+    assert!(!check_shrink_rate_limit().is_blocked());
+    update_rate_limit_state_after_shrink();
+    let state = shmem::read_state();
+    assert_eq!(state.shrinks_this_hour, 1);
+    assert_eq!(state.changes_this_hour, 2, "shrinks do not use the grow budget");
+
+    let second = check_shrink_rate_limit();
+    assert_eq!(second.blocked_by.as_deref(), Some("shrink_hourly_limit"));
+
+    // max_changes_per_hour = 0 stops shrinks even with budget left
+    set(c"walrus.max_shrinks_per_hour", c"10");
+    set(c"walrus.max_changes_per_hour", c"0");
+    let stopped = check_shrink_rate_limit();
+    assert_eq!(stopped.blocked_by.as_deref(), Some("hourly_limit"));
+
+    set(c"walrus.max_shrinks_per_hour", c"-1");
+    set(c"walrus.max_changes_per_hour", c"4");
+    set(c"walrus.cooldown_sec", c"300");
+    shmem::update_state(|state| *state = saved);
+}
+
+/// Test recommendation() reports an active cooldown would block the automatic apply
+#[pg_test]
+fn test_recommendation_would_be_rate_limited() {
+    use crate::shmem::{self, now_unix};
+
+    let saved = shmem::read_state();
+    shmem::update_state(|state| {
+        state.last_adjustment_time = 0;
+        state.changes_this_hour = 0;
+        state.hour_window_start = 0;
+    });
+    let allowed = Spi::get_one::<pgrx::JsonB>(
+        "SELECT walrus.recommendation(hypothetical_delta := 10)",
+    )
+    .expect("query failed")
+    .expect("NULL recommendation");
+
+    shmem::update_state(|state| state.last_adjustment_time = now_unix());
+    let blocked = Spi::get_one::<pgrx::JsonB>(
+        "SELECT walrus.recommendation(hypothetical_delta := 10)",
+    )
+    .expect("query failed")
+    .expect("NULL recommendation");
+
+    shmem::update_state(|state| *state = saved);
+
+    assert_eq!(blocked.0["action"], "increase");
+    assert_eq!(blocked.0["would_be_rate_limited"], true);
+    assert_eq!(blocked.0["rate_limit_reason"], "cooldown");
+    assert_eq!(blocked.0["manual_apply_bypasses_rate_limits"], true);
+    assert_eq!(allowed.0["would_be_rate_limited"], false);
+    assert!(allowed.0["rate_limit_reason"].is_null());
+}
+
+/// Test analyze(apply := true) waits out the cooldown after a recent adjustment unless forced
+#[pg_test]
+fn test_analyze_apply_respects_cooldown() {
+    use crate::shmem::{self, now_unix};
+
+    let saved = shmem::read_state();
+    let original = crate::stats::get_current_max_wal_size();
+    let current = crate::stats::get_requested_checkpoints();
+    shmem::update_state(|state| {
+        state.prev_requested = current.max(1);
+        state.pending_delta = 100;
+        state.ignore_checkpoints = 0;
+        state.last_adjustment_time = now_unix();
+    });
+
+    let declined = Spi::get_one::<pgrx::JsonB>("SELECT walrus.analyze(apply := true)")
+        .expect("query failed")
+        .expect("analyze should return JSONB");
+    let forced =
+        Spi::get_one::<pgrx::JsonB>("SELECT walrus.analyze(apply := true, force := true)")
+            .expect("query failed")
+            .expect("analyze should return JSONB");
+
+    shmem::update_state(|state| *state = saved);
+    // This backend has not reloaded, so the write is undone directly
+    crate::config::execute_alter_system(original).expect("restore failed");
+
+    assert_eq!(declined.0["recommendation"]["action"], "increase");
+    assert_eq!(declined.0["applied"], false);
+    assert_eq!(declined.0["reason"], "cooldown active");
+    assert_eq!(declined.0["recommendation"]["manual_apply_bypasses_rate_limits"], false);
+    assert_eq!(forced.0["applied"], true, "force overrides the cooldown");
+    assert!(forced.0.get("reason").is_none());
+}
+
+/// Override walrus.manual_resets_cooldown for this backend.
+fn set_manual_resets_cooldown(value: &std::ffi::CStr) {
+    unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.manual_resets_cooldown".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    }
+}
+
+/// Test a manual apply cooldown-blocks an immediate automatic grow, and counts
+/// in the open hourly window unless walrus.manual_resets_cooldown is off
+#[pg_test]
+fn test_manual_apply_resets_cooldown() {
+    use crate::rate_limit::check_grow_rate_limit;
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    let original = crate::stats::get_current_max_wal_size();
+    let target = if original == 2048 { 3072 } else { 2048 };
+    // Three automatic changes already counted in a window opened a minute ago
+    let window_start = shmem::now_unix() - 60;
+    let clear = || {
+        shmem::update_state(|state| {
+            state.last_adjustment_time = 0;
+            state.changes_this_hour = 3;
+            state.hour_window_start = window_start;
+        })
+    };
+
+    clear();
+    Spi::run(&format!("SELECT walrus.set_size({target})")).expect("query failed");
+    let counted = shmem::read_state();
+    let blocked = check_grow_rate_limit(64);
+
+    set_manual_resets_cooldown(c"off");
+    clear();
+    // This backend has not reloaded, so max_wal_size still differs from target
+    Spi::run(&format!("SELECT walrus.set_size({target})")).expect("query failed");
+    let uncounted = shmem::read_state();
+    let still_blocked = check_grow_rate_limit(64);
+
+    set_manual_resets_cooldown(c"on");
+    crate::config::execute_alter_system(original).expect("restore failed");
+    Spi::run("DELETE FROM walrus.history WHERE action = 'manual'").expect("delete failed");
+    shmem::update_state(|state| *state = saved);
+
+    assert!(counted.last_adjustment_time > 0);
+    assert_eq!(counted.changes_this_hour, 4, "counted in the open window");
+    assert_eq!(counted.hour_window_start, window_start, "the window is kept");
+    assert_eq!(blocked.blocked_by.as_deref(), Some("cooldown"));
+    assert!(uncounted.last_adjustment_time > 0, "the cooldown starts either way");
+    assert_eq!(uncounted.changes_this_hour, 3);
+    assert_eq!(still_blocked.blocked_by.as_deref(), Some("cooldown"));
+}
+
+/// Test walrus.temporary_max() raises the grow cap until it expires
+#[pg_test]
+fn test_temporary_max_raises_cap_until_expiry() {
+    use crate::guc::WALRUS_MAX;
+    use crate::shmem;
+    use crate::temporary_max::effective_max;
+
+    let saved = shmem::read_state();
+    let base = WALRUS_MAX.get();
+    let raised = Spi::get_one::<bool>(&format!("SELECT walrus.temporary_max({}, 60)", base * 2))
+        .expect("query failed");
+    assert_eq!(raised, Some(true));
+    assert_eq!(effective_max(), base * 2, "the override is the cap while active");
+    let status = crate::status::status();
+    assert_eq!(status.0["temporary_max_mb"], base * 2);
+    assert!(status.0["temporary_max_until"].is_string());
+
+    // Expired: grows are capped at walrus.max again
+    shmem::update_state(|s| s.temp_max_until = shmem::now_unix() - 1);
+    assert_eq!(effective_max(), base);
+    assert!(crate::status::status().0["temporary_max_mb"].is_null());
+
+    shmem::update_state(|s| *s = saved);
+}
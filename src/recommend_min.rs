@@ -0,0 +1,110 @@
+//! walrus.min_size recommendation for pg_walrus.
+//!
+//! `walrus.recommend_min()` looks for shrinks that went too far: a 'decrease'
+//! whose next applied change was a grow ('increase' or 'capped') shortly
+//! afterwards. The smallest size such a shrink reached is the trough the
+//! workload could not live with, so walrus.min_size should sit slightly above
+//! it. Advisory only: nothing is changed.
+
+use crate::guc::{WALRUS_COOLDOWN_SEC, WALRUS_HISTORY_RETENTION_DAYS};
+use crate::history::{history_table, history_table_exists};
+
+use pgrx::JsonB;
+use pgrx::datum::DatumWithOid;
+use pgrx::prelude::*;
+use serde_json::json;
+
+/// Checkpoint intervals after walrus.cooldown_sec within which a grow
+/// following a shrink counts as a bounce.
+pub(crate) const BOUNCE_INTERVALS: i32 = 2;
+
+/// Margin added above the lowest bounced size, in percent.
+pub(crate) const MIN_MARGIN_PCT: i32 = 10;
+
+/// Suggested walrus.min_size for a lowest bounced size of `trough_mb`.
+///
+/// `trough_mb` plus `MIN_MARGIN_PCT` percent, rounded up to a whole MB.
+#[inline]
+pub(crate) fn suggest_min_size(trough_mb: i32) -> i32 {
+    let margin = (trough_mb.max(0) as i64 * MIN_MARGIN_PCT as i64 + 99) / 100;
+    (trough_mb as i64 + margin).min(i32::MAX as i64) as i32
+}
+
+/// Implementation for the walrus.min_size recommendation.
+///
+/// Within walrus.history_retention_days, a 'decrease' counts as a
+/// shrink-then-grow event when the next applied change is an 'increase' or
+/// 'capped' row no later than walrus.cooldown_sec plus `BOUNCE_INTERVALS`
+/// checkpoint intervals after it. Returns a JSONB object with:
+/// - `suggested_min_mb`: `suggest_min_size()` of the smallest size such a
+///   shrink reached (null when there were no events)
+/// - `observed_shrink_then_grow_events`: Number of events
+/// - `rationale`: Explanation of the suggestion
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn recommend_min() -> Result<JsonB, spi::Error> {
+    let retention_days = WALRUS_HISTORY_RETENTION_DAYS.get();
+    let cooldown_sec = WALRUS_COOLDOWN_SEC.get();
+
+    let (mut events, mut trough) = (0, None);
+    if history_table_exists()? {
+        let args: &[DatumWithOid<'_>] = &[retention_days.into(), cooldown_sec.into()];
+        let row = Spi::get_two_with_args::<i64, i32>(
+            &format!(
+                "WITH applied AS (
+                    SELECT action, timestamp, new_size_mb, checkpoint_timeout_sec,
+                           lead(action) OVER w AS next_action,
+                           lead(timestamp) OVER w AS next_time
+                    FROM {}
+                    WHERE action IN ('increase', 'decrease', 'capped', 'manual')
+                      AND timestamp >= now() - $1 * interval '1 day'
+                    WINDOW w AS (ORDER BY timestamp, id)
+                )
+                SELECT count(*), min(new_size_mb)
+                FROM applied
+                WHERE action = 'decrease'
+                  AND next_action IN ('increase', 'capped')
+                  AND next_time <= timestamp
+                      + ($2 + {} * checkpoint_timeout_sec) * interval '1 second'",
+                history_table(),
+                BOUNCE_INTERVALS
+            ),
+            args,
+        )?;
+        events = row.0.unwrap_or(0);
+        trough = row.1;
+    }
+
+    let suggested = trough.map(suggest_min_size);
+    let rationale = match (trough, suggested) {
+        (Some(trough), Some(suggested)) => format!(
+            "{} shrink(s) in the last {} days were followed by a grow; the lowest reached {} MB, so keep walrus.min_size at least {} MB",
+            events, retention_days, trough, suggested
+        ),
+        _ => format!(
+            "no shrink in the last {} days was followed by a grow; walrus.min_size is not limiting this workload",
+            retention_days
+        ),
+    };
+
+    Ok(JsonB(json!({
+        "suggested_min_mb": suggested,
+        "observed_shrink_then_grow_events": events,
+        "rationale": rationale,
+    })))
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the suggestion is slightly above the trough, rounded up
+    #[test]
+    fn test_suggest_min_size() {
+        assert_eq!(suggest_min_size(1000), 1100);
+        assert_eq!(suggest_min_size(1024), 1127, "102.4 MB margin rounds up");
+        assert_eq!(suggest_min_size(1), 2);
+        assert_eq!(suggest_min_size(i32::MAX), i32::MAX);
+    }
+}
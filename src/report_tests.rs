@@ -0,0 +1,813 @@
+// Integration tests for pg_walrus reporting functions.
+//
+// These tests cover the SQL functions that summarize the history table
+// rather than returning its rows.
+
+// =========================================================================
+// walrus.decision_stats() Tests
+// =========================================================================
+
+/// Test decision_stats() computes the gaps between increase/decrease rows only
+#[pg_test]
+fn test_decision_stats_mean_interval() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    // Decisions 40, 30, and 10 minutes ago: gaps of 600 s and 1200 s.
+    // The skipped row in between is not a decision and must not split a gap.
+    Spi::run(
+        "INSERT INTO walrus.history
+            (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES
+            (now() - interval '40 minutes', 'increase', 1024, 2048, 3, 300),
+            (now() - interval '30 minutes', 'decrease', 2048, 1536, 3, 300),
+            (now() - interval '20 minutes', 'skipped', 1536, 3072, 5, 300),
+            (now() - interval '10 minutes', 'increase', 1536, 3072, 5, 300)",
+    )
+    .expect("insert failed");
+
+    let stats = Spi::get_one::<pgrx::JsonB>("SELECT walrus.decision_stats()")
+        .expect("query failed")
+        .expect("decision_stats returned NULL")
+        .0;
+
+    assert_eq!(stats["increase_count"], serde_json::json!(2));
+    assert_eq!(stats["decrease_count"], serde_json::json!(1));
+    assert_eq!(stats["intervals"], serde_json::json!(2));
+    assert_eq!(stats["mean_interval_sec"].as_f64(), Some(900.0));
+    assert_eq!(stats["p50_interval_sec"].as_f64(), Some(900.0));
+    assert_eq!(stats["p95_interval_sec"].as_f64(), Some(1170.0));
+}
+
+/// Test decision_stats() has null interval statistics with a single decision
+#[pg_test]
+fn test_decision_stats_single_decision() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run(
+        "INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES ('increase', 1024, 2048, 3, 300)",
+    )
+    .expect("insert failed");
+
+    let stats = Spi::get_one::<pgrx::JsonB>("SELECT walrus.decision_stats()")
+        .expect("query failed")
+        .expect("decision_stats returned NULL")
+        .0;
+
+    assert_eq!(stats["intervals"], serde_json::json!(0));
+    assert!(stats["mean_interval_sec"].is_null());
+}
+
+// =========================================================================
+// walrus.history_archive Tests
+// =========================================================================
+
+/// Test archive_before_delete rolls deleted rows up by day across cleanup runs
+#[pg_test]
+fn test_archive_before_delete_accumulates_daily_rollup() {
+    let set_archive = |value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.archive_before_delete".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    let insert = |rows: &str| {
+        Spi::run(&format!(
+            "INSERT INTO walrus.history
+                (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints,
+                 checkpoint_timeout_sec)
+             VALUES {rows}"
+        ))
+        .expect("insert failed");
+    };
+    // One hour into the UTC day 20 days ago, well past the 7-day retention
+    let day = "((current_date - 20)::timestamp AT TIME ZONE 'UTC' + interval '1 hour')";
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run("DELETE FROM walrus.history_archive").expect("delete failed");
+    set_archive(c"on");
+
+    // The skipped row's proposed size is not applied and must not set the peak
+    insert(&format!(
+        "({day}, 'increase', 1024, 2048, 3, 300),
+         ({day}, 'capped', 2048, 4096, 5, 300),
+         ({day}, 'skipped', 2048, 8192, 5, 300),
+         (now(), 'increase', 4096, 8192, 5, 300)"
+    ));
+    assert_eq!(crate::history::cleanup_old_history().expect("cleanup failed"), 3);
+
+    // A later run deletes more rows for the same day
+    insert(&format!("({day}, 'decrease', 4096, 768, 0, 300)"));
+    assert_eq!(crate::history::cleanup_old_history().expect("cleanup failed"), 1);
+    set_archive(c"off");
+
+    let archived = Spi::get_one::<i64>("SELECT count(*) FROM walrus.history_archive")
+        .expect("query failed");
+    assert_eq!(archived, Some(1), "the recent row must not be archived");
+    let rollup = Spi::get_one::<pgrx::JsonB>(
+        "SELECT to_jsonb(a) - 'day' FROM walrus.history_archive a
+         WHERE day = current_date - 20",
+    )
+    .expect("query failed")
+    .expect("no archive row for the day")
+    .0;
+    assert_eq!(
+        rollup,
+        serde_json::json!({
+            "increases": 1,
+            "decreases": 1,
+            "capped": 1,
+            "peak_size_mb": 4096,
+            "min_size_mb": 768
+        })
+    );
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run("DELETE FROM walrus.history_archive").expect("delete failed");
+}
+
+// =========================================================================
+// walrus.validate_config() Tests
+// =========================================================================
+
+/// Names of the checks in a validate_config() result
+fn validate_config_checks() -> Vec<String> {
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.validate_config()")
+        .expect("query failed")
+        .expect("validate_config returned NULL")
+        .0;
+    let checks: Vec<String> = result["findings"]
+        .as_array()
+        .expect("findings should be an array")
+        .iter()
+        .map(|f| f["check"].as_str().expect("check should be a string").to_owned())
+        .collect();
+    assert_eq!(result["ok"], serde_json::json!(checks.is_empty()));
+    checks
+}
+
+/// Test crafted history and deltas trigger threshold_too_high and max_frequently_hit
+#[pg_test]
+fn test_validate_config_history_findings() {
+    let saved = crate::shmem::read_state();
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+
+    // Forced checkpoints in 4 of the last 5 intervals, none reaching the threshold
+    crate::shmem::update_state(|state| {
+        state.delta_ring = [1, 1, 0, 1, 1, 0, 0, 0];
+        state.delta_ring_len = 5;
+    });
+    let checks = validate_config_checks();
+    assert!(
+        !checks.contains(&"threshold_too_high".to_owned()),
+        "an empty history should not count as days without grows: {checks:?}"
+    );
+
+    // Two days of history without a grow, and walrus.max hit three times
+    Spi::run(
+        "INSERT INTO walrus.history
+            (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES
+            (now() - interval '2 days', 'skipped', 1024, 2048, 1, 300),
+            (now() - interval '1 day', 'capped', 4096, 4096, 6, 300),
+            (now() - interval '12 hours', 'capped', 4096, 4096, 6, 300),
+            (now() - interval '1 hour', 'capped', 4096, 4096, 6, 300)",
+    )
+    .expect("insert failed");
+    let checks = validate_config_checks();
+    assert!(checks.contains(&"threshold_too_high".to_owned()), "{checks:?}");
+    assert!(checks.contains(&"max_frequently_hit".to_owned()), "{checks:?}");
+
+    // A grow in the window clears the threshold finding
+    Spi::run(
+        "INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES ('increase', 1024, 2048, 3, 300)",
+    )
+    .expect("insert failed");
+    let checks = validate_config_checks();
+    assert!(!checks.contains(&"threshold_too_high".to_owned()), "{checks:?}");
+
+    crate::shmem::update_state(|state| {
+        state.delta_ring = saved.delta_ring;
+        state.delta_ring_len = saved.delta_ring_len;
+    });
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+}
+
+/// Test walrus.min_size at the current max_wal_size triggers shrink_cannot_fire
+#[pg_test]
+fn test_validate_config_shrink_cannot_fire() {
+    let set_min_size = |value: &str| {
+        let value = std::ffi::CString::new(value).expect("invalid value");
+        unsafe {
+            pg_sys::SetConfigOption(
+                c"walrus.min_size".as_ptr(),
+                value.as_ptr(),
+                pg_sys::GucContext::PGC_SIGHUP,
+                pg_sys::GucSource::PGC_S_OVERRIDE,
+            );
+        }
+    };
+    let saved =
+        Spi::get_one::<String>("SELECT setting FROM pg_settings WHERE name = 'walrus.min_size'")
+            .expect("query failed")
+            .expect("walrus.min_size not found");
+    let current = crate::stats::get_current_max_wal_size();
+
+    set_min_size(&current.to_string());
+    let checks = validate_config_checks();
+    assert!(checks.contains(&"shrink_cannot_fire".to_owned()), "{checks:?}");
+
+    set_min_size("2");
+    let checks = validate_config_checks();
+    assert!(!checks.contains(&"shrink_cannot_fire".to_owned()), "{checks:?}");
+
+    set_min_size(&saved);
+}
+
+// =========================================================================
+// walrus.checkpoint_stats() Tests
+// =========================================================================
+
+/// Test checkpoint_stats() has every field and delta is requested - prev_requested
+#[pg_test]
+fn test_checkpoint_stats_fields_and_delta() {
+    let saved = crate::shmem::read_state().prev_requested;
+    let requested = crate::stats::get_requested_checkpoints();
+    assert!(requested >= 0, "checkpoint statistics should be available");
+    crate::shmem::update_state(|state| state.prev_requested = requested - 3);
+
+    let stats = Spi::get_one::<pgrx::JsonB>("SELECT walrus.checkpoint_stats()")
+        .expect("query failed")
+        .expect("checkpoint_stats returned NULL")
+        .0;
+    crate::shmem::update_state(|state| state.prev_requested = saved);
+
+    for field in [
+        "requested",
+        "timed",
+        "prev_requested",
+        "delta",
+        "ignore_checkpoints",
+        "max_wal_size_mb",
+        "checkpoint_timeout_sec",
+    ] {
+        assert!(stats.get(field).is_some(), "missing field {field}: {stats}");
+    }
+    let requested = stats["requested"].as_i64().expect("requested should be a number");
+    let prev = stats["prev_requested"].as_i64().expect("prev_requested should be a number");
+    assert_eq!(prev, requested - 3);
+    assert_eq!(stats["delta"].as_i64(), Some(requested - prev));
+    assert!(stats["timed"].as_i64().is_some_and(|t| t >= 0));
+    assert_eq!(
+        stats["max_wal_size_mb"].as_i64(),
+        Some(crate::stats::get_current_max_wal_size() as i64)
+    );
+}
+
+// =========================================================================
+// walrus.status() last_action Tests
+// =========================================================================
+
+/// Test a grow decision is reported as last_action 'increase'
+#[pg_test]
+fn test_status_last_action_after_grow() {
+    use crate::history::{
+        DECISION_ACTIONS, decision_action_code, decision_action_name, insert_history_record,
+    };
+
+    let saved = crate::shmem::read_state();
+    for action in DECISION_ACTIONS {
+        assert_eq!(decision_action_name(decision_action_code(action)), Some(*action));
+    }
+    assert_eq!(decision_action_code("note"), 0);
+    assert_eq!(decision_action_name(0), None);
+
+    // A note is not a decision and leaves last_action unset
+    crate::shmem::update_state(|state| {
+        state.last_action = 0;
+        state.last_decision_time = 0;
+    });
+    insert_history_record("note", 1024, 1024, 0, 300, Some("test note"), None)
+        .expect("insert failed");
+    let status = Spi::get_one::<pgrx::JsonB>("SELECT walrus.status()")
+        .expect("query failed")
+        .expect("status returned NULL")
+        .0;
+    assert_eq!(status["last_action"], serde_json::Value::Null);
+    assert_eq!(status["last_decision_time"], serde_json::Value::Null);
+
+    // Record the row the grow path writes
+    insert_history_record(
+        "increase",
+        1024,
+        2048,
+        5,
+        300,
+        Some("Forced checkpoints exceeded threshold"),
+        None,
+    )
+    .expect("insert failed");
+    let status = Spi::get_one::<pgrx::JsonB>("SELECT walrus.status()")
+        .expect("query failed")
+        .expect("status returned NULL")
+        .0;
+    assert_eq!(status["last_action"], serde_json::json!("increase"));
+    assert!(status["last_decision_time"].is_string());
+
+    crate::shmem::update_state(|state| {
+        state.last_action = saved.last_action;
+        state.last_decision_time = saved.last_decision_time;
+    });
+    Spi::run(
+        "DELETE FROM walrus.history
+         WHERE reason IN ('test note', 'Forced checkpoints exceeded threshold')",
+    )
+    .expect("delete failed");
+}
+
+// =========================================================================
+// walrus.preview_shrink_path() Tests
+// =========================================================================
+
+/// Test the previewed path matches iterated calculate_shrink_size() calls
+#[pg_test]
+fn test_preview_shrink_path_matches_calculate_shrink_size() {
+    use crate::algorithm::{calculate_shrink_size, shrink_floor};
+
+    let current = crate::stats::get_current_max_wal_size();
+    let floor = shrink_floor(
+        crate::guc::WALRUS_MIN_SIZE.get(),
+        crate::stats::get_current_min_wal_size(),
+    );
+    let rounding = crate::guc::WALRUS_SHRINK_ROUNDING.get();
+
+    let mut expected = vec![(0, current)];
+    let mut size = current;
+    for step in 1..=6 {
+        if size > floor {
+            size = calculate_shrink_size(size, 0.6, floor, rounding);
+        }
+        expected.push((step, size));
+    }
+
+    let mut path = Vec::new();
+    Spi::connect(|client| {
+        let rows = client
+            .select(
+                "SELECT step, size_mb FROM walrus.preview_shrink_path(0.6, 6) ORDER BY step",
+                None,
+                &[],
+            )
+            .expect("query failed");
+        for row in rows {
+            let step = row.get::<i32>(1).expect("get failed").expect("step is NULL");
+            let size = row.get::<i32>(2).expect("get failed").expect("size_mb is NULL");
+            path.push((step, size));
+        }
+    });
+    assert_eq!(path, expected);
+
+    // steps defaults to 10, plus the starting size
+    let rows = Spi::get_one::<i64>("SELECT count(*) FROM walrus.preview_shrink_path(0.75)")
+        .expect("query failed");
+    assert_eq!(rows, Some(11));
+}
+
+/// Test preview_shrink_path() rejects a factor outside (0, 1)
+#[pg_test(error = "factor must be between 0 and 1 (exclusive)")]
+fn test_preview_shrink_path_rejects_factor() {
+    Spi::run("SELECT * FROM walrus.preview_shrink_path(1.5)").expect("query failed");
+}
+
+// =========================================================================
+// walrus.recommend_min() Tests
+// =========================================================================
+
+/// Test recommend_min() suggests a size above the lowest shrink that bounced back
+#[pg_test]
+fn test_recommend_min_from_bounce() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    // With the default 300 s cooldown and 300 s checkpoint_timeout, a grow up
+    // to 900 s after a shrink is a bounce. The 3072 and 1024 MB shrinks bounce;
+    // the 1536 MB shrink is followed by a grow only 30 minutes later.
+    Spi::run(
+        "INSERT INTO walrus.history
+            (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES
+            (now() - interval '120 minutes', 'decrease', 4096, 3072, 0, 300),
+            (now() - interval '115 minutes', 'skipped', 3072, 6144, 1, 300),
+            (now() - interval '110 minutes', 'increase', 3072, 6144, 1, 300),
+            (now() - interval '80 minutes', 'decrease', 2048, 1024, 0, 300),
+            (now() - interval '75 minutes', 'capped', 1024, 4096, 5, 300),
+            (now() - interval '50 minutes', 'decrease', 4096, 1536, 0, 300),
+            (now() - interval '20 minutes', 'increase', 1536, 3072, 2, 300)",
+    )
+    .expect("insert failed");
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.recommend_min()")
+        .expect("query failed")
+        .expect("recommend_min returned NULL")
+        .0;
+
+    assert_eq!(result["observed_shrink_then_grow_events"], serde_json::json!(2));
+    assert_eq!(result["suggested_min_mb"], serde_json::json!(1127));
+    assert!(
+        result["rationale"]
+            .as_str()
+            .is_some_and(|r| r.contains("1024 MB")),
+        "rationale names the trough: {}",
+        result["rationale"]
+    );
+}
+
+/// Test recommend_min() has no suggestion without a shrink-then-grow event
+#[pg_test]
+fn test_recommend_min_no_events() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run(
+        "INSERT INTO walrus.history
+            (timestamp, action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec)
+         VALUES
+            (now() - interval '30 minutes', 'increase', 1024, 2048, 3, 300),
+            (now() - interval '10 minutes', 'decrease', 2048, 1536, 0, 300)",
+    )
+    .expect("insert failed");
+
+    let result = Spi::get_one::<pgrx::JsonB>("SELECT walrus.recommend_min()")
+        .expect("query failed")
+        .expect("recommend_min returned NULL")
+        .0;
+
+    assert_eq!(result["observed_shrink_then_grow_events"], serde_json::json!(0));
+    assert!(result["suggested_min_mb"].is_null());
+}
+
+// =========================================================================
+// walrus.status() max_wal_size display Tests
+// =========================================================================
+
+/// Test status() shows max_wal_size as PostgreSQL displays it, matching the MB value
+#[pg_test]
+fn test_status_max_wal_size_display() {
+    let display = Spi::get_one::<String>(
+        "SELECT walrus.status()->>'current_max_wal_size_display'",
+    )
+    .expect("query failed")
+    .expect("display string missing");
+    let show = Spi::get_one::<String>("SHOW max_wal_size").expect("SHOW failed");
+    assert_eq!(Some(display.clone()), show, "matches SHOW max_wal_size");
+
+    // The display string parses back to within one WAL segment of the MB value
+    let (display_mb, status_mb) = Spi::get_two::<i64, i64>(&format!(
+        "SELECT pg_size_bytes('{display}') / (1024 * 1024),
+                (walrus.status()->>'current_max_wal_size_mb')::bigint"
+    ))
+    .expect("query failed");
+    let segment_mb = crate::stats::get_wal_segment_size_mb() as i64;
+    let diff = (display_mb.expect("parse failed") - status_mb.expect("MB missing")).abs();
+    assert!(diff <= segment_mb, "{display} is {diff} MB from the MB value");
+}
+
+// =========================================================================
+// History idempotency key Tests
+// =========================================================================
+
+/// Test inserting the same applied decision twice leaves one history row
+#[pg_test]
+fn test_same_decision_inserted_once() {
+    use crate::history::{
+        decision_key, insert_history_record, versioned_metadata, with_idempotency_key,
+    };
+
+    let key = decision_key(1_700_000_000, "increase", 5, 2048);
+    assert_eq!(key, decision_key(1_700_000_000, "increase", 5, 2048), "deterministic");
+    assert_ne!(key, decision_key(1_700_000_300, "increase", 5, 2048));
+
+    let metadata = versioned_metadata(with_idempotency_key(
+        serde_json::json!({"delta": 5, "multiplier": 6, "calculated_size_mb": 2048}),
+        &key,
+    ));
+    for _ in 0..2 {
+        let metadata = Some(metadata.clone());
+        insert_history_record("increase", 1024, 2048, 5, 300, Some("retry"), metadata)
+            .expect("insert failed");
+    }
+
+    let rows = Spi::get_one_with_args::<i64>(
+        "SELECT count(*) FROM walrus.history WHERE metadata->>'idempotency_key' = $1",
+        &[key.as_str().into()],
+    )
+    .expect("query failed");
+    assert_eq!(rows, Some(1), "the retried insert is deduplicated");
+
+    // Rows without a key are never deduplicated
+    for _ in 0..2 {
+        insert_history_record("note", 2048, 2048, 5, 300, Some("unkeyed"), None)
+            .expect("insert failed");
+    }
+    let notes = Spi::get_one::<i64>("SELECT count(*) FROM walrus.history WHERE reason = 'unkeyed'")
+        .expect("query failed");
+    assert_eq!(notes, Some(2));
+}
+
+// =========================================================================
+// walrus.history_ndjson() Tests
+// =========================================================================
+
+/// Test every NDJSON line parses on its own and since filters like the table
+#[pg_test]
+fn test_history_ndjson_lines_parse() {
+    use crate::history::insert_history_record;
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    let metadata = serde_json::json!({"note": "line \"one\"\nwith a newline"});
+    insert_history_record("increase", 1024, 2048, 3, 300, Some("a, b"), Some(metadata))
+        .expect("insert failed");
+    insert_history_record("decrease", 2048, 1536, 0, 300, None, None).expect("insert failed");
+    insert_history_record("note", 1536, 1536, 0, 300, Some("old"), None).expect("insert failed");
+    Spi::run(
+        "UPDATE walrus.history SET timestamp = now() - interval '2 hours' WHERE reason = 'old'",
+    )
+    .expect("update failed");
+
+    let lines = |sql: &str| -> Vec<String> {
+        Spi::connect(|client| {
+            client
+                .select(sql, None, &[])
+                .expect("query failed")
+                .filter_map(|row| row.get::<String>(1).ok().flatten())
+                .collect()
+        })
+    };
+
+    let all = lines("SELECT * FROM walrus.history_ndjson()");
+    assert_eq!(all.len(), 3);
+    for line in &all {
+        assert!(!line.contains('\n'), "one row per line: {line}");
+        let row: serde_json::Value = serde_json::from_str(line).expect("line is not valid JSON");
+        assert!(row["id"].is_i64() && row["action"].is_string());
+    }
+    let first: serde_json::Value = serde_json::from_str(&all[0]).expect("invalid JSON");
+    assert_eq!(first["reason"], "a, b");
+    assert_eq!(first["metadata"]["note"], "line \"one\"\nwith a newline");
+
+    let recent = lines("SELECT * FROM walrus.history_ndjson(since := now() - interval '1 hour')");
+    let expected = Spi::get_one::<i64>(
+        "SELECT count(*) FROM walrus.history WHERE timestamp >= now() - interval '1 hour'",
+    )
+    .expect("query failed");
+    assert_eq!(Some(recent.len() as i64), expected);
+    assert_eq!(recent.len(), 2, "the older row is filtered out");
+}
+
+/// Test walrus.threshold_inclusive decides whether delta == threshold grows
+#[pg_test]
+fn test_threshold_inclusive_edge() {
+    let set = |value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.threshold_inclusive".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    let action = |delta: i64| {
+        Spi::get_one::<String>(&format!(
+            "SELECT walrus.recommendation(hypothetical_delta := {delta})->>'action'"
+        ))
+        .expect("query failed")
+        .expect("action is null")
+    };
+    // explain() only reaches the threshold check once a baseline exists
+    let saved = crate::shmem::read_state();
+    let current = crate::stats::get_requested_checkpoints();
+    crate::shmem::update_state(|s| s.prev_requested = current.max(1));
+    let comparison = || {
+        let explain = Spi::get_one::<pgrx::JsonB>("SELECT walrus.explain()")
+            .expect("query failed")
+            .expect("explain is null");
+        let checks = explain.0.as_array().cloned().unwrap_or_default();
+        let check = checks
+            .iter()
+            .find(|c| c["condition"] == "delta_vs_threshold")
+            .expect("no delta_vs_threshold check");
+        check["inputs"]["comparison"].as_str().unwrap_or_default().to_string()
+    };
+    let threshold = Spi::get_one::<String>("SELECT current_setting('walrus.threshold')")
+        .expect("query failed")
+        .expect("threshold is null")
+        .parse::<i64>()
+        .expect("threshold is not an integer");
+
+    assert_eq!(action(threshold), "increase", "default: delta == threshold grows");
+    assert_eq!(comparison(), "delta >= threshold");
+
+    set(c"off");
+    assert_ne!(action(threshold), "increase", "off: delta == threshold stays quiet");
+    assert_eq!(action(threshold + 1), "increase");
+    assert_eq!(comparison(), "delta > threshold");
+    set(c"on");
+    crate::shmem::update_state(|s| *s = saved);
+}
+
+/// Test walrus.pin() only records worker decisions until the pin expires
+#[pg_test]
+fn test_pin_records_only_until_expiry() {
+    use crate::algorithm::{ModeGate, mode_gate};
+    use crate::guc::WalrusMode;
+    use crate::pin::effective_dry_run;
+    use crate::shmem::{self, now_unix};
+
+    let status = || {
+        Spi::get_one::<pgrx::JsonB>("SELECT walrus.status()")
+            .expect("query failed")
+            .expect("status should return JSONB")
+    };
+    let saved = shmem::read_state();
+    shmem::update_state(|state| state.pinned_until = 0);
+
+    let was_pinned = Spi::get_one::<bool>("SELECT walrus.pin(30)").expect("query failed");
+    assert_eq!(was_pinned, Some(false));
+    assert!(status().0["pinned_until"].is_string());
+    assert!(effective_dry_run());
+    assert_eq!(mode_gate(WalrusMode::Both, true, effective_dry_run()), ModeGate::Record);
+    assert_eq!(mode_gate(WalrusMode::Both, false, effective_dry_run()), ModeGate::Record);
+
+    // Past its expiry the worker applies again
+    shmem::update_state(|state| state.pinned_until = now_unix() - 1);
+    let expired = status();
+    let gate = mode_gate(WalrusMode::Both, true, effective_dry_run());
+    let released = Spi::get_one::<bool>("SELECT walrus.pin(0)").expect("query failed");
+    shmem::update_state(|state| *state = saved);
+
+    assert!(expired.0["pinned_until"].is_null());
+    assert_eq!(gate, ModeGate::Apply);
+    assert_eq!(released, Some(false), "an expired pin is no longer in effect");
+}
+
+/// Test decisions are also written to walrus.mirror_table, and a bad table never fails the insert
+#[pg_test]
+fn test_mirror_table_receives_decisions() {
+    use crate::history::insert_history_record;
+
+    let set_mirror = |value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.mirror_table".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    Spi::run(
+        "CREATE TABLE public.walrus_alerts (
+            id BIGSERIAL PRIMARY KEY,
+            timestamp TIMESTAMPTZ,
+            action TEXT,
+            old_size_mb INTEGER,
+            new_size_mb INTEGER,
+            forced_checkpoints BIGINT,
+            checkpoint_timeout_sec INTEGER,
+            reason TEXT,
+            acknowledged BOOLEAN NOT NULL DEFAULT false
+        )",
+    )
+    .expect("create failed");
+    Spi::run("CREATE TABLE public.walrus_alerts_narrow (action TEXT)").expect("create failed");
+
+    set_mirror(c"public.walrus_alerts");
+    insert_history_record("increase", 1024, 2048, 3, 300, Some("mirrored"), None)
+        .expect("insert failed");
+    insert_history_record("note", 2048, 2048, 3, 300, Some("not a decision"), None)
+        .expect("insert failed");
+    set_mirror(c"public.walrus_alerts_narrow");
+    let narrow = insert_history_record("decrease", 2048, 1024, 3, 300, None, None);
+    set_mirror(c"public.walrus_alerts_missing");
+    let missing = insert_history_record("decrease", 2048, 1024, 3, 300, None, None);
+    set_mirror(c"");
+
+    let (action, new_size) = Spi::get_two::<String, i32>(
+        "SELECT action, new_size_mb FROM public.walrus_alerts WHERE reason = 'mirrored'",
+    )
+    .expect("query failed");
+    assert_eq!(action.as_deref(), Some("increase"));
+    assert_eq!(new_size, Some(2048));
+    let mirrored = Spi::get_one::<i64>("SELECT count(*) FROM public.walrus_alerts")
+        .expect("query failed");
+    assert_eq!(mirrored, Some(1), "only decisions are mirrored");
+    let narrow_rows = Spi::get_one::<i64>("SELECT count(*) FROM public.walrus_alerts_narrow")
+        .expect("query failed");
+    assert_eq!(narrow_rows, Some(0), "a table lacking columns is skipped");
+    assert!(narrow.is_ok() && missing.is_ok(), "mirroring never fails the history insert");
+}
+
+/// Test a tiny baseline against a huge checkpoint count awaits a baseline instead of growing
+#[pg_test]
+fn test_lifetime_delta_awaits_baseline() {
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    shmem::update_state(|state| {
+        state.prev_requested = 1;
+        state.pending_delta = 1_000_000;
+        state.ignore_checkpoints = 0;
+    });
+    let rec = Spi::get_one::<pgrx::JsonB>("SELECT walrus.recommendation()")
+        .expect("query failed")
+        .expect("recommendation should return JSONB");
+    shmem::update_state(|state| *state = saved);
+
+    assert_eq!(rec.0["action"], "none", "no massive grow: {}", rec.0);
+    assert_eq!(rec.0["reason"], "awaiting baseline checkpoint count");
+    assert_eq!(rec.0["recommended_size_mb"], rec.0["current_size_mb"]);
+}
+
+/// Test walrus.guc_sources() lists every walrus.* GUC, including restart-only walrus.database
+#[pg_test]
+fn test_guc_sources_lists_all_settings() {
+    let listed = Spi::get_one::<i64>("SELECT count(*) FROM walrus.guc_sources()")
+        .expect("query failed");
+    let registered =
+        Spi::get_one::<i64>("SELECT count(*) FROM pg_settings WHERE name LIKE 'walrus.%'")
+            .expect("query failed");
+    assert_eq!(listed, registered, "every walrus.* GUC is listed");
+
+    let context = Spi::get_one::<&str>(
+        "SELECT s.context FROM walrus.guc_sources() g JOIN pg_settings s USING (name)
+         WHERE g.name = 'walrus.database'",
+    )
+    .expect("query failed");
+    assert_eq!(context, Some("postmaster"));
+
+    let (source, pending) = Spi::get_two::<&str, bool>(
+        "SELECT source, pending_restart FROM walrus.guc_sources()
+         WHERE name = 'walrus.threshold'",
+    )
+    .expect("query failed");
+    assert!(source.is_some());
+    assert_eq!(pending, Some(false));
+}
+
+/// Test a max_wal_size the reload did not take is logged as an external_change
+#[pg_test]
+fn test_verify_applied_size_records_mismatch() {
+    use crate::config::verify_applied_size;
+    use crate::stats::get_current_max_wal_size;
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    let saved = crate::shmem::read_state();
+    let effective = get_current_max_wal_size();
+    let intended = effective + 64;
+    // Written long before this backend's last configuration load
+    crate::shmem::update_state(|s| {
+        s.intended_size_mb = intended;
+        s.intended_size_at = 1;
+    });
+
+    assert!(verify_applied_size(effective, 300), "intended and effective differ");
+    let row = Spi::get_two::<i32, i32>(
+        "SELECT (metadata->>'intended_size_mb')::int, (metadata->>'actual_size_mb')::int
+         FROM walrus.history WHERE action = 'external_change'",
+    )
+    .expect("query failed");
+    assert_eq!(row, (Some(intended), Some(effective)));
+    assert_eq!(crate::shmem::read_state().intended_size_mb, 0, "verified once");
+
+    // A value that took effect records nothing
+    crate::shmem::update_state(|s| s.intended_size_mb = effective);
+    assert!(!verify_applied_size(effective, 300));
+    let count = Spi::get_one::<i64>(
+        "SELECT count(*) FROM walrus.history WHERE action = 'external_change'",
+    )
+    .expect("query failed");
+    assert_eq!(count, Some(1));
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    crate::shmem::update_state(|s| *s = saved);
+}
+
+/// Test status() reports the worker from its shared-memory pid, like worker_running()
+#[pg_test]
+fn test_status_worker_check_succeeds() {
+    let status = || crate::status::status().0;
+    assert_eq!(status()["worker_running"], true);
+    assert!(status()["worker_status_error"].is_null());
+
+    let saved = crate::shmem::read_state().worker_pid;
+    crate::shmem::update_state(|state| state.worker_pid = 0);
+    let without_pid = status();
+    crate::shmem::update_state(|state| state.worker_pid = saved);
+    assert_eq!(without_pid["worker_running"], false, "no published pid");
+}
+
+/// Test walrus.status() identifies the cluster by port and data directory
+#[pg_test]
+fn test_status_includes_cluster_id() {
+    let status = crate::status::status();
+    let cluster_id = status.0["cluster_id"].as_str().expect("cluster_id should be a string");
+    let port = Spi::get_one::<&str>("SELECT current_setting('port')").expect("query failed");
+    assert!(!cluster_id.is_empty());
+    assert!(cluster_id.starts_with(&format!("{}:", port.unwrap())));
+}
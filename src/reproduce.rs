@@ -0,0 +1,377 @@
+//! Decision replay for pg_walrus.
+//!
+//! Every grow (`increase` or `capped`) and shrink (`decrease`) the worker
+//! applies from checkpoint activity records the inputs the sizing rule ran on
+//! under `decision_inputs` in its metadata: the size it started from, the
+//! interval's delta and threshold, the quiet intervals, the hourly rate-limit
+//! state, the rule's own settings, and the size it produced.
+//!
+//! Applied rows that do not come from a sizing rule record no inputs:
+//! `walrus.analyze(apply := true)` changes, caps down to a lowered walrus.max
+//! (`ceiling`), emergency shrinks (`emergency`), and the startup walrus.min_size
+//! grow (`min_floor`). Their target is a setting or the recommendation, and
+//! their metadata already names it.
+//!
+//! `walrus.reproduce(history_id)` runs today's sizing code on those recorded
+//! inputs and reports whether it still produces the recorded size, so a
+//! surprising resize can be investigated after an upgrade or configuration
+//! change without reconstructing the worker's state by hand.
+
+use crate::algorithm::{
+    calculate_new_size, calculate_shrink_size, min_step_shrink_size, peak_ratio_shrink_size,
+    shrink_is_stuck,
+};
+use crate::guc::{ShrinkRounding, WALRUS_MAX_CHANGES_PER_HOUR};
+use crate::history::{history_table, history_table_exists};
+use crate::settling::settling_step_cap;
+use crate::shmem;
+
+use pgrx::datum::DatumWithOid;
+use pgrx::prelude::*;
+use pgrx::{JsonB, pg_sys};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value as JsonValue, json};
+
+/// Sizing rule a decision ran, with the settings it read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub(crate) enum SizingRule {
+    /// Grow formula, capped at the effective walrus.max and the settling step
+    Grow {
+        grow_exponent: f64,
+        max_allowed_mb: i32,
+        /// walrus.settling_max_step_mb while settling, otherwise None
+        settling_step_mb: Option<i32>,
+    },
+    /// walrus.shrink_factor shrink, with the one-segment step when forced
+    ShrinkFactor {
+        shrink_factor: f64,
+        min_size_mb: i32,
+        rounding: String,
+        /// WAL segment size when walrus.force_min_shrink stepped down, otherwise None
+        forced_step_mb: Option<i32>,
+    },
+    /// walrus.shrink_target = 'peak_ratio' shrink
+    PeakRatio {
+        peak_mb: i32,
+        peak_ratio: f64,
+        min_size_mb: i32,
+    },
+    /// walrus.fast_shrink_after_intervals shrink straight to the floor
+    FastShrink {
+        fast_shrink_after_intervals: i32,
+        min_size_mb: i32,
+    },
+}
+
+/// Inputs and result of one applied sizing decision.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct DecisionInputs {
+    pub current_size_mb: i32,
+    /// Delta the rule used (the per-interval rate with lookback or rolling delta)
+    pub delta: i64,
+    pub threshold: i64,
+    pub quiet_intervals: i32,
+    /// Changes in the current hourly window before this one
+    pub changes_this_hour: i32,
+    pub max_changes_per_hour: i32,
+    pub new_size_mb: i32,
+    #[serde(flatten)]
+    pub rule: SizingRule,
+}
+
+/// Snapshot of a decision about to be recorded, with the live rate-limit state.
+pub(crate) fn decision_inputs(
+    current_size_mb: i32,
+    delta: i64,
+    threshold: i64,
+    quiet_intervals: i32,
+    new_size_mb: i32,
+    rule: SizingRule,
+) -> DecisionInputs {
+    DecisionInputs {
+        current_size_mb,
+        delta,
+        threshold,
+        quiet_intervals,
+        changes_this_hour: shmem::read_state().changes_this_hour,
+        max_changes_per_hour: WALRUS_MAX_CHANGES_PER_HOUR.get(),
+        new_size_mb,
+        rule,
+    }
+}
+
+/// Add the `decision_inputs` object to history metadata.
+pub(crate) fn with_decision_inputs(mut metadata: JsonValue, inputs: &DecisionInputs) -> JsonValue {
+    if let (JsonValue::Object(map), Ok(value)) = (&mut metadata, serde_json::to_value(inputs)) {
+        map.insert("decision_inputs".to_string(), value);
+    }
+    metadata
+}
+
+/// walrus.shrink_rounding value for a recorded setting name.
+pub(crate) fn rounding_from_name(name: &str) -> Option<ShrinkRounding> {
+    [
+        ShrinkRounding::Up,
+        ShrinkRounding::Down,
+        ShrinkRounding::Nearest,
+    ]
+    .into_iter()
+    .find(|rounding| rounding.name() == name)
+}
+
+/// Size today's sizing code computes from recorded inputs.
+///
+/// None if the inputs name a setting value this version does not know.
+pub(crate) fn replay(inputs: &DecisionInputs) -> Option<i32> {
+    let current_size = inputs.current_size_mb;
+    match &inputs.rule {
+        SizingRule::Grow {
+            grow_exponent,
+            max_allowed_mb,
+            settling_step_mb,
+        } => {
+            let size =
+                calculate_new_size(current_size, inputs.delta, *grow_exponent).min(*max_allowed_mb);
+            Some(match settling_step_mb {
+                Some(step) => settling_step_cap(current_size, size, *step),
+                None => size,
+            })
+        }
+        SizingRule::ShrinkFactor {
+            shrink_factor,
+            min_size_mb,
+            rounding,
+            forced_step_mb,
+        } => {
+            let rounding = rounding_from_name(rounding)?;
+            Some(match forced_step_mb {
+                Some(step)
+                    if shrink_is_stuck(current_size, *shrink_factor, *min_size_mb, rounding) =>
+                {
+                    min_step_shrink_size(current_size, *step, *min_size_mb)
+                }
+                _ => calculate_shrink_size(current_size, *shrink_factor, *min_size_mb, rounding),
+            })
+        }
+        SizingRule::PeakRatio {
+            peak_mb,
+            peak_ratio,
+            min_size_mb,
+        } => Some(peak_ratio_shrink_size(
+            current_size,
+            *peak_mb,
+            *peak_ratio,
+            *min_size_mb,
+        )),
+        SizingRule::FastShrink { min_size_mb, .. } => Some(*min_size_mb),
+    }
+}
+
+/// Replay report for a row's recorded inputs, applied size, and action.
+pub(crate) fn replay_report(
+    history_id: i64,
+    action: &str,
+    applied_size_mb: i32,
+    inputs: &DecisionInputs,
+) -> JsonValue {
+    let reproduced = replay(inputs);
+    let divergence = match reproduced {
+        None => {
+            Some("recorded inputs name a setting value this version does not support".to_string())
+        }
+        Some(size) if size != inputs.new_size_mb => Some(format!(
+            "today's code computes {} MB from these inputs, {} MB was recorded",
+            size, inputs.new_size_mb
+        )),
+        Some(_) if applied_size_mb != inputs.new_size_mb => Some(format!(
+            "row applied {} MB, but its inputs record {} MB",
+            applied_size_mb, inputs.new_size_mb
+        )),
+        Some(_) => None,
+    };
+    json!({
+        "history_id": history_id,
+        "action": action,
+        "inputs": inputs,
+        "recorded_new_size_mb": inputs.new_size_mb,
+        "reproduced_new_size_mb": reproduced,
+        "matches": divergence.is_none(),
+        "divergence": divergence,
+    })
+}
+
+/// Implementation for replaying a recorded sizing decision.
+///
+/// Reads `decision_inputs` from the metadata of history row `history_id` and
+/// runs today's sizing code on them. Returns a JSONB object with:
+/// - `history_id`, `action`: The row replayed
+/// - `inputs`: The recorded inputs
+/// - `recorded_new_size_mb`: Size the decision produced
+/// - `reproduced_new_size_mb`: Size today's code produces from the same inputs
+/// - `matches`: Whether they agree (and the row applied that size)
+/// - `divergence`: Description of the difference, or null when they match
+///
+/// Raises an error if the row does not exist or has no recorded inputs
+/// (informational rows, manual changes, the applied rows listed in the module
+/// docs, and rows written before metadata version 14).
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn reproduce(history_id: i64) -> Result<JsonB, spi::Error> {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.reproduce() requires superuser");
+    }
+    if !history_table_exists()? {
+        pgrx::error!("history row {} does not exist", history_id);
+    }
+
+    let args: &[DatumWithOid<'_>] = &[history_id.into()];
+    let row = Spi::get_one_with_args::<JsonB>(
+        &format!(
+            "SELECT jsonb_build_object('action', action, 'new_size_mb', new_size_mb,
+                                       'inputs', metadata->'decision_inputs')
+             FROM {} WHERE id = $1",
+            history_table()
+        ),
+        args,
+    )?;
+    let Some(JsonB(row)) = row else {
+        pgrx::error!("history row {} does not exist", history_id);
+    };
+    let action = row["action"].as_str().unwrap_or_default().to_string();
+    let applied_size_mb = row["new_size_mb"].as_i64().unwrap_or_default() as i32;
+    let Ok(inputs) = serde_json::from_value::<DecisionInputs>(row["inputs"].clone()) else {
+        pgrx::error!(
+            "history row {} ({}) has no recorded decision inputs",
+            history_id,
+            action
+        );
+    };
+
+    Ok(JsonB(replay_report(
+        history_id,
+        &action,
+        applied_size_mb,
+        &inputs,
+    )))
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(
+        current_size_mb: i32,
+        delta: i64,
+        new_size_mb: i32,
+        rule: SizingRule,
+    ) -> DecisionInputs {
+        DecisionInputs {
+            current_size_mb,
+            delta,
+            threshold: 2,
+            quiet_intervals: 0,
+            changes_this_hour: 1,
+            max_changes_per_hour: 4,
+            new_size_mb,
+            rule,
+        }
+    }
+
+    /// Test grows replay through the cap and the settling step
+    #[test]
+    fn test_replay_grow() {
+        let grow = |max_allowed_mb, settling_step_mb| SizingRule::Grow {
+            grow_exponent: 1.0,
+            max_allowed_mb,
+            settling_step_mb,
+        };
+        assert_eq!(
+            replay(&inputs(1024, 3, 4096, grow(65536, None))),
+            Some(4096)
+        );
+        assert_eq!(replay(&inputs(1024, 3, 2048, grow(2048, None))), Some(2048));
+        assert_eq!(
+            replay(&inputs(1024, 3, 1536, grow(65536, Some(512)))),
+            Some(1536)
+        );
+    }
+
+    /// Test shrinks replay by factor, forced step, and peak ratio
+    #[test]
+    fn test_replay_shrink() {
+        let factor = |shrink_factor, forced_step_mb| SizingRule::ShrinkFactor {
+            shrink_factor,
+            min_size_mb: 1024,
+            rounding: "up".into(),
+            forced_step_mb,
+        };
+        assert_eq!(
+            replay(&inputs(4096, 0, 3072, factor(0.75, None))),
+            Some(3072)
+        );
+        assert_eq!(
+            replay(&inputs(1025, 0, 1024, factor(0.9999, Some(16)))),
+            Some(1024),
+            "stuck shrink steps down one segment"
+        );
+        let peak = SizingRule::PeakRatio {
+            peak_mb: 2048,
+            peak_ratio: 1.5,
+            min_size_mb: 1024,
+        };
+        assert_eq!(replay(&inputs(8192, 0, 3072, peak)), Some(3072));
+        let fast = SizingRule::FastShrink {
+            fast_shrink_after_intervals: 100,
+            min_size_mb: 1024,
+        };
+        assert_eq!(replay(&inputs(8192, 0, 1024, fast)), Some(1024));
+    }
+
+    /// Test the inputs round-trip through JSON with the rule tagged
+    #[test]
+    fn test_inputs_round_trip() {
+        let recorded = inputs(
+            4096,
+            0,
+            3072,
+            SizingRule::ShrinkFactor {
+                shrink_factor: 0.75,
+                min_size_mb: 1024,
+                rounding: "nearest".into(),
+                forced_step_mb: None,
+            },
+        );
+        let value = serde_json::to_value(&recorded).unwrap();
+        assert_eq!(value["rule"], "shrink_factor");
+        assert_eq!(
+            serde_json::from_value::<DecisionInputs>(value).unwrap(),
+            recorded
+        );
+        assert_eq!(rounding_from_name("down"), Some(ShrinkRounding::Down));
+        assert_eq!(rounding_from_name("sideways"), None);
+    }
+
+    /// Test a changed result is reported as a divergence
+    #[test]
+    fn test_replay_report_divergence() {
+        let grow = SizingRule::Grow {
+            grow_exponent: 1.0,
+            max_allowed_mb: 65536,
+            settling_step_mb: None,
+        };
+        let same = replay_report(7, "increase", 4096, &inputs(1024, 3, 4096, grow.clone()));
+        assert_eq!(same["matches"], true);
+        assert!(same["divergence"].is_null());
+
+        let changed = replay_report(8, "increase", 3000, &inputs(1024, 3, 3000, grow));
+        assert_eq!(changed["matches"], false);
+        assert_eq!(changed["reproduced_new_size_mb"], 4096);
+        assert!(changed["divergence"].as_str().unwrap().contains("4096 MB"));
+    }
+}
@@ -0,0 +1,97 @@
+//! Unexpected worker restart detection for pg_walrus.
+//!
+//! The worker is registered with a restart time, so after a crash (a panic or
+//! an ERROR escaping the main loop) the postmaster starts it again and nothing
+//! else tells operators it happened. Each instance sets `clean_shutdown` in
+//! shared memory when it exits on SIGTERM. At startup the next instance checks
+//! the flag: if a previous instance ran (`worker_start_time` is set) without
+//! setting it, the worker logs a WARNING and records a 'note' history row. The
+//! flag is cleared at every startup.
+
+use crate::functions::unix_timestamp_to_iso;
+use crate::history;
+use crate::shmem;
+use crate::stats::{checkpoint_timeout, get_current_max_wal_size, get_requested_checkpoints};
+use crate::worker::in_transaction;
+
+use serde_json::json;
+
+/// History reason for the note recorded after an unexpected termination.
+pub(crate) const UNEXPECTED_RESTART_REASON: &str = "worker restarted after unexpected termination";
+
+/// Whether the previous worker instance terminated without a clean shutdown.
+///
+/// `prev_start_time` is 0 when no instance has run since PostgreSQL started.
+#[inline]
+pub(crate) fn restarted_unexpectedly(prev_start_time: i64, clean_shutdown: bool) -> bool {
+    prev_start_time != 0 && !clean_shutdown
+}
+
+/// Read how the previous instance exited and clear the flag for this one.
+///
+/// Must run before this instance publishes its own `worker_start_time`.
+pub(crate) fn take_unexpected_restart() -> bool {
+    let mut unexpected = false;
+    shmem::update_state(|state| {
+        unexpected = restarted_unexpectedly(state.worker_start_time, state.clean_shutdown);
+        state.clean_shutdown = false;
+    });
+    unexpected
+}
+
+/// Record that this instance is exiting on SIGTERM.
+pub(crate) fn mark_clean_shutdown() {
+    shmem::update_state(|state| state.clean_shutdown = true);
+}
+
+/// Warn and record a 'note' row if the previous instance terminated unexpectedly.
+///
+/// Called once at worker startup. Returns whether it did.
+pub(crate) fn check_unexpected_restart() -> bool {
+    let prev_started = unix_timestamp_to_iso(shmem::read_state().worker_start_time);
+    if !take_unexpected_restart() {
+        return false;
+    }
+    pgrx::warning!(
+        "pg_walrus: {}; the previous worker (started {}) did not shut down cleanly",
+        UNEXPECTED_RESTART_REASON,
+        prev_started.as_deref().unwrap_or("unknown")
+    );
+
+    let current_size = get_current_max_wal_size();
+    let metadata = json!({ "previous_worker_start_time": prev_started });
+    if let Err(e) = in_transaction(|| {
+        history::insert_history_record(
+            "note",
+            current_size,
+            current_size,
+            get_requested_checkpoints().max(0),
+            checkpoint_timeout().as_secs() as i32,
+            Some(UNEXPECTED_RESTART_REASON),
+            Some(history::versioned_metadata(metadata.clone())),
+        )
+    }) {
+        pgrx::warning!("pg_walrus: failed to log restart note: {}", e);
+    }
+    true
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test only a previous instance without a clean shutdown counts as a crash
+    #[test]
+    fn test_restarted_unexpectedly() {
+        assert!(restarted_unexpectedly(1_700_000_000, false));
+        assert!(
+            !restarted_unexpectedly(1_700_000_000, true),
+            "clean SIGTERM exit"
+        );
+        assert!(
+            !restarted_unexpectedly(0, false),
+            "first start since PostgreSQL started"
+        );
+    }
+}
@@ -0,0 +1,853 @@
+// Integration tests for pg_walrus safety gates.
+//
+// These tests cover the guards that keep pg_walrus from applying
+// questionable changes, such as the minimum apply confidence.
+
+// =========================================================================
+// walrus.min_apply_confidence Tests
+// =========================================================================
+
+/// Test that walrus.min_apply_confidence GUC has correct default value (0)
+#[pg_test]
+fn test_guc_min_apply_confidence_default() {
+    let result = Spi::get_one::<&str>("SHOW walrus.min_apply_confidence").expect("SHOW failed");
+    assert_eq!(
+        result,
+        Some("0"),
+        "walrus.min_apply_confidence should default to '0'"
+    );
+}
+
+/// Test that walrus.min_apply_confidence GUC has correct range 0-100
+#[pg_test]
+fn test_guc_min_apply_confidence_range() {
+    let min_val = Spi::get_one::<&str>(
+        "SELECT min_val FROM pg_settings WHERE name = 'walrus.min_apply_confidence'",
+    )
+    .expect("query failed");
+    assert_eq!(min_val, Some("0"), "min_apply_confidence min should be 0");
+
+    let max_val = Spi::get_one::<&str>(
+        "SELECT max_val FROM pg_settings WHERE name = 'walrus.min_apply_confidence'",
+    )
+    .expect("query failed");
+    assert_eq!(
+        max_val,
+        Some("100"),
+        "min_apply_confidence max should be 100"
+    );
+}
+
+/// Test that a low-confidence state is blocked by the apply gate
+#[pg_test]
+fn test_confidence_gate_blocks_low_confidence_state() {
+    // Fresh state: no baseline and no quiet intervals, so confidence <= 70
+    let state = crate::shmem::WalrusState::default();
+    let confidence = algorithm::compute_confidence(&state, stats::get_requested_checkpoints());
+    assert!(confidence <= 70, "fresh state confidence should be <= 70");
+    assert!(
+        !algorithm::meets_confidence_threshold(confidence, 90),
+        "low-confidence state should not pass a 90 gate"
+    );
+}
+
+/// Test that a high-confidence state passes the apply gate
+#[pg_test]
+fn test_confidence_gate_allows_high_confidence_state() {
+    let state = crate::shmem::WalrusState {
+        quiet_intervals: 3,
+        prev_requested: 100,
+        ..Default::default()
+    };
+    let confidence = algorithm::compute_confidence(&state, 50);
+    assert_eq!(confidence, 100);
+    assert!(
+        algorithm::meets_confidence_threshold(confidence, 90),
+        "high-confidence state should pass a 90 gate"
+    );
+}
+
+/// Test that with the default gate (0), analyze() reports no gate reason
+#[pg_test]
+fn test_analyze_no_confidence_reason_by_default() {
+    let has_reason =
+        Spi::get_one::<bool>("SELECT walrus.analyze() ? 'reason'").expect("query failed");
+    assert_eq!(
+        has_reason,
+        Some(false),
+        "analyze() should not report a gate reason when the gate is disabled"
+    );
+}
+
+// =========================================================================
+// walrus.emergency_free_disk_mb Tests
+// =========================================================================
+
+/// Test that walrus.emergency_free_disk_mb GUC defaults to 0 (disabled)
+#[pg_test]
+fn test_guc_emergency_free_disk_mb_default() {
+    let result = Spi::get_one::<&str>("SHOW walrus.emergency_free_disk_mb").expect("SHOW failed");
+    assert_eq!(
+        result,
+        Some("0"),
+        "walrus.emergency_free_disk_mb should default to '0'"
+    );
+}
+
+/// Test that free space on the pg_wal filesystem can be read from a backend
+#[pg_test]
+fn test_wal_free_disk_mb_available() {
+    let free_mb = crate::emergency::wal_free_disk_mb();
+    assert!(
+        free_mb.is_some_and(|mb| mb >= 0),
+        "pg_wal free space should be readable: {:?}",
+        free_mb
+    );
+}
+
+/// Test that the default configuration never triggers an emergency shrink
+#[pg_test]
+fn test_emergency_shrink_disabled_by_default() {
+    let free_mb = crate::emergency::wal_free_disk_mb();
+    assert!(!crate::emergency::should_emergency_shrink(
+        free_mb,
+        crate::guc::WALRUS_EMERGENCY_FREE_DISK_MB.get()
+    ));
+}
+
+// =========================================================================
+// walrus.shrink_max_wal_mb_per_interval Tests
+// =========================================================================
+
+/// Test that walrus.shrink_max_wal_mb_per_interval GUC defaults to 0 (disabled)
+#[pg_test]
+fn test_guc_shrink_max_wal_mb_per_interval_default() {
+    let result =
+        Spi::get_one::<&str>("SHOW walrus.shrink_max_wal_mb_per_interval").expect("SHOW failed");
+    assert_eq!(
+        result,
+        Some("0"),
+        "walrus.shrink_max_wal_mb_per_interval should default to '0'"
+    );
+}
+
+/// Test that the WAL insert LSN matches pg_current_wal_insert_lsn()
+#[pg_test]
+fn test_get_current_wal_lsn_matches_sql() {
+    let before = stats::get_current_wal_lsn();
+    let sql_lsn = Spi::get_one::<i64>(
+        "SELECT (pg_current_wal_insert_lsn() - '0/0'::pg_lsn)::bigint",
+    )
+    .expect("query failed")
+    .expect("LSN should not be NULL");
+    let after = stats::get_current_wal_lsn();
+    assert!(before > 0, "LSN should be positive");
+    assert!(
+        before <= sql_lsn && sql_lsn <= after,
+        "SQL LSN {} should be between {} and {}",
+        sql_lsn,
+        before,
+        after
+    );
+}
+
+// =========================================================================
+// checkpoint_completion_target Co-Tuning Tests
+// =========================================================================
+
+/// Test co-tuning GUC defaults (off, 0.9)
+#[pg_test]
+fn test_guc_completion_target_defaults() {
+    let manage =
+        Spi::get_one::<&str>("SHOW walrus.manage_completion_target").expect("SHOW failed");
+    assert_eq!(manage, Some("off"));
+
+    let target = Spi::get_one::<&str>("SHOW walrus.completion_target").expect("SHOW failed");
+    assert_eq!(target, Some("0.9"));
+}
+
+/// Test the real-valued A_Const node built for ALTER SYSTEM
+#[pg_test]
+fn test_make_real_const_node() {
+    use std::ffi::CStr;
+
+    assert_eq!(crate::config::format_real(0.9), "0.9");
+    assert_eq!(crate::config::format_real(1.0), "1.0");
+
+    unsafe {
+        let node = crate::config::make_real_const(0.9);
+        assert_eq!((*node).type_, pg_sys::NodeTag::T_A_Const);
+
+        #[cfg(any(feature = "pg13", feature = "pg14"))]
+        let (tag, value) = ((*node).val.type_, CStr::from_ptr((*node).val.val.str_));
+        #[cfg(not(any(feature = "pg13", feature = "pg14")))]
+        let (tag, value) = {
+            assert!(!(*node).isnull, "constant should not be NULL");
+            ((*node).val.fval.type_, CStr::from_ptr((*node).val.fval.fval))
+        };
+
+        assert_eq!(tag, pg_sys::NodeTag::T_Float);
+        assert_eq!(value.to_str().expect("invalid UTF-8"), "0.9");
+    }
+}
+
+/// Test that grow metadata records the checkpoint_completion_target co-change
+#[pg_test]
+fn test_grow_metadata_records_completion_target() {
+    let base = serde_json::json!({ "delta": 3, "multiplier": 4, "calculated_size_mb": 4096 });
+
+    let cotuned = crate::history::with_completion_target(base.clone(), Some((0.5, 0.9)));
+    assert_eq!(cotuned["completion_target"], serde_json::json!(0.9));
+    assert_eq!(cotuned["previous_completion_target"], serde_json::json!(0.5));
+    assert_eq!(cotuned["delta"], serde_json::json!(3));
+
+    // No co-change: metadata is unchanged
+    let unchanged = crate::history::with_completion_target(base.clone(), None);
+    assert_eq!(unchanged, base);
+}
+
+/// Test that applied-change metadata records apply_duration_ms and status()
+/// reports the slowest apply
+#[pg_test]
+fn test_apply_duration_recorded() {
+    use crate::history::{insert_history_record, versioned_metadata, with_apply_duration};
+    use crate::shmem;
+
+    // Build the metadata the way the grow path does after a timed apply
+    let metadata = crate::history::grow_metadata(3, 4096, None, 1.0);
+    let metadata = versioned_metadata(with_apply_duration(metadata, 42));
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    insert_history_record("increase", 1024, 4096, 3, 300, None, Some(metadata))
+        .expect("insert failed");
+    let recorded = Spi::get_one::<i64>(
+        "SELECT (metadata->>'apply_duration_ms')::bigint FROM walrus.history",
+    )
+    .expect("query failed");
+    assert_eq!(recorded, Some(42));
+
+    let saved = shmem::read_state().max_apply_duration_ms;
+    shmem::update_state(|state| state.max_apply_duration_ms = 1500);
+    let max = Spi::get_one::<i64>("SELECT (walrus.status()->>'max_apply_duration_ms')::bigint")
+        .expect("query failed");
+    shmem::update_state(|state| state.max_apply_duration_ms = saved);
+    assert_eq!(max, Some(1500));
+}
+
+// =========================================================================
+// walrus.sample_interval_sec Tests
+// =========================================================================
+
+/// Test that walrus.sample_interval_sec defaults to 0 (sample once per checkpoint_timeout)
+#[pg_test]
+fn test_guc_sample_interval_sec_default() {
+    let result = Spi::get_one::<&str>("SHOW walrus.sample_interval_sec").expect("SHOW failed");
+    assert_eq!(
+        result,
+        Some("0"),
+        "walrus.sample_interval_sec should default to '0'"
+    );
+}
+
+/// Test that status() reports one sample per interval by default
+#[pg_test]
+fn test_status_samples_per_interval_default() {
+    let result = Spi::get_one::<i32>("SELECT (walrus.status()->>'samples_per_interval')::int")
+        .expect("query failed");
+    assert_eq!(result, Some(1), "default should sample once per interval");
+
+    let taken = Spi::get_one::<i32>("SELECT (walrus.status()->>'samples_taken')::int")
+        .expect("query failed");
+    assert!(taken.is_some(), "status should include samples_taken");
+}
+
+/// Test that walrus.use_rolling_delta defaults to off
+#[pg_test]
+fn test_guc_use_rolling_delta_default() {
+    let result = Spi::get_one::<String>("SHOW walrus.use_rolling_delta").expect("SHOW failed");
+    assert_eq!(result, Some("off".to_string()));
+}
+
+/// Test that status() reports the rolling average of the recorded deltas
+#[pg_test]
+fn test_status_reports_rolling_avg_delta() {
+    use crate::algorithm::{DELTA_RING_SIZE, push_delta};
+    use crate::shmem;
+
+    let saved = shmem::read_state();
+    shmem::update_state(|state| {
+        state.delta_ring = [0; DELTA_RING_SIZE];
+        (state.delta_ring_pos, state.delta_ring_len) = (0, 0);
+        for delta in [6, 2, 4] {
+            (state.delta_ring_pos, state.delta_ring_len) =
+                push_delta(&mut state.delta_ring, state.delta_ring_pos, state.delta_ring_len, delta);
+        }
+    });
+    let avg = Spi::get_one::<i64>("SELECT (walrus.status()->>'rolling_avg_delta')::bigint")
+        .expect("query failed");
+    assert_eq!(avg, Some(4), "average of 6, 2, 4");
+
+    shmem::update_state(|state| *state = saved);
+}
+
+// =========================================================================
+// walrus.min_shrink_confidence Tests
+// =========================================================================
+
+/// Test that walrus.min_shrink_confidence defaults to 0 (no gate)
+#[pg_test]
+fn test_guc_min_shrink_confidence_default() {
+    let result =
+        Spi::get_one::<&str>("SHOW walrus.min_shrink_confidence").expect("SHOW failed");
+    assert_eq!(result, Some("0"));
+}
+
+/// Test a low-confidence quiet state holds the shrink while a high-confidence one proceeds
+#[pg_test]
+fn test_min_shrink_confidence_holds_low_confidence_shrink() {
+    use crate::algorithm::{compute_confidence, meets_confidence_threshold};
+    use crate::shmem::WalrusState;
+
+    let min_shrink_confidence = 80;
+
+    // Quiet, but few checkpoints observed and no baseline: 50 + 15 = 65
+    let thin = WalrusState {
+        quiet_intervals: 5,
+        ..Default::default()
+    };
+    let confidence = compute_confidence(&thin, 3);
+    assert_eq!(confidence, 65);
+    assert!(
+        !meets_confidence_threshold(confidence, min_shrink_confidence),
+        "low-confidence shrink should be held"
+    );
+
+    // Quiet with plenty of samples and an established baseline: 100
+    let established = WalrusState {
+        quiet_intervals: 5,
+        prev_requested: 40,
+        ..Default::default()
+    };
+    let confidence = compute_confidence(&established, 40);
+    assert_eq!(confidence, 100);
+    assert!(
+        meets_confidence_threshold(confidence, min_shrink_confidence),
+        "high-confidence shrink should proceed"
+    );
+
+    // The default of 0 never holds a shrink
+    assert!(meets_confidence_threshold(compute_confidence(&thin, 0), 0));
+}
+
+// =========================================================================
+// walrus.mode Tests
+// =========================================================================
+
+/// Test that walrus.mode defaults to 'both'
+#[pg_test]
+fn test_guc_mode_default() {
+    let result = Spi::get_one::<&str>("SHOW walrus.mode").expect("SHOW failed");
+    assert_eq!(result, Some("both"), "walrus.mode should default to 'both'");
+}
+
+/// Test that walrus.mode accepts exactly the four documented values
+#[pg_test]
+fn test_guc_mode_enum_values() {
+    let values = Spi::get_one::<String>(
+        "SELECT array_to_string(enumvals, ',') FROM pg_settings WHERE name = 'walrus.mode'",
+    )
+    .expect("query failed");
+    assert_eq!(
+        values.as_deref(),
+        Some("both,grow_only,shrink_only,observe")
+    );
+}
+
+/// Test that the default mode applies in both directions from the worker and analyze
+#[pg_test]
+fn test_mode_gate_uses_live_setting() {
+    use crate::algorithm::{ModeGate, mode_gate};
+    use crate::guc::{WALRUS_MODE, WalrusMode};
+
+    assert_eq!(WALRUS_MODE.get(), WalrusMode::Both);
+    assert_eq!(mode_gate(WALRUS_MODE.get(), true, false), ModeGate::Apply);
+    assert_eq!(mode_gate(WALRUS_MODE.get(), false, false), ModeGate::Apply);
+}
+
+// =========================================================================
+// Post-startup settling Tests
+// =========================================================================
+
+/// Test that walrus.settling_max_step_mb defaults to 1GB
+#[pg_test]
+fn test_guc_settling_max_step_mb_default() {
+    let result = Spi::get_one::<&str>("SHOW walrus.settling_max_step_mb").expect("SHOW failed");
+    assert_eq!(
+        result,
+        Some("1GB"),
+        "walrus.settling_max_step_mb should default to '1GB'"
+    );
+}
+
+/// Test that status() reports settling from the shared memory deadline
+#[pg_test]
+fn test_status_reports_settling() {
+    use crate::shmem::{self, now_unix};
+
+    let saved = shmem::read_state();
+    shmem::update_state(|state| state.settling_until = now_unix() + 3600);
+    let settling = Spi::get_one::<bool>("SELECT (walrus.status()->>'settling')::boolean")
+        .expect("query failed");
+    assert_eq!(settling, Some(true), "should be settling before the deadline");
+
+    shmem::update_state(|state| state.settling_until = now_unix() - 1);
+    let settling = Spi::get_one::<bool>("SELECT (walrus.status()->>'settling')::boolean")
+        .expect("query failed");
+    assert_eq!(settling, Some(false), "should not be settling after the deadline");
+
+    shmem::update_state(|state| *state = saved);
+}
+
+// =========================================================================
+// walrus.grow_exponent Tests
+// =========================================================================
+
+/// Test that walrus.grow_exponent defaults to 1.0 (linear growth)
+#[pg_test]
+fn test_guc_grow_exponent_default() {
+    let result = Spi::get_one::<&str>("SHOW walrus.grow_exponent").expect("SHOW failed");
+    assert_eq!(result, Some("1"), "walrus.grow_exponent should default to '1'");
+}
+
+/// Test grow metadata keeps the integer multiplier at the default exponent
+#[pg_test]
+fn test_grow_metadata_exponent() {
+    use crate::history::grow_metadata;
+
+    let linear = grow_metadata(3, 4096, None, 1.0);
+    assert_eq!(linear["multiplier"], serde_json::json!(4));
+    assert!(linear.get("grow_exponent").is_none());
+    assert!(linear.get("walrus_max_mb").is_none());
+
+    let dampened = grow_metadata(16, 5120, Some(4096), 0.5);
+    assert_eq!(dampened["multiplier"], serde_json::json!(5.0));
+    assert_eq!(dampened["grow_exponent"], serde_json::json!(0.5));
+    assert_eq!(dampened["walrus_max_mb"], serde_json::json!(4096));
+}
+
+// =========================================================================
+// Startup Drift Reconciliation Tests
+// =========================================================================
+
+/// Test drift is detected only when the recorded size differs from the actual size
+#[pg_test]
+fn test_startup_drift_detection() {
+    use crate::history::startup_drift;
+
+    assert_eq!(startup_drift(None, 1024), None, "no history, nothing to bridge");
+    assert_eq!(startup_drift(Some(1024), 1024), None);
+    assert_eq!(startup_drift(Some(2048), 1024), Some(2048));
+}
+
+/// Test a mismatched newest change produces an external_change row
+#[pg_test]
+fn test_reconcile_startup_drift_inserts_external_change() {
+    use crate::history::{insert_history_record, reconcile_startup_drift};
+    use crate::stats::get_current_max_wal_size;
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    let current = get_current_max_wal_size();
+    let recorded = current + 512;
+    insert_history_record("increase", current, recorded, 3, 300, None, None)
+        .expect("insert failed");
+    // Rows that did not change configuration are not part of the chain
+    insert_history_record("dry_run", recorded, recorded * 2, 3, 300, None, None)
+        .expect("insert failed");
+
+    assert!(reconcile_startup_drift(current, 300).expect("reconcile failed"));
+
+    let row = Spi::get_two::<i32, i32>(
+        "SELECT old_size_mb, new_size_mb FROM walrus.history \
+         WHERE action = 'external_change'",
+    )
+    .expect("query failed");
+    assert_eq!(row, (Some(recorded), Some(current)), "row bridges the gap");
+    let actual = Spi::get_one::<i32>(
+        "SELECT (metadata->>'actual_size_mb')::int FROM walrus.history \
+         WHERE action = 'external_change'",
+    )
+    .expect("query failed");
+    assert_eq!(actual, Some(current));
+
+    // The chain is now continuous, so a second startup records nothing
+    assert!(!reconcile_startup_drift(current, 300).expect("reconcile failed"));
+}
+
+/// Test no row is written when history matches the actual size or is empty
+#[pg_test]
+fn test_reconcile_startup_drift_no_mismatch() {
+    use crate::history::{insert_history_record, reconcile_startup_drift};
+    use crate::stats::get_current_max_wal_size;
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    let current = get_current_max_wal_size();
+    assert!(!reconcile_startup_drift(current, 300).expect("reconcile failed"));
+
+    insert_history_record("increase", current / 2 + 1, current, 3, 300, None, None)
+        .expect("insert failed");
+    assert!(!reconcile_startup_drift(current, 300).expect("reconcile failed"));
+
+    let count = Spi::get_one::<i64>(
+        "SELECT count(*) FROM walrus.history WHERE action = 'external_change'",
+    )
+    .expect("query failed");
+    assert_eq!(count, Some(0));
+}
+
+// =========================================================================
+// Unusable max_wal_size Tests
+// =========================================================================
+
+/// Test a zero max_wal_size reading yields an 'error' recommendation, not a garbage target
+#[pg_test]
+fn test_recommendation_errors_on_zero_max_wal_size() {
+    // SAFETY: max_wal_size_mb is a backend-local global; restored below.
+    let saved = unsafe { pg_sys::max_wal_size_mb };
+    unsafe { pg_sys::max_wal_size_mb = 0 };
+
+    let state = crate::shmem::WalrusState {
+        prev_requested: 1,
+        ..Default::default()
+    };
+    let rec = algorithm::compute_recommendation_for_delta(&state, Some(5));
+
+    unsafe { pg_sys::max_wal_size_mb = saved };
+
+    assert_eq!(rec.action, "error");
+    assert_eq!(rec.recommended_size_mb, 0, "no target should be computed");
+    assert!(rec.reason.contains("max_wal_size"), "reason: {}", rec.reason);
+}
+
+// =========================================================================
+// Missing History Schema Tests
+// =========================================================================
+
+/// Test a missing history table sets schema_missing and warns only once
+#[pg_test]
+fn test_schema_missing_flag_warns_once() {
+    let saved = crate::shmem::read_state().schema_missing;
+    crate::shmem::update_state(|state| state.schema_missing = false);
+
+    // walrus.schema_name is postmaster-context; override it for this backend only
+    let set_schema = |name: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.schema_name".as_ptr(),
+            name.as_ptr(),
+            pg_sys::GucContext::PGC_POSTMASTER,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+
+    set_schema(c"walrus_not_installed");
+    let first = crate::history::check_history_schema().expect("check failed");
+    let second = crate::history::check_history_schema().expect("check failed");
+    assert!(first, "first check should report the missing table");
+    assert!(!second, "repeated checks should not warn again");
+
+    let status = Spi::get_one::<pgrx::JsonB>("SELECT walrus.status()")
+        .expect("query failed")
+        .expect("status returned NULL")
+        .0;
+    assert_eq!(status["schema_missing"], serde_json::json!(true));
+
+    // Once the table exists again the flag clears
+    set_schema(c"walrus");
+    let found = crate::history::check_history_schema().expect("check failed");
+    assert!(!found);
+    assert!(!crate::shmem::read_state().schema_missing);
+
+    crate::shmem::update_state(|state| state.schema_missing = saved);
+}
+
+// =========================================================================
+// walrus.reload_method Tests
+// =========================================================================
+
+/// Test that walrus.reload_method GUC has correct default value ('signal')
+#[pg_test]
+fn test_guc_reload_method_default() {
+    let result = Spi::get_one::<&str>("SHOW walrus.reload_method").expect("SHOW failed");
+    assert_eq!(result, Some("signal"));
+}
+
+/// Test the 'sql' reload method runs pg_reload_conf() without error
+#[pg_test]
+fn test_reload_method_sql_uses_pg_reload_conf() {
+    use crate::guc::ReloadMethod;
+
+    let set_method = |value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.reload_method".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+
+    set_method(c"sql");
+    let method = crate::config::signal_postmaster_reload();
+    set_method(c"signal");
+    assert_eq!(method, ReloadMethod::Sql);
+    assert_eq!(method.name(), "sql");
+
+    // The transaction is still usable after the reload
+    let one = Spi::get_one::<i32>("SELECT 1").expect("query failed");
+    assert_eq!(one, Some(1));
+}
+
+/// Test each reload request is counted and reported by walrus.status()
+#[pg_test]
+fn test_sighups_counted() {
+    use crate::shmem;
+
+    let set_method = |value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.reload_method".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    let saved = shmem::read_state();
+    shmem::update_state(|state| state.sighup_hour_window_start = 0);
+
+    set_method(c"sql");
+    crate::config::signal_postmaster_reload();
+    crate::config::signal_postmaster_reload();
+    set_method(c"signal");
+    let status = Spi::get_one::<pgrx::JsonB>("SELECT walrus.status()")
+        .expect("query failed")
+        .expect("status should return JSONB");
+    shmem::update_state(|state| *state = saved);
+
+    assert_eq!(status.0["sighups_sent"], saved.sighups_sent + 2);
+    assert_eq!(status.0["sighups_last_hour"], 2, "expired window restarts the count");
+}
+
+/// Test a shrink that rounding collapses to zero records nothing and applies nothing
+#[pg_test]
+fn test_shrink_collapsed_by_rounding_is_noop() {
+    use crate::shmem;
+    use crate::shrink::maybe_shrink;
+
+    let set = |name: &std::ffi::CStr, value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            name.as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+    let history_rows = || {
+        Spi::get_one::<i64>("SELECT count(*) FROM walrus.history")
+            .expect("query failed")
+            .unwrap_or(0)
+    };
+
+    // ceil(99 * 0.99) = 99 MB: above the 80 MB min_wal_size floor, but no progress
+    let saved_size = unsafe { pg_sys::max_wal_size_mb };
+    unsafe { pg_sys::max_wal_size_mb = 99 };
+    set(c"walrus.shrink_enable", c"on");
+    set(c"walrus.shrink_factor", c"0.99");
+    set(c"walrus.shrink_rounding", c"up");
+    set(c"walrus.force_min_shrink", c"off");
+    set(c"walrus.min_size", c"2");
+
+    let rows_before = history_rows();
+    let adjustments_before = shmem::read_state().total_adjustments;
+    let applied = maybe_shrink(0, 0, 2, i32::MAX);
+
+    unsafe { pg_sys::max_wal_size_mb = saved_size };
+    set(c"walrus.shrink_factor", c"0.75");
+    set(c"walrus.min_size", c"1024");
+
+    assert!(!applied, "a zero-size change must not be applied");
+    assert_eq!(history_rows(), rows_before, "no history row for a no-op");
+    assert_eq!(shmem::read_state().total_adjustments, adjustments_before);
+}
+
+/// Test a failing ALTER SYSTEM returns an Err instead of a silent Ok
+#[pg_test]
+fn test_alter_system_failure_returns_err() {
+    use crate::config::{ConfigError, execute_alter_system_setting};
+
+    let unknown = execute_alter_system_setting("walrus_no_such_setting", "1");
+    assert!(
+        matches!(unknown, Err(ConfigError::InvalidValue(_))),
+        "unknown parameter should fail: {:?}",
+        unknown
+    );
+
+    let bad_value = execute_alter_system_setting("max_wal_size", "not a size");
+    assert!(
+        matches!(bad_value, Err(ConfigError::InvalidValue(_))),
+        "invalid value should fail: {:?}",
+        bad_value
+    );
+
+    // The failure was rolled back; the calling transaction is still usable
+    let alive = Spi::get_one::<i32>("SELECT 1").expect("transaction aborted");
+    assert_eq!(alive, Some(1));
+}
+
+/// Test a cycle skipped after our own reload is counted in suppressed_cycles
+#[pg_test]
+fn test_suppressed_cycles_counted() {
+    use crate::shmem;
+    use crate::worker::{should_skip_iteration, suppress_next_sighup};
+
+    let before = shmem::read_state().suppressed_cycles;
+    assert!(!should_skip_iteration(), "no reload pending");
+    assert_eq!(shmem::read_state().suppressed_cycles, before);
+
+    suppress_next_sighup();
+    assert!(should_skip_iteration(), "the cycle after our reload is skipped");
+    assert_eq!(shmem::read_state().suppressed_cycles, before + 1);
+
+    // Only the one cycle is skipped
+    assert!(!should_skip_iteration());
+    assert_eq!(shmem::read_state().suppressed_cycles, before + 1);
+
+    let status = Spi::get_one::<pgrx::JsonB>("SELECT walrus.status()")
+        .expect("query failed")
+        .expect("status() returned NULL");
+    assert_eq!(status.0["suppressed_cycles"], before + 1);
+}
+
+/// Test 'peak_ratio' shrinks toward walrus.peak_ratio times the largest recent grow
+#[pg_test]
+fn test_peak_ratio_shrink_target_from_history() {
+    use crate::history::{insert_history_record, recent_peak_size_mb};
+
+    let set = |name: &std::ffi::CStr, value: &std::ffi::CStr| unsafe {
+        pg_sys::SetConfigOption(
+            name.as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_SIGHUP,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    };
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    for (action, old, new) in [
+        ("increase", 800, 1000),
+        ("capped", 1000, 1200),
+        ("decrease", 4096, 2500),
+        ("increase", 1200, 3000),
+    ] {
+        insert_history_record(action, old, new, 0, 300, None, None).expect("insert failed");
+    }
+    // The 3000 MB grow is older than walrus.history_retention_days
+    Spi::run(
+        "UPDATE walrus.history SET timestamp = now() - interval '30 days' \
+         WHERE new_size_mb = 3000",
+    )
+    .expect("update failed");
+    assert_eq!(recent_peak_size_mb().expect("query failed"), Some(1200));
+
+    let saved_size = unsafe { pg_sys::max_wal_size_mb };
+    unsafe { pg_sys::max_wal_size_mb = 4096 };
+    set(c"walrus.shrink_target", c"peak_ratio");
+    set(c"walrus.peak_ratio", c"1.5");
+    set(c"walrus.min_size", c"1024");
+    let rec = Spi::get_one::<pgrx::JsonB>(
+        "SELECT walrus.recommendation(hypothetical_delta := 0, hypothetical_quiet_intervals := 100)",
+    )
+    .expect("query failed")
+    .expect("NULL recommendation");
+
+    unsafe { pg_sys::max_wal_size_mb = saved_size };
+    set(c"walrus.shrink_target", c"factor");
+
+    assert_eq!(rec.0["action"], "decrease");
+    assert_eq!(rec.0["recommended_size_mb"], 1800, "1.5 x 1200 MB peak");
+}
+
+// =========================================================================
+// walrus.read_only Tests
+// =========================================================================
+
+/// Set walrus.read_only (postmaster-context) for this backend only
+fn set_read_only(value: &std::ffi::CStr) {
+    unsafe {
+        pg_sys::SetConfigOption(
+            c"walrus.read_only".as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_POSTMASTER,
+            pg_sys::GucSource::PGC_S_OVERRIDE,
+        );
+    }
+}
+
+/// Test no history rows are written and worker decisions are skipped when read_only is on
+#[pg_test]
+fn test_read_only_writes_no_history() {
+    use crate::access::skip_read_only;
+    use crate::history::insert_history_record;
+
+    let history_rows = || {
+        Spi::get_one::<i64>("SELECT count(*) FROM walrus.history")
+            .expect("query failed")
+            .unwrap_or(0)
+    };
+
+    assert!(!skip_read_only(1024, 2048, "threshold exceeded"), "off by default");
+
+    set_read_only(c"on");
+    let rows_before = history_rows();
+    let inserted = insert_history_record("increase", 1024, 2048, 3, 300, None, None);
+    let skipped = skip_read_only(1024, 2048, "threshold exceeded");
+    let status = Spi::get_one::<pgrx::JsonB>("SELECT walrus.status()")
+        .expect("query failed")
+        .expect("status() returned NULL");
+    let rows_after = history_rows();
+    set_read_only(c"off");
+
+    assert!(inserted.is_ok(), "a skipped insert is not an error");
+    assert_eq!(rows_after, rows_before, "no history rows while read_only is on");
+    assert!(skipped, "worker decisions stop before ALTER SYSTEM");
+    assert_eq!(status.0["read_only"], true);
+}
+
+/// Test walrus.analyze(apply := true) errors when read_only is on
+#[pg_test(error = "walrus.analyze(apply := true) is not allowed while walrus.read_only is on")]
+fn test_read_only_analyze_apply_errors() {
+    set_read_only(c"on");
+    Spi::run("SELECT walrus.analyze(apply := true)").expect("query failed");
+}
+
+/// Test walrus.set_size() errors when read_only is on
+#[pg_test(error = "walrus.set_size() is not allowed while walrus.read_only is on")]
+fn test_read_only_set_size_errors() {
+    set_read_only(c"on");
+    Spi::run("SELECT walrus.set_size(2048)").expect("query failed");
+}
+
+/// Test a recommendation is actionable only for a confident increase or decrease
+#[pg_test]
+fn test_recommendation_actionable() {
+    use crate::functions::is_actionable;
+
+    assert!(!is_actionable("error", 0, 0), "an error is never actionable");
+    assert!(!is_actionable("none", 100, 0));
+    assert!(!is_actionable("increase", 40, 80), "below walrus.min_apply_confidence");
+    assert!(is_actionable("increase", 90, 80));
+    assert!(is_actionable("decrease", 80, 80));
+
+    // A large hypothetical delta recommends a grow, passing the default gate of 0
+    let row = Spi::get_two::<&str, bool>(
+        "SELECT r->>'action', (r->>'actionable')::bool
+         FROM walrus.recommendation(hypothetical_delta := 10) r",
+    )
+    .expect("query failed");
+    assert_eq!(row.1, Some(row.0 == Some("increase")));
+}
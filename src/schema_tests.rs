@@ -0,0 +1,158 @@
+// Integration tests for the shapes pg_walrus exposes.
+//
+// These tests cover the history table columns added after the first release,
+// how the exports carry them, and the raw shared memory document returned by
+// walrus.state().
+
+/// Test insert_history_record() records the current database and history() filters on it
+#[pg_test]
+fn test_history_records_database_name() {
+    use crate::history::insert_history_record;
+
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    insert_history_record("increase", 1024, 2048, 5, 300, Some("test"), None)
+        .expect("insert failed");
+
+    let recorded = Spi::get_one::<bool>(
+        "SELECT database_name = current_database() FROM walrus.history WHERE new_size_mb = 2048",
+    )
+    .expect("query failed");
+    assert_eq!(recorded, Some(true));
+
+    let listed = Spi::get_one::<i64>(
+        "SELECT count(*) FROM walrus.history(database_name := current_database())
+         WHERE database_name = current_database()",
+    )
+    .expect("query failed");
+    assert_eq!(listed, Some(1));
+    let other = Spi::get_one::<i64>(
+        "SELECT count(*) FROM walrus.history(database_name := 'no_such_database')",
+    )
+    .expect("query failed");
+    assert_eq!(other, Some(0));
+}
+
+/// Test database_name survives export and import and is part of each NDJSON line
+#[pg_test]
+fn test_database_name_exported() {
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::run(
+        "INSERT INTO walrus.history
+            (action, old_size_mb, new_size_mb, forced_checkpoints, checkpoint_timeout_sec, database_name)
+         VALUES ('increase', 1024, 2048, 5, 300, 'analytics')",
+    )
+    .expect("insert failed");
+
+    let line = Spi::get_one::<String>("SELECT * FROM walrus.history_ndjson()")
+        .expect("query failed")
+        .expect("no NDJSON line");
+    let row: serde_json::Value = serde_json::from_str(&line).expect("line is not valid JSON");
+    assert_eq!(row["database_name"], "analytics");
+
+    let csv = Spi::get_one::<String>("SELECT walrus.export_history()")
+        .expect("query failed")
+        .expect("export returned NULL");
+    Spi::run("DELETE FROM walrus.history").expect("delete failed");
+    Spi::get_one_with_args::<i64>("SELECT walrus.import_history($1)", &[csv.as_str().into()])
+        .expect("query failed");
+    let imported = Spi::get_one::<String>("SELECT database_name FROM walrus.history")
+        .expect("query failed");
+    assert_eq!(imported.as_deref(), Some("analytics"));
+}
+
+/// Test walrus.state() has every WalrusState field, with integer, boolean, or array values
+#[pg_test]
+fn test_state_has_every_field() {
+    use crate::shmem::WalrusState;
+
+    const INTEGERS: &[&str] = &[
+        "quiet_intervals",
+        "total_adjustments",
+        "prev_requested",
+        "last_check_time",
+        "last_adjustment_time",
+        "changes_this_hour",
+        "hour_window_start",
+        "ignore_checkpoints",
+        "prev_lsn",
+        "last_cleanup_time",
+        "pending_delta",
+        "sample_count",
+        "settling_until",
+        "delta_ring_pos",
+        "delta_ring_len",
+        "worker_pid",
+        "growth_mb_this_day",
+        "day_window_start",
+        "bulk_load_until",
+        "max_apply_duration_ms",
+        "effective_shrink_intervals",
+        "last_action",
+        "last_decision_time",
+        "worker_start_time",
+        "suppressed_cycles",
+        "shrinks_this_hour",
+        "shrink_hour_window_start",
+        "sighups_sent",
+        "sighups_this_hour",
+        "sighup_hour_window_start",
+        "pinned_until",
+        "decision_intervals",
+        "last_grow_interval",
+        "intended_size_mb",
+        "intended_size_at",
+        "wal_pressure_checkpoints",
+        "other_checkpoints",
+        "temp_max_mb",
+        "temp_max_until",
+        "idle_intervals",
+    ];
+    const BOOLEANS: &[&str] = &[
+        "stats_unavailable_noted",
+        "schema_missing",
+        "causes_known",
+        "suppress_next_sighup",
+        "clean_shutdown",
+        "needs_rebaseline",
+    ];
+    const ARRAYS: &[&str] = &["delta_ring", "cluster_id"];
+
+    let saved = crate::shmem::read_state();
+    crate::shmem::update_state(|state| {
+        state.prev_requested = 12345;
+        state.hour_window_start = 1_700_000_000;
+    });
+    let state = Spi::get_one::<pgrx::JsonB>("SELECT walrus.state()")
+        .expect("query failed")
+        .expect("state should return JSONB")
+        .0;
+    crate::shmem::update_state(|state| *state = saved);
+    let fields = state.as_object().expect("state() should be an object");
+    let struct_fields = serde_json::to_value(WalrusState::default()).unwrap();
+    assert_eq!(
+        fields.len(),
+        struct_fields.as_object().unwrap().len(),
+        "one key per WalrusState field"
+    );
+    assert_eq!(fields.len(), INTEGERS.len() + BOOLEANS.len() + ARRAYS.len());
+
+    for name in INTEGERS {
+        assert!(fields[*name].is_i64(), "{name} should be an integer");
+    }
+    for name in BOOLEANS {
+        assert!(fields[*name].is_boolean(), "{name} should be a boolean");
+    }
+    for name in ARRAYS {
+        let values = fields[*name]
+            .as_array()
+            .unwrap_or_else(|| panic!("{name} is an array"));
+        assert!(
+            values.iter().all(|v| v.is_i64()),
+            "{name} should hold integers"
+        );
+    }
+
+    // Raw values, not status()'s conversions
+    assert_eq!(fields["prev_requested"], 12345);
+    assert_eq!(fields["hour_window_start"], 1_700_000_000);
+}
@@ -0,0 +1,240 @@
+//! Post-startup settling period for pg_walrus.
+//!
+//! The worker starts once recovery finishes (BgWorkerStartTime::RecoveryFinished),
+//! which includes a standby being promoted. Checkpoint behavior can change
+//! abruptly at that point, and the fresh worker has no baseline, so the first
+//! real delta may be misleadingly large. For the first `walrus.shrink_intervals`
+//! checkpoint_timeout intervals after the worker starts, each grow is limited
+//! to `walrus.settling_max_step_mb` above the current max_wal_size.
+//!
+//! The end of the period is tracked as `settling_until` in shared memory.
+//!
+//! Before that, `walrus.startup_grace_sec` can hold off decisions entirely:
+//! for that many seconds after `worker_start_time`, the worker keeps its
+//! checkpoint baseline current but neither grows nor shrinks. Emergency shrinks
+//! and the cap-down to a lowered walrus.max run before the grace check and
+//! still apply: they act on a setting or on free disk space, not on a delta
+//! that startup checkpoints could distort. Settling counts intervals and limits
+//! grow steps; the grace period is time-based and skips decisions.
+//!
+//! A stall has the same problem as a fresh start: if the worker (or the
+//! scheduler calling walrus.tick()) was blocked for more than
+//! `walrus.max_interval_drift` checkpoint_timeout intervals, the checkpoints
+//! accumulated over the gap are not one interval's worth. That cycle makes no
+//! decision and re-establishes the baseline instead (`interval_drifted()`).
+
+use crate::guc::{
+    WALRUS_MAX_INTERVAL_DRIFT, WALRUS_SETTLING_MAX_STEP_MB, WALRUS_SHRINK_INTERVALS,
+    WALRUS_STARTUP_GRACE_SEC,
+};
+use crate::logging;
+use crate::shmem::{self, now_unix};
+use crate::stats::checkpoint_timeout;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set while the worker is in its startup grace period, so its end is logged once.
+static IN_GRACE: AtomicBool = AtomicBool::new(false);
+
+/// Unix timestamp at which the settling period ends.
+///
+/// `start` plus `intervals` checkpoint_timeout intervals (saturating).
+#[inline]
+pub(crate) fn settling_deadline(start: i64, intervals: i32, timeout_secs: i64) -> i64 {
+    start.saturating_add((intervals.max(0) as i64).saturating_mul(timeout_secs.max(0)))
+}
+
+/// Whether the settling period is still in effect at `now`.
+///
+/// A `settling_until` of 0 (never set, or cleared by walrus.reset()) is not settling.
+#[inline]
+pub(crate) fn is_settling(now: i64, settling_until: i64) -> bool {
+    now < settling_until
+}
+
+/// Limit a grow target to a single step above the current size.
+#[inline]
+pub(crate) fn settling_step_cap(current_size: i32, new_size: i32, max_step_mb: i32) -> i32 {
+    new_size.min(current_size.saturating_add(max_step_mb))
+}
+
+/// Whether the startup grace period is in effect at `now`.
+///
+/// A `grace_sec` of 0 disables it, and a `worker_start_time` of 0 (worker not
+/// started) is never in grace.
+#[inline]
+pub(crate) fn in_startup_grace(now: i64, worker_start_time: i64, grace_sec: i32) -> bool {
+    grace_sec > 0
+        && worker_start_time > 0
+        && now < worker_start_time.saturating_add(grace_sec as i64)
+}
+
+/// Check the startup grace period (walrus.startup_grace_sec) for this cycle.
+///
+/// Returns true while decisions must be skipped. Logs once when a grace
+/// period that was in effect ends.
+pub(crate) fn startup_grace_active() -> bool {
+    let active = in_startup_grace(
+        now_unix(),
+        shmem::read_state().worker_start_time,
+        WALRUS_STARTUP_GRACE_SEC.get(),
+    );
+    if active {
+        IN_GRACE.store(true, Ordering::Relaxed);
+    } else if IN_GRACE.swap(false, Ordering::Relaxed) {
+        logging::info(format_args!(
+            "pg_walrus: startup grace period ended, sizing decisions resume"
+        ));
+    }
+    active
+}
+
+/// Whether `elapsed_secs` since the previous cycle is too long to be one interval.
+///
+/// True above `max_drift` checkpoint_timeout intervals; a `max_drift` of 0
+/// disables the check.
+#[inline]
+pub(crate) fn is_interval_drift(elapsed_secs: i64, timeout_secs: i64, max_drift: i32) -> bool {
+    max_drift > 0 && elapsed_secs > timeout_secs.max(1).saturating_mul(max_drift as i64)
+}
+
+/// Check the gap since the previous cycle (walrus.max_interval_drift).
+///
+/// `prev_check_time` of 0 (no previous cycle) never drifts. Returns true, after
+/// logging a WARNING, when this cycle must re-baseline instead of deciding.
+pub(crate) fn interval_drifted(prev_check_time: i64, now: i64) -> bool {
+    let timeout_secs = checkpoint_timeout().as_secs() as i64;
+    let max_drift = WALRUS_MAX_INTERVAL_DRIFT.get();
+    let elapsed = now - prev_check_time;
+    if prev_check_time <= 0 || !is_interval_drift(elapsed, timeout_secs, max_drift) {
+        return false;
+    }
+    pgrx::warning!(
+        "pg_walrus: {} seconds since the previous cycle exceeds walrus.max_interval_drift ({} x {}s checkpoint_timeout), skipping decisions and re-establishing the baseline",
+        elapsed,
+        max_drift,
+        timeout_secs
+    );
+    true
+}
+
+/// Start the settling period. Called once when the worker starts.
+pub(crate) fn begin_settling() {
+    let intervals = WALRUS_SHRINK_INTERVALS.get();
+    let until = settling_deadline(now_unix(), intervals, checkpoint_timeout().as_secs() as i64);
+    shmem::update_state(|state| {
+        state.settling_until = until;
+    });
+    logging::info(format_args!(
+        "pg_walrus: settling for {} intervals after startup, grows limited to {} MB per step",
+        intervals,
+        WALRUS_SETTLING_MAX_STEP_MB.get()
+    ));
+}
+
+/// Grow step limit in effect right now, or None once settling has ended.
+pub(crate) fn settling_step() -> Option<i32> {
+    is_settling(now_unix(), shmem::read_state().settling_until)
+        .then(|| WALRUS_SETTLING_MAX_STEP_MB.get())
+}
+
+/// Apply the settling step limit to a grow target, if settling.
+///
+/// Returns `new_size` unchanged once the settling period has ended.
+pub(crate) fn limit_grow_while_settling(current_size: i32, new_size: i32) -> i32 {
+    let Some(max_step_mb) = settling_step() else {
+        return new_size;
+    };
+    let limited = settling_step_cap(current_size, new_size, max_step_mb);
+    if limited < new_size {
+        logging::info(format_args!(
+            "pg_walrus: post-promotion settling period, limiting grow to {} MB (requested {} MB)",
+            limited, new_size
+        ));
+    }
+    limited
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test only a gap above max_drift intervals counts as drift
+    #[test]
+    fn test_is_interval_drift() {
+        assert!(!is_interval_drift(300, 300, 3));
+        assert!(
+            !is_interval_drift(900, 300, 3),
+            "exactly 3 intervals is fine"
+        );
+        assert!(is_interval_drift(901, 300, 3));
+        assert!(!is_interval_drift(86400, 300, 0), "0 disables the check");
+    }
+
+    /// Test the settling deadline covers the configured number of intervals
+    #[test]
+    fn test_settling_deadline() {
+        assert_eq!(settling_deadline(1000, 5, 300), 2500);
+        assert_eq!(settling_deadline(1000, 0, 300), 1000);
+        assert_eq!(settling_deadline(i64::MAX - 1, 5, 300), i64::MAX);
+    }
+
+    /// Test settling is active only before the deadline
+    #[test]
+    fn test_is_settling() {
+        assert!(is_settling(1000, 2500));
+        assert!(is_settling(2499, 2500));
+        assert!(!is_settling(2500, 2500));
+        assert!(!is_settling(1000, 0));
+    }
+
+    /// Test grows are step-limited while settling and unchanged afterward
+    #[test]
+    fn test_grow_step_limited_while_settling() {
+        let settling_until = settling_deadline(1000, 5, 300);
+        let (current, requested, step) = (1024, 8192, 1024);
+
+        let while_settling = if is_settling(1200, settling_until) {
+            settling_step_cap(current, requested, step)
+        } else {
+            requested
+        };
+        assert_eq!(while_settling, 2048);
+
+        let afterward = if is_settling(2600, settling_until) {
+            settling_step_cap(current, requested, step)
+        } else {
+            requested
+        };
+        assert_eq!(afterward, 8192);
+    }
+
+    /// Test no decision is allowed until the grace period has elapsed
+    #[test]
+    fn test_no_decisions_during_startup_grace() {
+        let (start, grace) = (1000, 120);
+        let decided: Vec<i64> = (1000..=1300)
+            .step_by(30)
+            .filter(|&now| !in_startup_grace(now, start, grace))
+            .collect();
+        assert_eq!(decided.first(), Some(&1120));
+        assert!(decided.iter().all(|&now| now >= start + grace as i64));
+    }
+
+    /// Test the grace period is off when disabled or before the worker starts
+    #[test]
+    fn test_startup_grace_disabled() {
+        assert!(!in_startup_grace(1000, 1000, 0));
+        assert!(!in_startup_grace(1000, 0, 120));
+        // The end saturates instead of overflowing
+        assert!(in_startup_grace(i64::MAX - 1, i64::MAX - 10, 120));
+    }
+
+    /// Test the step cap leaves small grows alone and does not overflow
+    #[test]
+    fn test_settling_step_cap_bounds() {
+        assert_eq!(settling_step_cap(1024, 1536, 1024), 1536);
+        assert_eq!(settling_step_cap(i32::MAX - 10, i32::MAX, 1024), i32::MAX);
+    }
+}
@@ -9,15 +9,43 @@
 //! - `prev_requested`: Previous checkpoint count baseline
 //! - `last_check_time`: Unix timestamp of last analysis cycle
 //! - `last_adjustment_time`: Unix timestamp of last sizing adjustment
+//! - `ignore_checkpoints`: Forced checkpoints to exclude from the next delta
+//! - `prev_lsn`: WAL insert position baseline for WAL volume tracking
+//! - `last_cleanup_time`: Unix timestamp of last history cleanup
+//! - `stats_unavailable_noted`: Whether the current stats outage was recorded
+//! - `pending_delta`: Forced checkpoints accumulated across sub-samples
+//! - `sample_count`: Sub-samples taken in the current interval
+//! - `settling_until`: Unix timestamp when the post-startup settling period ends
+//! - `delta_ring`, `delta_ring_pos`, `delta_ring_len`: Recent interval deltas
+//! - `worker_pid`: Process ID of the running background worker
+//! - `growth_mb_this_day`, `day_window_start`: Cumulative growth in the rolling day window
+//! - `bulk_load_until`: Unix timestamp when bulk-load mode ends
+//! - `max_apply_duration_ms`: Slowest worker ALTER SYSTEM since start
+//! - `schema_missing`: Whether the worker's database lacks the history table
+//! - `effective_shrink_intervals`: Quiet intervals required by walrus.shrink_backoff
+//! - `last_action`, `last_decision_time`: Most recent sizing decision
+//! - `worker_start_time`: Unix timestamp when the background worker started
+//! - `suppressed_cycles`: Worker cycles skipped after our own configuration reloads
+//! - `shrinks_this_hour`, `shrink_hour_window_start`: Shrinks in the walrus.max_shrinks_per_hour window
+//! - `sighups_sent`, `sighups_this_hour`, `sighup_hour_window_start`: Configuration reloads requested
+//! - `pinned_until`: Unix timestamp when the walrus.pin() pin expires
+//! - `suppress_next_sighup`: Whether the worker will skip its next cycle as our own reload
+//! - `cluster_id`: Port and data directory of this cluster, for telling co-located clusters apart
+//! - `idle_intervals`: Consecutive quiet intervals, counted across shrinks
+//! - `needs_rebaseline`: Whether the worker must re-establish its baseline after walrus.reset()
 
+use crate::algorithm::DELTA_RING_SIZE;
+use crate::cluster_id::CLUSTER_ID_LEN;
 use pgrx::lwlock::PgLwLock;
 use pgrx::shmem::PGRXSharedMemory;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Worker state exposed via PostgreSQL shared memory for real-time SQL function access.
 ///
 /// This struct must implement `Copy`, `Clone`, and `Default` for shared memory safety.
 /// All fields use primitive types that can be safely read/written across process boundaries.
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Default, Debug, Serialize)]
 pub struct WalrusState {
     /// Consecutive intervals with low checkpoint activity (delta < threshold).
     /// Reset to 0 after grow or shrink operations.
@@ -28,7 +56,7 @@ pub struct WalrusState {
     pub total_adjustments: i64,
 
     /// Previous checkpoint count baseline (for delta calculation).
-    /// Updated each monitoring cycle after the first iteration.
+    /// Updated each monitoring cycle (or sub-sample) after the first iteration.
     pub prev_requested: i64,
 
     /// Unix timestamp of last analysis cycle (seconds since epoch).
@@ -49,9 +77,175 @@ pub struct WalrusState {
     /// Value of 0 means no adjustments have occurred since server start.
     /// Updated when first adjustment in a new hour window occurs.
     pub hour_window_start: i64,
+
+    /// Pending count of forced checkpoints to exclude from delta calculation.
+    /// Set by `walrus.ignore_next_checkpoints()` before manual CHECKPOINT commands.
+    /// Decremented by the worker as checkpoints are excluded.
+    pub ignore_checkpoints: i64,
+
+    /// WAL insert position (LSN, in bytes) at the previous analysis cycle.
+    /// Used to measure WAL generated per interval. Value of 0 means no baseline.
+    pub prev_lsn: i64,
+
+    /// Unix timestamp of last history cleanup (seconds since epoch).
+    /// Value of 0 means cleanup has not run since server start (or reset).
+    pub last_cleanup_time: i64,
+
+    /// True once a 'note' history row has been written for the current
+    /// contiguous checkpoint statistics outage. Cleared on the next successful read.
+    pub stats_unavailable_noted: bool,
+
+    /// Forced checkpoints accumulated across sub-samples of the current interval
+    /// (walrus.sample_interval_sec). Cleared when a grow/shrink decision is made.
+    pub pending_delta: i64,
+
+    /// Sub-samples taken in the current interval. Cleared when a decision is made.
+    pub sample_count: i32,
+
+    /// Unix timestamp when the post-startup settling period ends.
+    /// Set when the worker starts; value of 0 means not settling.
+    pub settling_until: i64,
+
+    /// Ring of the most recent interval deltas (walrus.use_rolling_delta,
+    /// walrus.lookback_intervals).
+    pub delta_ring: [i32; DELTA_RING_SIZE],
+
+    /// Next slot of `delta_ring` to write.
+    pub delta_ring_pos: i32,
+
+    /// Number of filled slots in `delta_ring` (at most DELTA_RING_SIZE).
+    pub delta_ring_len: i32,
+
+    /// Process ID of the background worker, published at startup so
+    /// walrus.kick() can set its latch. Value of 0 means not running.
+    /// Not cleared by walrus.reset().
+    pub worker_pid: i32,
+
+    /// Cumulative grow MB applied in the current rolling 24-hour window
+    /// (walrus.max_growth_mb_per_day). Reset when a new window starts.
+    pub growth_mb_this_day: i64,
+
+    /// Unix timestamp when the current growth window started (seconds since epoch).
+    /// Value of 0 means no grows have occurred since server start.
+    pub day_window_start: i64,
+
+    /// Unix timestamp when bulk-load mode ends (walrus.begin_bulk_load()).
+    /// Value of 0 means bulk-load mode is not active.
+    pub bulk_load_until: i64,
+
+    /// Longest time, in milliseconds, a worker grow or shrink spent in
+    /// ALTER SYSTEM since PostgreSQL start (or reset).
+    pub max_apply_duration_ms: i64,
+
+    /// True when the worker found no history table in its database (walrus.database),
+    /// i.e. CREATE EXTENSION pg_walrus was not run there. Checked at startup
+    /// and before each scheduled cleanup. Not cleared by walrus.reset().
+    pub schema_missing: bool,
+
+    /// Quiet intervals required for the next shrink under walrus.shrink_backoff,
+    /// raised after each shrink. Value of 0 means walrus.shrink_intervals
+    /// (no backoff); reset to 0 by a grow.
+    pub effective_shrink_intervals: i32,
+
+    /// Most recent sizing decision, as the 1-based position of its action in
+    /// `history::DECISION_ACTIONS` (see `decision_action_name()`).
+    /// Value of 0 means no decision since PostgreSQL start (or reset).
+    pub last_action: i32,
+
+    /// Unix timestamp of the most recent sizing decision (seconds since epoch).
+    /// Unlike `last_adjustment_time`, also set by skipped and dry-run decisions.
+    pub last_decision_time: i64,
+
+    /// Unix timestamp when the background worker started (walrus.startup_grace_sec).
+    /// Value of 0 means the worker has not started. Not cleared by walrus.reset().
+    pub worker_start_time: i64,
+
+    /// Worker cycles skipped because they followed our own configuration reload
+    /// (see `should_skip_iteration()`). A fast-growing count points to a reload storm.
+    pub suppressed_cycles: i64,
+
+    /// Shrinks made in the current rolling hour window, counted only while
+    /// walrus.max_shrinks_per_hour gives shrinks their own budget.
+    pub shrinks_this_hour: i32,
+
+    /// Unix timestamp when the current shrink hour window started (seconds since epoch).
+    /// Value of 0 means no shrinks have been counted since server start.
+    pub shrink_hour_window_start: i64,
+
+    /// Configuration reloads requested by pg_walrus since PostgreSQL start
+    /// (or the last walrus.reset()).
+    pub sighups_sent: i64,
+
+    /// Configuration reloads requested in the current rolling hour window.
+    pub sighups_this_hour: i32,
+
+    /// Unix timestamp when the current reload hour window started (seconds since epoch).
+    /// Value of 0 means no reload has been requested since server start.
+    pub sighup_hour_window_start: i64,
+
+    /// Unix timestamp until which walrus.pin() holds max_wal_size; decisions
+    /// are only recorded before then. Value of 0 means not pinned.
+    pub pinned_until: i64,
+
+    /// Decision intervals the worker has completed since PostgreSQL start
+    /// (or reset); counts each interval that reached a grow/shrink decision.
+    pub decision_intervals: i64,
+
+    /// `decision_intervals` at the most recent applied grow
+    /// (walrus.grow_protect_intervals). Value of 0 means no grow since start or reset.
+    pub last_grow_interval: i64,
+
+    /// max_wal_size last written with ALTER SYSTEM, checked against the
+    /// effective value once the configuration has been reloaded. Value of 0
+    /// means nothing is waiting to be verified.
+    pub intended_size_mb: i32,
+
+    /// When `intended_size_mb` was written (TimestampTz, microseconds since 2000).
+    pub intended_size_at: i64,
+
+    /// Whether the last decision interval's checkpoint causes are known
+    /// (false without a WAL position baseline).
+    pub causes_known: bool,
+
+    /// Checkpoints of the last decision interval attributed to WAL volume.
+    pub wal_pressure_checkpoints: i64,
+
+    /// Remaining requested checkpoints of the last decision interval.
+    pub other_checkpoints: i64,
+
+    /// walrus.temporary_max() override in MB. Value of 0 means none; kept
+    /// after expiry until max_wal_size is back within walrus.max.
+    pub temp_max_mb: i32,
+
+    /// Unix timestamp (seconds) when the walrus.temporary_max() override expires.
+    pub temp_max_until: i64,
+
+    /// Cluster id ("port:data_directory"), zero-padded, published by the worker
+    /// at startup (see `cluster_id`). Not cleared by walrus.reset().
+    pub cluster_id: [u8; CLUSTER_ID_LEN],
+
+    /// Mirror of the worker's suppress-next-SIGHUP flag: true while its next
+    /// cycle will be skipped as our own configuration reload. Cleared by the
+    /// worker when it skips, or by walrus.clear_suppress() to cancel the skip.
+    pub suppress_next_sighup: bool,
+
+    /// Set by the worker when it exits on SIGTERM, cleared when it starts. Still
+    /// false at startup after a previous instance (`worker_start_time` set) means
+    /// that instance terminated unexpectedly. Not cleared by walrus.reset().
+    pub clean_shutdown: bool,
+
+    /// Consecutive quiet intervals, like `quiet_intervals` but not reset by a
+    /// shrink (walrus.fast_shrink_after_intervals). Reset to 0 by an interval
+    /// that is not quiet and by a grow.
+    pub idle_intervals: i32,
+
+    /// Set by walrus.reset(), which zeros `prev_requested` under a running
+    /// worker: its next cycle establishes the baseline as on its first
+    /// iteration instead of measuring a delta from 0, then clears the flag.
+    pub needs_rebaseline: bool,
 }
 
-// SAFETY: WalrusState contains only primitive types (i32, i64) which are Copy
+// SAFETY: WalrusState contains only primitive types (i32, i64, bool, [i32; N]) which are Copy
 // and can be safely accessed across PostgreSQL backends via shared memory.
 // The struct has no pointers or non-Copy fields.
 unsafe impl PGRXSharedMemory for WalrusState {}
@@ -65,9 +259,42 @@ unsafe impl PGRXSharedMemory for WalrusState {}
 /// requires a direct identifier, not a path.
 pub static WALRUS_STATE: PgLwLock<WalrusState> = unsafe { PgLwLock::new(c"walrus_state") };
 
+/// Error reported when shared memory was never set up.
+pub const NOT_INITIALIZED_MESSAGE: &str =
+    "pg_walrus: shared memory not initialized; load via shared_preload_libraries";
+
+/// Set by `mark_initialized()` once `pg_shmem_init!(WALRUS_STATE)` has run.
+///
+/// _PG_init runs in the postmaster, so forked backends and the worker inherit it.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Record that `pg_shmem_init!(WALRUS_STATE)` was called (from _PG_init).
+#[inline]
+pub fn mark_initialized() {
+    INITIALIZED.store(true, Ordering::Relaxed);
+}
+
+/// Whether WALRUS_STATE is backed by shared memory.
+///
+/// False when pg_walrus was loaded without shared_preload_libraries (e.g. only
+/// via CREATE EXTENSION), in which case WALRUS_STATE must not be touched.
+#[inline]
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::Relaxed)
+}
+
+/// Raise a clear ERROR instead of touching an uninitialized WALRUS_STATE.
+#[inline]
+fn require_initialized() {
+    if !is_initialized() {
+        pgrx::error!("{}", NOT_INITIALIZED_MESSAGE);
+    }
+}
+
 /// Read the current shared memory state with a shared lock.
 ///
 /// Returns a copy of the state. The lock is held only during the read.
+/// Raises an ERROR if shared memory is not initialized.
 ///
 /// # Example
 ///
@@ -77,6 +304,7 @@ pub static WALRUS_STATE: PgLwLock<WalrusState> = unsafe { PgLwLock::new(c"walrus
 /// ```
 #[inline]
 pub fn read_state() -> WalrusState {
+    require_initialized();
     *WALRUS_STATE.share()
 }
 
@@ -84,6 +312,7 @@ pub fn read_state() -> WalrusState {
 ///
 /// The provided closure receives a mutable reference to the state.
 /// The lock is held only during the update.
+/// Raises an ERROR if shared memory is not initialized.
 ///
 /// # Example
 ///
@@ -98,6 +327,7 @@ pub fn update_state<F>(f: F)
 where
     F: FnOnce(&mut WalrusState),
 {
+    require_initialized();
     let mut state = WALRUS_STATE.exclusive();
     f(&mut state);
 }
@@ -105,10 +335,13 @@ where
 /// Reset all shared memory state to zero.
 ///
 /// Called by `walrus.reset()` to clear counters and timestamps.
-/// The worker will see the reset state on its next cycle.
+/// The worker will see the reset state on its next cycle, which re-establishes
+/// the baseline (`needs_rebaseline`).
 /// This includes rate limiting state (changes_this_hour, hour_window_start).
+/// The worker's pid is kept, since the worker is still running.
 #[inline]
 pub fn reset_state() {
+    require_initialized();
     let mut state = WALRUS_STATE.exclusive();
     state.quiet_intervals = 0;
     state.total_adjustments = 0;
@@ -117,6 +350,42 @@ pub fn reset_state() {
     state.last_adjustment_time = 0;
     state.changes_this_hour = 0;
     state.hour_window_start = 0;
+    state.ignore_checkpoints = 0;
+    state.prev_lsn = 0;
+    state.last_cleanup_time = 0;
+    state.stats_unavailable_noted = false;
+    state.pending_delta = 0;
+    state.sample_count = 0;
+    state.settling_until = 0;
+    state.delta_ring = [0; DELTA_RING_SIZE];
+    state.delta_ring_pos = 0;
+    state.delta_ring_len = 0;
+    state.growth_mb_this_day = 0;
+    state.day_window_start = 0;
+    state.bulk_load_until = 0;
+    state.max_apply_duration_ms = 0;
+    state.effective_shrink_intervals = 0;
+    state.last_action = 0;
+    state.last_decision_time = 0;
+    state.suppressed_cycles = 0;
+    state.shrinks_this_hour = 0;
+    state.shrink_hour_window_start = 0;
+    state.sighups_sent = 0;
+    state.sighups_this_hour = 0;
+    state.sighup_hour_window_start = 0;
+    state.pinned_until = 0;
+    state.decision_intervals = 0;
+    state.last_grow_interval = 0;
+    state.intended_size_mb = 0;
+    state.intended_size_at = 0;
+    state.causes_known = false;
+    state.wal_pressure_checkpoints = 0;
+    state.other_checkpoints = 0;
+    state.temp_max_mb = 0;
+    state.temp_max_until = 0;
+    state.suppress_next_sighup = false;
+    state.idle_intervals = 0;
+    state.needs_rebaseline = true;
 }
 
 /// Get current Unix timestamp in seconds.
@@ -0,0 +1,541 @@
+//! Shrink path for pg_walrus.
+//!
+//! Once enough consecutive quiet intervals have accumulated, the worker shrinks
+//! max_wal_size by walrus.shrink_factor, never below the shrink floor (the
+//! larger of walrus.min_size and min_wal_size). Shrinks go through the same
+//! walrus.mode, confidence, rate-limit, and dry-run gates as grows.
+//!
+//! With walrus.shrink_backoff, each applied shrink multiplies the quiet
+//! intervals required for the next one by SHRINK_BACKOFF_FACTOR
+//! (`effective_shrink_intervals`); the worker resets it on a grow.
+//!
+//! `walrus.preview_shrink_path()` shows the descent a shrink_factor would
+//! take from the current size, without shrinking.
+//!
+//! With walrus.shrink_target = 'peak_ratio', the target is instead
+//! walrus.peak_ratio times the largest grow in history
+//! (`history::recent_peak_size_mb()`), still clamped to the floor and never
+//! above the current size. Without a recent grow it falls back to the factor.
+//!
+//! Because the target is rounded up, a factor near 0.99 just above the floor
+//! can leave it at the current size. That stuck state is logged once, and
+//! with walrus.force_min_shrink the worker shrinks by one WAL segment instead.
+//!
+//! With walrus.grow_protect_intervals, no shrink is made for that many
+//! decision intervals after an applied grow (`grow_protected()`), however
+//! many quiet intervals have accumulated; quiet intervals keep counting.
+//!
+//! With walrus.fast_shrink_after_intervals, once the database has been quiet
+//! for more than that many consecutive intervals (`idle_intervals`, which a
+//! shrink does not reset), the worker shrinks straight to the floor in one step
+//! instead of by the factor, so a long-idle database is not walked down one
+//! walrus.shrink_intervals at a time. The row records `fast_shrink: true`.
+//!
+//! The shrink is applied with `abort_if_stale`: if another session wrote a
+//! different max_wal_size after the cycle read it (e.g. `walrus.set_size()`,
+//! whose reload reaches the worker only before its next cycle),
+//! `apply_max_wal_size()` aborts it under the adjustment lock and the next
+//! cycle computes from the new size.
+
+use crate::access::skip_read_only;
+use crate::algorithm::{
+    ModeGate, action_confidence, backed_off_shrink_intervals, calculate_shrink_size,
+    compute_confidence, current_shrink_intervals, grow_protected, is_noop_resize,
+    meets_confidence_threshold, min_step_shrink_size, mode_gate, peak_ratio_shrink_size,
+    shrink_floor, shrink_is_stuck, shrink_path,
+};
+use crate::apply::{ApplyError, ApplySource, SizeChange, apply_max_wal_size};
+use crate::guc::{
+    ShrinkTarget, WALRUS_FAST_SHRINK_AFTER_INTERVALS, WALRUS_FORCE_MIN_SHRINK,
+    WALRUS_GROW_PROTECT_INTERVALS, WALRUS_MIN_SHRINK_CONFIDENCE, WALRUS_MIN_SIZE, WALRUS_MODE,
+    WALRUS_PEAK_RATIO, WALRUS_SHRINK_BACKOFF, WALRUS_SHRINK_CONFIDENCE_PENALTY,
+    WALRUS_SHRINK_ENABLE, WALRUS_SHRINK_FACTOR, WALRUS_SHRINK_ROUNDING, WALRUS_SHRINK_TARGET,
+};
+use crate::history;
+use crate::logging;
+use crate::pin::effective_dry_run;
+use crate::rate_limit::{check_shrink_rate_limit, update_rate_limit_state_after_shrink};
+use crate::reproduce::{SizingRule, decision_inputs, with_decision_inputs};
+use crate::shmem::{self, now_unix};
+use crate::stats::{
+    checkpoint_timeout, get_current_max_wal_size, get_current_min_wal_size, get_wal_segment_size_mb,
+};
+use crate::worker::in_transaction;
+
+use pgrx::iter::TableIterator;
+use pgrx::name;
+use serde_json::{Value as JsonValue, json};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the stuck-shrink warning has been logged; cleared when a shrink can progress.
+static STUCK_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Whether `idle_intervals` consecutive quiet intervals call for a fast shrink.
+///
+/// Never when walrus.fast_shrink_after_intervals (`after`) is 0.
+#[inline]
+pub(crate) fn fast_shrink_due(idle_intervals: i32, after: i32) -> bool {
+    after > 0 && idle_intervals > after
+}
+
+/// Record that a shrink went straight to the floor (walrus.fast_shrink_after_intervals).
+///
+/// Unchanged when `idle_intervals` is None (an ordinary shrink).
+pub(crate) fn with_fast_shrink(mut metadata: JsonValue, idle_intervals: Option<i32>) -> JsonValue {
+    if let (JsonValue::Object(map), Some(idle_intervals)) = (&mut metadata, idle_intervals) {
+        map.insert("fast_shrink".to_string(), JsonValue::Bool(true));
+        map.insert(
+            "idle_intervals".to_string(),
+            JsonValue::from(idle_intervals),
+        );
+    }
+    metadata
+}
+
+/// Shrink max_wal_size after a quiet interval, if all shrink conditions hold.
+///
+/// Called by the worker once `new_quiet_intervals` has been recorded for a
+/// quiet interval; `delta` and `threshold` are recorded with an applied
+/// shrink. Returns true when the shrink was applied through
+/// `apply_max_wal_size()`, which also requested the reload. Blocked, held, and
+/// dry-run shrinks are recorded in history and return false.
+pub(crate) fn maybe_shrink(
+    current_requested: i64,
+    delta: i64,
+    threshold: i64,
+    new_quiet_intervals: i32,
+) -> bool {
+    // Check all shrink conditions
+    let shrink_enable = WALRUS_SHRINK_ENABLE.get();
+    let shrink_intervals = current_shrink_intervals(&shmem::read_state());
+    let fast_shrink_after = WALRUS_FAST_SHRINK_AFTER_INTERVALS.get();
+    let idle_intervals = shmem::read_state().idle_intervals;
+    let fast = fast_shrink_due(idle_intervals, fast_shrink_after);
+    let current_size = get_current_max_wal_size();
+
+    // Never shrink below PostgreSQL's live min_wal_size, even if walrus.min_size is lower
+    let walrus_min_size = WALRUS_MIN_SIZE.get();
+    let min_wal_size = get_current_min_wal_size();
+    let min_size = shrink_floor(walrus_min_size, min_wal_size);
+
+    // Shrink condition: enabled (and allowed by walrus.mode) AND enough quiet intervals
+    // AND above minimum floor
+    let gate = mode_gate(WALRUS_MODE.get(), false, effective_dry_run());
+    if !shrink_enable || gate == ModeGate::Skip {
+        return false;
+    }
+
+    // A fast shrink is due however few quiet intervals the last shrink left
+    if new_quiet_intervals < shrink_intervals && !fast {
+        return false;
+    }
+
+    if current_size <= min_size {
+        pgrx::debug1!(
+            "pg_walrus: skipping shrink, max_wal_size ({} MB) already at or below min_size ({} MB)",
+            current_size,
+            min_size
+        );
+        return false;
+    }
+
+    // 'peak_ratio' shrinks toward a multiple of the largest recent grow, if any
+    let peak_mb = match WALRUS_SHRINK_TARGET.get() {
+        ShrinkTarget::Factor => None,
+        // A fast shrink goes to the floor, not toward the peak
+        ShrinkTarget::PeakRatio if fast => None,
+        ShrinkTarget::PeakRatio => match in_transaction(history::recent_peak_size_mb) {
+            Ok(peak) => peak,
+            Err(e) => {
+                pgrx::warning!(
+                    "pg_walrus: failed to read recent peak, using walrus.shrink_factor: {}",
+                    e
+                );
+                None
+            }
+        },
+    };
+    let peak_ratio = WALRUS_PEAK_RATIO.get();
+
+    // Calculate new shrink target
+    let shrink_factor = WALRUS_SHRINK_FACTOR.get();
+    let rounding = WALRUS_SHRINK_ROUNDING.get();
+    let new_size = match peak_mb {
+        Some(peak) => peak_ratio_shrink_size(current_size, peak, peak_ratio, min_size),
+        None if fast => min_size,
+        None => calculate_shrink_size(current_size, shrink_factor, min_size, rounding),
+    };
+    if fast {
+        pgrx::debug1!(
+            "pg_walrus: {} consecutive quiet intervals (walrus.fast_shrink_after_intervals = {}), shrinking straight to {} MB",
+            idle_intervals,
+            fast_shrink_after,
+            min_size
+        );
+    }
+    let fast_idle_intervals = fast.then_some(idle_intervals);
+    if new_size == min_size && min_wal_size > walrus_min_size {
+        pgrx::debug1!(
+            "pg_walrus: shrink target clamped to min_wal_size ({} MB), above walrus.min_size ({} MB)",
+            min_wal_size,
+            walrus_min_size
+        );
+    }
+
+    // Rounding up can leave the target at the current size (current_size is above
+    // the floor here), so the shrink would never make progress. A peak_ratio target
+    // at the current size is intended and is skipped as a no-op below.
+    let (new_size, forced_step_mb) = if !fast
+        && peak_mb.is_none()
+        && shrink_is_stuck(current_size, shrink_factor, min_size, rounding)
+    {
+        let segment_mb = get_wal_segment_size_mb();
+        let force = WALRUS_FORCE_MIN_SHRINK.get();
+        if !STUCK_LOGGED.swap(true, Ordering::Relaxed) {
+            pgrx::warning!(
+                "pg_walrus: walrus.shrink_factor {} cannot shrink max_wal_size below {} MB{}",
+                shrink_factor,
+                current_size,
+                if force {
+                    format!(", forcing a {} MB reduction", segment_mb)
+                } else {
+                    "; set walrus.force_min_shrink, set walrus.shrink_rounding = 'down', or lower walrus.shrink_factor".to_string()
+                }
+            );
+        }
+        if !force {
+            return false;
+        }
+        (
+            min_step_shrink_size(current_size, segment_mb, min_size),
+            Some(segment_mb),
+        )
+    } else {
+        STUCK_LOGGED.store(false, Ordering::Relaxed);
+        (new_size, None)
+    };
+
+    // Nothing to apply once every clamp and rounding step has run
+    if is_noop_resize(current_size, new_size) {
+        pgrx::debug1!(
+            "pg_walrus: shrink target equals max_wal_size ({} MB); skipping",
+            current_size
+        );
+        return false;
+    }
+
+    // READ-ONLY: log the decision instead of recording or applying it
+    if skip_read_only(current_size, new_size, "sustained low activity") {
+        return false;
+    }
+
+    let timeout_secs = checkpoint_timeout().as_secs();
+
+    // GROW PROTECTION: No shrink within walrus.grow_protect_intervals of a grow
+    let protect_intervals = WALRUS_GROW_PROTECT_INTERVALS.get();
+    let state = shmem::read_state();
+    if grow_protected(
+        state.decision_intervals,
+        state.last_grow_interval,
+        protect_intervals,
+    ) {
+        let since_grow = state.decision_intervals - state.last_grow_interval;
+        logging::info(format_args!(
+            "pg_walrus: shrink held - {} interval(s) since the last grow, walrus.grow_protect_intervals = {}",
+            since_grow, protect_intervals
+        ));
+        let metadata = json!({
+            "blocked_by": "grow_protect",
+            "intervals_since_grow": since_grow,
+            "grow_protect_intervals": protect_intervals
+        });
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "skipped",
+                current_size,
+                new_size,
+                current_requested,
+                timeout_secs as i32,
+                Some("shrink within walrus.grow_protect_intervals of a grow"),
+                Some(history::versioned_metadata(metadata.clone())),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log skipped shrink history: {}", e);
+        }
+        return false;
+    }
+
+    // CONFIDENCE GATE: Hold shrinks below walrus.min_shrink_confidence, after
+    // walrus.shrink_confidence_penalty
+    let min_confidence = WALRUS_MIN_SHRINK_CONFIDENCE.get();
+    let confidence = action_confidence(
+        compute_confidence(&shmem::read_state(), current_requested),
+        "decrease",
+        WALRUS_SHRINK_CONFIDENCE_PENALTY.get(),
+    );
+    if !meets_confidence_threshold(confidence, min_confidence) {
+        logging::info(format_args!(
+            "pg_walrus: shrink held - confidence {} below walrus.min_shrink_confidence ({})",
+            confidence, min_confidence
+        ));
+        let metadata = json!({
+            "blocked_by": "low_confidence",
+            "confidence": confidence,
+            "min_shrink_confidence": min_confidence
+        });
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "skipped",
+                current_size,
+                new_size,
+                current_requested,
+                timeout_secs as i32,
+                Some("shrink confidence below threshold"),
+                Some(history::versioned_metadata(metadata.clone())),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log skipped shrink history: {}", e);
+        }
+        return false;
+    }
+
+    // RATE LIMIT CHECK: Must occur BEFORE dry-run check per FR-014
+    // This ensures rate-limited shrink adjustments are logged correctly in both modes.
+    let rate_limit_result = check_shrink_rate_limit();
+    if rate_limit_result.is_blocked() {
+        let reason = rate_limit_result
+            .reason
+            .as_deref()
+            .unwrap_or("rate limit blocked");
+        rate_limit_result.log_blocked("shrink");
+
+        // Record skipped shrink in history
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "skipped",
+                current_size,
+                new_size,
+                current_requested,
+                timeout_secs as i32,
+                Some(reason),
+                rate_limit_result
+                    .metadata
+                    .clone()
+                    .map(history::versioned_metadata),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log skipped shrink history: {}", e);
+        }
+
+        // Do NOT reset quiet_intervals when rate-limited - we want to try again next cycle
+        return false;
+    }
+
+    // DRY-RUN CHECK: If dry-run (or observe mode) enabled, log what would happen and
+    // insert history, but skip ALTER SYSTEM and SIGHUP. Mode change takes effect on next iteration.
+    if gate == ModeGate::Record {
+        // Log dry-run message with [DRY-RUN] prefix
+        logging::info(format_args!(
+            "pg_walrus [DRY-RUN]: would change max_wal_size from {} MB to {} MB (sustained low activity)",
+            current_size, new_size
+        ));
+
+        // Build metadata with dry-run fields
+        let metadata = history::with_peak_ratio(
+            json!({
+                "dry_run": true,
+                "would_apply": "decrease",
+                "shrink_factor": shrink_factor,
+                "quiet_intervals": new_quiet_intervals,
+                "calculated_size_mb": new_size
+            }),
+            peak_mb,
+            peak_ratio,
+        );
+        let metadata = with_fast_shrink(metadata, fast_idle_intervals);
+
+        // Insert history with action='dry_run'
+        if let Err(e) = in_transaction(|| {
+            history::insert_history_record(
+                "dry_run",
+                current_size,
+                new_size,
+                current_requested,
+                timeout_secs as i32,
+                Some("sustained low activity"),
+                Some(history::versioned_metadata(metadata.clone())),
+            )
+        }) {
+            pgrx::warning!("pg_walrus: failed to log dry-run history: {}", e);
+        }
+
+        // Reset quiet_intervals after dry-run shrink decision (algorithm state must update)
+        shmem::update_state(|state| {
+            state.quiet_intervals = 0;
+        });
+
+        // Update rate limiting state for dry-run (counts against limits per FR-014)
+        update_rate_limit_state_after_shrink();
+
+        // Skip ALTER SYSTEM and SIGHUP in dry-run mode
+        return false;
+    }
+
+    // Log the shrink decision (normal mode)
+    logging::applied(format_args!(
+        "pg_walrus: shrinking max_wal_size from {} MB to {} MB",
+        current_size, new_size
+    ));
+
+    // Apply, record history (FR-004, FR-011), and reload under the adjustment lock
+    let change = SizeChange {
+        action: "decrease",
+        old_size_mb: current_size,
+        new_size_mb: new_size,
+        forced_checkpoints: current_requested,
+        checkpoint_timeout_sec: timeout_secs as i32,
+        reason: "Sustained low checkpoint activity",
+        abort_if_stale: true,
+    };
+    let rule = match peak_mb {
+        Some(peak_mb) => SizingRule::PeakRatio {
+            peak_mb,
+            peak_ratio,
+            min_size_mb: min_size,
+        },
+        None if fast => SizingRule::FastShrink {
+            fast_shrink_after_intervals: fast_shrink_after,
+            min_size_mb: min_size,
+        },
+        None => SizingRule::ShrinkFactor {
+            shrink_factor,
+            min_size_mb: min_size,
+            rounding: rounding.name().to_string(),
+            forced_step_mb,
+        },
+    };
+    let inputs = decision_inputs(
+        current_size,
+        delta,
+        threshold,
+        new_quiet_intervals,
+        new_size,
+        rule,
+    );
+    let update = |state: &mut shmem::WalrusState| {
+        state.last_adjustment_time = now_unix();
+        state.quiet_intervals = 0; // Reset after successful shrink
+        if WALRUS_SHRINK_BACKOFF.get() {
+            state.effective_shrink_intervals = backed_off_shrink_intervals(shrink_intervals);
+        }
+    };
+    let metadata = |apply_duration_ms| {
+        let key = history::decision_key(
+            shmem::read_state().last_check_time,
+            "decrease",
+            current_requested,
+            new_size,
+        );
+        let metadata = history::with_apply_duration(
+            history::with_peak_ratio(
+                json!({
+                    "shrink_factor": shrink_factor,
+                    "quiet_intervals": new_quiet_intervals,
+                    "required_quiet_intervals": shrink_intervals,
+                    "calculated_size_mb": new_size
+                }),
+                peak_mb,
+                peak_ratio,
+            ),
+            apply_duration_ms,
+        );
+        let metadata = with_fast_shrink(metadata, fast_idle_intervals);
+        let metadata = history::with_idempotency_key(metadata, &key);
+        with_decision_inputs(metadata, &inputs)
+    };
+    match apply_max_wal_size(&change, ApplySource::Worker, update, metadata) {
+        Ok(_) => {}
+        Err(ApplyError::Stale(size)) => {
+            logging::info(format_args!(
+                "pg_walrus: shrink to {} MB aborted, max_wal_size was set to {} MB after it was computed from {} MB",
+                new_size, size, current_size
+            ));
+            return false;
+        }
+        Err(e) => {
+            pgrx::warning!(
+                "pg_walrus: failed to execute ALTER SYSTEM for shrink, will retry next cycle: {}",
+                e
+            );
+            return false;
+        }
+    }
+
+    // Update rate limiting state
+    update_rate_limit_state_after_shrink();
+
+    true
+}
+
+/// Implementation for previewing the shrink descent for a shrink_factor.
+///
+/// Starting from the current max_wal_size (step 0), applies
+/// `calculate_shrink_size()` with `factor`, the current shrink floor, and
+/// walrus.shrink_rounding for `steps` shrinks (see `shrink_path()`). Nothing
+/// is changed.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn preview_shrink_path(
+    factor: f64,
+    steps: i32,
+) -> TableIterator<'static, (name!(step, i32), name!(size_mb, i32))> {
+    if !(factor > 0.0 && factor < 1.0) {
+        pgrx::error!("factor must be between 0 and 1 (exclusive)");
+    }
+    if !(1..=1000).contains(&steps) {
+        pgrx::error!("steps must be between 1 and 1000");
+    }
+
+    let floor = shrink_floor(WALRUS_MIN_SIZE.get(), get_current_min_wal_size());
+    let path = shrink_path(
+        get_current_max_wal_size(),
+        factor,
+        floor,
+        WALRUS_SHRINK_ROUNDING.get(),
+        steps,
+    );
+    TableIterator::new(
+        path.into_iter()
+            .enumerate()
+            .map(|(step, size)| (step as i32, size)),
+    )
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test a fast shrink is due only past a nonzero threshold
+    #[test]
+    fn test_fast_shrink_due() {
+        assert!(fast_shrink_due(101, 100));
+        assert!(
+            !fast_shrink_due(100, 100),
+            "at the threshold is not past it"
+        );
+        assert!(!fast_shrink_due(5, 100));
+        assert!(!fast_shrink_due(i32::MAX, 0), "disabled");
+    }
+
+    /// Test only a fast shrink is marked in the metadata
+    #[test]
+    fn test_with_fast_shrink() {
+        let fast = with_fast_shrink(json!({ "quiet_intervals": 1 }), Some(101));
+        assert_eq!(fast["fast_shrink"], true);
+        assert_eq!(fast["idle_intervals"], 101);
+
+        let ordinary = with_fast_shrink(json!({ "quiet_intervals": 5 }), None);
+        assert!(ordinary.get("fast_shrink").is_none());
+    }
+}
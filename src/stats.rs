@@ -1,9 +1,18 @@
 //! Checkpoint statistics access for pg_walrus.
 //!
-//! This module provides version-specific access to PostgreSQL checkpoint statistics
-//! and the checkpoint_timeout GUC variable.
+//! This module provides version-specific access to PostgreSQL checkpoint statistics,
+//! the checkpoint_timeout GUC variable, and the current WAL insert position.
+//!
+//! The forced checkpoint count is read with the pgstat C API by default. With
+//! walrus.stats_source = 'sql' it is read from pg_stat_checkpointer (or
+//! pg_stat_bgwriter before PostgreSQL 17) through SPI instead, which costs a
+//! query per read but does not depend on the C API returning a snapshot.
+
+use crate::guc::{StatsSource, WALRUS_STATS_SOURCE};
+use crate::worker::in_transaction;
 
 use pgrx::pg_sys;
+use pgrx::prelude::*;
 use std::time::Duration;
 
 #[cfg(unix)]
@@ -108,15 +117,38 @@ fn parse_interval_to_secs(s: &str) -> Duration {
     Duration::from_secs(300)
 }
 
-/// Returns the current count of forced (requested) checkpoints since PostgreSQL startup.
+/// Forced (requested) checkpoint count from the pgstat C API.
 ///
 /// Returns -1 if checkpoint statistics are unavailable (null pointer from pgstat).
 ///
-/// The field name differs between PostgreSQL versions:
-/// - PG 15-16: `requested_checkpoints`
-/// - PG 17+: `num_requested`
+/// The source differs between PostgreSQL versions:
+/// - PG 13-14: `requested_checkpoints` in the bgwriter global stats
+/// - PG 15-16: `requested_checkpoints` in the checkpointer stats
+/// - PG 17+: `num_requested` in the checkpointer stats
+#[cfg(any(feature = "pg13", feature = "pg14"))]
+pub fn direct_requested_checkpoints() -> i64 {
+    unsafe {
+        // Clear snapshot to get fresh statistics
+        pg_sys::pgstat_clear_snapshot();
+        // Before PG 15 checkpoint counters live in the global (bgwriter) stats
+        let stats = pg_sys::pgstat_fetch_global();
+        if stats.is_null() {
+            return -1;
+        }
+        (*stats).requested_checkpoints
+    }
+}
+
+/// Forced (requested) checkpoint count from the pgstat C API.
+///
+/// Returns -1 if checkpoint statistics are unavailable (null pointer from pgstat).
+///
+/// The source differs between PostgreSQL versions:
+/// - PG 13-14: `requested_checkpoints` in the bgwriter global stats
+/// - PG 15-16: `requested_checkpoints` in the checkpointer stats
+/// - PG 17+: `num_requested` in the checkpointer stats
 #[cfg(any(feature = "pg15", feature = "pg16"))]
-pub fn get_requested_checkpoints() -> i64 {
+pub fn direct_requested_checkpoints() -> i64 {
     unsafe {
         // Clear snapshot to get fresh statistics
         pg_sys::pgstat_clear_snapshot();
@@ -128,15 +160,16 @@ pub fn get_requested_checkpoints() -> i64 {
     }
 }
 
-/// Returns the current count of forced (requested) checkpoints since PostgreSQL startup.
+/// Forced (requested) checkpoint count from the pgstat C API.
 ///
 /// Returns -1 if checkpoint statistics are unavailable (null pointer from pgstat).
 ///
-/// The field name differs between PostgreSQL versions:
-/// - PG 15-16: `requested_checkpoints`
-/// - PG 17+: `num_requested`
+/// The source differs between PostgreSQL versions:
+/// - PG 13-14: `requested_checkpoints` in the bgwriter global stats
+/// - PG 15-16: `requested_checkpoints` in the checkpointer stats
+/// - PG 17+: `num_requested` in the checkpointer stats
 #[cfg(any(feature = "pg17", feature = "pg18"))]
-pub fn get_requested_checkpoints() -> i64 {
+pub fn direct_requested_checkpoints() -> i64 {
     unsafe {
         // Clear snapshot to get fresh statistics
         pg_sys::pgstat_clear_snapshot();
@@ -148,6 +181,112 @@ pub fn get_requested_checkpoints() -> i64 {
     }
 }
 
+/// Query returning the forced checkpoint count: `checkpoints_req` in
+/// pg_stat_bgwriter before PG 17, `num_requested` in pg_stat_checkpointer from PG 17.
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15", feature = "pg16"))]
+const REQUESTED_CHECKPOINTS_SQL: &str = "SELECT checkpoints_req FROM pg_stat_bgwriter";
+
+/// Query returning the forced checkpoint count: `checkpoints_req` in
+/// pg_stat_bgwriter before PG 17, `num_requested` in pg_stat_checkpointer from PG 17.
+#[cfg(any(feature = "pg17", feature = "pg18"))]
+const REQUESTED_CHECKPOINTS_SQL: &str = "SELECT num_requested FROM pg_stat_checkpointer";
+
+/// Forced (requested) checkpoint count from the statistics views, through SPI.
+///
+/// Returns -1 if the query fails or returns no count, like an unavailable
+/// direct read. Starts a transaction when called outside one (the worker).
+pub fn sql_requested_checkpoints() -> i64 {
+    let count = in_transaction(|| {
+        // Clear the transaction's statistics snapshot so the view reads fresh values
+        unsafe { pg_sys::pgstat_clear_snapshot() };
+        Spi::get_one::<i64>(REQUESTED_CHECKPOINTS_SQL)
+    });
+    match count {
+        Ok(Some(count)) => count,
+        Ok(None) => -1,
+        Err(e) => {
+            pgrx::debug1!(
+                "pg_walrus: failed to read checkpoint statistics view: {}",
+                e
+            );
+            -1
+        }
+    }
+}
+
+/// Returns the current count of forced (requested) checkpoints since PostgreSQL startup.
+///
+/// Returns -1 if checkpoint statistics are unavailable. Read with the pgstat
+/// C API (`direct_requested_checkpoints()`), or from the statistics views when
+/// walrus.stats_source = 'sql' (`sql_requested_checkpoints()`).
+pub fn get_requested_checkpoints() -> i64 {
+    match WALRUS_STATS_SOURCE.get() {
+        StatsSource::Direct => direct_requested_checkpoints(),
+        StatsSource::Sql => sql_requested_checkpoints(),
+    }
+}
+
+/// Returns the current count of timed (scheduled) checkpoints since PostgreSQL startup.
+///
+/// Returns -1 if checkpoint statistics are unavailable (null pointer from pgstat).
+/// Same sources as `direct_requested_checkpoints()`: `timed_checkpoints` before
+/// PG 17, `num_timed` from PG 17.
+#[cfg(any(feature = "pg13", feature = "pg14"))]
+pub fn get_timed_checkpoints() -> i64 {
+    unsafe {
+        pg_sys::pgstat_clear_snapshot();
+        let stats = pg_sys::pgstat_fetch_global();
+        if stats.is_null() {
+            return -1;
+        }
+        (*stats).timed_checkpoints
+    }
+}
+
+/// Returns the current count of timed (scheduled) checkpoints since PostgreSQL startup.
+///
+/// Returns -1 if checkpoint statistics are unavailable (null pointer from pgstat).
+/// Same sources as `direct_requested_checkpoints()`: `timed_checkpoints` before
+/// PG 17, `num_timed` from PG 17.
+#[cfg(any(feature = "pg15", feature = "pg16"))]
+pub fn get_timed_checkpoints() -> i64 {
+    unsafe {
+        pg_sys::pgstat_clear_snapshot();
+        let stats = pg_sys::pgstat_fetch_stat_checkpointer();
+        if stats.is_null() {
+            return -1;
+        }
+        (*stats).timed_checkpoints
+    }
+}
+
+/// Returns the current count of timed (scheduled) checkpoints since PostgreSQL startup.
+///
+/// Returns -1 if checkpoint statistics are unavailable (null pointer from pgstat).
+/// Same sources as `direct_requested_checkpoints()`: `timed_checkpoints` before
+/// PG 17, `num_timed` from PG 17.
+#[cfg(any(feature = "pg17", feature = "pg18"))]
+pub fn get_timed_checkpoints() -> i64 {
+    unsafe {
+        pg_sys::pgstat_clear_snapshot();
+        let stats = pg_sys::pgstat_fetch_stat_checkpointer();
+        if stats.is_null() {
+            return -1;
+        }
+        (*stats).num_timed
+    }
+}
+
+/// Returns the current min_wal_size value in MB.
+///
+/// This reads directly from PostgreSQL's global variable, which is
+/// automatically updated when configuration is reloaded.
+#[inline]
+pub fn get_current_min_wal_size() -> i32 {
+    // SAFETY: min_wal_size_mb is a global PostgreSQL variable, always valid.
+    unsafe { pg_sys::min_wal_size_mb }
+}
+
 /// Returns the current max_wal_size value in MB.
 ///
 /// This reads directly from PostgreSQL's global variable, which is
@@ -157,3 +296,47 @@ pub fn get_current_max_wal_size() -> i32 {
     // SAFETY: max_wal_size_mb is a global PostgreSQL variable, always valid.
     unsafe { pg_sys::max_wal_size_mb }
 }
+
+/// Returns the current checkpoint_completion_target.
+///
+/// Read from PostgreSQL's global variable, like the sizes above.
+#[inline]
+pub fn get_checkpoint_completion_target() -> f64 {
+    // SAFETY: CheckPointCompletionTarget is a global PostgreSQL variable, always valid.
+    unsafe { pg_sys::CheckPointCompletionTarget }
+}
+
+/// Returns max_wal_size formatted the way `SHOW max_wal_size` displays it (e.g. "1GB").
+///
+/// The same setting as `get_current_max_wal_size()`, but in PostgreSQL's
+/// chosen unit, so a configured value that does not convert exactly to MB
+/// shows up when the two are compared.
+pub fn get_max_wal_size_display() -> Option<String> {
+    // SAFETY: max_wal_size always exists; the result is palloc'd in the current context.
+    unsafe {
+        let value =
+            pg_sys::GetConfigOptionByName(c"max_wal_size".as_ptr(), std::ptr::null_mut(), false);
+        (!value.is_null()).then(|| {
+            std::ffi::CStr::from_ptr(value)
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+/// Returns the WAL segment size in MB (at least 1).
+#[inline]
+pub fn get_wal_segment_size_mb() -> i32 {
+    // SAFETY: wal_segment_size is a global PostgreSQL variable, set at startup.
+    (unsafe { pg_sys::wal_segment_size } / (1024 * 1024)).max(1)
+}
+
+/// Returns the current WAL insert position (LSN) in bytes.
+///
+/// The difference between two readings is the WAL generated in between.
+/// Must not be called during recovery; the worker starts after recovery finishes.
+#[inline]
+pub fn get_current_wal_lsn() -> i64 {
+    // SAFETY: GetXLogInsertRecPtr reads shared WAL insert state, valid outside recovery.
+    unsafe { pg_sys::GetXLogInsertRecPtr() as i64 }
+}
@@ -0,0 +1,259 @@
+//! Extension status for pg_walrus.
+//!
+//! Implements `walrus.status()`, the single JSONB document combining the
+//! current configuration, the worker's shared-memory state, and values
+//! derived from them (cooldown, hourly limit, ceiling forecast).
+
+use crate::algorithm::{
+    current_shrink_intervals, estimated_intervals_to_ceiling, min_grow_delta, rolling_average,
+    samples_per_interval,
+};
+use crate::bulk_load::{effective_threshold, is_bulk_load_active};
+use crate::checkpoint_causes::causes_json;
+use crate::cluster_id::cluster_id;
+use crate::device_max;
+use crate::functions::{unix_timestamp_to_iso, worker_running};
+use crate::guc::{
+    WALRUS_COOLDOWN_SEC, WALRUS_ENABLE, WALRUS_GROW_EXPONENT, WALRUS_MAX,
+    WALRUS_MAX_CHANGES_PER_HOUR, WALRUS_MAX_GROWTH_MB_PER_DAY, WALRUS_MAX_PERCENT_OF_DEVICE,
+    WALRUS_MAX_SHRINKS_PER_HOUR, WALRUS_MIN_SIZE, WALRUS_READ_ONLY, WALRUS_SAMPLE_INTERVAL_SEC,
+    WALRUS_SHRINK_ENABLE, WALRUS_SHRINK_FACTOR, WALRUS_SHRINK_INTERVALS, WALRUS_THRESHOLD,
+    WALRUS_THRESHOLD_INCLUSIVE,
+};
+use crate::history::decision_action_name;
+use crate::pin::is_pinned;
+use crate::rate_limit::{GROWTH_WINDOW_SECS, window_expired};
+use crate::settling::is_settling;
+use crate::shmem::{self, now_unix, read_state};
+use crate::stats::{checkpoint_timeout, get_current_max_wal_size, get_max_wal_size_display};
+use crate::temporary_max::{grow_cap, is_override_active};
+
+use pgrx::JsonB;
+use serde_json::json;
+
+/// Implementation for getting current extension status.
+///
+/// Returns JSONB with 15 fields covering configuration, worker state, and counters.
+///
+/// # Fields
+///
+/// Configuration:
+/// - `enabled`: Whether auto-sizing is enabled
+/// - `current_max_wal_size_mb`: Current max_wal_size in MB
+/// - `current_max_wal_size_display`: Current max_wal_size as `SHOW max_wal_size`
+///   displays it (e.g. "1GB")
+/// - `configured_maximum_mb`: walrus.max setting in MB
+/// - `max_percent_of_device`: walrus.max_percent_of_device setting
+/// - `threshold`: walrus.threshold setting
+/// - `checkpoint_timeout_sec`: PostgreSQL checkpoint_timeout in seconds
+/// - `sample_interval_sec`: walrus.sample_interval_sec setting
+/// - `samples_per_interval`: Checkpoint count samples per checkpoint_timeout
+///
+/// Shrink configuration:
+/// - `shrink_enabled`: Whether auto-shrink is enabled
+/// - `shrink_factor`: walrus.shrink_factor setting
+/// - `shrink_intervals`: walrus.shrink_intervals setting
+/// - `effective_shrink_intervals`: Quiet intervals required now (walrus.shrink_backoff)
+/// - `min_size_mb`: walrus.min_size in MB
+///
+/// Worker state:
+/// - `worker_running`: Whether background worker is active, from the pid it
+///   publishes in shared memory (as `walrus.worker_running()`)
+/// - `worker_status_error`: Why `worker_running` is null (only when shared
+///   memory is not initialized; null otherwise)
+/// - `cluster_id`: Port and data directory of this cluster ("5432:/path"), as
+///   tagged on the worker's log lines
+/// - `last_check_time`: ISO 8601 timestamp of last analysis (null if never)
+/// - `last_adjustment_time`: ISO 8601 timestamp of last resize (null if never)
+/// - `last_action`: Most recent decision ('increase', 'decrease', 'capped',
+///   'skipped', or 'dry_run'; null if none)
+/// - `last_decision_time`: ISO 8601 timestamp of that decision (null if none)
+/// - `settling`: Whether grows are step-limited after startup or promotion
+/// - `bulk_load_active`: Whether walrus.begin_bulk_load() is raising the threshold
+/// - `pinned_until`: When the walrus.pin() pin expires (null if not pinned)
+/// - `temporary_max_mb`, `temporary_max_until`: Active walrus.temporary_max()
+///   override and its expiry (null if none)
+/// - `schema_missing`: Whether the worker's database has no history table
+///
+/// Counters:
+/// - `total_adjustments`: Number of sizing changes since PostgreSQL start
+/// - `quiet_intervals`: Consecutive low-activity intervals
+/// - `pending_delta`: Forced checkpoints accumulated across sub-samples
+/// - `samples_taken`: Sub-samples taken so far in the current interval
+/// - `rolling_avg_delta`: Average of the last 8 interval deltas (walrus.use_rolling_delta)
+/// - `estimated_checkpoint_causes`: The last decision interval's requested
+///   checkpoints split into `wal_pressure` and `other` causes, estimated from
+///   WAL volume since PostgreSQL does not record them (null when unknown)
+/// - `max_apply_duration_ms`: Slowest worker ALTER SYSTEM since start, in milliseconds
+/// - `suppressed_cycles`: Worker cycles skipped after our own configuration reloads
+/// - `suppress_next_sighup`: Whether the worker will skip its next cycle as our
+///   own reload (walrus.clear_suppress() cancels it)
+/// - `sighups_sent`: Configuration reloads requested since PostgreSQL start
+/// - `sighups_last_hour`: Configuration reloads in the current rolling hour window
+///
+/// Derived:
+/// - `effective_maximum_mb`: Grow cap in effect (configured_maximum_mb, lowered
+///   by max_percent_of_device, or a higher temporary_max_mb while active)
+/// - `at_ceiling`: Whether current_max_wal_size_mb >= effective_maximum_mb
+/// - `estimated_intervals_to_ceiling`: Heuristic forecast of grow intervals until
+///   walrus.max at the rolling average delta (null when that rate would not grow)
+///
+/// Checkpoint exclusion:
+/// - `ignore_checkpoints_pending`: Checkpoints still to be excluded from delta
+///
+/// Shrink hourly limit:
+/// - `max_shrinks_per_hour`: walrus.max_shrinks_per_hour setting (-1 shares
+///   walrus.max_changes_per_hour)
+/// - `shrinks_this_hour`: Shrinks counted in the current shrink hour window
+///
+/// Daily growth cap:
+/// - `max_growth_mb_per_day`: walrus.max_growth_mb_per_day setting
+/// - `growth_mb_this_day`: Growth applied in the current rolling 24-hour window
+///
+/// `shmem_initialized` is true whenever the fields above are present. Without
+/// shared_preload_libraries, only `shmem_initialized` (false) and `error` are
+/// returned.
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn status() -> JsonB {
+    if !shmem::is_initialized() {
+        return JsonB(json!({
+            "shmem_initialized": false,
+            "error": shmem::NOT_INITIALIZED_MESSAGE,
+        }));
+    }
+
+    let state = read_state();
+    let now = now_unix();
+    let current_size = get_current_max_wal_size();
+    let configured_max = WALRUS_MAX.get();
+    let temp_max_active = is_override_active(now, state.temp_max_until);
+    let effective_max = grow_cap(
+        device_max::configured_max(),
+        state.temp_max_mb,
+        temp_max_active,
+    );
+    let timeout_secs = checkpoint_timeout().as_secs() as i32;
+    let worker_running = worker_running();
+
+    // Rate limiting GUC values
+    let cooldown_sec = WALRUS_COOLDOWN_SEC.get();
+    let max_changes_per_hour = WALRUS_MAX_CHANGES_PER_HOUR.get();
+
+    // Compute cooldown status
+    let cooldown_active = cooldown_sec > 0
+        && state.last_adjustment_time > 0
+        && now
+            < state
+                .last_adjustment_time
+                .saturating_add(cooldown_sec as i64);
+
+    let cooldown_remaining_sec = if cooldown_active {
+        state
+            .last_adjustment_time
+            .saturating_add(cooldown_sec as i64)
+            .saturating_sub(now) as i32
+    } else {
+        0
+    };
+
+    // Compute hourly limit status
+    let hour_expired = if state.hour_window_start > 0 {
+        now >= state.hour_window_start.saturating_add(3600)
+    } else {
+        true
+    };
+
+    let hourly_limit_reached = !hour_expired
+        && max_changes_per_hour > 0
+        && state.changes_this_hour >= max_changes_per_hour;
+
+    JsonB(json!({
+        "shmem_initialized": true,
+        "enabled": WALRUS_ENABLE.get(),
+        "read_only": WALRUS_READ_ONLY.get(),
+        "current_max_wal_size_mb": current_size,
+        "current_max_wal_size_display": get_max_wal_size_display(),
+        "configured_maximum_mb": configured_max,
+        "max_percent_of_device": WALRUS_MAX_PERCENT_OF_DEVICE.get(),
+        "threshold": WALRUS_THRESHOLD.get(),
+        "checkpoint_timeout_sec": timeout_secs,
+        "sample_interval_sec": WALRUS_SAMPLE_INTERVAL_SEC.get(),
+        "samples_per_interval": samples_per_interval(timeout_secs as i64, WALRUS_SAMPLE_INTERVAL_SEC.get()),
+        "shrink_enabled": WALRUS_SHRINK_ENABLE.get(),
+        "shrink_factor": WALRUS_SHRINK_FACTOR.get(),
+        "shrink_intervals": WALRUS_SHRINK_INTERVALS.get(),
+        "effective_shrink_intervals": current_shrink_intervals(&state),
+        "min_size_mb": WALRUS_MIN_SIZE.get(),
+        "worker_running": worker_running,
+        "worker_status_error": worker_running.is_none().then_some(shmem::NOT_INITIALIZED_MESSAGE),
+        "cluster_id": cluster_id(),
+        "last_check_time": unix_timestamp_to_iso(state.last_check_time),
+        "last_adjustment_time": unix_timestamp_to_iso(state.last_adjustment_time),
+        "last_action": decision_action_name(state.last_action),
+        "last_decision_time": unix_timestamp_to_iso(state.last_decision_time),
+        "settling": is_settling(now, state.settling_until),
+        "bulk_load_active": is_bulk_load_active(now, state.bulk_load_until),
+        "pinned_until": if is_pinned(now, state.pinned_until) {
+            unix_timestamp_to_iso(state.pinned_until)
+        } else {
+            None
+        },
+        "temporary_max_mb": temp_max_active.then_some(state.temp_max_mb),
+        "temporary_max_until": if temp_max_active {
+            unix_timestamp_to_iso(state.temp_max_until)
+        } else {
+            None
+        },
+        "schema_missing": state.schema_missing,
+        "total_adjustments": state.total_adjustments,
+        "quiet_intervals": state.quiet_intervals,
+        "pending_delta": state.pending_delta,
+        "samples_taken": state.sample_count,
+        "rolling_avg_delta": rolling_average(&state.delta_ring, state.delta_ring_len),
+        "estimated_checkpoint_causes": causes_json(
+            state.causes_known,
+            state.wal_pressure_checkpoints,
+            state.other_checkpoints,
+        ),
+        "max_apply_duration_ms": state.max_apply_duration_ms,
+        "suppressed_cycles": state.suppressed_cycles,
+        "suppress_next_sighup": state.suppress_next_sighup,
+        "sighups_sent": state.sighups_sent,
+        "sighups_last_hour": if window_expired(state.sighup_hour_window_start, now, 3600) {
+            0
+        } else {
+            state.sighups_this_hour
+        },
+        "effective_maximum_mb": effective_max,
+        "at_ceiling": current_size >= effective_max,
+        "estimated_intervals_to_ceiling": estimated_intervals_to_ceiling(
+            current_size,
+            rolling_average(&state.delta_ring, state.delta_ring_len),
+            min_grow_delta(effective_threshold(), WALRUS_THRESHOLD_INCLUSIVE.get()),
+            effective_max,
+            WALRUS_GROW_EXPONENT.get(),
+        ),
+        // Rate limiting fields (7 new fields per FR-012)
+        "cooldown_sec": cooldown_sec,
+        "max_changes_per_hour": max_changes_per_hour,
+        "cooldown_active": cooldown_active,
+        "cooldown_remaining_sec": cooldown_remaining_sec,
+        "changes_this_hour": state.changes_this_hour,
+        "hourly_window_start": unix_timestamp_to_iso(state.hour_window_start),
+        "hourly_limit_reached": hourly_limit_reached,
+        "ignore_checkpoints_pending": state.ignore_checkpoints,
+        "max_shrinks_per_hour": WALRUS_MAX_SHRINKS_PER_HOUR.get(),
+        "shrinks_this_hour": if window_expired(state.shrink_hour_window_start, now, 3600) {
+            0
+        } else {
+            state.shrinks_this_hour
+        },
+        "max_growth_mb_per_day": WALRUS_MAX_GROWTH_MB_PER_DAY.get(),
+        "growth_mb_this_day": if window_expired(state.day_window_start, now, GROWTH_WINDOW_SECS) {
+            0
+        } else {
+            state.growth_mb_this_day
+        },
+    }))
+}
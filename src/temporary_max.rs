@@ -0,0 +1,168 @@
+//! Temporary walrus.max override for pg_walrus.
+//!
+//! Before a planned heavy event (a migration, a large backfill) the ceiling
+//! can be raised for a while without editing configuration:
+//! `walrus.temporary_max(mb, minutes)` stores `temp_max_mb` and
+//! `temp_max_until` in shared memory. Until it expires, grows are capped at
+//! the higher of walrus.max and the override.
+//!
+//! After expiry, grows are capped at walrus.max again, but a size reached under
+//! the override is not capped down at once: it comes back down through normal
+//! shrinking, and the override is released once max_wal_size is within
+//! walrus.max (`release_expired_override()`).
+//!
+//! Here walrus.max means the configured ceiling, which
+//! walrus.max_percent_of_device can lower (`configured_max()`).
+
+use crate::device_max::configured_max;
+use crate::shmem::{self, now_unix};
+
+use pgrx::pg_sys;
+
+/// Whether the override is in effect at `now`.
+///
+/// A `temp_max_until` of 0 (never set, or cleared by walrus.reset()) is not active.
+#[inline]
+pub(crate) fn is_override_active(now: i64, temp_max_until: i64) -> bool {
+    now < temp_max_until
+}
+
+/// Grow cap: the higher of walrus.max and an active override.
+#[inline]
+pub(crate) fn grow_cap(walrus_max: i32, temp_max_mb: i32, active: bool) -> i32 {
+    if active {
+        walrus_max.max(temp_max_mb)
+    } else {
+        walrus_max
+    }
+}
+
+/// Whether an expired override can be released at `current_size`.
+///
+/// The override stays recorded after expiry until max_wal_size has shrunk back
+/// to within walrus.max, so the ceiling check does not cap it down meanwhile.
+#[inline]
+pub(crate) fn can_release(
+    active: bool,
+    temp_max_mb: i32,
+    current_size: i32,
+    walrus_max: i32,
+) -> bool {
+    !active && temp_max_mb > 0 && current_size <= walrus_max
+}
+
+/// Effective walrus.max for grow decisions right now.
+pub(crate) fn effective_max() -> i32 {
+    let state = shmem::read_state();
+    grow_cap(
+        configured_max(),
+        state.temp_max_mb,
+        is_override_active(now_unix(), state.temp_max_until),
+    )
+}
+
+/// Ceiling above which max_wal_size is capped down.
+///
+/// walrus.max, or the override while it is recorded (active or shrinking back).
+pub(crate) fn ceiling_max() -> i32 {
+    let state = shmem::read_state();
+    grow_cap(configured_max(), state.temp_max_mb, state.temp_max_mb > 0)
+}
+
+/// Release an expired override once max_wal_size is back within walrus.max.
+///
+/// Called each worker cycle.
+pub(crate) fn release_expired_override(current_size: i32) {
+    let walrus_max = configured_max();
+    let mut released = 0;
+    shmem::update_state(|state| {
+        let active = is_override_active(now_unix(), state.temp_max_until);
+        if can_release(active, state.temp_max_mb, current_size, walrus_max) {
+            released = state.temp_max_mb;
+            state.temp_max_mb = 0;
+            state.temp_max_until = 0;
+        }
+    });
+    if released > 0 {
+        pgrx::log!(
+            "pg_walrus: temporary walrus.max of {} MB released, walrus.max ({} MB) applies",
+            released,
+            walrus_max
+        );
+    }
+}
+
+/// Implementation for temporarily raising walrus.max.
+///
+/// Stores an override of `mb` for `minutes` from now, replacing any earlier
+/// one. While it is active, grows are capped at the higher of walrus.max and
+/// `mb`.
+///
+/// # Returns
+///
+/// true if the override raises the cap above the current walrus.max.
+///
+/// # Authorization
+///
+/// Superuser only (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn temporary_max(mb: i32, minutes: i32) -> bool {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.temporary_max() requires superuser");
+    }
+    if mb < 2 {
+        pgrx::error!("mb must be >= 2");
+    }
+    if minutes <= 0 {
+        pgrx::error!("minutes must be > 0");
+    }
+
+    let until = now_unix().saturating_add(minutes as i64 * 60);
+    shmem::update_state(|state| {
+        state.temp_max_mb = mb;
+        state.temp_max_until = until;
+    });
+    let walrus_max = configured_max();
+    pgrx::log!(
+        "pg_walrus: walrus.max raised to {} MB for {} minutes (walrus.max is {} MB)",
+        mb,
+        minutes,
+        walrus_max
+    );
+    mb > walrus_max
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the override is active only before its end time
+    #[test]
+    fn test_is_override_active() {
+        assert!(is_override_active(1000, 1600));
+        assert!(!is_override_active(1600, 1600));
+        assert!(!is_override_active(1000, 0));
+    }
+
+    /// Test the cap is the higher value while active and walrus.max otherwise
+    #[test]
+    fn test_grow_cap() {
+        assert_eq!(grow_cap(4096, 16384, true), 16384);
+        assert_eq!(grow_cap(4096, 16384, false), 4096);
+        assert_eq!(grow_cap(4096, 1024, true), 4096, "never lowers walrus.max");
+    }
+
+    /// Test an expired override is released only once back within walrus.max
+    #[test]
+    fn test_can_release() {
+        assert!(!can_release(true, 16384, 1024, 4096), "still active");
+        assert!(
+            !can_release(false, 16384, 8192, 4096),
+            "still shrinking back"
+        );
+        assert!(can_release(false, 16384, 4096, 4096));
+        assert!(!can_release(false, 0, 1024, 4096), "no override");
+    }
+}
@@ -162,10 +162,20 @@ fn test_guc_dry_run_set_fails() {
     Spi::run("SET walrus.dry_run = true").unwrap();
 }
 
-/// Test that all 11 walrus GUCs are visible in pg_settings with correct context (T029).
-/// (walrus.database has context 'postmaster', not 'sighup')
-/// GUCs: enable, max, threshold, shrink_enable, shrink_factor, shrink_intervals, min_size,
-/// history_retention_days, dry_run, cooldown_sec, max_changes_per_hour
+/// Test that all 52 walrus GUCs are visible in pg_settings with correct context (T029).
+/// (walrus.database, walrus.schema_name, walrus.apply_allowed_databases, and walrus.read_only have context 'postmaster', not 'sighup')
+/// GUCs: enable, max, max_percent_of_device, threshold, threshold_inclusive,
+/// bulk_load_threshold_multiplier, grow_exponent, sample_interval_sec, use_rolling_delta,
+/// lookback_intervals, wal_pressure_only, settling_max_step_mb, startup_grace_sec,
+/// max_interval_drift, shrink_enable, shrink_factor, shrink_intervals, min_size,
+/// enforce_min_on_start, shrink_max_wal_mb_per_interval, force_min_shrink, shrink_rounding,
+/// shrink_backoff, shrink_target, peak_ratio, grow_protect_intervals, fast_shrink_after_intervals,
+/// stats_source, history_retention_days, cleanup_interval_sec, history_max_rows,
+/// archive_before_delete, record_stats_unavailable, verbose_history, mirror_table, dry_run, mode,
+/// reload_method, log_level, cooldown_sec, max_changes_per_hour, max_shrinks_per_hour,
+/// max_growth_mb_per_day, max_sighups_per_hour_warn, manual_resets_cooldown, min_apply_confidence,
+/// min_shrink_confidence, shrink_confidence_penalty, emergency_free_disk_mb,
+/// manage_completion_target, completion_target, warn_wal_buffers
 #[pg_test]
 fn test_guc_context_is_sighup() {
     let count = Spi::get_one::<i64>(
@@ -174,8 +184,8 @@ fn test_guc_context_is_sighup() {
     .expect("query failed");
     assert_eq!(
         count,
-        Some(11),
-        "All 11 walrus GUCs (except walrus.database) should have context = 'sighup'"
+        Some(52),
+        "All 52 walrus GUCs (except walrus.database, walrus.schema_name, walrus.apply_allowed_databases, and walrus.read_only) should have context = 'sighup'"
     );
 }
 
@@ -375,6 +385,7 @@ fn test_get_current_max_wal_size_matches_show() {
 // NOTE: execute_alter_system() cannot be tested directly in pg_test because
 // ALTER SYSTEM cannot run inside a transaction block. The actual ALTER SYSTEM
 // execution is tested via pg_regress tests which run outside transaction context.
+// Failures (returned as ConfigError) are tested in safety_tests.rs.
 //
 // The config module is implicitly tested by the background worker's ability
 // to successfully modify max_wal_size during runtime operation.
@@ -388,36 +399,36 @@ fn test_get_current_max_wal_size_matches_show() {
 #[pg_test]
 fn test_calculate_new_size_formula() {
     // 1024 MB with 3 forced checkpoints: 1024 * 4 = 4096
-    assert_eq!(algorithm::calculate_new_size(1024, 3), 4096);
+    assert_eq!(algorithm::calculate_new_size(1024, 3, 1.0), 4096);
 
     // 2048 MB with 1 forced checkpoint: 2048 * 2 = 4096
-    assert_eq!(algorithm::calculate_new_size(2048, 1), 4096);
+    assert_eq!(algorithm::calculate_new_size(2048, 1, 1.0), 4096);
 
     // 512 MB with 2 forced checkpoints: 512 * 3 = 1536
-    assert_eq!(algorithm::calculate_new_size(512, 2), 1536);
+    assert_eq!(algorithm::calculate_new_size(512, 2, 1.0), 1536);
 }
 
 /// Test that calculate_new_size handles edge cases.
 #[pg_test]
 fn test_calculate_new_size_edge_cases() {
     // Delta of 0 should multiply by 1 (no change)
-    assert_eq!(algorithm::calculate_new_size(1024, 0), 1024);
+    assert_eq!(algorithm::calculate_new_size(1024, 0, 1.0), 1024);
 
     // Small values
-    assert_eq!(algorithm::calculate_new_size(1, 1), 2);
+    assert_eq!(algorithm::calculate_new_size(1, 1, 1.0), 2);
 
     // Large delta
-    assert_eq!(algorithm::calculate_new_size(100, 10), 1100);
+    assert_eq!(algorithm::calculate_new_size(100, 10, 1.0), 1100);
 }
 
 /// Test that calculate_new_size protects against overflow.
 #[pg_test]
 fn test_calculate_new_size_overflow_protection() {
     // Values that would overflow should saturate to i32::MAX
-    let result = algorithm::calculate_new_size(i32::MAX, 1);
+    let result = algorithm::calculate_new_size(i32::MAX, 1, 1.0);
     assert_eq!(result, i32::MAX, "Should saturate on overflow");
 
-    let result = algorithm::calculate_new_size(1_000_000_000, 3);
+    let result = algorithm::calculate_new_size(1_000_000_000, 3, 1.0);
     assert_eq!(result, i32::MAX, "Should saturate on overflow");
 }
 
@@ -434,7 +445,7 @@ fn test_max_cap_enforcement() {
 
     // Direct test of the cap logic:
     let walrus_max = 4096;
-    let calculated = algorithm::calculate_new_size(2048, 5); // 2048 * 6 = 12288
+    let calculated = algorithm::calculate_new_size(2048, 5, 1.0); // 2048 * 6 = 12288
     let capped = if calculated > walrus_max {
         walrus_max
     } else {
@@ -490,7 +501,7 @@ fn test_resize_calculation_matches_walsizer() {
     let forced_checkpoints = 4i64;
     let expected = current.saturating_mul((forced_checkpoints + 1) as i32);
 
-    let calculated = algorithm::calculate_new_size(current, forced_checkpoints);
+    let calculated = algorithm::calculate_new_size(current, forced_checkpoints, 1.0);
 
     assert_eq!(
         calculated, expected,
@@ -512,7 +523,7 @@ fn test_complete_resize_flow_without_alter_system() {
 
     // Step 3: Calculate new size (simulating 3 forced checkpoints)
     let delta = 3i64;
-    let new_size = algorithm::calculate_new_size(current_size, delta);
+    let new_size = algorithm::calculate_new_size(current_size, delta, 1.0);
     assert!(
         new_size > current_size,
         "New size should be larger than current"
@@ -610,14 +621,16 @@ fn test_delta_calculation() {
 /// Test that calculate_shrink_size correctly clamps to min_size (T038)
 #[pg_test]
 fn test_calculate_shrink_size_clamping() {
+    use crate::guc::ShrinkRounding;
+
     // 2560 MB * 0.75 = 1920, but min_size is 2048 -> returns 2048
-    assert_eq!(algorithm::calculate_shrink_size(2560, 0.75, 2048), 2048);
+    assert_eq!(algorithm::calculate_shrink_size(2560, 0.75, 2048, ShrinkRounding::Up), 2048);
 
     // 1024 MB * 0.75 = 768, but min_size is 1024 -> returns 1024
-    assert_eq!(algorithm::calculate_shrink_size(1024, 0.75, 1024), 1024);
+    assert_eq!(algorithm::calculate_shrink_size(1024, 0.75, 1024, ShrinkRounding::Up), 1024);
 
     // 900 MB * 0.75 = 675, but min_size is 1024 -> returns 1024 (below floor)
-    assert_eq!(algorithm::calculate_shrink_size(900, 0.75, 1024), 1024);
+    assert_eq!(algorithm::calculate_shrink_size(900, 0.75, 1024, ShrinkRounding::Up), 1024);
 }
 
 /// Test that shrink GUC statics can be accessed (T047)
@@ -0,0 +1,122 @@
+//! Worker-free operation for pg_walrus.
+//!
+//! Some managed PostgreSQL services do not allow extensions to start
+//! background workers. `walrus.tick()` runs one worker cycle in the calling
+//! backend instead, so a scheduler such as pg_cron can drive pg_walrus every
+//! checkpoint_timeout. The cycle keeps its baseline and counters in shared
+//! memory exactly like the worker, and its history rows and ALTER SYSTEM run
+//! in the caller's transaction. While a worker is running, `walrus.tick()`
+//! does not run a cycle: a second cycle per interval would split the delta
+//! the worker measures.
+//!
+//! Cycles never overlap: each one holds the cycle lock (`CYCLE_LOCK_KEY`),
+//! `walrus.tick()` for its transaction and the worker for one cycle at a
+//! time. A worker that finds the lock held skips that cycle, and
+//! `walrus.tick()` returns without one while the worker is mid-cycle.
+
+use crate::access;
+use crate::functions::{unix_timestamp_to_iso, worker_proc};
+use crate::guc::WALRUS_ENABLE;
+use crate::history::decision_action_name;
+use crate::shmem::read_state;
+use crate::worker::run_single_cycle;
+
+use pgrx::bgworkers::BackgroundWorker;
+use pgrx::datum::DatumWithOid;
+use pgrx::prelude::*;
+use pgrx::{JsonB, pg_sys};
+use serde_json::json;
+
+/// Advisory lock key keeping monitoring cycles apart ("wcycle" in ASCII).
+pub(crate) const CYCLE_LOCK_KEY: i64 = 0x7763_7963_6C65;
+
+/// Take the cycle lock for the worker's next cycle.
+///
+/// Session-scoped, so it outlasts the cycle's own transactions; released by
+/// `release_cycle_lock()`. Returns false while another session holds it
+/// (`walrus.tick()` in progress), and the worker skips the cycle.
+pub(crate) fn claim_cycle_lock() -> bool {
+    let args: &[DatumWithOid<'_>] = &[CYCLE_LOCK_KEY.into()];
+    match BackgroundWorker::transaction(|| {
+        Spi::get_one_with_args::<bool>("SELECT pg_try_advisory_lock($1)", args)
+    }) {
+        Ok(acquired) => acquired == Some(true),
+        Err(e) => {
+            pgrx::warning!("pg_walrus: failed to take the cycle lock: {}", e);
+            false
+        }
+    }
+}
+
+/// Release the cycle lock taken by `claim_cycle_lock()`.
+pub(crate) fn release_cycle_lock() {
+    let args: &[DatumWithOid<'_>] = &[CYCLE_LOCK_KEY.into()];
+    if let Err(e) = BackgroundWorker::transaction(|| {
+        Spi::get_one_with_args::<bool>("SELECT pg_advisory_unlock($1)", args)
+    }) {
+        pgrx::warning!("pg_walrus: failed to release the cycle lock: {}", e);
+    }
+}
+
+/// Take the cycle lock until the calling transaction ends.
+///
+/// Returns false while the worker is in the middle of a cycle. A session that
+/// already holds it takes it again.
+fn try_cycle_xact_lock() -> bool {
+    let args: &[DatumWithOid<'_>] = &[CYCLE_LOCK_KEY.into()];
+    Spi::get_one_with_args::<bool>("SELECT pg_try_advisory_xact_lock($1)", args)
+        .ok()
+        .flatten()
+        .unwrap_or(false)
+}
+
+/// Implementation for running one monitoring cycle without the worker.
+///
+/// Does nothing while walrus.enable is off, like the worker, or while the
+/// background worker is running or in the middle of a cycle (the cycle lock
+/// is held until the caller's transaction ends). Returns a JSONB object with:
+/// - `ran`: Whether a cycle ran
+/// - `reason`: Why no cycle ran (null when one did)
+/// - `baseline_established`: Whether this call established the baseline
+///   (the first cycle after startup or walrus.reset() only records it)
+/// - `prev_requested`: Checkpoint count the next cycle measures from
+/// - `quiet_intervals`: Consecutive quiet intervals after this cycle
+/// - `last_action`, `last_decision_time`: Most recent sizing decision, as in
+///   walrus.status()
+///
+/// # Authorization
+///
+/// Superuser only, in a database allowed by walrus.apply_allowed_databases
+/// (raises error otherwise)
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn tick() -> JsonB {
+    if unsafe { !pg_sys::superuser() } {
+        pgrx::error!("permission denied: walrus.tick() requires superuser");
+    }
+
+    access::require_apply_allowed("walrus.tick()");
+
+    let reason = if !WALRUS_ENABLE.get() {
+        Some("walrus.enable is off")
+    } else if !worker_proc().is_null() {
+        Some("the background worker is running")
+    } else if !try_cycle_xact_lock() {
+        Some("another cycle is running")
+    } else {
+        None
+    };
+    let ran = reason.is_none();
+    let baseline_established = ran && run_single_cycle();
+
+    let state = read_state();
+    JsonB(json!({
+        "ran": ran,
+        "reason": reason,
+        "baseline_established": baseline_established,
+        "prev_requested": state.prev_requested,
+        "quiet_intervals": state.quiet_intervals,
+        "last_action": decision_action_name(state.last_action),
+        "last_decision_time": unix_timestamp_to_iso(state.last_decision_time),
+    }))
+}
@@ -0,0 +1,327 @@
+//! Configuration linter for pg_walrus.
+//!
+//! `walrus.validate_config()` compares the current GUCs with recent history
+//! and the worker's live state, and reports settings that cannot work well
+//! for this workload, each with a severity. It is advisory only: nothing is
+//! changed, and a configuration with findings still runs.
+//!
+//! The rules are pure functions of `ConfigFacts`, gathered once per call.
+
+use crate::algorithm::{DELTA_RING_SIZE, shrink_floor};
+use crate::guc::{
+    WALRUS_HISTORY_RETENTION_DAYS, WALRUS_MAX, WALRUS_MIN_SIZE, WALRUS_SHRINK_ENABLE,
+    WALRUS_THRESHOLD,
+};
+use crate::history::{history_table, history_table_exists};
+use crate::shmem::read_state;
+use crate::stats::{get_current_max_wal_size, get_current_min_wal_size};
+
+use pgrx::JsonB;
+use pgrx::datum::DatumWithOid;
+use pgrx::prelude::*;
+use serde_json::{Value as JsonValue, json};
+
+/// 'capped' rows in the history window at which walrus.max counts as frequently hit.
+pub(crate) const FREQUENT_CAPPED: i64 = 3;
+
+/// Recent intervals needed before forced checkpoints count as frequent.
+pub(crate) const MIN_OBSERVED_INTERVALS: i32 = 4;
+
+/// How serious a finding is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Severity {
+    /// The configuration works but likely not as intended
+    Info,
+    /// The configuration is working against the workload
+    Warning,
+}
+
+impl Severity {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A single linter result.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct Finding {
+    /// Stable identifier of the rule
+    pub check: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Inputs the rules evaluate.
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct ConfigFacts {
+    /// Whole days of history available, at most walrus.history_retention_days
+    pub history_days: i32,
+    /// 'increase' rows in the window
+    pub increases: i64,
+    /// 'capped' rows in the window
+    pub capped: i64,
+    /// Recent interval deltas recorded by the worker
+    pub observed_intervals: i32,
+    /// Recent intervals with at least one forced checkpoint
+    pub forced_intervals: i32,
+    pub threshold: i32,
+    pub walrus_max: i32,
+    pub current_size: i32,
+    /// Size shrinking never goes below (see `shrink_floor()`)
+    pub floor: i32,
+    pub shrink_enabled: bool,
+}
+
+/// Count the recent intervals and those with a forced checkpoint.
+pub(crate) fn forced_interval_counts(ring: &[i32; DELTA_RING_SIZE], len: i32) -> (i32, i32) {
+    let len = len.clamp(0, DELTA_RING_SIZE as i32);
+    let forced = ring[..len as usize].iter().filter(|&&d| d > 0).count() as i32;
+    (len, forced)
+}
+
+/// Evaluate every rule against `facts`, in a fixed order.
+pub(crate) fn lint(facts: &ConfigFacts) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    // Forced checkpoints in at least half of the recent intervals, but none
+    // ever added up to the threshold.
+    if facts.history_days >= 1
+        && facts.increases == 0
+        && facts.observed_intervals >= MIN_OBSERVED_INTERVALS
+        && facts.forced_intervals * 2 >= facts.observed_intervals
+    {
+        findings.push(Finding {
+            check: "threshold_too_high",
+            severity: Severity::Warning,
+            message: format!(
+                "walrus.threshold ({}) looks too high: no grows in {} days despite forced checkpoints in {} of the last {} intervals; consider lowering it",
+                facts.threshold, facts.history_days, facts.forced_intervals, facts.observed_intervals
+            ),
+        });
+    }
+
+    if facts.capped >= FREQUENT_CAPPED {
+        findings.push(Finding {
+            check: "max_frequently_hit",
+            severity: Severity::Warning,
+            message: format!(
+                "walrus.max ({} MB) was hit {} times in {} days; consider raising it",
+                facts.walrus_max, facts.capped, facts.history_days
+            ),
+        });
+    }
+
+    if facts.shrink_enabled && facts.current_size <= facts.floor {
+        findings.push(Finding {
+            check: "shrink_cannot_fire",
+            severity: Severity::Info,
+            message: format!(
+                "shrink floor ({} MB, from walrus.min_size and min_wal_size) is at or above max_wal_size ({} MB); shrink can never fire",
+                facts.floor, facts.current_size
+            ),
+        });
+    }
+
+    findings
+}
+
+/// Gather the rule inputs from history, shared memory, and configuration.
+fn gather_facts() -> Result<ConfigFacts, spi::Error> {
+    let retention_days = WALRUS_HISTORY_RETENTION_DAYS.get();
+    let (mut history_days, mut increases, mut capped) = (0, 0, 0);
+    if history_table_exists()? {
+        let args: &[DatumWithOid<'_>] = &[retention_days.into()];
+        let row = Spi::get_three_with_args::<i32, i64, i64>(
+            &format!(
+                "SELECT least(floor(extract(epoch FROM now() - min(timestamp)) / 86400), $1)::int4,
+                        count(*) FILTER (WHERE action = 'increase'),
+                        count(*) FILTER (WHERE action = 'capped')
+                 FROM {}
+                 WHERE timestamp >= now() - $1 * interval '1 day'",
+                history_table()
+            ),
+            args,
+        )?;
+        history_days = row.0.unwrap_or(0);
+        increases = row.1.unwrap_or(0);
+        capped = row.2.unwrap_or(0);
+    }
+
+    let state = read_state();
+    let (observed_intervals, forced_intervals) =
+        forced_interval_counts(&state.delta_ring, state.delta_ring_len);
+
+    Ok(ConfigFacts {
+        history_days,
+        increases,
+        capped,
+        observed_intervals,
+        forced_intervals,
+        threshold: WALRUS_THRESHOLD.get(),
+        walrus_max: WALRUS_MAX.get(),
+        current_size: get_current_max_wal_size(),
+        floor: shrink_floor(WALRUS_MIN_SIZE.get(), get_current_min_wal_size()),
+        shrink_enabled: WALRUS_SHRINK_ENABLE.get(),
+    })
+}
+
+/// Implementation for the configuration linter.
+///
+/// Returns a JSONB object with:
+/// - `ok`: true when there are no findings
+/// - `history_days`: Days of history the history-based checks covered
+/// - `findings`: Array of `{check, severity, message}`, where severity is
+///   'warning' or 'info'
+///
+/// Note: Not marked #[pg_extern] - exposed via lib.rs walrus module.
+pub fn validate_config() -> Result<JsonB, spi::Error> {
+    let facts = gather_facts()?;
+    let findings = lint(&facts)
+        .into_iter()
+        .map(|f| {
+            json!({
+                "check": f.check,
+                "severity": f.severity.name(),
+                "message": f.message,
+            })
+        })
+        .collect::<Vec<JsonValue>>();
+
+    Ok(JsonB(json!({
+        "ok": findings.is_empty(),
+        "history_days": facts.history_days,
+        "findings": findings,
+    })))
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Facts for a healthy configuration with no findings
+    fn healthy() -> ConfigFacts {
+        ConfigFacts {
+            history_days: 7,
+            increases: 2,
+            capped: 0,
+            observed_intervals: 8,
+            forced_intervals: 6,
+            threshold: 2,
+            walrus_max: 4096,
+            current_size: 2048,
+            floor: 1024,
+            shrink_enabled: true,
+        }
+    }
+
+    fn checks(facts: &ConfigFacts) -> Vec<&'static str> {
+        lint(facts).iter().map(|f| f.check).collect()
+    }
+
+    /// Test a healthy configuration has no findings
+    #[test]
+    fn test_lint_healthy() {
+        assert!(lint(&healthy()).is_empty());
+    }
+
+    /// Test frequent forced checkpoints without grows flag the threshold
+    #[test]
+    fn test_lint_threshold_too_high() {
+        let facts = ConfigFacts {
+            increases: 0,
+            threshold: 10,
+            ..healthy()
+        };
+        let findings = lint(&facts);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, "threshold_too_high");
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert!(findings[0].message.contains("no grows in 7 days"));
+        assert!(findings[0].message.contains("6 of the last 8 intervals"));
+    }
+
+    /// Test the threshold rule needs enough evidence
+    #[test]
+    fn test_lint_threshold_needs_evidence() {
+        let base = ConfigFacts {
+            increases: 0,
+            ..healthy()
+        };
+        let rare = ConfigFacts {
+            forced_intervals: 3,
+            ..base
+        };
+        let few = ConfigFacts {
+            observed_intervals: 3,
+            forced_intervals: 3,
+            ..base
+        };
+        let new_install = ConfigFacts {
+            history_days: 0,
+            ..base
+        };
+        assert!(checks(&rare).is_empty());
+        assert!(checks(&few).is_empty());
+        assert!(checks(&new_install).is_empty());
+        assert_eq!(
+            checks(&ConfigFacts {
+                forced_intervals: 4,
+                ..base
+            }),
+            vec!["threshold_too_high"]
+        );
+    }
+
+    /// Test repeated capped rows flag walrus.max
+    #[test]
+    fn test_lint_max_frequently_hit() {
+        let facts = ConfigFacts {
+            capped: FREQUENT_CAPPED,
+            ..healthy()
+        };
+        let findings = lint(&facts);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, "max_frequently_hit");
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert!(findings[0].message.contains("hit 3 times in 7 days"));
+
+        let once = ConfigFacts {
+            capped: FREQUENT_CAPPED - 1,
+            ..healthy()
+        };
+        assert!(checks(&once).is_empty());
+    }
+
+    /// Test a floor at or above the current size flags shrink, only when enabled
+    #[test]
+    fn test_lint_shrink_cannot_fire() {
+        let at_floor = ConfigFacts {
+            current_size: 1024,
+            ..healthy()
+        };
+        let findings = lint(&at_floor);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, "shrink_cannot_fire");
+        assert_eq!(findings[0].severity, Severity::Info);
+
+        let disabled = ConfigFacts {
+            shrink_enabled: false,
+            ..at_floor
+        };
+        assert!(checks(&disabled).is_empty());
+    }
+
+    /// Test forced intervals count only the filled slots with a forced checkpoint
+    #[test]
+    fn test_forced_interval_counts() {
+        let ring = [1, 0, 3, 0, 0, 9, 9, 9];
+        assert_eq!(forced_interval_counts(&ring, 5), (5, 2));
+        assert_eq!(forced_interval_counts(&ring, 8), (8, 5));
+        assert_eq!(forced_interval_counts(&ring, 0), (0, 0));
+        assert_eq!(forced_interval_counts(&ring, 20), (8, 5));
+    }
+}
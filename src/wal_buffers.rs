@@ -0,0 +1,118 @@
+//! wal_buffers advisory check for pg_walrus.
+//!
+//! After a large grow, a small wal_buffers can become the next bottleneck,
+//! and operators rarely notice. When `walrus.warn_wal_buffers` is enabled, the
+//! worker compares wal_buffers (read from pg_settings) against a fraction of
+//! the new max_wal_size after each applied grow. An undersized value logs a
+//! WARNING once per worker lifetime and flags the grow history record with
+//! `wal_buffers_warning`. wal_buffers itself is never changed.
+
+use crate::guc::WALRUS_WARN_WAL_BUFFERS;
+use crate::worker::in_transaction;
+
+use pgrx::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// wal_buffers is considered undersized below max_wal_size / this divisor.
+const WAL_BUFFERS_DIVISOR: i32 = 512;
+
+/// Smallest suggested wal_buffers: PostgreSQL's auto-tuned ceiling (one WAL segment).
+const MIN_SUGGESTED_WAL_BUFFERS_MB: i32 = 16;
+
+/// Set once the WARNING has been logged by this worker.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Whether wal_buffers looks undersized for the given max_wal_size.
+#[inline]
+pub(crate) fn wal_buffers_undersized(wal_buffers_mb: i32, max_wal_size_mb: i32) -> bool {
+    wal_buffers_mb < max_wal_size_mb / WAL_BUFFERS_DIVISOR
+}
+
+/// wal_buffers value to suggest for the given max_wal_size, in MB.
+#[inline]
+pub(crate) fn suggested_wal_buffers_mb(max_wal_size_mb: i32) -> i32 {
+    (max_wal_size_mb / WAL_BUFFERS_DIVISOR).max(MIN_SUGGESTED_WAL_BUFFERS_MB)
+}
+
+/// Read the effective wal_buffers from pg_settings, in MB.
+fn get_wal_buffers_mb() -> Result<Option<i64>, spi::Error> {
+    Spi::get_one::<i64>(
+        "SELECT setting::bigint * pg_size_bytes(unit) / (1024 * 1024) \
+         FROM pg_settings WHERE name = 'wal_buffers'",
+    )
+}
+
+/// Check wal_buffers against a newly applied max_wal_size.
+///
+/// Returns true when wal_buffers looks undersized, so the caller can flag the
+/// grow history record. The WARNING is only logged the first time. Returns
+/// false when walrus.warn_wal_buffers is off or wal_buffers cannot be read.
+pub(crate) fn check_wal_buffers(new_size: i32) -> bool {
+    if !WALRUS_WARN_WAL_BUFFERS.get() {
+        return false;
+    }
+
+    let wal_buffers_mb = match in_transaction(get_wal_buffers_mb) {
+        Ok(Some(mb)) => mb.clamp(0, i32::MAX as i64) as i32,
+        Ok(None) => return false,
+        Err(e) => {
+            pgrx::debug1!("pg_walrus: failed to read wal_buffers: {}", e);
+            return false;
+        }
+    };
+
+    if !wal_buffers_undersized(wal_buffers_mb, new_size) {
+        return false;
+    }
+
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        pgrx::warning!(
+            "pg_walrus: wal_buffers ({} MB) looks small for max_wal_size {} MB, consider wal_buffers = '{}MB'",
+            wal_buffers_mb,
+            new_size,
+            suggested_wal_buffers_mb(new_size)
+        );
+    }
+    true
+}
+
+// Pure Rust unit tests (do not require PostgreSQL)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test wal_buffers is flagged only once max_wal_size outgrows it
+    #[test]
+    fn test_wal_buffers_undersized() {
+        // Default-ish 4 MB wal_buffers is fine up to 2 GB max_wal_size
+        assert!(!wal_buffers_undersized(4, 1024));
+        assert!(!wal_buffers_undersized(4, 2047));
+        assert!(wal_buffers_undersized(4, 4096));
+
+        // 16 MB wal_buffers covers up to 8 GB
+        assert!(!wal_buffers_undersized(16, 8192));
+        assert!(wal_buffers_undersized(16, 16384));
+
+        // Nonsensical sizes never warn
+        assert!(!wal_buffers_undersized(4, 0));
+        assert!(!wal_buffers_undersized(4, -1));
+    }
+
+    /// Test the suggested value scales with max_wal_size and has a floor
+    #[test]
+    fn test_suggested_wal_buffers_mb() {
+        assert_eq!(suggested_wal_buffers_mb(4096), 16);
+        assert_eq!(suggested_wal_buffers_mb(16384), 32);
+        assert_eq!(suggested_wal_buffers_mb(65536), 128);
+        assert_eq!(suggested_wal_buffers_mb(i32::MAX), i32::MAX / 512);
+    }
+
+    /// Test a flagged size always gets a suggestion above the current value
+    #[test]
+    fn test_suggestion_exceeds_undersized_value() {
+        for (wal_buffers_mb, max_wal_size_mb) in [(4, 4096), (16, 16384), (64, 65536)] {
+            assert!(wal_buffers_undersized(wal_buffers_mb, max_wal_size_mb));
+            assert!(suggested_wal_buffers_mb(max_wal_size_mb) > wal_buffers_mb);
+        }
+    }
+}
@@ -8,220 +8,266 @@
 //! Worker state is persisted to shared memory (`shmem::WALRUS_STATE`) so SQL functions
 //! can read real-time metrics.
 
-use crate::algorithm::{calculate_new_size, calculate_shrink_size};
-use crate::config::{execute_alter_system, signal_postmaster_reload};
+use crate::access::skip_read_only;
+use crate::algorithm::{
+    ModeGate, accumulate_sample, apply_ignored_checkpoints, calculate_new_size, compute_confidence,
+    is_noop_resize, is_usable_current_size, is_wal_quiet, lacks_real_baseline,
+    meets_confidence_threshold, min_grow_delta, mode_gate, next_quiet_intervals, push_delta,
+    rolling_average, samples_per_interval,
+};
+use crate::apply::{ApplySource, SizeChange, apply_max_wal_size};
+use crate::bulk_load::effective_threshold;
+use crate::ceiling::check_ceiling_excess;
+use crate::checkpoint_causes::{checkpoint_distance_bytes, pressure_delta, split_requested};
+use crate::cluster_id::publish_cluster_id;
+use crate::config::{cotune_completion_target, verify_applied_size};
+use crate::emergency::check_emergency_shrink;
 use crate::guc::{
-    WALRUS_COOLDOWN_SEC, WALRUS_DRY_RUN, WALRUS_ENABLE, WALRUS_MAX, WALRUS_MAX_CHANGES_PER_HOUR,
-    WALRUS_MIN_SIZE, WALRUS_SHRINK_ENABLE, WALRUS_SHRINK_FACTOR, WALRUS_SHRINK_INTERVALS,
-    WALRUS_THRESHOLD,
+    WALRUS_CLEANUP_INTERVAL_SEC, WALRUS_ENABLE, WALRUS_ENFORCE_MIN_ON_START, WALRUS_GROW_EXPONENT,
+    WALRUS_LOOKBACK_INTERVALS, WALRUS_MIN_APPLY_CONFIDENCE, WALRUS_MODE, WALRUS_READ_ONLY,
+    WALRUS_RECORD_STATS_UNAVAILABLE, WALRUS_SAMPLE_INTERVAL_SEC,
+    WALRUS_SHRINK_MAX_WAL_MB_PER_INTERVAL, WALRUS_THRESHOLD_INCLUSIVE, WALRUS_USE_ROLLING_DELTA,
+    WALRUS_VERBOSE_HISTORY,
 };
 use crate::history;
+use crate::logging;
+use crate::lookback::{per_interval_delta, window_delta, window_span, with_lookback};
+use crate::min_floor::enforce_min_on_start;
+use crate::pin::effective_dry_run;
+use crate::rate_limit::{
+    check_grow_rate_limit, record_growth, update_rate_limit_state_after_adjustment,
+};
+use crate::reproduce::{SizingRule, decision_inputs, with_decision_inputs};
+use crate::restart::{check_unexpected_restart, mark_clean_shutdown};
+use crate::settling::{
+    begin_settling, interval_drifted, limit_grow_while_settling, settling_step,
+    startup_grace_active,
+};
 use crate::shmem::{self, now_unix};
-use crate::stats::{checkpoint_timeout, get_current_max_wal_size, get_requested_checkpoints};
+use crate::shrink::maybe_shrink;
+use crate::stats::{
+    checkpoint_timeout, get_checkpoint_completion_target, get_current_max_wal_size,
+    get_current_wal_lsn, get_requested_checkpoints,
+};
+use crate::temporary_max::{effective_max, release_expired_override};
+use crate::tick::{claim_cycle_lock, release_cycle_lock};
+use crate::wal_buffers::check_wal_buffers;
 
 use pgrx::bgworkers::{BackgroundWorker, SignalWakeFlags};
 use pgrx::pg_sys;
 use pgrx::prelude::*;
 
 use serde_json::json;
+use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 /// Atomic flag to suppress processing of self-triggered SIGHUP.
 ///
 /// When we send SIGHUP to the postmaster after ALTER SYSTEM, we set this flag
 /// to prevent the next iteration from reprocessing the configuration reload.
+/// The worker mirrors it into shared memory (`suppress_next_sighup`) for
+/// walrus.status(); walrus.clear_suppress() clears the mirror, which cancels
+/// the skip.
 static SUPPRESS_NEXT_SIGHUP: AtomicBool = AtomicBool::new(false);
 
-/// Send SIGHUP to the postmaster to trigger configuration reload.
+/// Skip the next iteration, which would otherwise process our own reload.
 ///
-/// This is called after executing ALTER SYSTEM to apply the new max_wal_size.
-/// The atomic flag is set to suppress our own handling of the resulting SIGHUP.
-fn send_sighup_to_postmaster() {
+/// Called by `apply_max_wal_size()` before it requests the reload after a
+/// worker change; the postmaster forwards it with either walrus.reload_method.
+#[inline]
+pub(crate) fn suppress_next_sighup() {
     SUPPRESS_NEXT_SIGHUP.store(true, Ordering::SeqCst);
-    signal_postmaster_reload();
+    shmem::update_state(|state| state.suppress_next_sighup = true);
 }
 
 /// Check if we should skip this iteration due to self-triggered SIGHUP.
 ///
-/// Returns true if we should skip processing (self-triggered signal).
+/// Returns true if we should skip processing (self-triggered signal), unless
+/// walrus.clear_suppress() cleared the shared-memory mirror meanwhile. Each
+/// skipped cycle is counted in shared memory as `suppressed_cycles`.
 #[inline]
-fn should_skip_iteration() -> bool {
-    SUPPRESS_NEXT_SIGHUP.swap(false, Ordering::SeqCst)
-}
-
-/// Result of a rate limit check.
-///
-/// If the adjustment is blocked, contains the reason and metadata for history logging.
-/// If allowed, the `blocked_by` field is None.
-pub struct RateLimitResult {
-    /// If Some, the adjustment is blocked. The string describes which limit blocked it.
-    pub blocked_by: Option<String>,
-    /// Reason text for history record (only set when blocked).
-    pub reason: Option<String>,
-    /// Metadata for history record (only set when blocked).
-    pub metadata: Option<serde_json::Value>,
-}
-
-impl RateLimitResult {
-    /// Create an allowed result (not blocked).
-    fn allowed() -> Self {
-        Self {
-            blocked_by: None,
-            reason: None,
-            metadata: None,
-        }
+pub(crate) fn should_skip_iteration() -> bool {
+    if !SUPPRESS_NEXT_SIGHUP.swap(false, Ordering::SeqCst) {
+        return false;
     }
-
-    /// Create a blocked result with the specified reason and metadata.
-    fn blocked(blocked_by: &str, reason: &str, metadata: serde_json::Value) -> Self {
-        Self {
-            blocked_by: Some(blocked_by.to_string()),
-            reason: Some(reason.to_string()),
-            metadata: Some(metadata),
+    let mut skip = false;
+    shmem::update_state(|state| {
+        skip = state.suppress_next_sighup;
+        state.suppress_next_sighup = false;
+        if skip {
+            state.suppressed_cycles += 1;
         }
-    }
+    });
+    skip
+}
 
-    /// Check if the adjustment is blocked.
-    fn is_blocked(&self) -> bool {
-        self.blocked_by.is_some()
+/// Run `body` with SPI access: in a new transaction from the background
+/// worker, or in the caller's transaction from walrus.tick().
+///
+/// `BackgroundWorker::transaction()` only works in a registered background
+/// worker, so code shared by both paths uses this instead.
+pub(crate) fn in_transaction<F, R>(body: F) -> R
+where
+    F: FnOnce() -> R + UnwindSafe + RefUnwindSafe,
+{
+    if unsafe { pg_sys::IsTransactionState() } {
+        body()
+    } else {
+        BackgroundWorker::transaction(body)
     }
 }
 
-/// Check rate limiting constraints before applying an adjustment.
-///
-/// Checks two rate limits in order (FR-014 specifies cooldown is checked first):
-/// 1. Cooldown period: minimum seconds between adjustments (walrus.cooldown_sec)
-/// 2. Hourly limit: maximum adjustments per rolling one-hour window (walrus.max_changes_per_hour)
-///
-/// Special cases:
-/// - If cooldown_sec = 0, cooldown check is skipped entirely
-/// - If max_changes_per_hour = 0, all automatic adjustments are blocked
+/// Run one cycle in the calling backend instead of the worker (walrus.tick()).
 ///
-/// Returns:
-/// - RateLimitResult with blocked_by=None if adjustment is allowed
-/// - RateLimitResult with blocked_by=Some("cooldown"|"hourly_limit") if blocked
-fn check_rate_limit() -> RateLimitResult {
-    let now = now_unix();
-    let cooldown_sec = WALRUS_COOLDOWN_SEC.get();
-    let max_changes_per_hour = WALRUS_MAX_CHANGES_PER_HOUR.get();
+/// The worker keeps "first iteration" in a local; here it comes from shared
+/// memory instead: the cycle establishes the baseline when no cycle has read
+/// the checkpoint statistics since startup or walrus.reset(). Returns whether
+/// it did. A reload the cycle requests is not suppressed for this backend.
+pub(crate) fn run_single_cycle() -> bool {
     let state = shmem::read_state();
-
-    // Edge case: max_changes_per_hour = 0 blocks all automatic adjustments
-    if max_changes_per_hour == 0 {
-        return RateLimitResult::blocked(
-            "hourly_limit",
-            "automatic adjustments disabled (max_changes_per_hour = 0)",
-            json!({
-                "blocked_by": "hourly_limit",
-                "max_changes_per_hour": 0,
-                "changes_this_hour": state.changes_this_hour
-            }),
-        );
-    }
-
-    // Check 1: Cooldown period (skip if cooldown_sec = 0)
-    if cooldown_sec > 0 && state.last_adjustment_time > 0 {
-        let cooldown_end = state.last_adjustment_time.saturating_add(cooldown_sec as i64);
-        // Use strict inequality: blocked if now < cooldown_end (not <=)
-        // This means adjustment is allowed when now >= cooldown_end
-        if now < cooldown_end {
-            let remaining = cooldown_end.saturating_sub(now);
-            return RateLimitResult::blocked(
-                "cooldown",
-                "cooldown active",
-                json!({
-                    "blocked_by": "cooldown",
-                    "cooldown_sec": cooldown_sec,
-                    "cooldown_remaining_sec": remaining,
-                    "last_adjustment_time": state.last_adjustment_time
-                }),
-            );
-        }
+    let baseline = state.last_check_time == 0 || state.needs_rebaseline;
+    let mut first_iteration = baseline;
+    process_checkpoint_stats(&mut first_iteration);
+    if SUPPRESS_NEXT_SIGHUP.swap(false, Ordering::SeqCst) {
+        shmem::update_state(|state| state.suppress_next_sighup = false);
     }
+    baseline && !first_iteration
+}
 
-    // Check 2: Hourly limit (only after cooldown passes)
-    // First, check if the current hour window has expired
-    let hour_expired = if state.hour_window_start > 0 {
-        now >= state.hour_window_start.saturating_add(3600)
-    } else {
-        // No previous window, will start fresh
-        true
-    };
-
-    // If window hasn't expired, check if we're at the limit
-    if !hour_expired && state.changes_this_hour >= max_changes_per_hour {
-        return RateLimitResult::blocked(
-            "hourly_limit",
-            "hourly limit reached",
-            json!({
-                "blocked_by": "hourly_limit",
-                "max_changes_per_hour": max_changes_per_hour,
-                "changes_this_hour": state.changes_this_hour,
-                "hour_window_start": state.hour_window_start
-            }),
-        );
+/// Record how this interval was classified (walrus.verbose_history).
+fn note_interval(current_requested: i64, metadata: serde_json::Value) {
+    let current_size = get_current_max_wal_size();
+    let timeout_secs = checkpoint_timeout().as_secs() as i32;
+    if let Err(e) = in_transaction(|| {
+        history::insert_interval_note(
+            WALRUS_VERBOSE_HISTORY.get(),
+            current_size,
+            current_requested,
+            timeout_secs,
+            metadata.clone(),
+        )
+    }) {
+        pgrx::warning!("pg_walrus: failed to log interval note: {}", e);
     }
-
-    // Adjustment is allowed
-    RateLimitResult::allowed()
 }
 
-/// Update rate limiting state after a successful adjustment.
+/// Time to sleep between worker cycles.
 ///
-/// Called after an adjustment is applied (or would be applied in dry-run mode).
-/// Updates changes_this_hour and hour_window_start in shared memory.
-fn update_rate_limit_state_after_adjustment() {
-    let now = now_unix();
-    shmem::update_state(|state| {
-        // Check if hour window has expired
-        let hour_expired = if state.hour_window_start > 0 {
-            now >= state.hour_window_start.saturating_add(3600)
-        } else {
-            true
-        };
-
-        if hour_expired {
-            // Start new window
-            state.changes_this_hour = 1;
-            state.hour_window_start = now;
-        } else {
-            // Increment count in current window
-            state.changes_this_hour += 1;
-        }
-    });
+/// checkpoint_timeout, or walrus.sample_interval_sec when that is set and shorter.
+fn cycle_wait() -> Duration {
+    let timeout = checkpoint_timeout();
+    let sample_secs = WALRUS_SAMPLE_INTERVAL_SEC.get();
+    if sample_secs > 0 && (sample_secs as u64) < timeout.as_secs() {
+        Duration::from_secs(sample_secs as u64)
+    } else {
+        timeout
+    }
 }
 
 /// Process checkpoint statistics and trigger resize if needed.
 ///
 /// This is the core monitoring logic called each wake cycle:
+/// 0. EMERGENCY: If the pg_wal filesystem is nearly full, shrink to the floor and stop
 /// 1. Fetch current checkpoint statistics
-/// 2. Calculate delta from previous count
-/// 3. GROW PATH: If delta >= threshold, calculate and apply new max_wal_size, reset quiet_intervals
-/// 4. SHRINK PATH: If delta < threshold, increment quiet_intervals, potentially shrink
+/// 2. Calculate delta from previous count, accumulating sub-samples
+///    (walrus.sample_interval_sec) until a decision is due
+/// 3. GROW PATH: If delta >= threshold (> with walrus.threshold_inclusive off), calculate and
+///    apply new max_wal_size, reset quiet_intervals
+/// 4. SHRINK PATH: If delta is below that, increment quiet_intervals (or reset them if WAL
+///    volume exceeded walrus.shrink_max_wal_mb_per_interval), potentially shrink
 /// 5. Update shared memory state for SQL function visibility
 ///
 /// The quiet_intervals counter tracks consecutive intervals with low activity.
 /// State is persisted to shared memory so SQL functions can read real-time metrics.
-fn process_checkpoint_stats(first_iteration: &mut bool) {
+pub(crate) fn process_checkpoint_stats(first_iteration: &mut bool) {
+    // A zero or negative max_wal_size would turn every calculation into garbage
+    let current_size = get_current_max_wal_size();
+    if !is_usable_current_size(current_size) {
+        pgrx::warning!(
+            "pg_walrus: max_wal_size reads as {} MB, skipping cycle",
+            current_size
+        );
+        return;
+    }
+
+    // Catch a size written last cycle that the reload refused or clamped
+    verify_applied_size(current_size, checkpoint_timeout().as_secs() as i32);
+    release_expired_override(current_size);
+
     // Fetch current checkpoint count
     let current_requested = get_requested_checkpoints();
 
+    // EMERGENCY CHECK: bypasses quiet intervals, cooldown, and hourly limit
+    if check_emergency_shrink(current_requested.max(0)).is_some() {
+        // Keep the baseline current so the next normal cycle sees a fresh delta
+        if current_requested >= 0 {
+            let current_lsn = get_current_wal_lsn();
+            shmem::update_state(|state| {
+                state.prev_requested = current_requested;
+                state.prev_lsn = current_lsn;
+                state.last_check_time = now_unix();
+                state.pending_delta = 0;
+                state.sample_count = 0;
+                state.needs_rebaseline = false;
+            });
+        }
+        return;
+    }
+
     // Handle null pointer from pgstat (returns -1)
     if current_requested < 0 {
         pgrx::warning!("pg_walrus: checkpoint statistics unavailable, skipping cycle");
+
+        // Optionally record the start of the outage (once per contiguous outage)
+        let (record, _) = history::stats_unavailable_note(
+            shmem::read_state().stats_unavailable_noted,
+            current_requested,
+            WALRUS_RECORD_STATS_UNAVAILABLE.get(),
+        );
+        if record {
+            let current_size = get_current_max_wal_size();
+            match in_transaction(|| {
+                history::insert_history_record(
+                    "note",
+                    current_size,
+                    current_size,
+                    0,
+                    checkpoint_timeout().as_secs() as i32,
+                    Some("checkpoint statistics unavailable"),
+                    None,
+                )
+            }) {
+                Ok(()) => shmem::update_state(|state| {
+                    state.stats_unavailable_noted = true;
+                }),
+                Err(e) => pgrx::warning!("pg_walrus: failed to log stats outage note: {}", e),
+            }
+        }
         return;
     }
 
-    // Update last_check_time in shared memory
+    // Update last_check_time in shared memory; a successful read ends any stats outage
     let now = now_unix();
+    let mut prev_check_time = 0;
+    let mut needs_rebaseline = false;
     shmem::update_state(|state| {
+        prev_check_time = state.last_check_time;
+        needs_rebaseline = std::mem::take(&mut state.needs_rebaseline);
         state.last_check_time = now;
+        state.stats_unavailable_noted = false;
     });
 
-    // First iteration: establish baseline
-    if *first_iteration {
+    // Current WAL position, for measuring WAL generated since the last cycle
+    let current_lsn = get_current_wal_lsn();
+
+    // First iteration (or first cycle after walrus.reset()): establish baseline
+    if *first_iteration || needs_rebaseline {
         shmem::update_state(|state| {
             state.prev_requested = current_requested;
+            state.prev_lsn = current_lsn;
+            state.pending_delta = 0;
+            state.sample_count = 0;
         });
         *first_iteration = false;
         pgrx::debug1!(
@@ -236,35 +282,192 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
     let prev_requested = state.prev_requested;
     let quiet_intervals = state.quiet_intervals;
 
-    // Calculate delta since last check
-    let delta = current_requested - prev_requested;
+    // STALL CHECK: checkpoints accumulated over a long gap (system freeze, a
+    // blocked worker) are not one interval's worth; re-baseline instead
+    if interval_drifted(prev_check_time, now) {
+        shmem::update_state(|state| {
+            state.prev_requested = current_requested;
+            state.prev_lsn = current_lsn;
+            state.pending_delta = 0;
+            state.sample_count = 0;
+        });
+        return;
+    }
+
+    // A delta that is the counter's lifetime total (baseline cleared by
+    // walrus.reset(), or tiny next to a huge counter) is not a signal: re-baseline
+    let raw_delta = current_requested - prev_requested;
+    if lacks_real_baseline(
+        prev_requested,
+        raw_delta,
+        checkpoint_timeout().as_secs() as i64,
+    ) {
+        shmem::update_state(|state| {
+            state.prev_requested = current_requested;
+            state.prev_lsn = current_lsn;
+            state.pending_delta = 0;
+            state.sample_count = 0;
+        });
+        pgrx::debug1!(
+            "pg_walrus: delta {} from baseline {} is not a real interval, re-established baseline: {}",
+            raw_delta,
+            prev_requested,
+            current_requested
+        );
+        return;
+    }
+
+    // Calculate delta since last sample, excluding checkpoints registered via
+    // walrus.ignore_next_checkpoints() (e.g., manual CHECKPOINT during maintenance)
+    let (sub_delta, ignored) = apply_ignored_checkpoints(raw_delta, state.ignore_checkpoints);
+    if ignored > 0 {
+        logging::info(format_args!(
+            "pg_walrus: ignoring {} forced checkpoints as requested by walrus.ignore_next_checkpoints()",
+            ignored
+        ));
+    }
 
-    // Update prev_requested in shared memory
+    // WAL generated since last check (0 without a baseline, e.g. after walrus.reset())
+    let wal_bytes = if state.prev_lsn > 0 {
+        (current_lsn - state.prev_lsn).max(0)
+    } else {
+        0
+    };
+
+    // Accumulate sub-samples (walrus.sample_interval_sec) until a decision is due
+    let threshold = effective_threshold();
+    let grow_at = min_grow_delta(threshold, WALRUS_THRESHOLD_INCLUSIVE.get());
+    let step = accumulate_sample(
+        state.pending_delta,
+        state.sample_count,
+        sub_delta,
+        samples_per_interval(
+            checkpoint_timeout().as_secs() as i64,
+            WALRUS_SAMPLE_INTERVAL_SEC.get(),
+        ),
+        grow_at,
+    );
+
+    // Update prev_requested and consume ignored checkpoints in shared memory.
+    // prev_lsn only advances on decisions so WAL volume covers the whole interval.
     shmem::update_state(|state| {
         state.prev_requested = current_requested;
+        state.ignore_checkpoints = (state.ignore_checkpoints - ignored).max(0);
+        state.pending_delta = step.pending_delta;
+        state.sample_count = step.sample_count;
+        if step.decision_delta.is_some() {
+            state.prev_lsn = current_lsn;
+            state.decision_intervals += 1;
+        }
     });
 
-    // Check threshold
-    let threshold = WALRUS_THRESHOLD.get() as i64;
+    // CEILING CHECK: walrus.max lowered below the current size takes precedence,
+    // including over the startup grace below
+    if check_ceiling_excess(current_requested).is_some() {
+        return;
+    }
+
+    // STARTUP GRACE: the baseline above stays current, but nothing accumulates
+    // toward a decision until walrus.startup_grace_sec has elapsed
+    if startup_grace_active() {
+        shmem::update_state(|state| {
+            state.pending_delta = 0;
+            state.sample_count = 0;
+            state.prev_lsn = current_lsn;
+        });
+        pgrx::debug1!("pg_walrus: startup grace period, skipping decisions");
+        return;
+    }
 
-    if delta >= threshold {
+    let Some(delta) = step.decision_delta else {
+        pgrx::debug1!(
+            "pg_walrus: sample {} accumulated {} forced checkpoints",
+            step.sample_count,
+            step.pending_delta
+        );
+        return;
+    };
+
+    // Split the interval's checkpoints by cause; walrus.wal_pressure_only
+    // leaves out the ones its WAL volume cannot account for
+    let causes = split_requested(
+        delta,
+        (state.prev_lsn > 0).then_some(wal_bytes),
+        checkpoint_distance_bytes(current_size, get_checkpoint_completion_target()),
+    );
+    shmem::update_state(|state| {
+        state.causes_known = causes.is_some();
+        (state.wal_pressure_checkpoints, state.other_checkpoints) =
+            causes.map_or((0, 0), |c| (c.wal_pressure, c.other));
+    });
+    let delta = pressure_delta(delta, causes);
+
+    // Record the delta; walrus.use_rolling_delta decides on the recent average
+    // instead, and walrus.lookback_intervals on the last N intervals combined
+    let lookback = WALRUS_LOOKBACK_INTERVALS.get();
+    let (mut rolling_avg, mut window, mut span) = (delta, delta, 1);
+    shmem::update_state(|state| {
+        (state.delta_ring_pos, state.delta_ring_len) = push_delta(
+            &mut state.delta_ring,
+            state.delta_ring_pos,
+            state.delta_ring_len,
+            delta,
+        );
+        rolling_avg = rolling_average(&state.delta_ring, state.delta_ring_len);
+        span = window_span(lookback, state.decision_intervals, state.last_grow_interval);
+        window = window_delta(
+            &state.delta_ring,
+            state.delta_ring_pos,
+            state.delta_ring_len,
+            span,
+        );
+    });
+    let use_lookback = lookback > 1 && !WALRUS_USE_ROLLING_DELTA.get();
+    let (delta, size_delta) = if WALRUS_USE_ROLLING_DELTA.get() {
+        (rolling_avg, rolling_avg)
+    } else if use_lookback {
+        (window, per_interval_delta(window, span))
+    } else {
+        (delta, delta)
+    };
+    logging::cycle(format_args!(
+        "pg_walrus: cycle delta {} (threshold {}, rolling average {}), {} quiet intervals",
+        delta, threshold, rolling_avg, quiet_intervals
+    ));
+
+    // Check threshold (delta > threshold when walrus.threshold_inclusive is off)
+    if delta >= grow_at {
         // =====================================================================
         // GROW PATH: Activity detected, reset quiet intervals and potentially grow
         // =====================================================================
         // Reset quiet intervals (we only need the shmem update, not local variable)
         shmem::update_state(|state| {
             state.quiet_intervals = 0;
+            state.idle_intervals = 0;
         });
+        note_interval(
+            current_requested,
+            history::interval_note_metadata(delta, threshold, 0, "active", wal_bytes),
+        );
+
+        // MODE CHECK: shrink_only disables grows; observe (or dry-run, or a pin) records only
+        let mode = WALRUS_MODE.get();
+        let gate = mode_gate(mode, true, effective_dry_run());
+        if gate == ModeGate::Skip {
+            pgrx::debug1!("pg_walrus: grow skipped, walrus.mode = {}", mode.name());
+            return;
+        }
 
         // Get current max_wal_size
         let current_size = get_current_max_wal_size();
 
         // Calculate new size with overflow protection
-        let calculated_size = calculate_new_size(current_size, delta);
+        let grow_exponent = WALRUS_GROW_EXPONENT.get();
+        let calculated_size = calculate_new_size(current_size, size_delta, grow_exponent);
         let mut new_size = calculated_size;
 
         // Cap at walrus.max and track if capped
-        let max_allowed = WALRUS_MAX.get();
+        let max_allowed = effective_max();
         let is_capped = new_size > max_allowed;
         if is_capped {
             pgrx::warning!(
@@ -275,6 +478,21 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
             new_size = max_allowed;
         }
 
+        // Limit the step while settling after startup or promotion (no longer at walrus.max)
+        let settling_step_mb = settling_step();
+        let settled_size = limit_grow_while_settling(current_size, new_size);
+        let is_capped = is_capped && settled_size == new_size;
+        let new_size = settled_size;
+
+        // Nothing to apply once every clamp and rounding step has run
+        if is_noop_resize(current_size, new_size) {
+            pgrx::debug1!(
+                "pg_walrus: grow target equals max_wal_size ({} MB); skipping",
+                current_size
+            );
+            return;
+        }
+
         // Skip if already at cap
         if current_size >= new_size {
             pgrx::debug1!(
@@ -291,53 +509,52 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
             "threshold exceeded"
         };
 
+        // READ-ONLY: log the decision instead of recording or applying it
+        if skip_read_only(current_size, new_size, reason_text) {
+            return;
+        }
+
         let timeout_secs = checkpoint_timeout().as_secs();
 
+        // CONFIDENCE GATE: Refuse automatic grows below walrus.min_apply_confidence
+        let min_confidence = WALRUS_MIN_APPLY_CONFIDENCE.get();
+        let confidence = compute_confidence(&state, current_requested);
+        if !meets_confidence_threshold(confidence, min_confidence) {
+            logging::info(format_args!(
+                "pg_walrus: adjustment blocked - confidence {} below walrus.min_apply_confidence ({})",
+                confidence, min_confidence
+            ));
+            let metadata = json!({
+                "blocked_by": "low_confidence",
+                "confidence": confidence,
+                "min_apply_confidence": min_confidence
+            });
+            if let Err(e) = in_transaction(|| {
+                history::insert_history_record(
+                    "skipped",
+                    current_size,
+                    new_size,
+                    current_requested,
+                    timeout_secs as i32,
+                    Some("confidence below threshold"),
+                    Some(history::versioned_metadata(metadata.clone())),
+                )
+            }) {
+                pgrx::warning!("pg_walrus: failed to log skipped history: {}", e);
+            }
+            return;
+        }
+
         // RATE LIMIT CHECK: Must occur BEFORE dry-run check per FR-014
         // This ensures rate-limited adjustments are logged correctly in both modes.
-        let rate_limit_result = check_rate_limit();
+        let growth_mb = (new_size - current_size) as i64;
+        let rate_limit_result = check_grow_rate_limit(growth_mb);
         if rate_limit_result.is_blocked() {
-            let blocked_by = rate_limit_result.blocked_by.as_deref().unwrap_or("unknown");
             let reason = rate_limit_result.reason.as_deref().unwrap_or("rate limit blocked");
-            let remaining = if blocked_by == "cooldown" {
-                rate_limit_result
-                    .metadata
-                    .as_ref()
-                    .and_then(|m| m.get("cooldown_remaining_sec"))
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0)
-            } else {
-                0
-            };
-
-            // Log rate limit message
-            if blocked_by == "cooldown" {
-                pgrx::log!(
-                    "pg_walrus: adjustment blocked - cooldown active ({} seconds remaining)",
-                    remaining
-                );
-            } else {
-                let changes = rate_limit_result
-                    .metadata
-                    .as_ref()
-                    .and_then(|m| m.get("changes_this_hour"))
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0);
-                let max_changes = rate_limit_result
-                    .metadata
-                    .as_ref()
-                    .and_then(|m| m.get("max_changes_per_hour"))
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0);
-                pgrx::log!(
-                    "pg_walrus: adjustment blocked - hourly limit reached ({} of {})",
-                    changes,
-                    max_changes
-                );
-            }
+            rate_limit_result.log_blocked("adjustment");
 
             // Record skipped adjustment in history
-            if let Err(e) = BackgroundWorker::transaction(|| {
+            if let Err(e) = in_transaction(|| {
                 history::insert_history_record(
                     "skipped",
                     current_size,
@@ -345,7 +562,10 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
                     current_requested,
                     timeout_secs as i32,
                     Some(reason),
-                    rate_limit_result.metadata.clone(),
+                    rate_limit_result
+                        .metadata
+                        .clone()
+                        .map(history::versioned_metadata),
                 )
             }) {
                 pgrx::warning!("pg_walrus: failed to log skipped history: {}", e);
@@ -354,40 +574,30 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
             return;
         }
 
-        // DRY-RUN CHECK: If dry-run enabled, log what would happen and insert history,
-        // but skip ALTER SYSTEM and SIGHUP. Mode change takes effect on next iteration.
-        if WALRUS_DRY_RUN.get() {
+        // DRY-RUN CHECK: If dry-run (or observe mode) enabled, log what would happen and
+        // insert history, but skip ALTER SYSTEM and SIGHUP. Mode change takes effect on next iteration.
+        if gate == ModeGate::Record {
             // Log dry-run message with [DRY-RUN] prefix
-            pgrx::log!(
+            logging::info(format_args!(
                 "pg_walrus [DRY-RUN]: would change max_wal_size from {} MB to {} MB ({})",
-                current_size,
-                new_size,
-                reason_text
-            );
+                current_size, new_size, reason_text
+            ));
 
             // Build metadata with dry-run fields
             let would_apply = if is_capped { "capped" } else { "increase" };
-            let metadata = if is_capped {
-                json!({
-                    "dry_run": true,
-                    "would_apply": would_apply,
-                    "delta": delta,
-                    "multiplier": delta + 1,
-                    "calculated_size_mb": calculated_size,
-                    "walrus_max_mb": max_allowed
-                })
-            } else {
-                json!({
-                    "dry_run": true,
-                    "would_apply": would_apply,
-                    "delta": delta,
-                    "multiplier": delta + 1,
-                    "calculated_size_mb": calculated_size
-                })
-            };
+            let metadata = history::grow_metadata(
+                size_delta,
+                calculated_size,
+                is_capped.then_some(max_allowed),
+                grow_exponent,
+            );
+            let metadata = history::with_effective_ceiling(metadata, max_allowed);
+            let mut metadata = with_lookback(metadata, use_lookback.then_some((span, delta)));
+            metadata["dry_run"] = json!(true);
+            metadata["would_apply"] = json!(would_apply);
 
             // Insert history with action='dry_run'
-            if let Err(e) = BackgroundWorker::transaction(|| {
+            if let Err(e) = in_transaction(|| {
                 history::insert_history_record(
                     "dry_run",
                     current_size,
@@ -395,7 +605,7 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
                     current_requested,
                     timeout_secs as i32,
                     Some(reason_text),
-                    Some(metadata.clone()),
+                    Some(history::versioned_metadata(metadata.clone())),
                 )
             }) {
                 pgrx::warning!("pg_walrus: failed to log dry-run history: {}", e);
@@ -403,25 +613,80 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
 
             // Update rate limiting state for dry-run (counts against limits per FR-014)
             update_rate_limit_state_after_adjustment();
+            record_growth(growth_mb);
 
             // Skip ALTER SYSTEM and SIGHUP in dry-run mode
             return;
         }
 
         // Log the resize decision (normal mode)
-        pgrx::log!(
+        logging::applied(format_args!(
             "pg_walrus: detected {} forced checkpoints over {} seconds",
-            delta,
-            timeout_secs
-        );
-        pgrx::log!(
+            delta, timeout_secs
+        ));
+        logging::applied(format_args!(
             "pg_walrus: resizing max_wal_size from {} MB to {} MB",
+            current_size, new_size
+        ));
+
+        // Apply, record history (FR-002, FR-003, FR-011), and reload under the adjustment lock
+        let (action, reason) = if is_capped {
+            ("capped", "Calculated size exceeded walrus.max")
+        } else {
+            ("increase", "Forced checkpoints exceeded threshold")
+        };
+        let change = SizeChange {
+            action,
+            old_size_mb: current_size,
+            new_size_mb: new_size,
+            forced_checkpoints: current_requested,
+            checkpoint_timeout_sec: timeout_secs as i32,
+            reason,
+            abort_if_stale: false,
+        };
+        let inputs = decision_inputs(
             current_size,
-            new_size
+            size_delta,
+            threshold,
+            quiet_intervals,
+            new_size,
+            SizingRule::Grow {
+                grow_exponent,
+                max_allowed_mb: max_allowed,
+                settling_step_mb,
+            },
         );
-
-        // Execute ALTER SYSTEM
-        if let Err(e) = execute_alter_system(new_size) {
+        // A grow ends any walrus.shrink_backoff and starts walrus.grow_protect_intervals
+        let update = |state: &mut shmem::WalrusState| {
+            state.last_adjustment_time = now_unix();
+            state.effective_shrink_intervals = 0;
+            state.last_grow_interval = state.decision_intervals;
+        };
+        // Side effects stay out of the metadata builder, which runs under the
+        // adjustment lock. Co-tune checkpoint_completion_target first
+        // (walrus.manage_completion_target) so the apply's reload picks it up.
+        let completion_target = cotune_completion_target();
+        let wal_buffers_warning = check_wal_buffers(new_size);
+        let metadata = |apply_duration_ms| {
+            let metadata = history::grow_metadata(
+                size_delta,
+                calculated_size,
+                is_capped.then_some(max_allowed),
+                grow_exponent,
+            );
+            let metadata = history::with_effective_ceiling(metadata, max_allowed);
+            let metadata = with_lookback(metadata, use_lookback.then_some((span, delta)));
+            let metadata = history::with_completion_target(metadata, completion_target);
+            let key = history::decision_key(now, action, current_requested, new_size);
+            let metadata = history::with_apply_duration(metadata, apply_duration_ms);
+            let metadata = history::with_idempotency_key(metadata, &key);
+            let mut metadata = with_decision_inputs(metadata, &inputs);
+            if wal_buffers_warning {
+                metadata["wal_buffers_warning"] = json!(true);
+            }
+            metadata
+        };
+        if let Err(e) = apply_max_wal_size(&change, ApplySource::Worker, update, metadata) {
             pgrx::warning!(
                 "pg_walrus: failed to execute ALTER SYSTEM, will retry next cycle: {}",
                 e
@@ -429,275 +694,63 @@ fn process_checkpoint_stats(first_iteration: &mut bool) {
             return;
         }
 
-        // Update shared memory state for successful adjustment
-        shmem::update_state(|state| {
-            state.total_adjustments += 1;
-            state.last_adjustment_time = now_unix();
-        });
-
         // Update rate limiting state
         update_rate_limit_state_after_adjustment();
-
-        // Log to history table (FR-002, FR-003, FR-011)
-        let (action, reason, metadata) = if is_capped {
-            (
-                "capped",
-                "Calculated size exceeded walrus.max",
-                json!({
-                    "delta": delta,
-                    "multiplier": delta + 1,
-                    "calculated_size_mb": calculated_size,
-                    "walrus_max_mb": max_allowed
-                }),
-            )
-        } else {
-            (
-                "increase",
-                "Forced checkpoints exceeded threshold",
-                json!({
-                    "delta": delta,
-                    "multiplier": delta + 1,
-                    "calculated_size_mb": calculated_size
-                }),
-            )
-        };
-
-        if let Err(e) = BackgroundWorker::transaction(|| {
-            history::insert_history_record(
-                action,
-                current_size,
-                new_size,
-                current_requested,
-                timeout_secs as i32,
-                Some(reason),
-                Some(metadata.clone()),
-            )
-        }) {
-            pgrx::warning!("pg_walrus: failed to log history: {}", e);
-        }
-
-        // Send SIGHUP to postmaster to apply configuration
-        send_sighup_to_postmaster();
+        record_growth(growth_mb);
     } else {
         // =====================================================================
         // SHRINK PATH: Low activity, increment quiet intervals and potentially shrink
         // =====================================================================
+        // An interval only counts as quiet if WAL volume was also low
+        let max_wal_mb = WALRUS_SHRINK_MAX_WAL_MB_PER_INTERVAL.get();
+        let wal_quiet = is_wal_quiet(wal_bytes, max_wal_mb);
+        let new_quiet_intervals = next_quiet_intervals(quiet_intervals, wal_quiet);
         shmem::update_state(|state| {
-            state.quiet_intervals += 1;
+            state.quiet_intervals = new_quiet_intervals;
+            state.idle_intervals = next_quiet_intervals(state.idle_intervals, wal_quiet);
         });
-        // Re-read the incremented value for shrink logic
-        let new_quiet_intervals = quiet_intervals + 1;
-
-        // Check all shrink conditions
-        let shrink_enable = WALRUS_SHRINK_ENABLE.get();
-        let shrink_intervals = WALRUS_SHRINK_INTERVALS.get();
-        let min_size = WALRUS_MIN_SIZE.get();
-        let current_size = get_current_max_wal_size();
-
-        // Shrink condition: enabled AND enough quiet intervals AND above minimum floor
-        if !shrink_enable {
-            return;
-        }
-
-        if new_quiet_intervals < shrink_intervals {
-            return;
-        }
-
-        if current_size <= min_size {
-            pgrx::debug1!(
-                "pg_walrus: skipping shrink, max_wal_size ({} MB) already at or below min_size ({} MB)",
-                current_size,
-                min_size
-            );
-            return;
-        }
-
-        // Calculate new shrink target
-        let shrink_factor = WALRUS_SHRINK_FACTOR.get();
-        let new_size = calculate_shrink_size(current_size, shrink_factor, min_size);
-
-        // Skip if shrink would not reduce size (e.g., already at floor)
-        if new_size >= current_size {
-            pgrx::debug1!(
-                "pg_walrus: skipping shrink, calculated size ({} MB) >= current ({} MB)",
-                new_size,
-                current_size
-            );
-            return;
-        }
-
-        let timeout_secs = checkpoint_timeout().as_secs();
-
-        // RATE LIMIT CHECK: Must occur BEFORE dry-run check per FR-014
-        // This ensures rate-limited shrink adjustments are logged correctly in both modes.
-        let rate_limit_result = check_rate_limit();
-        if rate_limit_result.is_blocked() {
-            let blocked_by = rate_limit_result.blocked_by.as_deref().unwrap_or("unknown");
-            let reason = rate_limit_result.reason.as_deref().unwrap_or("rate limit blocked");
-            let remaining = if blocked_by == "cooldown" {
-                rate_limit_result
-                    .metadata
-                    .as_ref()
-                    .and_then(|m| m.get("cooldown_remaining_sec"))
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0)
-            } else {
-                0
-            };
-
-            // Log rate limit message
-            if blocked_by == "cooldown" {
-                pgrx::log!(
-                    "pg_walrus: shrink blocked - cooldown active ({} seconds remaining)",
-                    remaining
-                );
-            } else {
-                let changes = rate_limit_result
-                    .metadata
-                    .as_ref()
-                    .and_then(|m| m.get("changes_this_hour"))
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0);
-                let max_changes = rate_limit_result
-                    .metadata
-                    .as_ref()
-                    .and_then(|m| m.get("max_changes_per_hour"))
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0);
-                pgrx::log!(
-                    "pg_walrus: shrink blocked - hourly limit reached ({} of {})",
-                    changes,
-                    max_changes
-                );
-            }
-
-            // Record skipped shrink in history
-            if let Err(e) = BackgroundWorker::transaction(|| {
-                history::insert_history_record(
-                    "skipped",
-                    current_size,
-                    new_size,
-                    current_requested,
-                    timeout_secs as i32,
-                    Some(reason),
-                    rate_limit_result.metadata.clone(),
-                )
-            }) {
-                pgrx::warning!("pg_walrus: failed to log skipped shrink history: {}", e);
-            }
-
-            // Do NOT reset quiet_intervals when rate-limited - we want to try again next cycle
-            return;
-        }
-
-        // DRY-RUN CHECK: If dry-run enabled, log what would happen and insert history,
-        // but skip ALTER SYSTEM and SIGHUP. Mode change takes effect on next iteration.
-        if WALRUS_DRY_RUN.get() {
-            // Log dry-run message with [DRY-RUN] prefix
-            pgrx::log!(
-                "pg_walrus [DRY-RUN]: would change max_wal_size from {} MB to {} MB (sustained low activity)",
-                current_size,
-                new_size
-            );
-
-            // Build metadata with dry-run fields
-            let metadata = json!({
-                "dry_run": true,
-                "would_apply": "decrease",
-                "shrink_factor": shrink_factor,
-                "quiet_intervals": new_quiet_intervals,
-                "calculated_size_mb": new_size
-            });
-
-            // Insert history with action='dry_run'
-            if let Err(e) = BackgroundWorker::transaction(|| {
-                history::insert_history_record(
-                    "dry_run",
-                    current_size,
-                    new_size,
-                    current_requested,
-                    timeout_secs as i32,
-                    Some("sustained low activity"),
-                    Some(metadata.clone()),
-                )
-            }) {
-                pgrx::warning!("pg_walrus: failed to log dry-run history: {}", e);
-            }
-
-            // Reset quiet_intervals after dry-run shrink decision (algorithm state must update)
-            shmem::update_state(|state| {
-                state.quiet_intervals = 0;
-            });
-
-            // Update rate limiting state for dry-run (counts against limits per FR-014)
-            update_rate_limit_state_after_adjustment();
-
-            // Skip ALTER SYSTEM and SIGHUP in dry-run mode
-            return;
-        }
-
-        // Log the shrink decision (normal mode)
-        pgrx::log!(
-            "pg_walrus: shrinking max_wal_size from {} MB to {} MB",
-            current_size,
-            new_size
+        note_interval(
+            current_requested,
+            history::interval_note_metadata(
+                delta,
+                threshold,
+                new_quiet_intervals,
+                if wal_quiet { "quiet" } else { "active" },
+                wal_bytes,
+            ),
         );
 
-        // Execute ALTER SYSTEM for shrink
-        if let Err(e) = execute_alter_system(new_size) {
-            pgrx::warning!(
-                "pg_walrus: failed to execute ALTER SYSTEM for shrink, will retry next cycle: {}",
-                e
+        if !wal_quiet {
+            pgrx::debug1!(
+                "pg_walrus: {} MB of WAL generated (limit {} MB), interval not counted as quiet",
+                wal_bytes / (1024 * 1024),
+                max_wal_mb
             );
             return;
         }
 
-        // Update shared memory state for successful adjustment
-        shmem::update_state(|state| {
-            state.total_adjustments += 1;
-            state.last_adjustment_time = now_unix();
-            state.quiet_intervals = 0; // Reset after successful shrink
-        });
-
-        // Update rate limiting state
-        update_rate_limit_state_after_adjustment();
-
-        // Log to history table (FR-004, FR-011)
-        let metadata = json!({
-            "shrink_factor": shrink_factor,
-            "quiet_intervals": new_quiet_intervals,
-            "calculated_size_mb": new_size
-        });
-
-        if let Err(e) = BackgroundWorker::transaction(|| {
-            history::insert_history_record(
-                "decrease",
-                current_size,
-                new_size,
-                current_requested,
-                timeout_secs as i32,
-                Some("Sustained low checkpoint activity"),
-                Some(metadata.clone()),
-            )
-        }) {
-            pgrx::warning!("pg_walrus: failed to log history: {}", e);
-        }
-
-        // Send SIGHUP to postmaster to apply configuration
-        send_sighup_to_postmaster();
+        maybe_shrink(current_requested, delta, threshold, new_quiet_intervals);
     }
 }
 
 /// Background worker main entry point.
 ///
 /// This function is called by PostgreSQL when the background worker starts.
-/// It runs the main monitoring loop until SIGTERM is received.
+/// It runs the main monitoring loop until SIGTERM is received, then marks the
+/// shutdown as clean (see `restart`).
 #[pg_guard]
 #[unsafe(no_mangle)]
 pub extern "C-unwind" fn walrus_worker_main(_arg: pg_sys::Datum) {
     // Attach signal handlers for SIGHUP (config reload) and SIGTERM (shutdown)
     BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
 
+    // Without shared memory there is no state to monitor with; exit instead of
+    // touching an uninitialized lock
+    if !shmem::is_initialized() {
+        pgrx::warning!("{}", shmem::NOT_INITIALIZED_MESSAGE);
+        return;
+    }
+
     // Set application_name to match pg_walsizer behavior.
     // This makes the worker more identifiable in pg_stat_activity.
     unsafe {
@@ -717,13 +770,49 @@ pub extern "C-unwind" fn walrus_worker_main(_arg: pg_sys::Datum) {
         .unwrap_or_else(|| "postgres".to_owned());
     BackgroundWorker::connect_worker_to_spi(Some(&db_name), None);
 
+    // Tell co-located clusters apart in walrus.status() and in our log lines
+    publish_cluster_id();
+
     pgrx::log!("pg_walrus worker started");
 
+    // Notice a previous instance that crashed, before publishing our own start time
+    check_unexpected_restart();
+
+    // Publish our pid so walrus.kick() can set our latch, and our start time
+    // for walrus.startup_grace_sec
+    shmem::update_state(|state| {
+        state.worker_pid = unsafe { pg_sys::MyProcPid };
+        state.worker_start_time = now_unix();
+    });
+
+    // Post-startup (or post-promotion) settling period
+    begin_settling();
+
+    // Report once if walrus.database has no history table
+    if let Err(e) = BackgroundWorker::transaction(history::check_history_schema) {
+        pgrx::warning!("pg_walrus: failed to check for the history table: {}", e);
+    }
+
+    // Bridge any max_wal_size change made while the worker was not running
+    let (current_size, timeout_secs) = (get_current_max_wal_size(), checkpoint_timeout().as_secs());
+    if let Err(e) = BackgroundWorker::transaction(|| {
+        history::reconcile_startup_drift(current_size, timeout_secs as i32)
+    }) {
+        pgrx::warning!(
+            "pg_walrus: failed to reconcile history with max_wal_size: {}",
+            e
+        );
+    }
+
+    // Raise max_wal_size to walrus.min_size once if configured (walrus.enforce_min_on_start)
+    let enforce_min = WALRUS_ENABLE.get() && WALRUS_ENFORCE_MIN_ON_START.get();
+    enforce_min_on_start(enforce_min, current_size);
+
     // Worker state - only first_iteration is local, rest is in shared memory
     let mut first_iteration = true;
 
-    // Main loop: wake every checkpoint_timeout, process stats, repeat
-    while BackgroundWorker::wait_latch(Some(checkpoint_timeout())) {
+    // Main loop: wake every checkpoint_timeout (or sample interval), process stats, repeat
+    while BackgroundWorker::wait_latch(Some(cycle_wait())) {
         // Check for SIGHUP (configuration reload) - must process BEFORE skip check
         // so that self-triggered SIGHUPs still reload our copy of GUC values
         if BackgroundWorker::sighup_received() {
@@ -746,136 +835,45 @@ pub extern "C-unwind" fn walrus_worker_main(_arg: pg_sys::Datum) {
             continue;
         }
 
+        // Never overlap a cycle another session runs through walrus.tick()
+        if !claim_cycle_lock() {
+            pgrx::debug1!("pg_walrus: another session is running a cycle, skipping");
+            continue;
+        }
+
         // Process checkpoint statistics and potentially resize or shrink
         // State (quiet_intervals, prev_requested, etc.) is managed in shared memory
         process_checkpoint_stats(&mut first_iteration);
-
-        // Cleanup old history records (FR-009)
-        if let Err(e) = BackgroundWorker::transaction(history::cleanup_old_history) {
-            pgrx::warning!("pg_walrus: failed to cleanup history: {}", e);
+        release_cycle_lock();
+
+        // Cleanup old history records (FR-009), at most once per cleanup interval
+        let now = now_unix();
+        let last_cleanup_time = shmem::read_state().last_cleanup_time;
+        if !WALRUS_READ_ONLY.get()
+            && history::cleanup_due(last_cleanup_time, now, WALRUS_CLEANUP_INTERVAL_SEC.get())
+        {
+            // Refresh schema_missing, e.g. after CREATE EXTENSION was run
+            match BackgroundWorker::transaction(|| {
+                history::check_history_schema()?;
+                history::cleanup_old_history()
+            }) {
+                Ok(_) => shmem::update_state(|state| {
+                    state.last_cleanup_time = now;
+                }),
+                Err(e) => pgrx::warning!("pg_walrus: failed to cleanup history: {}", e),
+            }
         }
     }
 
+    shmem::update_state(|state| state.worker_pid = 0);
+    mark_clean_shutdown();
     pgrx::log!("pg_walrus worker shutting down");
 }
 
 // Pure Rust unit tests (do not require PostgreSQL)
 // These tests verify the algorithm functions imported from the algorithm module.
+// Tests are in a separate file to keep worker.rs under 900 LOC
 #[cfg(test)]
 mod tests {
-    use crate::algorithm::{calculate_new_size, calculate_shrink_size};
-
-    // =========================================================================
-    // Tests for calculate_new_size (grow)
-    // =========================================================================
-
-    /// Test that calculate_new_size follows the formula: current_size * (delta + 1)
-    #[test]
-    fn test_new_size_calculation() {
-        // 1024 MB with 3 forced checkpoints: 1024 * 4 = 4096
-        assert_eq!(calculate_new_size(1024, 3), 4096);
-
-        // 2048 MB with 1 forced checkpoint: 2048 * 2 = 4096
-        assert_eq!(calculate_new_size(2048, 1), 4096);
-
-        // 512 MB with 2 forced checkpoints: 512 * 3 = 1536
-        assert_eq!(calculate_new_size(512, 2), 1536);
-
-        // Minimum case: 1 MB with 0 delta (should not happen, but test anyway)
-        assert_eq!(calculate_new_size(1, 0), 1);
-    }
-
-    /// Test that calculate_new_size handles i32 overflow with saturating_mul
-    #[test]
-    fn test_overflow_protection() {
-        // Large base * large multiplier should saturate to i32::MAX
-        // i32::MAX / 2 = 1073741823, * 3 = 3221225469 which overflows
-        let result = calculate_new_size(i32::MAX / 2, 2);
-        assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
-
-        // i32::MAX * 2 overflows
-        let result = calculate_new_size(i32::MAX, 1);
-        assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
-
-        // 1_000_000_000 * 3 = 3_000_000_000 which overflows i32
-        let result = calculate_new_size(1_000_000_000, 2);
-        assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
-    }
-
-    // =========================================================================
-    // Tests for calculate_shrink_size (shrink)
-    // =========================================================================
-
-    /// Test that calculate_shrink_size follows the formula: ceil(current_size * shrink_factor)
-    #[test]
-    fn test_shrink_size_normal() {
-        // 4096 MB * 0.75 = 3072.0 -> ceil = 3072
-        assert_eq!(calculate_shrink_size(4096, 0.75, 1024), 3072);
-
-        // 2048 MB * 0.75 = 1536.0 -> ceil = 1536
-        assert_eq!(calculate_shrink_size(2048, 0.75, 1024), 1536);
-
-        // 1536 MB * 0.75 = 1152.0 -> ceil = 1152
-        assert_eq!(calculate_shrink_size(1536, 0.75, 1024), 1152);
-    }
-
-    /// Test that calculate_shrink_size rounds up via f64::ceil()
-    #[test]
-    fn test_shrink_size_rounding_up() {
-        // 1001 MB * 0.75 = 750.75 -> ceil = 751
-        assert_eq!(calculate_shrink_size(1001, 0.75, 100), 751);
-
-        // 1000 MB * 0.75 = 750.0 -> ceil = 750
-        assert_eq!(calculate_shrink_size(1000, 0.75, 100), 750);
-
-        // 1003 MB * 0.75 = 752.25 -> ceil = 753
-        assert_eq!(calculate_shrink_size(1003, 0.75, 100), 753);
-
-        // Test with very small fraction
-        // 101 MB * 0.01 = 1.01 -> ceil = 2
-        assert_eq!(calculate_shrink_size(101, 0.01, 1), 2);
-    }
-
-    /// Test that calculate_shrink_size clamps to min_size
-    #[test]
-    fn test_shrink_size_clamped_to_min() {
-        // 2560 MB * 0.75 = 1920.0, but min_size is 2048 -> returns 2048
-        assert_eq!(calculate_shrink_size(2560, 0.75, 2048), 2048);
-
-        // 1024 MB * 0.75 = 768.0, but min_size is 1024 -> returns 1024
-        assert_eq!(calculate_shrink_size(1024, 0.75, 1024), 1024);
-
-        // 900 MB * 0.75 = 675.0, but min_size is 1024 -> returns 1024 (below floor)
-        assert_eq!(calculate_shrink_size(900, 0.75, 1024), 1024);
-    }
-
-    /// Test calculate_shrink_size with different shrink factors (US5)
-    #[test]
-    fn test_shrink_size_different_factors() {
-        // 4096 MB * 0.5 = 2048.0 (50% reduction)
-        assert_eq!(calculate_shrink_size(4096, 0.5, 1024), 2048);
-
-        // 4096 MB * 0.9 = 3686.4 -> ceil = 3687 (10% reduction)
-        assert_eq!(calculate_shrink_size(4096, 0.9, 1024), 3687);
-
-        // 4096 MB * 0.1 = 409.6 -> ceil = 410, but min_size 1024 -> 1024
-        assert_eq!(calculate_shrink_size(4096, 0.1, 1024), 1024);
-    }
-
-    /// Test fractional MB rounding edge case (T053)
-    #[test]
-    fn test_shrink_size_fractional_mb() {
-        // 1001 MB * 0.75 = 750.75 -> ceil = 751
-        assert_eq!(calculate_shrink_size(1001, 0.75, 100), 751);
-    }
-
-    /// Test large value edge case (T058)
-    #[test]
-    fn test_shrink_size_large_value() {
-        // i32::MAX * 0.99 should not overflow (shrink always produces smaller values)
-        let result = calculate_shrink_size(i32::MAX, 0.99, 1024);
-        // i32::MAX = 2147483647, * 0.99 = 2126008810.53 -> ceil = 2126008811
-        assert_eq!(result, 2126008811);
-        assert!(result < i32::MAX);
-    }
+    include!("worker_tests.rs");
 }
@@ -0,0 +1,116 @@
+use crate::algorithm::{calculate_new_size, calculate_shrink_size};
+use crate::guc::ShrinkRounding;
+
+// =========================================================================
+// Tests for calculate_new_size (grow)
+// =========================================================================
+
+/// Test that calculate_new_size follows the formula: current_size * (delta + 1)
+#[test]
+fn test_new_size_calculation() {
+    // 1024 MB with 3 forced checkpoints: 1024 * 4 = 4096
+    assert_eq!(calculate_new_size(1024, 3, 1.0), 4096);
+
+    // 2048 MB with 1 forced checkpoint: 2048 * 2 = 4096
+    assert_eq!(calculate_new_size(2048, 1, 1.0), 4096);
+
+    // 512 MB with 2 forced checkpoints: 512 * 3 = 1536
+    assert_eq!(calculate_new_size(512, 2, 1.0), 1536);
+
+    // Minimum case: 1 MB with 0 delta (should not happen, but test anyway)
+    assert_eq!(calculate_new_size(1, 0, 1.0), 1);
+}
+
+/// Test that calculate_new_size handles i32 overflow with saturating_mul
+#[test]
+fn test_overflow_protection() {
+    // Large base * large multiplier should saturate to i32::MAX
+    // i32::MAX / 2 = 1073741823, * 3 = 3221225469 which overflows
+    let result = calculate_new_size(i32::MAX / 2, 2, 1.0);
+    assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
+
+    // i32::MAX * 2 overflows
+    let result = calculate_new_size(i32::MAX, 1, 1.0);
+    assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
+
+    // 1_000_000_000 * 3 = 3_000_000_000 which overflows i32
+    let result = calculate_new_size(1_000_000_000, 2, 1.0);
+    assert_eq!(result, i32::MAX, "Should saturate to i32::MAX on overflow");
+}
+
+// =========================================================================
+// Tests for calculate_shrink_size (shrink)
+// =========================================================================
+
+/// Test that calculate_shrink_size follows the formula: ceil(current_size * shrink_factor)
+#[test]
+fn test_shrink_size_normal() {
+    // 4096 MB * 0.75 = 3072.0 -> ceil = 3072
+    assert_eq!(calculate_shrink_size(4096, 0.75, 1024, ShrinkRounding::Up), 3072);
+
+    // 2048 MB * 0.75 = 1536.0 -> ceil = 1536
+    assert_eq!(calculate_shrink_size(2048, 0.75, 1024, ShrinkRounding::Up), 1536);
+
+    // 1536 MB * 0.75 = 1152.0 -> ceil = 1152
+    assert_eq!(calculate_shrink_size(1536, 0.75, 1024, ShrinkRounding::Up), 1152);
+}
+
+/// Test that calculate_shrink_size rounds up via f64::ceil()
+#[test]
+fn test_shrink_size_rounding_up() {
+    // 1001 MB * 0.75 = 750.75 -> ceil = 751
+    assert_eq!(calculate_shrink_size(1001, 0.75, 100, ShrinkRounding::Up), 751);
+
+    // 1000 MB * 0.75 = 750.0 -> ceil = 750
+    assert_eq!(calculate_shrink_size(1000, 0.75, 100, ShrinkRounding::Up), 750);
+
+    // 1003 MB * 0.75 = 752.25 -> ceil = 753
+    assert_eq!(calculate_shrink_size(1003, 0.75, 100, ShrinkRounding::Up), 753);
+
+    // Test with very small fraction
+    // 101 MB * 0.01 = 1.01 -> ceil = 2
+    assert_eq!(calculate_shrink_size(101, 0.01, 1, ShrinkRounding::Up), 2);
+}
+
+/// Test that calculate_shrink_size clamps to min_size
+#[test]
+fn test_shrink_size_clamped_to_min() {
+    // 2560 MB * 0.75 = 1920.0, but min_size is 2048 -> returns 2048
+    assert_eq!(calculate_shrink_size(2560, 0.75, 2048, ShrinkRounding::Up), 2048);
+
+    // 1024 MB * 0.75 = 768.0, but min_size is 1024 -> returns 1024
+    assert_eq!(calculate_shrink_size(1024, 0.75, 1024, ShrinkRounding::Up), 1024);
+
+    // 900 MB * 0.75 = 675.0, but min_size is 1024 -> returns 1024 (below floor)
+    assert_eq!(calculate_shrink_size(900, 0.75, 1024, ShrinkRounding::Up), 1024);
+}
+
+/// Test calculate_shrink_size with different shrink factors (US5)
+#[test]
+fn test_shrink_size_different_factors() {
+    // 4096 MB * 0.5 = 2048.0 (50% reduction)
+    assert_eq!(calculate_shrink_size(4096, 0.5, 1024, ShrinkRounding::Up), 2048);
+
+    // 4096 MB * 0.9 = 3686.4 -> ceil = 3687 (10% reduction)
+    assert_eq!(calculate_shrink_size(4096, 0.9, 1024, ShrinkRounding::Up), 3687);
+
+    // 4096 MB * 0.1 = 409.6 -> ceil = 410, but min_size 1024 -> 1024
+    assert_eq!(calculate_shrink_size(4096, 0.1, 1024, ShrinkRounding::Up), 1024);
+}
+
+/// Test fractional MB rounding edge case (T053)
+#[test]
+fn test_shrink_size_fractional_mb() {
+    // 1001 MB * 0.75 = 750.75 -> ceil = 751
+    assert_eq!(calculate_shrink_size(1001, 0.75, 100, ShrinkRounding::Up), 751);
+}
+
+/// Test large value edge case (T058)
+#[test]
+fn test_shrink_size_large_value() {
+    // i32::MAX * 0.99 should not overflow (shrink always produces smaller values)
+    let result = calculate_shrink_size(i32::MAX, 0.99, 1024, ShrinkRounding::Up);
+    // i32::MAX = 2147483647, * 0.99 = 2126008810.53 -> ceil = 2126008811
+    assert_eq!(result, 2126008811);
+    assert!(result < i32::MAX);
+}
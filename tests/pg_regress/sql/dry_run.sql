@@ -73,9 +73,20 @@ ORDER BY id DESC LIMIT 1;
 -- Cleanup test records
 DELETE FROM walrus.history WHERE action = 'dry_run';
 
--- Test 9: Count of walrus GUCs with sighup context should be 11
--- (enable, max, threshold, shrink_enable, shrink_factor, shrink_intervals, min_size,
---  history_retention_days, dry_run, cooldown_sec, max_changes_per_hour)
+-- Test 9: Count of walrus GUCs with sighup context should be 52
+-- (enable, max, max_percent_of_device, threshold, threshold_inclusive,
+--  bulk_load_threshold_multiplier, grow_exponent, sample_interval_sec, use_rolling_delta,
+--  lookback_intervals, wal_pressure_only, settling_max_step_mb, startup_grace_sec,
+--  max_interval_drift, shrink_enable, shrink_factor, shrink_intervals, min_size,
+--  enforce_min_on_start, shrink_max_wal_mb_per_interval, force_min_shrink, shrink_rounding,
+--  shrink_backoff, shrink_target, peak_ratio, grow_protect_intervals,
+--  fast_shrink_after_intervals, stats_source, history_retention_days, cleanup_interval_sec,
+--  history_max_rows, archive_before_delete, record_stats_unavailable, verbose_history,
+--  mirror_table, dry_run, mode, reload_method, log_level, cooldown_sec, max_changes_per_hour,
+--  max_shrinks_per_hour, max_growth_mb_per_day, max_sighups_per_hour_warn,
+--  manual_resets_cooldown, min_apply_confidence, min_shrink_confidence,
+--  shrink_confidence_penalty, emergency_free_disk_mb, manage_completion_target,
+--  completion_target, warn_wal_buffers)
 SELECT COUNT(*) AS sighup_guc_count
 FROM pg_settings
 WHERE name LIKE 'walrus.%' AND context = 'sighup';